@@ -0,0 +1,46 @@
+use std::env;
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Build metadata printed by 'version --verbose'. The commit can be provided
+// with DYNUUPDATER_GIT_COMMIT where the sources are not a git checkout (as in
+// the Docker builds), and SOURCE_DATE_EPOCH fixes the build date for
+// reproducible builds.
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string()).filter(|o| !o.is_empty())
+}
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=DYNUUPDATER_GIT_COMMIT");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+    let commit = env::var("DYNUUPDATER_GIT_COMMIT")
+        .ok()
+        .filter(|c| !c.is_empty())
+        .or_else(|| output("git", &["rev-parse", "--short=12", "HEAD"]))
+        .unwrap_or_else(|| "unknown".to_string());
+    let built_at = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .map(|epoch| UNIX_EPOCH + Duration::from_secs(epoch))
+        .unwrap_or_else(SystemTime::now);
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    println!("cargo:rustc-env=DYNUUPDATER_GIT_COMMIT={}", commit);
+    println!(
+        "cargo:rustc-env=DYNUUPDATER_BUILD_DATE={}",
+        humantime::format_rfc3339_seconds(built_at)
+    );
+    println!(
+        "cargo:rustc-env=DYNUUPDATER_RUSTC={}",
+        output(&rustc, &["--version"]).unwrap_or_else(|| "unknown".to_string())
+    );
+    println!(
+        "cargo:rustc-env=DYNUUPDATER_TARGET={}",
+        env::var("TARGET").unwrap_or_default()
+    );
+}
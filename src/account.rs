@@ -0,0 +1,99 @@
+use serde::Serialize;
+
+use crate::dynu::AccountDTO;
+use crate::output::{or_dash, Table};
+use crate::SelfError;
+
+// Usage of the account against the limits of its plan, which are None when
+// dynu doesn't report them.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Usage {
+    pub membership_level: Option<String>,
+    pub domains: u64,
+    pub max_domains: Option<u64>,
+    pub records: u64,
+    pub max_records: Option<u64>,
+}
+
+fn free(used: u64, max: Option<u64>) -> Option<u64> {
+    max.map(|max| max.saturating_sub(used))
+}
+
+fn usage_cell(used: u64, max: Option<u64>) -> String {
+    match max {
+        Some(max) => format!("{}/{}", used, max),
+        None => used.to_string(),
+    }
+}
+
+impl Usage {
+    pub fn new(account: &AccountDTO, domains: u64, records: u64) -> Usage {
+        Usage {
+            membership_level: account.membership_level.clone(),
+            domains,
+            max_domains: account.max_domains,
+            records,
+            max_records: account.max_records,
+        }
+    }
+
+    pub fn table(&self) -> Table {
+        let mut table = Table::new(vec!["LEVEL", "DOMAINS", "RECORDS"]);
+        table.push(vec![
+            or_dash(&self.membership_level),
+            usage_cell(self.domains, self.max_domains),
+            usage_cell(self.records, self.max_records),
+        ]);
+        table
+    }
+
+    // Fails when fewer domains or records than required can still be created.
+    // Limits dynu doesn't report can't be checked and never fail.
+    pub fn require(&self, domains: Option<u64>, records: Option<u64>) -> Result<(), SelfError> {
+        for (what, required, free) in [
+            ("domains", domains, free(self.domains, self.max_domains)),
+            ("records", records, free(self.records, self.max_records)),
+        ] {
+            if let (Some(required), Some(free)) = (required, free) {
+                if free < required {
+                    return Err(SelfError::MsgError(format!(
+                        "the account can only hold {} more {}, {} required",
+                        free, what, required
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage() -> Usage {
+        Usage::new(
+            &AccountDTO {
+                membership_level: Some("Basic".to_string()),
+                max_domains: Some(4),
+                ..Default::default()
+            },
+            3,
+            40,
+        )
+    }
+
+    #[test]
+    fn table_should_show_usage_against_limits() {
+        let table = usage().table();
+        assert_eq!(table.rows[0], vec!["Basic", "3/4", "40"]);
+    }
+
+    #[test]
+    fn require_should_fail_beyond_known_limits() {
+        let usage = usage();
+        assert!(usage.require(Some(1), None).is_ok());
+        assert!(usage.require(Some(2), None).is_err());
+        assert!(usage.require(None, Some(1000)).is_ok());
+    }
+}
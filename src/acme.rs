@@ -0,0 +1,213 @@
+use std::time::{Duration, SystemTime};
+
+use crate::content::same_content;
+use crate::dynu::{DomainDTO, DynuClient, RecordDTO};
+use crate::names::{node_of, normalise_fqdn, same_name, to_ascii};
+use crate::notify::{Event, Notifier, Status};
+use crate::progress::Progress;
+use crate::SelfError;
+
+pub const DEFAULT_CHALLENGE_TTL: u64 = 120;
+const CHALLENGE_NODE: &str = "_acme-challenge";
+
+// Finds the Dynu domain hosting fqdn and the node name of fqdn inside it. When
+// zone is not provided, the longest domain of the account which is a suffix of
+// fqdn is used.
+pub fn find_zone(
+    dynu_client: &DynuClient,
+    fqdn: &str,
+    zone: Option<&str>,
+) -> Result<(DomainDTO, String), SelfError> {
+    let fqdn = normalise_fqdn(&to_ascii(fqdn)?);
+    let zone = match zone {
+        Some(z) => Some(normalise_fqdn(&to_ascii(z)?)),
+        None => None,
+    };
+    let domains = dynu_client.get_domains()?.domains;
+    domains
+        .into_iter()
+        .filter(|d| match &zone {
+            Some(z) => &d.name.to_lowercase() == z,
+            None => true,
+        })
+        .filter_map(|d| node_of(&fqdn, &d.name.to_lowercase()).map(|node| (d, node)))
+        .max_by_key(|(d, _)| d.name.len())
+        .ok_or_else(|| SelfError::MsgError(format!("no domain in dynu is hosting fqdn={}", fqdn)))
+}
+
+fn matching_txt_records<'a>(
+    records: &'a [RecordDTO],
+    node: &'a str,
+    value: &'a str,
+) -> impl Iterator<Item = &'a RecordDTO> {
+    records.iter().filter(move |r| match r {
+        RecordDTO::TxtRecord {
+            node_name,
+            text_data,
+            ..
+        } => same_name(node_name, node) && same_content("TXT", text_data, value),
+        _ => false,
+    })
+}
+
+// Adds a TXT record for fqdn with the given value, leaving other TXT records on
+// the same node untouched so that concurrent challenges can coexist.
+pub fn present(
+    dynu_client: &DynuClient,
+    fqdn: &str,
+    zone: Option<&str>,
+    value: &str,
+    ttl: u64,
+) -> Result<(), SelfError> {
+    let (domain, node) = find_zone(dynu_client, fqdn, zone)?;
+    let domain_id = domain.require_id()?;
+    let records = dynu_client.get_records(domain_id)?;
+    if matching_txt_records(&records.dns_records, &node, value)
+        .next()
+        .is_some()
+    {
+        eprintln!(
+            "TXT record {} in domain {} already present with the requested value",
+            node, domain.name
+        );
+        return Ok(());
+    }
+    let txt_record = RecordDTO::txt_record(&node, value, ttl, None);
+    let id = dynu_client.create_record(domain_id, &txt_record)?;
+    eprintln!(
+        "created TXT record {} in domain {} with id={}",
+        node, domain.name, id
+    );
+    Ok(())
+}
+
+// Removes the TXT records for fqdn holding the given value. Missing records are
+// not an error so that cleanup can be safely retried.
+pub fn cleanup(
+    dynu_client: &DynuClient,
+    fqdn: &str,
+    zone: Option<&str>,
+    value: &str,
+) -> Result<(), SelfError> {
+    let (domain, node) = find_zone(dynu_client, fqdn, zone)?;
+    let domain_id = domain.require_id()?;
+    let records = dynu_client.get_records(domain_id)?;
+    for record in matching_txt_records(&records.dns_records, &node, value) {
+        let record_id = record.require_id()?;
+        dynu_client.delete_record(domain_id, record_id)?;
+        eprintln!(
+            "deleted TXT record {} in domain {} with id={}",
+            node, domain.name, record_id
+        );
+    }
+    Ok(())
+}
+
+fn is_challenge_node(node: &str) -> bool {
+    let node = node.to_lowercase();
+    node == CHALLENGE_NODE || node.starts_with(&format!("{}.", CHALLENGE_NODE))
+}
+
+// Dynu's updatedOn timestamps have no offset and are in UTC.
+fn updated_at(record: &RecordDTO) -> Option<SystemTime> {
+    record
+        .updated_on()
+        .and_then(|u| humantime::parse_rfc3339_weak(u).ok())
+}
+
+// Challenge TXT records last updated before now - older_than, left behind by
+// issuances which failed before cleaning up. Records without an update time
+// are never considered stale.
+pub fn stale_challenges(
+    records: &[RecordDTO],
+    now: SystemTime,
+    older_than: Duration,
+) -> Vec<&RecordDTO> {
+    let threshold = now
+        .checked_sub(older_than)
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    records
+        .iter()
+        .filter(|r| r.record_type() == "TXT" && is_challenge_node(r.node_name()))
+        .filter(|r| updated_at(r).is_some_and(|updated| updated < threshold))
+        .collect()
+}
+
+// Deletes the stale challenge records of every domain of the account.
+pub fn cleanup_stale(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    older_than: Duration,
+    dry_run: bool,
+) -> Result<(), SelfError> {
+    let now = SystemTime::now();
+    let mut deleted = 0;
+    let domains: Vec<DomainDTO> = dynu_client.domains_iter()?.collect();
+    let progress = Progress::new(domains.len(), "acme cleanup");
+    for domain in &domains {
+        deleted += progress.item(&domain.name, || {
+            let domain_id = domain.require_id()?;
+            let records: Vec<RecordDTO> = dynu_client
+                .records_iter(domain_id)
+                .collect::<Result<_, _>>()?;
+            let stale = stale_challenges(&records, now, older_than);
+            for record in &stale {
+                let record_id = record.require_id()?;
+                eprintln!(
+                    "domain={} deleting stale TXT record {} with id={} updated on {}",
+                    domain.name,
+                    record.node_name(),
+                    record_id,
+                    record.updated_on().unwrap_or_default()
+                );
+                if !dry_run {
+                    dynu_client.delete_record(domain_id, record_id)?;
+                    notifier.notify(
+                        &Event::new("acme-cleanup", Status::Updated, &domain.name)
+                            .node(record.node_name())
+                            .record_type("TXT")
+                            .values(Some(record.value()), None),
+                    );
+                }
+            }
+            Ok(stale.len())
+        })?;
+    }
+    progress.finish();
+    eprintln!(
+        "{} {} stale challenge records{}",
+        if dry_run { "would delete" } else { "deleted" },
+        deleted,
+        if dry_run { " (dry run)" } else { "" }
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn txt(id: u64, node: &str, updated_on: Option<&str>) -> RecordDTO {
+        serde_json::from_value(serde_json::json!({
+            "recordType": "TXT", "id": id, "domainId": 1, "domainName": "example.dynu.net",
+            "nodeName": node, "hostname": null, "ttl": 120, "state": true, "content": null,
+            "updatedOn": updated_on, "textData": "token"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn stale_challenges_should_only_select_old_challenge_records() {
+        let records = vec![
+            txt(1, "_acme-challenge", Some("2024-05-01T10:00:00.123")),
+            txt(2, "_acme-challenge.www", Some("2024-05-01T10:00:00")),
+            txt(3, "_acme-challenge", Some("2024-05-02T09:00:00")),
+            txt(4, "_acme-challenge", None),
+            txt(5, "verification", Some("2024-05-01T10:00:00")),
+        ];
+        let now = humantime::parse_rfc3339("2024-05-02T10:00:00Z").unwrap();
+        let stale = stale_challenges(&records, now, Duration::from_secs(12 * 3600));
+        let ids: Vec<Option<u64>> = stale.iter().map(|r| r.id()).collect();
+        assert_eq!(ids, vec![Some(1), Some(2)]);
+    }
+}
@@ -0,0 +1,159 @@
+use clap::{Arg, Command};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// A command line the [aliases] table of the configuration names, given as a
+// string split on whitespace or as the list of its words when some of them
+// hold spaces, e.g. prod-txt = "--profile production txt-update".
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Alias {
+    Line(String),
+    Words(Vec<String>),
+}
+
+impl Alias {
+    fn words(&self) -> Vec<String> {
+        match self {
+            Alias::Line(line) => line.split_whitespace().map(|w| w.to_string()).collect(),
+            Alias::Words(words) => words.clone(),
+        }
+    }
+}
+
+fn takes_value(command: &Command, matches: impl Fn(&Arg) -> bool) -> bool {
+    command
+        .get_arguments()
+        .any(|arg| matches(arg) && arg.get_action().takes_values())
+}
+
+// Index of the word naming the command, the first one which is neither an
+// option nor the value of an option.
+fn command_index(args: &[String], command: &Command) -> Option<usize> {
+    let mut index = 1;
+    while index < args.len() {
+        let arg = args[index].as_str();
+        let skip_value = match (arg.strip_prefix("--"), arg.strip_prefix('-')) {
+            (Some(""), _) => return None,
+            (Some(long), _) => {
+                !long.contains('=') && takes_value(command, |a| a.get_long() == Some(long))
+            }
+            (None, Some(short)) if short.chars().count() == 1 => {
+                takes_value(command, |a| a.get_short() == short.chars().next())
+            }
+            (None, Some(_)) => false,
+            (None, None) => return Some(index),
+        };
+        index += if skip_value { 2 } else { 1 };
+    }
+    None
+}
+
+fn is_builtin(command: &Command, name: &str) -> bool {
+    name == "help"
+        || command
+            .get_subcommands()
+            .any(|sub| sub.get_name() == name || sub.get_all_aliases().any(|a| a == name))
+}
+
+// The word naming the command when it isn't one of dynuupdater's commands, so
+// that it may be an alias.
+pub fn alias_name<'a>(args: &'a [String], command: &Command) -> Option<&'a str> {
+    let index = command_index(args, command)?;
+    let name = args[index].as_str();
+    (!is_builtin(command, name)).then_some(name)
+}
+
+// The value of an option given on the command line as --name value or
+// --name=value, e.g. the configuration file the aliases are read from.
+pub fn option_value(args: &[String], name: &str) -> Option<String> {
+    let flag = format!("--{}", name);
+    let prefix = format!("--{}=", name);
+    args.iter().enumerate().find_map(|(index, arg)| {
+        if arg == &flag {
+            args.get(index + 1).cloned()
+        } else {
+            arg.strip_prefix(&prefix).map(|value| value.to_string())
+        }
+    })
+}
+
+// Replaces the alias naming the command by its command line, the words
+// following the alias being appended. dynuupdater's own commands can't be
+// redefined, and aliases aren't expanded recursively.
+pub fn expand(
+    args: Vec<String>,
+    command: &Command,
+    aliases: &BTreeMap<String, Alias>,
+) -> Vec<String> {
+    let Some(alias) = alias_name(&args, command).and_then(|name| aliases.get(name)) else {
+        return args;
+    };
+    let index = command_index(&args, command).unwrap();
+    let mut expanded = args[..index].to_vec();
+    expanded.extend(alias.words());
+    expanded.extend_from_slice(&args[index + 1..]);
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command() -> Command {
+        Command::new("dynuupdater")
+            .arg(Arg::new("config").long("config").global(true))
+            .arg(
+                Arg::new("dry-run")
+                    .long("dry-run")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .subcommand(Command::new("refresh").visible_alias("rf"))
+            .subcommand(Command::new("records"))
+    }
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn expand_should_replace_the_alias_by_its_command_line() {
+        let aliases = BTreeMap::from([
+            (
+                "zone".to_string(),
+                Alias::Line("records --sort type".to_string()),
+            ),
+            ("rf".to_string(), Alias::Line("records".to_string())),
+        ]);
+        assert_eq!(
+            expand(
+                args("dynuupdater --config records zone example.dynu.net"),
+                &command(),
+                &aliases
+            ),
+            args("dynuupdater --config records records --sort type example.dynu.net")
+        );
+        assert_eq!(
+            expand(args("dynuupdater --dry-run rf"), &command(), &aliases),
+            args("dynuupdater --dry-run rf")
+        );
+        assert_eq!(
+            expand(args("dynuupdater unknown"), &command(), &aliases),
+            args("dynuupdater unknown")
+        );
+    }
+
+    #[test]
+    fn option_value_should_accept_both_forms() {
+        assert_eq!(
+            option_value(&args("dynuupdater --config a.toml zone"), "config").as_deref(),
+            Some("a.toml")
+        );
+        assert_eq!(
+            option_value(&args("dynuupdater zone --config=b.toml"), "config").as_deref(),
+            Some("b.toml")
+        );
+        assert_eq!(option_value(&args("dynuupdater zone"), "profile"), None);
+        assert_eq!(alias_name(&args("dynuupdater --dry-run"), &command()), None);
+    }
+}
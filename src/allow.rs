@@ -0,0 +1,99 @@
+use clap::{ArgMatches, Command};
+
+use crate::suggest::did_you_mean;
+use crate::SelfError;
+
+// Path of the subcommand selected on the command line, e.g. "records dedupe".
+pub fn command_path(matches: &ArgMatches) -> String {
+    let mut path = Vec::new();
+    let mut matches = matches;
+    while let Some((name, sub_matches)) = matches.subcommand() {
+        path.push(name);
+        matches = sub_matches;
+    }
+    path.join(" ")
+}
+
+// Paths of the subcommands which can be run, a command whose subcommand is
+// optional (e.g. records) being a path of its own.
+fn command_paths(command: &Command, prefix: &str, paths: &mut Vec<String>) {
+    for sub in command.get_subcommands() {
+        let path = match prefix {
+            "" => sub.get_name().to_string(),
+            prefix => format!("{} {}", prefix, sub.get_name()),
+        };
+        if !sub.has_subcommands() || !sub.is_subcommand_required_set() {
+            paths.push(path.clone());
+        }
+        command_paths(sub, &path, paths);
+    }
+}
+
+// The allow setting of the configuration restricts the commands which can be
+// run to the ones it lists, whatever the API key permits, e.g. to the
+// txt-update and txt-delete of an ACME client. Every command is allowed when
+// the setting is missing.
+pub fn check(allow: Option<&[String]>, command: &Command, path: &str) -> Result<(), SelfError> {
+    let Some(allow) = allow else {
+        return Ok(());
+    };
+    let mut known = Vec::new();
+    command_paths(command, "", &mut known);
+    for allowed in allow {
+        if !known.contains(allowed) {
+            return Err(SelfError::MsgError(format!(
+                "allow lists unknown command {}{}",
+                allowed,
+                did_you_mean(allowed, known.iter().map(|k| k.as_str()))
+            )));
+        }
+    }
+    if allow.iter().any(|allowed| allowed == path) {
+        return Ok(());
+    }
+    Err(SelfError::MsgError(format!(
+        "{} is not allowed by the configuration, which only allows {}",
+        path,
+        allow.join(", ")
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command() -> Command {
+        Command::new("dynuupdater")
+            .subcommand(Command::new("txt-update"))
+            .subcommand(Command::new("records").subcommand(Command::new("dedupe")))
+            .subcommand(
+                Command::new("serve")
+                    .subcommand_required(true)
+                    .subcommand(Command::new("dashboard")),
+            )
+    }
+
+    #[test]
+    fn check_should_only_allow_the_listed_commands() {
+        let allow = vec!["txt-update".to_string(), "records".to_string()];
+        assert!(check(None, &command(), "records dedupe").is_ok());
+        assert!(check(Some(&allow), &command(), "txt-update").is_ok());
+        assert!(check(Some(&allow), &command(), "records").is_ok());
+        assert!(check(Some(&allow), &command(), "records dedupe").is_err());
+        assert!(check(Some(&[]), &command(), "txt-update").is_err());
+    }
+
+    #[test]
+    fn check_should_reject_unknown_commands() {
+        let matches = command().get_matches_from(["dynuupdater", "serve", "dashboard"]);
+        assert_eq!(command_path(&matches), "serve dashboard");
+        let allow = vec!["serve dashboard".to_string()];
+        assert!(check(Some(&allow), &command(), "serve dashboard").is_ok());
+        assert!(check(Some(&["serve".to_string()]), &command(), "serve dashboard").is_err());
+        let error = check(Some(&["txt-updte".to_string()]), &command(), "txt-update");
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            "MsgError(allow lists unknown command txt-updte, did you mean txt-update?)"
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::notify::{Event, Sink, Status};
+use crate::SelfError;
+
+// Records the events notified during a run, the updates being the changes it
+// made. Commands that only report what they would change, such as ensure
+// --check, mark the run as changed explicitly.
+#[derive(Clone, Default)]
+pub struct Recorder {
+    events: Arc<Mutex<Vec<Event>>>,
+    changed: Arc<AtomicBool>,
+}
+
+impl Recorder {
+    pub fn mark_changed(&self) {
+        self.changed.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Sink for Recorder {
+    fn name(&self) -> String {
+        "ansible".to_string()
+    }
+
+    fn send(&self, event: &Event) -> Result<(), SelfError> {
+        self.events.lock().unwrap().push(event.clone());
+        Ok(())
+    }
+}
+
+// Diff in the format of ansible's --diff, one line per record.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Diff {
+    pub before: String,
+    pub after: String,
+}
+
+// Result of a run in the JSON protocol of ansible modules: the printed output
+// of the command, if any, is kept in output.
+#[derive(Serialize, Debug)]
+pub struct Report {
+    pub changed: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub failed: bool,
+    pub msg: String,
+    pub diff: Diff,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<Value>,
+}
+
+fn host(event: &Event) -> String {
+    match event.node.as_deref() {
+        Some(node) if !node.is_empty() => format!("{}.{}", node, event.domain),
+        _ => event.domain.clone(),
+    }
+}
+
+fn diff_lines(updates: &[&Event], value: impl Fn(&Event) -> Option<&String>) -> String {
+    updates
+        .iter()
+        .filter_map(|event| {
+            value(event).map(|value| {
+                format!(
+                    "{} {} {}\n",
+                    host(event),
+                    event.record_type.as_deref().unwrap_or("-"),
+                    value
+                )
+            })
+        })
+        .collect()
+}
+
+fn describe(event: &Event) -> String {
+    let value = |value: &Option<String>| value.clone().unwrap_or("-".to_string());
+    format!(
+        "{} {}: {} -> {}",
+        event.kind,
+        host(event),
+        value(&event.old),
+        value(&event.new)
+    )
+}
+
+impl Report {
+    pub fn new(recorder: &Recorder, mut output: Vec<Value>, result: Result<(), String>) -> Report {
+        let events = recorder.events.lock().unwrap();
+        let updates: Vec<&Event> = events
+            .iter()
+            .filter(|e| e.status == Status::Updated)
+            .collect();
+        let changed = !updates.is_empty() || recorder.changed.load(Ordering::SeqCst);
+        let failed = result.is_err();
+        let msg = match result {
+            Err(e) => e,
+            Ok(()) if updates.is_empty() && changed => "changes required".to_string(),
+            Ok(()) if updates.is_empty() => "no changes".to_string(),
+            Ok(()) => updates
+                .iter()
+                .map(|e| describe(e))
+                .collect::<Vec<_>>()
+                .join("; "),
+        };
+        Report {
+            changed,
+            failed,
+            msg,
+            diff: Diff {
+                before: diff_lines(&updates, |e| e.old.as_ref()),
+                after: diff_lines(&updates, |e| e.new.as_ref()),
+            },
+            output: match output.len() {
+                0 => None,
+                1 => output.pop(),
+                _ => Some(Value::Array(output)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_should_describe_recorded_updates() {
+        let recorder = Recorder::default();
+        recorder
+            .send(
+                &Event::new("txt-update", Status::Updated, "example.dynu.net")
+                    .node("_acme-challenge")
+                    .record_type("TXT")
+                    .values(Some("old".to_string()), Some("new".to_string())),
+            )
+            .unwrap();
+        recorder
+            .send(&Event::new("refresh", Status::Failed, "other.dynu.net"))
+            .unwrap();
+        let report = Report::new(&recorder, vec![], Ok(()));
+        assert!(report.changed);
+        assert!(!report.failed);
+        assert_eq!(
+            report.msg,
+            "txt-update _acme-challenge.example.dynu.net: old -> new"
+        );
+        assert_eq!(
+            report.diff,
+            Diff {
+                before: "_acme-challenge.example.dynu.net TXT old\n".to_string(),
+                after: "_acme-challenge.example.dynu.net TXT new\n".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn report_should_keep_failures_and_output() {
+        let recorder = Recorder::default();
+        let report = Report::new(
+            &recorder,
+            vec![Value::String("listing".to_string())],
+            Err("MsgError(boom)".to_string()),
+        );
+        assert!(!report.changed);
+        assert_eq!(report.msg, "MsgError(boom)");
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["failed"], Value::Bool(true));
+        assert_eq!(json["output"], Value::String("listing".to_string()));
+
+        recorder.mark_changed();
+        let report = Report::new(&recorder, vec![], Ok(()));
+        assert!(report.changed);
+        assert_eq!(report.msg, "changes required");
+        assert!(serde_json::to_value(&report)
+            .unwrap()
+            .get("failed")
+            .is_none());
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::dynu::ApiCalls;
+
+const METRIC: &str = "dynuupdater_api_calls_total";
+
+// How many requests to dynu the command sent per endpoint, printed on stderr
+// and added to the counters of a metrics file, so that users close to the
+// quotas of dynu can tell which of their workflows spend them.
+struct Accounting {
+    command: String,
+    print: bool,
+    file: Option<PathBuf>,
+    calls: ApiCalls,
+    reported: BTreeMap<String, u64>,
+}
+
+// Global so that the requests are still reported when the command exits with
+// a specific code rather than returning.
+static ACCOUNTING: Mutex<Option<Accounting>> = Mutex::new(None);
+
+pub fn install(command: &str, print: bool, file: Option<PathBuf>, calls: ApiCalls) {
+    if !print && file.is_none() {
+        return;
+    }
+    *ACCOUNTING.lock().unwrap() = Some(Accounting {
+        command: command.to_string(),
+        print,
+        file,
+        calls,
+        reported: BTreeMap::new(),
+    });
+}
+
+// Reports the requests sent since the last report: when the command ends,
+// and after each cycle of daemon.
+pub fn flush() {
+    let mut accounting = ACCOUNTING.lock().unwrap();
+    let Some(accounting) = accounting.as_mut() else {
+        return;
+    };
+    let counts = accounting.calls.counts();
+    let sent: BTreeMap<String, u64> = counts
+        .iter()
+        .map(|(endpoint, count)| {
+            let reported = accounting.reported.get(endpoint).copied().unwrap_or(0);
+            (endpoint.clone(), count - reported)
+        })
+        .filter(|(_, count)| *count > 0)
+        .collect();
+    accounting.reported = counts;
+    if accounting.print {
+        eprintln!("{}", describe(&accounting.command, &sent));
+    }
+    if let Some(file) = &accounting.file {
+        if let Err(e) = add_to_metrics(file, &accounting.command, &sent) {
+            eprintln!("cannot write the api calls to {}: {}", file.display(), e);
+        }
+    }
+}
+
+fn describe(command: &str, sent: &BTreeMap<String, u64>) -> String {
+    let endpoints: Vec<String> = sent
+        .iter()
+        .map(|(endpoint, count)| format!("{}={}", endpoint, count))
+        .collect();
+    format!(
+        "api calls of {}: {} ({} in total)",
+        command,
+        if endpoints.is_empty() {
+            "none".to_string()
+        } else {
+            endpoints.join(" ")
+        },
+        sent.values().sum::<u64>()
+    )
+}
+
+// Counters of the file, written by earlier runs, by command and endpoint.
+// Lines which aren't such counters are dropped.
+fn parse_metrics(content: &str) -> BTreeMap<(String, String), u64> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let labels = line.strip_prefix(METRIC)?.strip_prefix("{command=\"")?;
+            let (command, labels) = labels.split_once("\",endpoint=\"")?;
+            let (endpoint, count) = labels.split_once("\"} ")?;
+            Some((
+                (command.to_string(), endpoint.to_string()),
+                count.trim().parse().ok()?,
+            ))
+        })
+        .collect()
+}
+
+// The file is in the Prometheus text format, e.g. for the textfile collector
+// of node_exporter, the counters of each run being added to the ones of the
+// earlier runs. It is rewritten through a temporary file, so that the
+// collector never reads it halfway.
+fn add_to_metrics(path: &Path, command: &str, sent: &BTreeMap<String, u64>) -> io::Result<()> {
+    let mut counters = match fs::read_to_string(path) {
+        Ok(content) => parse_metrics(&content),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(e) => return Err(e),
+    };
+    for (endpoint, count) in sent {
+        *counters
+            .entry((command.to_string(), endpoint.clone()))
+            .or_default() += count;
+    }
+    let mut content = format!(
+        "# HELP {} Requests sent to dynu, by command and endpoint.\n# TYPE {} counter\n",
+        METRIC, METRIC
+    );
+    for ((command, endpoint), count) in &counters {
+        content.push_str(&format!(
+            "{}{{command=\"{}\",endpoint=\"{}\"}} {}\n",
+            METRIC, command, endpoint, count
+        ));
+    }
+    let temporary = path.with_extension(format!("tmp.{}", std::process::id()));
+    fs::write(&temporary, content)?;
+    fs::rename(&temporary, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_to_metrics_should_sum_the_counters_of_the_runs() {
+        let path = std::env::temp_dir().join("dynu_api_calls_test.prom");
+        let _ = fs::remove_file(&path);
+        let sent = BTreeMap::from([("domains".to_string(), 1), ("records".to_string(), 2)]);
+        assert_eq!(
+            describe("refresh", &sent),
+            "api calls of refresh: domains=1 records=2 (3 in total)"
+        );
+        add_to_metrics(&path, "refresh", &sent).unwrap();
+        add_to_metrics(&path, "records dedupe", &sent).unwrap();
+        add_to_metrics(&path, "refresh", &sent).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("# TYPE dynuupdater_api_calls_total counter\n"));
+        assert!(content
+            .contains("dynuupdater_api_calls_total{command=\"refresh\",endpoint=\"records\"} 4\n"));
+        assert_eq!(
+            parse_metrics(&content).get(&("records dedupe".to_string(), "domains".to_string())),
+            Some(&1)
+        );
+        fs::remove_file(path).unwrap();
+    }
+}
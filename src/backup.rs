@@ -0,0 +1,381 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::dynu::{DomainDTO, RecordDTO};
+use crate::names::normalise_fqdn;
+use crate::output::{field_diff, Table};
+use crate::state::StateDir;
+use crate::SelfError;
+
+const BACKUP_PREFIX: &str = "backup-";
+const BACKUP_SUFFIX: &str = ".json";
+
+// Domain fields dynu changes by itself, left out of the differences.
+const IGNORED_FIELDS: [&str; 2] = ["id", "updatedOn"];
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ZoneBackup {
+    pub domain: DomainDTO,
+    pub records: Vec<RecordDTO>,
+}
+
+// Snapshot of the settings and the records of dynu domains.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Backup {
+    pub taken_at: String,
+    pub zones: Vec<ZoneBackup>,
+}
+
+impl Backup {
+    pub fn new(zones: Vec<ZoneBackup>) -> Backup {
+        Backup {
+            taken_at: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            zones,
+        }
+    }
+
+    pub fn read(path: &str) -> Result<Backup, SelfError> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| SelfError::MsgError(format!("cannot read {}: {}", path, e)))?;
+        serde_json::from_str(&content)
+            .map_err(|e| SelfError::MsgError(format!("cannot parse backup {}: {}", path, e)))
+    }
+
+    fn zone(&self, name: &str) -> Option<&ZoneBackup> {
+        let name = normalise_fqdn(name);
+        self.zones
+            .iter()
+            .find(|z| normalise_fqdn(&z.domain.name) == name)
+    }
+}
+
+// Backups taken periodically by the daemon, kept in the state directory as
+// backup-<unix time>.json, the oldest ones being deleted beyond keep.
+pub struct Rotation<'a> {
+    state: &'a StateDir,
+    keep: usize,
+}
+
+impl<'a> Rotation<'a> {
+    pub fn new(state: &'a StateDir, keep: usize) -> Rotation<'a> {
+        Rotation { state, keep }
+    }
+
+    // Times and paths of the backups, oldest first.
+    fn backups(&self) -> Result<Vec<(u64, PathBuf)>, SelfError> {
+        let dir = self.state.file("")?;
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut backups: Vec<(u64, PathBuf)> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_str()?.to_string();
+                let seconds = name
+                    .strip_prefix(BACKUP_PREFIX)?
+                    .strip_suffix(BACKUP_SUFFIX)?
+                    .parse()
+                    .ok()?;
+                Some((seconds, entry.path()))
+            })
+            .collect();
+        backups.sort();
+        Ok(backups)
+    }
+
+    pub fn last(&self) -> Result<Option<SystemTime>, SelfError> {
+        Ok(self
+            .backups()?
+            .last()
+            .map(|(seconds, _)| UNIX_EPOCH + Duration::from_secs(*seconds)))
+    }
+
+    pub fn store(&self, backup: &Backup, at: SystemTime) -> Result<PathBuf, SelfError> {
+        let seconds = at
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let name = format!("{}{}{}", BACKUP_PREFIX, seconds, BACKUP_SUFFIX);
+        self.state.write_json(&name, backup)?;
+        let backups = self.backups()?;
+        for (_, path) in backups.iter().take(backups.len().saturating_sub(self.keep)) {
+            fs::remove_file(path)?;
+        }
+        self.state.file(&name)
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Difference {
+    pub domain: String,
+    pub change: &'static str,
+    pub what: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl Difference {
+    fn new(
+        domain: &str,
+        change: &'static str,
+        what: String,
+        before: Option<String>,
+        after: Option<String>,
+    ) -> Difference {
+        Difference {
+            domain: domain.to_string(),
+            change,
+            what,
+            before,
+            after,
+        }
+    }
+}
+
+fn record_label(record: &RecordDTO) -> String {
+    format!("{} node='{}'", record.record_type(), record.node_name())
+}
+
+fn record_content(record: &RecordDTO) -> String {
+    let disabled = match record {
+        RecordDTO::TxtRecord { state, .. }
+        | RecordDTO::SpfRecord { state, .. }
+        | RecordDTO::SoaRecord { state, .. }
+        | RecordDTO::ARecord { state, .. }
+        | RecordDTO::AaaaRecord { state, .. }
+        | RecordDTO::CnameRecord { state, .. }
+        | RecordDTO::NsRecord { state, .. }
+        | RecordDTO::PtrRecord { state, .. }
+        | RecordDTO::CaaRecord { state, .. } => !state,
+        RecordDTO::Unknown { other, .. } => {
+            other.get("state").and_then(|state| state.as_bool()) == Some(false)
+        }
+    };
+    format!(
+        "{} ttl={}{}",
+        record.value(),
+        record.ttl(),
+        if disabled { " (disabled)" } else { "" }
+    )
+}
+
+fn same_record(old: &RecordDTO, new: &RecordDTO) -> bool {
+    match (old.id(), new.id()) {
+        (Some(old_id), Some(new_id)) => old_id == new_id,
+        _ => {
+            old.record_type() == new.record_type()
+                && normalise_fqdn(old.node_name()) == normalise_fqdn(new.node_name())
+                && old.value() == new.value()
+        }
+    }
+}
+
+fn field_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+pub fn zone_diff(old: &ZoneBackup, new: &ZoneBackup) -> Vec<Difference> {
+    let domain = &new.domain.name;
+    let mut differences: Vec<Difference> = field_diff(&old.domain, &new.domain)
+        .into_iter()
+        .filter(|(field, _, _)| !IGNORED_FIELDS.contains(&field.as_str()))
+        .map(|(field, before, after)| {
+            Difference::new(
+                domain,
+                "modified",
+                field,
+                Some(field_value(&before)),
+                Some(field_value(&after)),
+            )
+        })
+        .collect();
+    for record in &old.records {
+        match new.records.iter().find(|r| same_record(record, r)) {
+            None => differences.push(Difference::new(
+                domain,
+                "removed",
+                record_label(record),
+                Some(record_content(record)),
+                None,
+            )),
+            Some(current) if record_content(record) != record_content(current) => {
+                differences.push(Difference::new(
+                    domain,
+                    "modified",
+                    record_label(current),
+                    Some(record_content(record)),
+                    Some(record_content(current)),
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+    for record in &new.records {
+        if !old.records.iter().any(|r| same_record(r, record)) {
+            differences.push(Difference::new(
+                domain,
+                "added",
+                record_label(record),
+                None,
+                Some(record_content(record)),
+            ));
+        }
+    }
+    differences
+}
+
+// What changed from the old to the new backup, domains being matched by name
+// and records by id.
+pub fn diff(old: &Backup, new: &Backup) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    for zone in &old.zones {
+        if new.zone(&zone.domain.name).is_none() {
+            differences.push(Difference::new(
+                &zone.domain.name,
+                "removed",
+                "domain".to_string(),
+                Some(format!("{} records", zone.records.len())),
+                None,
+            ));
+        }
+    }
+    for zone in &new.zones {
+        match old.zone(&zone.domain.name) {
+            Some(previous) => differences.extend(zone_diff(previous, zone)),
+            None => differences.push(Difference::new(
+                &zone.domain.name,
+                "added",
+                "domain".to_string(),
+                None,
+                Some(format!("{} records", zone.records.len())),
+            )),
+        }
+    }
+    differences
+}
+
+pub fn diff_table(differences: &[Difference]) -> Table {
+    let mut table = Table::new(vec!["DOMAIN", "CHANGE", "WHAT", "BEFORE", "AFTER"]);
+    for difference in differences {
+        table.push(vec![
+            difference.domain.clone(),
+            difference.change.to_string(),
+            difference.what.clone(),
+            difference.before.clone().unwrap_or_else(|| "-".to_string()),
+            difference.after.clone().unwrap_or_else(|| "-".to_string()),
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zone(name: &str, ttl: u64, records: Vec<RecordDTO>) -> ZoneBackup {
+        ZoneBackup {
+            domain: DomainDTO {
+                name: name.to_string(),
+                ttl,
+                ..Default::default()
+            },
+            records,
+        }
+    }
+
+    #[test]
+    fn diff_should_report_records_and_settings() {
+        let old = Backup {
+            taken_at: "2024-05-01T10:00:00Z".to_string(),
+            zones: vec![
+                zone(
+                    "example.dynu.net",
+                    300,
+                    vec![
+                        RecordDTO::a_record("www", "192.0.2.1", 300, Some(1)),
+                        RecordDTO::txt_record("", "old", 300, Some(2)),
+                    ],
+                ),
+                zone("gone.dynu.net", 300, vec![]),
+            ],
+        };
+        let new = Backup {
+            taken_at: "2024-05-02T10:00:00Z".to_string(),
+            zones: vec![zone(
+                "example.dynu.net",
+                60,
+                vec![
+                    RecordDTO::a_record("www", "192.0.2.2", 300, Some(1)),
+                    RecordDTO::txt_record("", "new", 300, Some(3)),
+                ],
+            )],
+        };
+        let changes: Vec<(String, &str, String)> = diff(&old, &new)
+            .into_iter()
+            .map(|d| (d.domain, d.change, d.what))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("gone.dynu.net".to_string(), "removed", "domain".to_string()),
+                (
+                    "example.dynu.net".to_string(),
+                    "modified",
+                    "ttl".to_string()
+                ),
+                (
+                    "example.dynu.net".to_string(),
+                    "modified",
+                    "A node='www'".to_string()
+                ),
+                (
+                    "example.dynu.net".to_string(),
+                    "removed",
+                    "TXT node=''".to_string()
+                ),
+                (
+                    "example.dynu.net".to_string(),
+                    "added",
+                    "TXT node=''".to_string()
+                ),
+            ]
+        );
+        assert!(diff(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn rotation_should_keep_the_latest_backups() {
+        let dir = std::env::temp_dir().join("dynu_backup_rotation_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let state = StateDir::new(dir.to_str());
+        let rotation = Rotation::new(&state, 2);
+        assert_eq!(rotation.last().unwrap(), None);
+        let backup = Backup::new(vec![]);
+        for seconds in [1_700_000_000, 1_700_086_400, 1_700_172_800] {
+            rotation
+                .store(&backup, UNIX_EPOCH + Duration::from_secs(seconds))
+                .unwrap();
+        }
+        let kept: Vec<u64> = rotation
+            .backups()
+            .unwrap()
+            .into_iter()
+            .map(|(s, _)| s)
+            .collect();
+        assert_eq!(kept, vec![1_700_086_400, 1_700_172_800]);
+        assert_eq!(
+            rotation.last().unwrap(),
+            Some(UNIX_EPOCH + Duration::from_secs(1_700_172_800))
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::dynu::{DomainDTO, RecordDTO};
+use crate::names::normalise_fqdn;
+use crate::state::StateDir;
+use crate::SelfError;
+
+const CACHE_FILE: &str = "zone-cache.json";
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Cached<T> {
+    pub fetched_at: u64,
+    // updatedOn of the domain when the data was fetched, to tell whether it
+    // can be reused without downloading it again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_on: Option<String>,
+    pub data: T,
+}
+
+impl<T> Cached<T> {
+    fn now(data: T) -> Cached<T> {
+        Cached {
+            fetched_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            updated_on: None,
+            data,
+        }
+    }
+
+    pub fn fetched_at(&self) -> String {
+        humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(self.fetched_at))
+            .to_string()
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+struct Snapshot {
+    domains: Option<Cached<Vec<DomainDTO>>>,
+    #[serde(default)]
+    records: BTreeMap<String, Cached<Vec<RecordDTO>>>,
+}
+
+// Last domains and records fetched from Dynu, kept in the state directory so
+// that they can be inspected while offline.
+pub struct ZoneCache<'a> {
+    state: &'a StateDir,
+}
+
+impl<'a> ZoneCache<'a> {
+    pub fn new(state: &'a StateDir) -> ZoneCache<'a> {
+        ZoneCache { state }
+    }
+
+    fn snapshot(&self) -> Result<Snapshot, SelfError> {
+        Ok(self.state.read_json(CACHE_FILE)?.unwrap_or_default())
+    }
+
+    pub fn store_domains(&self, domains: Vec<DomainDTO>) -> Result<(), SelfError> {
+        let mut snapshot = self.snapshot()?;
+        snapshot.domains = Some(Cached::now(domains));
+        self.state.write_json(CACHE_FILE, &snapshot)
+    }
+
+    pub fn store_records(
+        &self,
+        domain: &str,
+        updated_on: Option<String>,
+        records: Vec<RecordDTO>,
+    ) -> Result<(), SelfError> {
+        let mut snapshot = self.snapshot()?;
+        let mut cached = Cached::now(records);
+        cached.updated_on = updated_on;
+        snapshot.records.insert(normalise_fqdn(domain), cached);
+        self.state.write_json(CACHE_FILE, &snapshot)
+    }
+
+    pub fn forget_records(&self, domain: &str) -> Result<(), SelfError> {
+        let mut snapshot = self.snapshot()?;
+        if snapshot.records.remove(&normalise_fqdn(domain)).is_some() {
+            self.state.write_json(CACHE_FILE, &snapshot)?;
+        }
+        Ok(())
+    }
+
+    // The cached records of the domain, when Dynu reports it unchanged since
+    // they were fetched.
+    pub fn current_records(
+        &self,
+        domain: &str,
+        updated_on: Option<&str>,
+    ) -> Result<Option<Vec<RecordDTO>>, SelfError> {
+        let Some(updated_on) = updated_on else {
+            return Ok(None);
+        };
+        Ok(self
+            .snapshot()?
+            .records
+            .remove(&normalise_fqdn(domain))
+            .filter(|cached| cached.updated_on.as_deref() == Some(updated_on))
+            .map(|cached| cached.data))
+    }
+
+    pub fn domains(&self) -> Result<Cached<Vec<DomainDTO>>, SelfError> {
+        self.snapshot()?.domains.ok_or_else(|| {
+            SelfError::MsgError("no cached domains, run 'domains' while online first".to_string())
+        })
+    }
+
+    pub fn records(&self, domain: &str) -> Result<Cached<Vec<RecordDTO>>, SelfError> {
+        self.snapshot()?
+            .records
+            .remove(&normalise_fqdn(domain))
+            .ok_or_else(|| {
+                SelfError::MsgError(format!(
+                    "no cached records for domain={}, run 'records {}' while online first",
+                    domain, domain
+                ))
+            })
+    }
+}
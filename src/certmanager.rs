@@ -0,0 +1,205 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tiny_http::{Method, Request};
+
+use crate::acme;
+use crate::dynu::DynuClient;
+use crate::server::{self, ServerOptions};
+use crate::SelfError;
+
+const SOLVER_VERSION: &str = "v1alpha1";
+const PAYLOAD_API_VERSION: &str = "webhook.acme.cert-manager.io/v1alpha1";
+
+pub struct CertManagerOptions {
+    pub server: ServerOptions,
+    pub group_name: String,
+    pub solver_name: String,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct SolverConfig {
+    ttl: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ChallengeRequest {
+    uid: String,
+    action: String,
+    #[serde(rename = "type")]
+    challenge_type: String,
+    key: String,
+    #[serde(rename = "resolvedFQDN")]
+    resolved_fqdn: String,
+    resolved_zone: String,
+    config: Option<SolverConfig>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChallengePayload {
+    request: ChallengeRequest,
+}
+
+#[derive(Serialize, Debug)]
+struct ChallengeStatus {
+    message: String,
+}
+
+#[derive(Serialize, Debug)]
+struct ChallengeResponse {
+    uid: String,
+    success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<ChallengeStatus>,
+}
+
+fn solve(dynu_client: &DynuClient, request: &ChallengeRequest) -> Result<(), SelfError> {
+    if request.challenge_type != "dns-01" {
+        return Err(SelfError::MsgError(format!(
+            "unsupported challenge type {}",
+            request.challenge_type
+        )));
+    }
+    let zone = Some(request.resolved_zone.as_str());
+    match request.action.as_str() {
+        "Present" => {
+            let ttl = request
+                .config
+                .as_ref()
+                .and_then(|c| c.ttl)
+                .unwrap_or(acme::DEFAULT_CHALLENGE_TTL);
+            acme::present(dynu_client, &request.resolved_fqdn, zone, &request.key, ttl)
+        }
+        "CleanUp" => acme::cleanup(dynu_client, &request.resolved_fqdn, zone, &request.key),
+        other => Err(SelfError::MsgError(format!("unsupported action {}", other))),
+    }
+}
+
+fn handle_challenge(dynu_client: &DynuClient, request: &mut Request) -> (u16, serde_json::Value) {
+    let mut body = String::new();
+    if let Err(e) = request.as_reader().read_to_string(&mut body) {
+        return (
+            400,
+            json!({ "message": format!("cannot read body: {}", e) }),
+        );
+    }
+    let payload: ChallengePayload = match serde_json::from_str(&body) {
+        Ok(payload) => payload,
+        Err(e) => return (400, json!({ "message": format!("invalid payload: {}", e) })),
+    };
+    eprintln!(
+        "cert-manager {} for fqdn={} zone={} uid={}",
+        payload.request.action,
+        payload.request.resolved_fqdn,
+        payload.request.resolved_zone,
+        payload.request.uid
+    );
+    let response = match solve(dynu_client, &payload.request) {
+        Ok(()) => ChallengeResponse {
+            uid: payload.request.uid,
+            success: true,
+            status: None,
+        },
+        Err(e) => {
+            eprintln!("cert-manager {} failed: {}", payload.request.action, e);
+            ChallengeResponse {
+                uid: payload.request.uid,
+                success: false,
+                status: Some(ChallengeStatus {
+                    message: format!("{}", e),
+                }),
+            }
+        }
+    };
+    (
+        200,
+        json!({
+            "apiVersion": PAYLOAD_API_VERSION,
+            "kind": "ChallengePayload",
+            "response": response,
+        }),
+    )
+}
+
+fn group_version(options: &CertManagerOptions) -> String {
+    format!("{}/{}", options.group_name, SOLVER_VERSION)
+}
+
+fn api_group(options: &CertManagerOptions) -> serde_json::Value {
+    let version = json!({
+        "groupVersion": group_version(options),
+        "version": SOLVER_VERSION,
+    });
+    json!({
+        "kind": "APIGroup",
+        "apiVersion": "v1",
+        "name": options.group_name,
+        "versions": [version],
+        "preferredVersion": version,
+    })
+}
+
+fn route(
+    dynu_client: &DynuClient,
+    options: &CertManagerOptions,
+    request: &mut Request,
+) -> (u16, serde_json::Value) {
+    let path = request.url().split('?').next().unwrap_or("").to_string();
+    let group_path = format!("/apis/{}", options.group_name);
+    let version_path = format!("{}/{}", group_path, SOLVER_VERSION);
+    let solver_path = format!("{}/{}", version_path, options.solver_name);
+    match (request.method(), path.as_str()) {
+        (Method::Get, "/healthz" | "/livez" | "/readyz") => (200, json!({ "status": "ok" })),
+        _ if !options.server.authorized(request) => {
+            (401, json!({ "message": "missing or invalid credentials" }))
+        }
+        (Method::Get, "/apis") => (
+            200,
+            json!({ "kind": "APIGroupList", "apiVersion": "v1", "groups": [api_group(options)] }),
+        ),
+        (Method::Get, p) if p == group_path => (200, api_group(options)),
+        (Method::Get, p) if p == version_path => (
+            200,
+            json!({
+                "kind": "APIResourceList",
+                "apiVersion": "v1",
+                "groupVersion": group_version(options),
+                "resources": [{
+                    "name": options.solver_name,
+                    "singularName": options.solver_name,
+                    "namespaced": false,
+                    "kind": "ChallengePayload",
+                    "verbs": ["create"],
+                }],
+            }),
+        ),
+        (Method::Post, p) if p == solver_path => handle_challenge(dynu_client, request),
+        _ => (404, json!({ "message": format!("{} not found", path) })),
+    }
+}
+
+// Serves the cert-manager DNS-01 webhook solver API. The process is meant to be
+// registered as an APIService in the cluster, so that the apiserver forwards
+// the ChallengePayload requests of the issuers using the solver.
+pub fn serve(dynu_client: DynuClient, options: CertManagerOptions) -> Result<(), SelfError> {
+    let server = options.server.bind()?;
+    eprintln!(
+        "serving cert-manager solver {}/{} on {}://{}",
+        group_version(&options),
+        options.solver_name,
+        options.server.scheme(),
+        options.server.listen
+    );
+    for mut request in server.incoming_requests() {
+        let (status, body) = route(&dynu_client, &options, &mut request);
+        let response = match status {
+            401 => options.server.unauthorized(),
+            _ => server::json_response(status, &body),
+        };
+        if let Err(e) = request.respond(response) {
+            eprintln!("cannot send response: {}", e);
+        }
+    }
+    Ok(())
+}
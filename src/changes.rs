@@ -0,0 +1,391 @@
+use serde::Serialize;
+use std::fmt;
+
+use crate::content::same_address;
+use crate::netutils::Addresses;
+use crate::notify::{Event, Status};
+use crate::output::Table;
+use crate::SelfError;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Family {
+    Ipv4,
+    Ipv6,
+}
+
+impl Family {
+    pub fn record_type(&self) -> &'static str {
+        match self {
+            Family::Ipv4 => "A",
+            Family::Ipv6 => "AAAA",
+        }
+    }
+
+    // Field of the records of the family holding the address.
+    pub fn address_field(&self) -> &'static str {
+        match self {
+            Family::Ipv4 => "ipv4Address",
+            Family::Ipv6 => "ipv6Address",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Unchanged,
+    Add,
+    Update,
+    Remove,
+}
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Unchanged => "unchanged",
+            Action::Add => "add",
+            Action::Update => "update",
+            Action::Remove => "remove",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FamilyChange {
+    pub family: Family,
+    pub old: Option<String>,
+    pub new: Option<String>,
+    pub action: Action,
+    // why the family could not be updated, its address being left as it was
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl FamilyChange {
+    pub fn new(family: Family, old: Option<String>, new: Option<String>) -> FamilyChange {
+        let action = match (&old, &new) {
+            _ if same_address(&old, &new) => Action::Unchanged,
+            (None, Some(_)) => Action::Add,
+            (Some(_), None) => Action::Remove,
+            _ => Action::Update,
+        };
+        FamilyChange {
+            family,
+            old,
+            new,
+            action,
+            error: None,
+        }
+    }
+}
+
+// Outcome of refreshing a domain: the addresses it resolved to, the addresses
+// detected for the host and what had to be done for each family.
+#[derive(Serialize, Debug, Clone)]
+pub struct ChangeSet {
+    pub domain: String,
+    pub changes: Vec<FamilyChange>,
+    pub applied: bool,
+    // updated although unchanged, its last update being older than --max-age
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub forced: bool,
+}
+
+impl ChangeSet {
+    pub fn new(domain: &str, resolved: Addresses, detected: &Addresses) -> ChangeSet {
+        ChangeSet {
+            domain: domain.to_string(),
+            changes: vec![
+                FamilyChange::new(Family::Ipv4, resolved.v4, detected.v4.clone()),
+                FamilyChange::new(Family::Ipv6, resolved.v6, detected.v6.clone()),
+            ],
+            applied: false,
+            forced: false,
+        }
+    }
+
+    pub fn is_unchanged(&self) -> bool {
+        self.changes.iter().all(|c| c.action == Action::Unchanged)
+    }
+
+    pub fn fail(&mut self, family: Family, error: String) {
+        for change in self.changes.iter_mut().filter(|c| c.family == family) {
+            change.error = Some(error.clone());
+        }
+    }
+
+    pub fn failed_families(&self) -> Vec<Family> {
+        self.changes
+            .iter()
+            .filter(|c| c.error.is_some())
+            .map(|c| c.family)
+            .collect()
+    }
+
+    // Whether some family has to be updated and can be.
+    pub fn has_applicable(&self) -> bool {
+        self.changes
+            .iter()
+            .any(|c| c.action != Action::Unchanged && c.error.is_none())
+    }
+
+    // Describes the families which could not be updated, None when there is
+    // none.
+    pub fn failure(&self) -> Option<String> {
+        let failures: Vec<String> = self
+            .changes
+            .iter()
+            .filter_map(|c| {
+                c.error
+                    .as_ref()
+                    .map(|e| format!("{} not updated: {}", c.family.record_type(), e))
+            })
+            .collect();
+        (!failures.is_empty()).then(|| failures.join("; "))
+    }
+
+    pub fn events(&self) -> Vec<Event> {
+        self.changes
+            .iter()
+            .filter(|c| c.action != Action::Unchanged)
+            .map(|c| {
+                let status = match c.error {
+                    Some(_) => Status::Failed,
+                    None => Status::Updated,
+                };
+                let mut event = Event::new("refresh", status, &self.domain)
+                    .record_type(c.family.record_type())
+                    .values(c.old.clone(), c.new.clone());
+                event.error = c.error.clone();
+                event
+            })
+            .collect()
+    }
+}
+
+// Outcome of a domain of refresh --all: skipped domains needed no update, or
+// were not updated because of --dry-run.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Succeeded,
+    Partial,
+    Failed,
+    Skipped,
+}
+
+impl Outcome {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Outcome::Succeeded => "succeeded",
+            Outcome::Partial => "partial",
+            Outcome::Failed => "failed",
+            Outcome::Skipped => "skipped",
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct DomainResult {
+    pub domain: String,
+    pub outcome: Outcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change_set: Option<ChangeSet>,
+}
+
+impl DomainResult {
+    pub fn new(domain: &str, result: Result<ChangeSet, SelfError>) -> DomainResult {
+        match result {
+            Ok(change_set) => DomainResult {
+                domain: domain.to_string(),
+                outcome: match change_set.failure() {
+                    Some(_) => Outcome::Partial,
+                    None if change_set.applied => Outcome::Succeeded,
+                    None => Outcome::Skipped,
+                },
+                error: change_set.failure(),
+                change_set: Some(change_set),
+            },
+            Err(e) => DomainResult {
+                domain: domain.to_string(),
+                outcome: Outcome::Failed,
+                error: Some(format!("{}", e)),
+                change_set: None,
+            },
+        }
+    }
+}
+
+pub fn results_table(results: &[DomainResult]) -> Table {
+    let mut table = Table::new(vec!["DOMAIN", "RESULT", "DETAILS"]);
+    for result in results {
+        let details = match (&result.error, &result.change_set) {
+            (Some(error), _) => error.clone(),
+            (None, Some(change_set)) => change_set.describe_changes(),
+            (None, None) => "-".to_string(),
+        };
+        table.push(vec![
+            result.domain.clone(),
+            result.outcome.name().to_string(),
+            details,
+        ]);
+    }
+    table
+}
+
+impl ChangeSet {
+    pub fn describe_changes(&self) -> String {
+        let or_none = |value: &Option<String>| value.clone().unwrap_or("none".to_string());
+        let changes: Vec<String> = self
+            .changes
+            .iter()
+            .map(|c| match c.action {
+                _ if c.error.is_some() => format!(
+                    "{}={} (kept, {} failed)",
+                    c.family.record_type(),
+                    or_none(&c.old),
+                    c.action.name()
+                ),
+                Action::Unchanged => {
+                    format!("{}={} (unchanged)", c.family.record_type(), or_none(&c.new))
+                }
+                _ => format!(
+                    "{}={} -> {} ({})",
+                    c.family.record_type(),
+                    or_none(&c.old),
+                    or_none(&c.new),
+                    c.action.name()
+                ),
+            })
+            .collect();
+        changes.join(", ")
+    }
+}
+
+impl fmt::Display for ChangeSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "domain={} {}", self.domain, self.describe_changes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn some(value: &str) -> Option<String> {
+        Some(value.to_string())
+    }
+
+    #[test]
+    fn change_set_should_classify_each_family() {
+        let resolved = Addresses {
+            v4: some("192.0.2.1"),
+            v6: None,
+        };
+        let detected = Addresses {
+            v4: some("192.0.2.2"),
+            v6: some("2001:db8::1"),
+        };
+        let change_set = ChangeSet::new("example.dynu.net", resolved, &detected);
+        let actions: Vec<Action> = change_set.changes.iter().map(|c| c.action).collect();
+        assert_eq!(actions, vec![Action::Update, Action::Add]);
+        assert_eq!(
+            change_set.to_string(),
+            "domain=example.dynu.net A=192.0.2.1 -> 192.0.2.2 (update), AAAA=none -> 2001:db8::1 (add)"
+        );
+        assert_eq!(change_set.events().len(), 2);
+    }
+
+    #[test]
+    fn change_set_should_be_unchanged_for_equivalent_addresses() {
+        let resolved = Addresses {
+            v4: None,
+            v6: some("2001:db8::1"),
+        };
+        let detected = Addresses {
+            v4: None,
+            v6: some("2001:0DB8:0:0:0:0:0:1"),
+        };
+        let change_set = ChangeSet::new("example.dynu.net", resolved, &detected);
+        assert!(change_set.is_unchanged());
+        assert!(change_set.events().is_empty());
+    }
+
+    #[test]
+    fn change_set_should_report_failed_families() {
+        let resolved = Addresses {
+            v4: some("192.0.2.1"),
+            v6: some("2001:db8::1"),
+        };
+        let detected = Addresses {
+            v4: some("192.0.2.2"),
+            v6: some("2001:db8::2"),
+        };
+        let mut change_set = ChangeSet::new("example.dynu.net", resolved, &detected);
+        assert_eq!(change_set.failure(), None);
+        change_set.fail(Family::Ipv6, "port 443 unreachable".to_string());
+        assert!(change_set.has_applicable());
+        assert_eq!(change_set.failed_families(), vec![Family::Ipv6]);
+        assert_eq!(
+            change_set.failure().as_deref(),
+            Some("AAAA not updated: port 443 unreachable")
+        );
+        assert_eq!(
+            change_set.to_string(),
+            "domain=example.dynu.net A=192.0.2.1 -> 192.0.2.2 (update), AAAA=2001:db8::1 (kept, update failed)"
+        );
+        let statuses: Vec<Status> = change_set
+            .events()
+            .iter()
+            .map(|e| e.status.clone())
+            .collect();
+        assert_eq!(statuses, vec![Status::Updated, Status::Failed]);
+
+        change_set.fail(Family::Ipv4, "port 443 unreachable".to_string());
+        assert!(!change_set.has_applicable());
+    }
+
+    #[test]
+    fn results_table_should_summarise_each_domain() {
+        let detected = || Addresses {
+            v4: some("192.0.2.2"),
+            v6: None,
+        };
+        let resolved = Addresses {
+            v4: some("192.0.2.1"),
+            v6: None,
+        };
+        let mut applied = ChangeSet::new("a.dynu.net", resolved, &detected());
+        applied.applied = true;
+        let skipped = ChangeSet::new("b.dynu.net", detected(), &detected());
+        let results = vec![
+            DomainResult::new("a.dynu.net", Ok(applied)),
+            DomainResult::new("b.dynu.net", Ok(skipped)),
+            DomainResult::new(
+                "c.dynu.net",
+                Err(SelfError::MsgError("dynu is down".to_string())),
+            ),
+        ];
+        let outcomes: Vec<Outcome> = results.iter().map(|r| r.outcome).collect();
+        assert_eq!(
+            outcomes,
+            vec![Outcome::Succeeded, Outcome::Skipped, Outcome::Failed]
+        );
+        let table = results_table(&results);
+        assert_eq!(
+            table.rows[0],
+            vec![
+                "a.dynu.net",
+                "succeeded",
+                "A=192.0.2.1 -> 192.0.2.2 (update), AAAA=none (unchanged)"
+            ]
+        );
+        assert_eq!(
+            table.rows[2],
+            vec!["c.dynu.net", "failed", "MsgError(dynu is down)"]
+        );
+    }
+}
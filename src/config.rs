@@ -0,0 +1,744 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
+
+use crate::alias::Alias;
+use crate::dynu;
+use crate::templates::TxtTemplate;
+use crate::SelfError;
+
+pub const CONFIG_VERSION: i64 = 1;
+const CONFIG_FILE: &str = "config.toml";
+
+// Migrations of the configuration file, MIGRATIONS[n] turning version n into
+// version n + 1. Files without the version key are version 0.
+const MIGRATIONS: [fn(&mut toml::Table); 1] = [unversioned];
+
+// Files written before the version key was introduced have the layout of
+// version 1.
+fn unversioned(_: &mut toml::Table) {}
+
+// Settings carrying secrets can be provided either as NAME or as NAME_FILE, the
+// latter pointing to a file whose content is the value (the Docker/Kubernetes
+// secrets convention). When both are defined, the plain variable wins.
+pub fn secret_env(name: &str) -> io::Result<Option<String>> {
+    if let Ok(value) = env::var(name) {
+        return Ok(Some(value));
+    }
+    let file_var = format!("{}_FILE", name);
+    match env::var(&file_var) {
+        Ok(path) => read_secret_file(&path)
+            .map(Some)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}={}: {}", file_var, path, e))),
+        Err(_) => Ok(None),
+    }
+}
+
+pub fn read_secret_file(path: &str) -> io::Result<String> {
+    let content = fs::read_to_string(path)?;
+    Ok(content.trim_end_matches(['\r', '\n']).to_string())
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .map(|base| base.join("dynuupdater"))
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: Option<String>,
+    pub template: Option<String>,
+    pub content_type: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct MqttConfig {
+    pub url: Option<String>,
+    pub discovery_prefix: Option<String>,
+}
+
+// Limits of the requests sent to dynu, budgets being keyed by endpoint.
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ClientConfig {
+    pub max_concurrent: Option<usize>,
+    #[serde(default)]
+    pub budgets: BTreeMap<String, u32>,
+}
+
+impl ClientConfig {
+    // The limits of the client, every budget naming a known endpoint and
+    // allowing at least one request per minute.
+    pub fn limits(&self) -> Result<dynu::Limits, SelfError> {
+        if let Some(endpoint) = self
+            .budgets
+            .keys()
+            .find(|endpoint| !dynu::ENDPOINTS.contains(&endpoint.as_str()))
+        {
+            return Err(SelfError::MsgError(format!(
+                "unknown endpoint {} in client.budgets, expected one of {}",
+                endpoint,
+                dynu::ENDPOINTS.join(", ")
+            )));
+        }
+        if let Some((endpoint, _)) = self.budgets.iter().find(|(_, budget)| **budget == 0) {
+            return Err(SelfError::MsgError(format!(
+                "budget of {} in client.budgets must allow at least one request per minute",
+                endpoint
+            )));
+        }
+        Ok(dynu::Limits {
+            max_concurrent: self.max_concurrent,
+            budgets: self.budgets.clone(),
+        })
+    }
+}
+
+// Values used by the record commands when they are not given on the command
+// line, resolvers being the ones checked by the verifications.
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct DefaultsConfig {
+    pub domain: Option<String>,
+    pub ttl: Option<u64>,
+    pub node: Option<String>,
+    pub resolvers: Option<Vec<String>>,
+}
+
+impl DefaultsConfig {
+    // The domain and node a record command targets. The default node only
+    // applies to the default domain: a domain given on the command line may be
+    // a full hostname, whose node is found by splitting it.
+    pub fn host(
+        &self,
+        domain: Option<String>,
+        node: Option<String>,
+    ) -> Option<(String, Option<String>)> {
+        match domain {
+            Some(domain) => Some((domain, node)),
+            None => self
+                .domain
+                .clone()
+                .map(|domain| (domain, node.or(self.node.clone()))),
+        }
+    }
+}
+
+// A domain refreshed by refresh and daemon when no domain is given, with the
+// TTL to set along its addresses and the TXT values to keep on its nodes ("@"
+// being the domain itself). With metadata, every update of the domain is
+// recorded in the TXT record of its _dynuupdater node. With lease, e.g. "10m",
+// the domain is only refreshed by the host holding its lease.
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DomainConfig {
+    pub ttl: Option<u64>,
+    #[serde(default)]
+    pub txt: BTreeMap<String, String>,
+    #[serde(default)]
+    pub metadata: bool,
+    pub lease: Option<String>,
+}
+
+// Settings read from the configuration file, used when neither the command
+// line nor the environment provide them.
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub version: i64,
+    pub api_key: Option<String>,
+    pub api_key_file: Option<String>,
+    pub state_dir: Option<String>,
+    pub api_calls_file: Option<String>,
+    pub ip_source: Option<String>,
+    pub ip_source_timeout: Option<String>,
+    pub user_agent: Option<String>,
+    pub read_only: Option<bool>,
+    pub allow: Option<Vec<String>>,
+    #[serde(default)]
+    pub webhook: WebhookConfig,
+    #[serde(default)]
+    pub mqtt: MqttConfig,
+    #[serde(default)]
+    pub defaults: DefaultsConfig,
+    #[serde(default)]
+    pub client: ClientConfig,
+    #[serde(default)]
+    pub domains: BTreeMap<String, DomainConfig>,
+    #[serde(default)]
+    pub aliases: BTreeMap<String, Alias>,
+    #[serde(default)]
+    pub txt_templates: BTreeMap<String, TxtTemplate>,
+}
+
+fn version_of(table: &toml::Table) -> Result<i64, SelfError> {
+    match table.get("version") {
+        None => Ok(0),
+        Some(toml::Value::Integer(version)) if (0..=CONFIG_VERSION).contains(version) => {
+            Ok(*version)
+        }
+        Some(toml::Value::Integer(version)) => Err(SelfError::MsgError(format!(
+            "configuration version {} is newer than the supported version {}, upgrade dynuupdater",
+            version, CONFIG_VERSION
+        ))),
+        Some(other) => Err(SelfError::MsgError(format!(
+            "configuration version must be an integer, found {}",
+            other
+        ))),
+    }
+}
+
+// Brings the table to CONFIG_VERSION, returning the version it had.
+pub fn migrate(table: &mut toml::Table) -> Result<i64, SelfError> {
+    let original = version_of(table)?;
+    for version in original..CONFIG_VERSION {
+        MIGRATIONS[version as usize](table);
+        table.insert("version".to_string(), toml::Value::Integer(version + 1));
+    }
+    Ok(original)
+}
+
+pub fn config_path(path: Option<&str>) -> Option<PathBuf> {
+    path.map(PathBuf::from)
+        .or_else(|| config_dir().map(|d| d.join(CONFIG_FILE)))
+}
+
+fn read_table(path: &Path) -> Result<toml::Table, SelfError> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| SelfError::MsgError(format!("cannot read {}: {}", path.display(), e)))?;
+    content
+        .parse::<toml::Table>()
+        .map_err(|e| SelfError::MsgError(format!("cannot parse {}: {}", path.display(), e)))
+}
+
+fn merge(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overrides)) => {
+                merge(base, overrides)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn wildcard_match(pattern: &[char], name: &[char]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some('*'), _) => {
+            wildcard_match(&pattern[1..], name)
+                || (!name.is_empty() && wildcard_match(pattern, &name[1..]))
+        }
+        (Some('?'), Some(_)) => wildcard_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => wildcard_match(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+// Files matching an include pattern, whose file name may hold the * and ?
+// wildcards, sorted by name. Hidden files, such as editor backups, only match
+// patterns starting with a dot.
+fn expand_include(dir: &Path, pattern: &str) -> Result<Vec<PathBuf>, SelfError> {
+    let path = dir.join(pattern);
+    let name: Vec<char> = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default()
+        .chars()
+        .collect();
+    if !name.contains(&'*') && !name.contains(&'?') {
+        return Ok(vec![path]);
+    }
+    let parent = path.parent().unwrap_or(dir);
+    let entries = fs::read_dir(parent)
+        .map_err(|e| SelfError::MsgError(format!("cannot read {}: {}", parent.display(), e)))?;
+    let mut matches: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|p| p.is_file())
+        .filter(|p| {
+            p.file_name().and_then(|n| n.to_str()).is_some_and(|n| {
+                (name.first() == Some(&'.') || !n.starts_with('.'))
+                    && wildcard_match(&name, &n.chars().collect::<Vec<char>>())
+            })
+        })
+        .collect();
+    matches.sort();
+    Ok(matches)
+}
+
+fn include_into(
+    table: &mut toml::Table,
+    path: &Path,
+    including: &mut Vec<PathBuf>,
+) -> Result<(), SelfError> {
+    let patterns = match table.remove("include") {
+        None => return Ok(()),
+        Some(toml::Value::Array(patterns)) => patterns,
+        Some(_) => {
+            return Err(SelfError::MsgError(format!(
+                "include of {} must be an array of paths",
+                path.display()
+            )))
+        }
+    };
+    let canonical = path.canonicalize().unwrap_or(path.to_path_buf());
+    if including.contains(&canonical) {
+        return Err(SelfError::MsgError(format!(
+            "include cycle through {}",
+            path.display()
+        )));
+    }
+    including.push(canonical);
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut included = toml::Table::new();
+    for pattern in patterns {
+        let toml::Value::String(pattern) = pattern else {
+            return Err(SelfError::MsgError(format!(
+                "include of {} must be an array of paths",
+                path.display()
+            )));
+        };
+        for fragment_path in expand_include(dir, &pattern)? {
+            let mut fragment = read_table(&fragment_path)?;
+            migrate(&mut fragment)?;
+            fragment.remove("version");
+            include_into(&mut fragment, &fragment_path, including)?;
+            merge(&mut included, fragment);
+        }
+    }
+    including.pop();
+    let own = std::mem::replace(table, included);
+    merge(table, own);
+    Ok(())
+}
+
+// The files listed in include, relative to the including file and possibly
+// with wildcards, are merged in order, the matches of a pattern by name, and
+// the settings of the including file last: a host file can include a shared
+// base and the zone files of a directory, and still override them.
+pub fn resolve_includes(table: &mut toml::Table, path: &Path) -> Result<(), SelfError> {
+    include_into(table, path, &mut Vec::new())
+}
+
+// Sections of tables holding settings which override the top-level ones when
+// selected by name.
+const PROFILES: &str = "profiles";
+const OVERRIDES: &str = "overrides";
+
+fn select_section(
+    table: &mut toml::Table,
+    section: &str,
+    kind: &str,
+    selected: Option<&str>,
+) -> Result<(), SelfError> {
+    let sections = match table.remove(section) {
+        None => toml::Table::new(),
+        Some(toml::Value::Table(sections)) => sections,
+        Some(_) => {
+            return Err(SelfError::MsgError(format!(
+                "{} must be a table of {}s",
+                section, kind
+            )))
+        }
+    };
+    let Some(name) = selected else {
+        return Ok(());
+    };
+    match sections.get(name) {
+        Some(toml::Value::Table(overrides)) => {
+            if [PROFILES, OVERRIDES, "version", "include"]
+                .iter()
+                .any(|key| overrides.contains_key(*key))
+            {
+                return Err(SelfError::MsgError(format!(
+                    "{} {} cannot set version, include, profiles or overrides",
+                    kind, name
+                )));
+            }
+            // a profile providing the API key in one form replaces both forms
+            for (key, alternative) in [("api_key", "api_key_file"), ("api_key_file", "api_key")] {
+                if overrides.contains_key(key) && !overrides.contains_key(alternative) {
+                    table.remove(alternative);
+                }
+            }
+            merge(table, overrides.clone());
+            Ok(())
+        }
+        Some(_) => Err(SelfError::MsgError(format!(
+            "{} {} must be a table",
+            kind, name
+        ))),
+        None => Err(SelfError::MsgError(format!(
+            "{} {} is not defined in the configuration{}",
+            kind,
+            name,
+            crate::suggest::did_you_mean(name, sections.keys().map(|k| k.as_str()))
+        ))),
+    }
+}
+
+// The [profiles.<name>] tables hold settings overriding the top-level ones
+// when the profile is selected, e.g. the API key of another account. The
+// profiles are removed from the table, which is left with the settings in
+// effect.
+pub fn select_profile(table: &mut toml::Table, profile: Option<&str>) -> Result<(), SelfError> {
+    select_section(table, PROFILES, "profile", profile)
+}
+
+// The [overrides.<env>] tables work as profiles, for the environments a
+// manifest manages such as production and staging. They are applied over the
+// profile, if any.
+pub fn select_env(table: &mut toml::Table, env: Option<&str>) -> Result<(), SelfError> {
+    select_section(table, OVERRIDES, "environment", env)
+}
+
+// Loads the configuration file given explicitly, which must exist, or the
+// default one when present. Older versions are migrated in memory.
+pub fn load(
+    path: Option<&str>,
+    profile: Option<&str>,
+    env: Option<&str>,
+) -> Result<Config, SelfError> {
+    let explicit = path.is_some();
+    let missing = || match (profile, env) {
+        (Some(name), _) => Err(SelfError::MsgError(format!(
+            "profile {} selected without a configuration file",
+            name
+        ))),
+        (_, Some(name)) => Err(SelfError::MsgError(format!(
+            "environment {} selected without a configuration file",
+            name
+        ))),
+        _ => Ok(Config::default()),
+    };
+    let Some(path) = config_path(path) else {
+        return missing();
+    };
+    if !explicit && !path.exists() {
+        return missing();
+    }
+    let mut table = read_table(&path)?;
+    let original = migrate(&mut table)?;
+    if original != CONFIG_VERSION {
+        eprintln!(
+            "{} has version {} and was migrated to version {} in memory, run 'config migrate' to rewrite it",
+            path.display(),
+            original,
+            CONFIG_VERSION
+        );
+    }
+    resolve_includes(&mut table, &path)?;
+    select_profile(&mut table, profile)?;
+    select_env(&mut table, env)?;
+    toml::Value::Table(table).try_into().map_err(|e| {
+        SelfError::MsgError(format!("invalid configuration {}: {}", path.display(), e))
+    })
+}
+
+// Rewrites the configuration file at the current version, keeping a copy of
+// the original next to it.
+pub fn migrate_file(path: Option<&str>) -> Result<(), SelfError> {
+    let path = config_path(path).ok_or_else(|| {
+        SelfError::MsgError(
+            "cannot determine the configuration file, provide 'config' argument".to_string(),
+        )
+    })?;
+    let mut table = read_table(&path)?;
+    let original = migrate(&mut table)?;
+    if original == CONFIG_VERSION {
+        eprintln!(
+            "{} is already at version {}",
+            path.display(),
+            CONFIG_VERSION
+        );
+        return Ok(());
+    }
+    let mut effective = table.clone();
+    resolve_includes(&mut effective, &path)?;
+    select_profile(&mut effective, None)?;
+    select_env(&mut effective, None)?;
+    toml::Value::Table(effective)
+        .try_into::<Config>()
+        .map_err(|e| {
+            SelfError::MsgError(format!("invalid configuration {}: {}", path.display(), e))
+        })?;
+    let backup = path.with_extension(format!("toml.v{}", original));
+    fs::copy(&path, &backup)?;
+    fs::write(&path, toml::to_string_pretty(&table).unwrap())?;
+    eprintln!(
+        "migrated {} from version {} to version {}, original kept in {}",
+        path.display(),
+        original,
+        CONFIG_VERSION,
+        backup.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn secret_env_should_prefer_plain_variable() {
+        env::set_var("DYNU_TEST_SECRET_A", "plain");
+        env::set_var("DYNU_TEST_SECRET_A_FILE", "/does/not/exist");
+        assert_eq!(
+            secret_env("DYNU_TEST_SECRET_A").unwrap(),
+            Some("plain".to_string())
+        );
+    }
+
+    #[test]
+    fn secret_env_should_read_file_variant_without_trailing_newline() {
+        let path = env::temp_dir().join("dynu_test_secret_b");
+        let mut file = fs::File::create(&path).unwrap();
+        writeln!(file, "from-file").unwrap();
+        env::set_var("DYNU_TEST_SECRET_B_FILE", &path);
+        assert_eq!(
+            secret_env("DYNU_TEST_SECRET_B").unwrap(),
+            Some("from-file".to_string())
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn secret_env_should_fail_on_unreadable_file() {
+        env::set_var("DYNU_TEST_SECRET_C_FILE", "/does/not/exist");
+        assert!(secret_env("DYNU_TEST_SECRET_C").is_err());
+    }
+
+    #[test]
+    fn secret_env_should_be_none_when_undefined() {
+        assert_eq!(secret_env("DYNU_TEST_SECRET_D").unwrap(), None);
+    }
+
+    #[test]
+    fn migrate_should_set_version_of_unversioned_files() {
+        let mut table: toml::Table = "ip_source = \"ipify\"".parse().unwrap();
+        assert_eq!(migrate(&mut table).unwrap(), 0);
+        let config: Config = toml::Value::Table(table).try_into().unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.ip_source.as_deref(), Some("ipify"));
+    }
+
+    #[test]
+    fn migrate_should_refuse_newer_versions() {
+        let mut table: toml::Table = format!("version = {}", CONFIG_VERSION + 1).parse().unwrap();
+        assert!(migrate(&mut table).is_err());
+    }
+
+    #[test]
+    fn config_should_reject_unknown_keys() {
+        let table: toml::Table = "version = 1\napi_kee = \"typo\"".parse().unwrap();
+        assert!(toml::Value::Table(table).try_into::<Config>().is_err());
+    }
+
+    #[test]
+    fn select_profile_should_override_top_level_settings() {
+        let content = r#"
+version = 1
+api_key = "production"
+state_dir = "/var/lib/dynuupdater"
+
+[webhook]
+url = "https://hooks.example.com/production"
+content_type = "text/plain"
+
+[profiles.staging]
+api_key_file = "/run/secrets/staging"
+
+[profiles.staging.webhook]
+url = "https://hooks.example.com/staging"
+"#;
+        let table: toml::Table = content.parse().unwrap();
+        let mut staging = table.clone();
+        select_profile(&mut staging, Some("staging")).unwrap();
+        let config: Config = toml::Value::Table(staging).try_into().unwrap();
+        assert_eq!(config.api_key, None);
+        assert_eq!(config.api_key_file.as_deref(), Some("/run/secrets/staging"));
+        assert_eq!(config.state_dir.as_deref(), Some("/var/lib/dynuupdater"));
+        assert_eq!(
+            config.webhook.url.as_deref(),
+            Some("https://hooks.example.com/staging")
+        );
+        assert_eq!(config.webhook.content_type.as_deref(), Some("text/plain"));
+
+        let mut production = table.clone();
+        select_profile(&mut production, None).unwrap();
+        let config: Config = toml::Value::Table(production).try_into().unwrap();
+        assert_eq!(config.api_key.as_deref(), Some("production"));
+
+        let mut unknown = table;
+        assert!(select_profile(&mut unknown, Some("stagign")).is_err());
+    }
+
+    #[test]
+    fn select_env_should_apply_over_the_profile() {
+        let content = r#"
+version = 1
+api_key = "personal"
+
+[defaults]
+domain = "example.dynu.net"
+ttl = 120
+
+[profiles.work]
+api_key = "work"
+
+[overrides.staging.defaults]
+domain = "staging.example.dynu.net"
+
+[overrides.production]
+ip_source = "local"
+"#;
+        let mut table: toml::Table = content.parse().unwrap();
+        select_profile(&mut table, Some("work")).unwrap();
+        select_env(&mut table, Some("staging")).unwrap();
+        let config: Config = toml::Value::Table(table).try_into().unwrap();
+        assert_eq!(config.api_key.as_deref(), Some("work"));
+        assert_eq!(
+            config.defaults.domain.as_deref(),
+            Some("staging.example.dynu.net")
+        );
+        assert_eq!(config.defaults.ttl, Some(120));
+
+        let mut table: toml::Table = content.parse().unwrap();
+        select_profile(&mut table, None).unwrap();
+        select_env(&mut table, None).unwrap();
+        assert!(toml::Value::Table(table).try_into::<Config>().is_ok());
+
+        let mut table: toml::Table = content.parse().unwrap();
+        assert!(select_env(&mut table, Some("prod")).is_err());
+    }
+
+    #[test]
+    fn load_should_merge_included_files() {
+        let dir = env::temp_dir().join("dynu_test_include");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("zones")).unwrap();
+        fs::write(
+            dir.join("base.toml"),
+            "version = 1\napi_key = \"base\"\nip_source = \"ipify\"\n[webhook]\nurl = \"https://hooks.example.com\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("zones/a.toml"),
+            "[defaults]\ndomain = \"a.dynu.net\"\nttl = 60\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("zones/b.toml"),
+            "[defaults]\ndomain = \"b.dynu.net\"\n",
+        )
+        .unwrap();
+        fs::write(dir.join("zones/.b.toml.swp"), "not toml").unwrap();
+        fs::write(
+            dir.join("host.toml"),
+            "version = 1\ninclude = [\"base.toml\", \"zones/*.toml\"]\nip_source = \"local\"\n",
+        )
+        .unwrap();
+        let config = load(Some(dir.join("host.toml").to_str().unwrap()), None, None).unwrap();
+        assert_eq!(config.api_key.as_deref(), Some("base"));
+        assert_eq!(config.ip_source.as_deref(), Some("local"));
+        assert_eq!(config.defaults.domain.as_deref(), Some("b.dynu.net"));
+        assert_eq!(config.defaults.ttl, Some(60));
+        assert!(config.webhook.url.is_some());
+
+        fs::write(dir.join("base.toml"), "include = [\"host.toml\"]\n").unwrap();
+        assert!(load(Some(dir.join("host.toml").to_str().unwrap()), None, None).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn config_should_read_defaults_section() {
+        let table: toml::Table =
+            "version = 1\n[defaults]\ndomain = \"example.dynu.net\"\nttl = 300"
+                .parse()
+                .unwrap();
+        let config: Config = toml::Value::Table(table).try_into().unwrap();
+        assert_eq!(config.defaults.domain.as_deref(), Some("example.dynu.net"));
+        assert_eq!(config.defaults.ttl, Some(300));
+        assert!(config.defaults.node.is_none());
+    }
+
+    #[test]
+    fn host_should_apply_the_default_node_to_the_default_domain_only() {
+        let defaults = DefaultsConfig {
+            domain: Some("example.dynu.net".to_string()),
+            node: Some("www".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            defaults.host(None, None),
+            Some(("example.dynu.net".to_string(), Some("www".to_string())))
+        );
+        assert_eq!(
+            defaults.host(None, Some("mail".to_string())),
+            Some(("example.dynu.net".to_string(), Some("mail".to_string())))
+        );
+        assert_eq!(
+            defaults.host(
+                Some("_acme-challenge.www.example.dynu.net".to_string()),
+                None
+            ),
+            Some(("_acme-challenge.www.example.dynu.net".to_string(), None))
+        );
+        assert_eq!(DefaultsConfig::default().host(None, None), None);
+    }
+
+    #[test]
+    fn limits_should_reject_unknown_endpoints_and_empty_budgets() {
+        let client = |budgets: &str| ClientConfig {
+            max_concurrent: Some(4),
+            budgets: toml::from_str(budgets).unwrap(),
+        };
+        let limits = client("records = 30").limits().unwrap();
+        assert_eq!(limits.max_concurrent, Some(4));
+        assert_eq!(limits.budgets.get("records"), Some(&30));
+        assert!(client("record = 30")
+            .limits()
+            .unwrap_err()
+            .to_string()
+            .contains("unknown endpoint record"));
+        assert!(client("records = 0")
+            .limits()
+            .unwrap_err()
+            .to_string()
+            .contains("budget of records"));
+    }
+
+    #[test]
+    fn config_should_read_managed_domains() {
+        let table: toml::Table = r#"
+            version = 1
+            [domains."example.dynu.net"]
+            ttl = 300
+            txt = { "_verification" = "token", "@" = "v=spf1 -all" }
+            metadata = true
+            lease = "10m"
+            [domains."other.dynu.net"]
+        "#
+        .parse()
+        .unwrap();
+        let config: Config = toml::Value::Table(table).try_into().unwrap();
+        assert_eq!(config.domains.len(), 2);
+        let example = &config.domains["example.dynu.net"];
+        assert_eq!(example.ttl, Some(300));
+        assert_eq!(example.txt["_verification"], "token");
+        assert_eq!(example.txt["@"], "v=spf1 -all");
+        assert!(example.metadata);
+        assert_eq!(example.lease.as_deref(), Some("10m"));
+        assert_eq!(config.domains["other.dynu.net"], DomainConfig::default());
+    }
+}
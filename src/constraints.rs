@@ -0,0 +1,144 @@
+use crate::dynu::ClientError;
+use crate::suggest::did_you_mean;
+use crate::SelfError;
+
+// TTLs dynu accepts, the free plan being limited to longer TTLs than the paid
+// ones.
+pub const MIN_TTL: u64 = 30;
+pub const FREE_MIN_TTL: u64 = 120;
+pub const MAX_TTL: u64 = 86400;
+
+// Record types which can be written, SOA records being managed by dynu.
+pub const WRITABLE_TYPES: [&str; 8] = ["A", "AAAA", "TXT", "SPF", "CNAME", "NS", "PTR", "CAA"];
+
+#[derive(Debug, PartialEq)]
+pub struct Constraints {
+    pub min_ttl: u64,
+}
+
+impl Constraints {
+    // An unknown membership level gets the lowest minimum, dynu rejecting the
+    // TTLs its plan doesn't allow anyway.
+    pub fn for_level(membership_level: Option<&str>) -> Constraints {
+        let free = membership_level.is_some_and(|level| level.to_lowercase().contains("free"));
+        Constraints {
+            min_ttl: if free { FREE_MIN_TTL } else { MIN_TTL },
+        }
+    }
+
+    pub fn nearest_ttl(&self, ttl: u64) -> u64 {
+        ttl.clamp(self.min_ttl, MAX_TTL)
+    }
+
+    pub fn check_ttl(&self, ttl: u64) -> Result<(), SelfError> {
+        let nearest = self.nearest_ttl(ttl);
+        if nearest == ttl {
+            return Ok(());
+        }
+        Err(SelfError::MsgError(format!(
+            "dynu doesn't accept TTL {}, it must be between {} and {} seconds, use {} instead",
+            ttl, self.min_ttl, MAX_TTL, nearest
+        )))
+    }
+}
+
+pub fn check_record_type(record_type: &str) -> Result<(), SelfError> {
+    let upper = record_type.to_uppercase();
+    if WRITABLE_TYPES.contains(&upper.as_str()) {
+        return Ok(());
+    }
+    if upper == "SOA" {
+        return Err(SelfError::MsgError(
+            "SOA records are managed by dynu and can't be written".to_string(),
+        ));
+    }
+    Err(SelfError::MsgError(format!(
+        "cannot write {} records, the supported types are {}{}",
+        record_type,
+        WRITABLE_TYPES.join(", "),
+        did_you_mean(&upper, WRITABLE_TYPES)
+    )))
+}
+
+// Replaces the validation errors dynu answers with when a TTL or a record type
+// is rejected by an actionable message, other errors being returned as is.
+pub fn explain(error: SelfError, ttl: Option<u64>, record_type: Option<&str>) -> SelfError {
+    let message = match &error {
+        SelfError::ClientError(ClientError::StatusError(400, message)) => message.to_lowercase(),
+        _ => return error,
+    };
+    if let Some(ttl) = ttl.filter(|_| message.contains("ttl")) {
+        // the TTL passed the checks for the plan, or the plan is unknown: the
+        // nearest TTL accepted on every plan is suggested, unless the TTL
+        // already is one
+        let nearest = Constraints::for_level(Some("free")).nearest_ttl(ttl);
+        if nearest == ttl {
+            return SelfError::MsgError(format!(
+                "dynu rejected TTL {} ({}), check the TTLs the plan of the account accepts",
+                ttl, error
+            ));
+        }
+        return SelfError::MsgError(format!(
+            "dynu rejected TTL {} ({}), use {} instead",
+            ttl, error, nearest
+        ));
+    }
+    if let Some(record_type) = record_type.filter(|_| message.contains("type")) {
+        return SelfError::MsgError(format!(
+            "dynu rejected the {} record ({}), check that the domain accepts {} records",
+            record_type, error, record_type
+        ));
+    }
+    error
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_ttl_should_suggest_the_nearest_valid_ttl() {
+        let free = Constraints::for_level(Some("Free"));
+        assert_eq!(free.min_ttl, FREE_MIN_TTL);
+        assert!(free.check_ttl(300).is_ok());
+        let error = format!("{}", free.check_ttl(60).unwrap_err());
+        assert!(error.contains("use 120 instead"), "{}", error);
+        let paid = Constraints::for_level(Some("Plus"));
+        assert!(paid.check_ttl(60).is_ok());
+        assert_eq!(paid.nearest_ttl(10), MIN_TTL);
+        assert_eq!(Constraints::for_level(None).nearest_ttl(100_000), MAX_TTL);
+    }
+
+    #[test]
+    fn check_record_type_should_only_accept_writable_types() {
+        assert!(check_record_type("txt").is_ok());
+        assert!(check_record_type("SOA").is_err());
+        let error = format!("{}", check_record_type("TX").unwrap_err());
+        assert!(error.contains("did you mean TXT"), "{}", error);
+    }
+
+    #[test]
+    fn explain_should_only_translate_rejected_ttls_and_types() {
+        let rejected = |message: &str| {
+            SelfError::ClientError(ClientError::StatusError(400, message.to_string()))
+        };
+        let error = format!(
+            "{}",
+            explain(rejected("Invalid TTL value."), Some(60), Some("TXT"))
+        );
+        assert!(error.contains("use 120 instead"), "{}", error);
+        let error = format!(
+            "{}",
+            explain(rejected("Invalid TTL value."), Some(300), Some("TXT"))
+        );
+        assert!(!error.contains("instead"), "{}", error);
+        assert!(error.contains("dynu rejected TTL 300"), "{}", error);
+        let error = format!(
+            "{}",
+            explain(rejected("Unsupported record type."), Some(300), Some("A"))
+        );
+        assert!(error.contains("accepts A records"), "{}", error);
+        let error = format!("{}", explain(rejected("Invalid node."), Some(60), None));
+        assert!(error.starts_with("ClientError"), "{}", error);
+    }
+}
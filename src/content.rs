@@ -0,0 +1,127 @@
+use std::net::IpAddr;
+
+use crate::dynu::RecordDTO;
+use crate::names::same_name;
+
+// Record contents are compared in a canonical form, so that values which are
+// equivalent for DNS but spelled differently don't cause needless writes.
+
+// TXT data may be provided with the zone file quoting, possibly split in
+// several character strings as in "v=spf1 " "-all".
+pub fn normalise_txt(value: &str) -> String {
+    let value = value.trim();
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return value.to_string();
+    }
+    let inner = &value[1..value.len() - 1];
+    inner
+        .split("\" \"")
+        .collect::<Vec<&str>>()
+        .join("")
+        .replace("\\\"", "\"")
+}
+
+// IPv6 addresses can be spelled with or without zero compression and in any
+// case, their parsed form is canonical.
+pub fn normalise_address(value: &str) -> String {
+    let value = value.trim();
+    value
+        .parse::<IpAddr>()
+        .map(|ip| ip.to_string())
+        .unwrap_or_else(|_| value.to_string())
+}
+
+pub fn normalise_target(value: &str) -> String {
+    value.trim().trim_end_matches('.').to_lowercase()
+}
+
+pub fn same_content(record_type: &str, a: &str, b: &str) -> bool {
+    let normalise = match record_type {
+        "TXT" | "SPF" => normalise_txt,
+        "A" | "AAAA" => normalise_address,
+        "CNAME" | "NS" | "MX" | "PTR" => normalise_target,
+        _ => |value: &str| value.trim().to_string(),
+    };
+    normalise(a) == normalise(b)
+}
+
+pub fn same_address(a: &Option<String>, b: &Option<String>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => same_content("A", a, b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+// Records with the same node, type and content, which dynu accepts and ACME
+// clients tend to create. The oldest record of each group, with the lowest
+// id, is the one kept and is not returned.
+pub fn duplicates(records: &[RecordDTO]) -> Vec<&RecordDTO> {
+    let mut sorted: Vec<&RecordDTO> = records.iter().collect();
+    sorted.sort_by_key(|r| r.id());
+    let mut kept: Vec<&RecordDTO> = Vec::new();
+    let mut duplicates = Vec::new();
+    for record in sorted {
+        let duplicate = kept.iter().any(|k| {
+            k.record_type() == record.record_type()
+                && same_name(k.node_name(), record.node_name())
+                && same_content(record.record_type(), &k.value(), &record.value())
+        });
+        if duplicate {
+            duplicates.push(record);
+        } else {
+            kept.push(record);
+        }
+    }
+    duplicates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn txt_should_ignore_quoting_and_whitespace() {
+        assert!(same_content("TXT", "\"v=spf1 -all\"", " v=spf1 -all\n"));
+        assert!(same_content("TXT", "\"v=spf1 \" \"-all\"", "v=spf1 -all"));
+        assert!(same_content("TXT", "\"say \\\"hi\\\"\"", "say \"hi\""));
+        assert!(!same_content("TXT", "token-a", "token-b"));
+    }
+
+    #[test]
+    fn addresses_should_ignore_ipv6_spelling() {
+        assert!(same_content(
+            "AAAA",
+            "2001:0db8:0000:0000:0000:0000:0000:0001",
+            "2001:DB8::1"
+        ));
+        assert!(same_address(
+            &Some("192.0.2.1\n".to_string()),
+            &Some("192.0.2.1".to_string())
+        ));
+        assert!(!same_address(&Some("192.0.2.1".to_string()), &None));
+    }
+
+    #[test]
+    fn duplicates_should_keep_the_oldest_record() {
+        let records = vec![
+            RecordDTO::txt_record("_acme-challenge", "token", 120, Some(7)),
+            RecordDTO::txt_record("_acme-challenge", "\"token\"", 300, Some(3)),
+            RecordDTO::txt_record("_ACME-challenge", "token", 120, Some(9)),
+            RecordDTO::txt_record("_acme-challenge", "other", 120, Some(4)),
+            RecordDTO::a_record("", "192.0.2.1", 300, Some(1)),
+            RecordDTO::a_record("www", "192.0.2.1", 300, Some(2)),
+        ];
+        let ids: Vec<Option<u64>> = duplicates(&records).iter().map(|r| r.id()).collect();
+        assert_eq!(ids, vec![Some(7), Some(9)]);
+    }
+
+    #[test]
+    fn targets_should_ignore_trailing_dot_and_case() {
+        assert!(same_content(
+            "CNAME",
+            "Target.Example.net.",
+            "target.example.net"
+        ));
+    }
+}
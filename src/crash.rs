@@ -0,0 +1,60 @@
+use std::any::Any;
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::panic;
+
+// Failures users can act upon are returned as errors, so a panic is a bug:
+// instead of the raw panic message, a compact report is printed with the
+// build details and where to report it. RUST_BACKTRACE=1 still adds the
+// backtrace.
+pub fn install_hook() {
+    panic::set_hook(Box::new(|info| {
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()))
+            .unwrap_or_else(|| "unknown location".to_string());
+        eprintln!("{}", report(&message(info.payload()), &location));
+        let backtrace = Backtrace::capture();
+        if backtrace.status() == BacktraceStatus::Captured {
+            eprintln!("{}", backtrace);
+        }
+    }));
+}
+
+fn message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        return message.to_string();
+    }
+    if let Some(message) = payload.downcast_ref::<String>() {
+        return message.clone();
+    }
+    "unknown cause".to_string()
+}
+
+fn report(message: &str, location: &str) -> String {
+    format!(
+        "dynuupdater crashed: {} ({})\n\
+         This is a bug of dynuupdater {} (commit {}, {}), please report it at {}/issues/new \
+         with this message, the command that failed and the output of dynuupdater version --verbose. \
+         Set RUST_BACKTRACE=1 to include a backtrace.",
+        message,
+        location,
+        env!("CARGO_PKG_VERSION"),
+        env!("DYNUUPDATER_GIT_COMMIT"),
+        env!("DYNUUPDATER_TARGET"),
+        env!("CARGO_PKG_REPOSITORY")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_should_name_the_cause_and_where_to_report_it() {
+        let payload: Box<dyn Any + Send> = Box::new(format!("index {} out of range", 3));
+        let report = report(&message(payload.as_ref()), "src/main.rs:1:1");
+        assert!(report.starts_with("dynuupdater crashed: index 3 out of range (src/main.rs:1:1)"));
+        assert!(report.contains("https://github.com/fburato/dynuupdater/issues/new"));
+        assert_eq!(message(&42), "unknown cause");
+    }
+}
@@ -0,0 +1,130 @@
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::apicalls;
+use crate::changes::{DomainResult, Outcome};
+use crate::dashboard::Round;
+use crate::netwatch;
+use crate::SelfError;
+
+// Refreshes the domains every interval until the process is stopped. A failed
+// cycle, e.g. because ipify or dynu are unavailable, is logged and retried at
+// the next one. With watch_network the next cycle also starts as soon as the
+// network configuration of the host changes.
+pub fn run<F>(interval: Duration, watch_network: bool, mut round: F) -> Result<(), SelfError>
+where
+    F: FnMut() -> Result<Round, SelfError>,
+{
+    // the sender is kept for the whole run, so that waiting on the channel
+    // times out after interval when the network is not watched
+    let (trigger, triggered) = mpsc::channel();
+    if watch_network {
+        netwatch::watch(trigger.clone())?;
+    }
+    eprintln!("refreshing every {}", humantime::format_duration(interval));
+    let mut cycle: u64 = 0;
+    loop {
+        cycle += 1;
+        match round() {
+            Ok(round) => {
+                let results: Vec<DomainResult> = round
+                    .into_iter()
+                    .map(|(domain, result)| DomainResult::new(&domain, result))
+                    .collect();
+                eprintln!("cycle={} {}", cycle, summary(&results));
+                for result in &results {
+                    if let Some(error) = &result.error {
+                        eprintln!(
+                            "cycle={} domain={} {}: {}",
+                            cycle,
+                            result.domain,
+                            result.outcome.name(),
+                            error
+                        );
+                    } else if result.outcome == Outcome::Succeeded {
+                        if let Some(change_set) = &result.change_set {
+                            eprintln!(
+                                "cycle={} domain={} updated: {}",
+                                cycle,
+                                result.domain,
+                                change_set.describe_changes()
+                            );
+                        }
+                    }
+                }
+            }
+            Err(e) => eprintln!("cycle={} failed: {}", cycle, e),
+        }
+        apicalls::flush();
+        if triggered.recv_timeout(interval).is_ok() {
+            // changes notified together are served by a single cycle
+            triggered.try_iter().for_each(drop);
+        }
+    }
+}
+
+fn summary(results: &[DomainResult]) -> String {
+    let count = |outcome: Outcome| results.iter().filter(|r| r.outcome == outcome).count();
+    format!(
+        "{} domains: {} updated, {} unchanged, {} partial, {} failed",
+        results.len(),
+        count(Outcome::Succeeded),
+        count(Outcome::Skipped),
+        count(Outcome::Partial),
+        count(Outcome::Failed)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::changes::{ChangeSet, Family};
+    use crate::netutils::Addresses;
+
+    fn change_set(domain: &str, resolved: &str, applied: bool) -> ChangeSet {
+        let addresses = |v4: &str| Addresses {
+            v4: Some(v4.to_string()),
+            v6: Some("2001:db8::2".to_string()),
+        };
+        let detected = Addresses {
+            v4: Some("192.0.2.2".to_string()),
+            v6: Some("2001:db8::1".to_string()),
+        };
+        let mut change_set = ChangeSet::new(domain, addresses(resolved), &detected);
+        change_set.applied = applied;
+        change_set
+    }
+
+    #[test]
+    fn summary_should_count_outcomes() {
+        let mut partial = change_set("c.dynu.net", "192.0.2.1", true);
+        partial.fail(Family::Ipv6, "port 443 unreachable".to_string());
+        let results = vec![
+            DomainResult::new(
+                "a.dynu.net",
+                Ok(change_set("a.dynu.net", "192.0.2.1", true)),
+            ),
+            DomainResult::new(
+                "b.dynu.net",
+                Ok(change_set("b.dynu.net", "192.0.2.1", true)),
+            ),
+            DomainResult::new("c.dynu.net", Ok(partial)),
+            DomainResult::new(
+                "d.dynu.net",
+                Ok(change_set("d.dynu.net", "192.0.2.2", false)),
+            ),
+            DomainResult::new(
+                "e.dynu.net",
+                Err(SelfError::MsgError("unavailable".to_string())),
+            ),
+        ];
+        assert_eq!(
+            summary(&results),
+            "5 domains: 2 updated, 1 unchanged, 1 partial, 1 failed"
+        );
+        assert_eq!(
+            summary(&[]),
+            "0 domains: 0 updated, 0 unchanged, 0 partial, 0 failed"
+        );
+    }
+}
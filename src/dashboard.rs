@@ -0,0 +1,379 @@
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{HashMap, VecDeque};
+use std::io::Cursor;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::changes::{Action, ChangeSet, Family};
+use crate::names::same_name;
+use crate::netwatch;
+use crate::notify::Event;
+use crate::server::{self, Auth, ServerOptions};
+use crate::SelfError;
+
+const PAGE: &str = include_str!("dashboard.html");
+const HISTORY: usize = 50;
+
+// Result of refreshing each managed domain during one round.
+pub type Round = Vec<(String, Result<ChangeSet, SelfError>)>;
+
+pub struct DashboardOptions {
+    pub server: ServerOptions,
+    pub interval: Duration,
+    pub watch_network: bool,
+    pub trigger_interval: Duration,
+}
+
+// A refresh request, of a single domain or of every managed domain.
+pub type Trigger = Option<String>;
+
+#[derive(Serialize, Debug, Default, Clone)]
+struct DomainStatus {
+    domain: String,
+    ipv4: Option<String>,
+    ipv6: Option<String>,
+    previous_ipv4: Option<String>,
+    previous_ipv6: Option<String>,
+    last_refresh: Option<u64>,
+    last_change: Option<u64>,
+    error: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct ErrorEntry {
+    timestamp: u64,
+    domain: Option<String>,
+    error: String,
+}
+
+// What the page shows: the managed domains with their current and previous
+// addresses, and the most recent events and errors, newest first.
+#[derive(Serialize, Debug, Default)]
+struct Status {
+    started_at: u64,
+    last_round: Option<u64>,
+    domains: Vec<DomainStatus>,
+    events: VecDeque<Event>,
+    errors: VecDeque<ErrorEntry>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn push_bounded<T>(queue: &mut VecDeque<T>, item: T) {
+    queue.push_front(item);
+    queue.truncate(HISTORY);
+}
+
+impl Status {
+    fn error(&mut self, domain: Option<&str>, error: &SelfError) {
+        push_bounded(
+            &mut self.errors,
+            ErrorEntry {
+                timestamp: now(),
+                domain: domain.map(|d| d.to_string()),
+                error: format!("{}", error),
+            },
+        );
+    }
+
+    fn record(&mut self, domain: &str, result: &Result<ChangeSet, SelfError>) {
+        let index = match self.domains.iter().position(|d| d.domain == domain) {
+            Some(index) => index,
+            None => {
+                self.domains.push(DomainStatus {
+                    domain: domain.to_string(),
+                    ..Default::default()
+                });
+                self.domains.len() - 1
+            }
+        };
+        let change_set = match result {
+            Ok(change_set) => change_set,
+            Err(e) => {
+                self.domains[index].error = Some(format!("{}", e));
+                self.error(Some(domain), e);
+                return;
+            }
+        };
+        let status = &mut self.domains[index];
+        status.last_refresh = Some(now());
+        status.error = change_set.failure();
+        for change in &change_set.changes {
+            let (current, previous) = match change.family {
+                Family::Ipv4 => (&mut status.ipv4, &mut status.previous_ipv4),
+                Family::Ipv6 => (&mut status.ipv6, &mut status.previous_ipv6),
+            };
+            if change.error.is_some() {
+                *current = change.old.clone();
+                continue;
+            }
+            if change.action != Action::Unchanged && change_set.applied {
+                *previous = change.old.clone();
+                status.last_change = status.last_refresh;
+            }
+            *current = change.new.clone();
+        }
+        if change_set.applied {
+            for event in change_set.events() {
+                push_bounded(&mut self.events, event);
+            }
+        }
+    }
+}
+
+// Minimum time between two refreshes of a domain requested through /trigger,
+// so that a misbehaving caller cannot exhaust the rate limits of dynu.
+struct RateLimit {
+    interval: Duration,
+    last: HashMap<String, Instant>,
+}
+
+impl RateLimit {
+    // Records the request unless it comes too early, returning then how long
+    // the caller has to wait.
+    fn check(&mut self, domain: &str, now: Instant) -> Result<(), Duration> {
+        if let Some(last) = self.last.get(domain) {
+            let elapsed = now.duration_since(*last);
+            if elapsed < self.interval {
+                return Err(self.interval - elapsed);
+            }
+        }
+        self.last.insert(domain.to_string(), now);
+        Ok(())
+    }
+}
+
+// Triggers received together are served by a single round, of every domain as
+// soon as one of them asks for every domain.
+fn coalesce(triggers: impl Iterator<Item = Trigger>) -> Option<Vec<String>> {
+    let mut all = false;
+    let mut domains: Vec<String> = Vec::new();
+    for trigger in triggers {
+        match trigger {
+            None => all = true,
+            Some(domain) if !domains.contains(&domain) => domains.push(domain),
+            Some(_) => {}
+        }
+    }
+    (!all).then_some(domains)
+}
+
+fn trigger_domain(
+    domain: &str,
+    status: &Mutex<Status>,
+    limits: &Mutex<RateLimit>,
+    trigger: &Sender<Trigger>,
+) -> Response<Cursor<Vec<u8>>> {
+    let managed = status
+        .lock()
+        .unwrap()
+        .domains
+        .iter()
+        .find(|d| same_name(&d.domain, domain))
+        .map(|d| d.domain.clone());
+    let Some(domain) = managed else {
+        return server::json_response(
+            404,
+            &json!({ "message": format!("{} is not managed", domain) }),
+        );
+    };
+    if let Err(wait) = limits.lock().unwrap().check(&domain, Instant::now()) {
+        let seconds = wait.as_secs() + 1;
+        return server::json_response(
+            429,
+            &json!({ "message": format!("{} was refreshed recently, retry in {}s", domain, seconds) }),
+        )
+        .with_header(Header::from_bytes("Retry-After", seconds.to_string()).unwrap());
+    }
+    let _ = trigger.send(Some(domain.clone()));
+    server::json_response(
+        202,
+        &json!({ "message": format!("refresh of {} requested", domain) }),
+    )
+}
+
+fn html_response(body: &str) -> Response<Cursor<Vec<u8>>> {
+    Response::from_data(body.as_bytes().to_vec())
+        .with_header(Header::from_bytes("Content-Type", "text/html; charset=utf-8").unwrap())
+}
+
+// With a token the page itself holds no data and is served to everyone, the
+// API it calls sending the token as a bearer token. With basic authentication
+// the page is protected as well, so that the browser asks for the credentials
+// and sends them along with the API calls.
+fn route(
+    request: &Request,
+    options: &DashboardOptions,
+    status: &Mutex<Status>,
+    limits: &Mutex<RateLimit>,
+    trigger: &Sender<Trigger>,
+) -> Response<Cursor<Vec<u8>>> {
+    let path = request.url().split('?').next().unwrap_or("");
+    let token = matches!(options.server.auth, Some(Auth::Token(_)));
+    match (request.method(), path) {
+        (Method::Get, "/healthz") => server::json_response(200, &json!({ "status": "ok" })),
+        (Method::Get, "/") if token => html_response(&PAGE.replace("__AUTH__", "token")),
+        _ if !options.server.authorized(request) => options.server.unauthorized(),
+        (Method::Get, "/") => html_response(&PAGE.replace("__AUTH__", "basic")),
+        (Method::Get, "/api/status") => server::json_response(200, &*status.lock().unwrap()),
+        (Method::Post, "/api/refresh") => {
+            let _ = trigger.send(None);
+            server::json_response(202, &json!({ "message": "refresh requested" }))
+        }
+        (Method::Get | Method::Post, _) if path.starts_with("/trigger/") => {
+            trigger_domain(&path["/trigger/".len()..], status, limits, trigger)
+        }
+        _ => server::json_response(404, &json!({ "message": format!("{} not found", path) })),
+    }
+}
+
+fn serve_requests(
+    server: Server,
+    options: &DashboardOptions,
+    status: &Mutex<Status>,
+    trigger: Sender<Trigger>,
+) {
+    let limits = Mutex::new(RateLimit {
+        interval: options.trigger_interval,
+        last: HashMap::new(),
+    });
+    for request in server.incoming_requests() {
+        let response = route(&request, options, status, &limits, &trigger);
+        if let Err(e) = request.respond(response) {
+            eprintln!("cannot send response: {}", e);
+        }
+    }
+}
+
+// Refreshes the managed domains every interval, or as soon as a refresh is
+// requested through the API, while serving the dashboard from another thread.
+// refresh is given the domains to refresh when a refresh of some domains only
+// is requested, and returns the result of each domain, or fails as a whole
+// when the public ip or the domains cannot be determined.
+pub fn serve<F>(options: DashboardOptions, mut refresh: F) -> Result<(), SelfError>
+where
+    F: FnMut(Option<&[String]>) -> Result<Round, SelfError>,
+{
+    let server = options.server.bind()?;
+    eprintln!(
+        "serving dashboard on {}://{}, refreshing every {}",
+        options.server.scheme(),
+        options.server.listen,
+        humantime::format_duration(options.interval)
+    );
+    let status = Mutex::new(Status {
+        started_at: now(),
+        ..Default::default()
+    });
+    let (trigger, triggered) = mpsc::channel();
+    if options.watch_network {
+        netwatch::watch(trigger.clone())?;
+    }
+    thread::scope(|scope| {
+        let options = &options;
+        let status = &status;
+        scope.spawn(move || serve_requests(server, options, status, trigger));
+        let mut only: Option<Vec<String>> = None;
+        loop {
+            let results = refresh(only.as_deref());
+            let mut current = status.lock().unwrap();
+            current.last_round = Some(now());
+            match &results {
+                Ok(results) => {
+                    for (domain, result) in results {
+                        current.record(domain, result);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("refresh failed: {}", e);
+                    current.error(None, e);
+                }
+            }
+            drop(current);
+            only = match triggered.recv_timeout(options.interval) {
+                Ok(first) => coalesce(std::iter::once(first).chain(triggered.try_iter())),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(SelfError::MsgError("dashboard server stopped".to_string()))
+                }
+            };
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::netutils::Addresses;
+
+    fn addresses(v4: &str) -> Addresses {
+        Addresses {
+            v4: Some(v4.to_string()),
+            v6: None,
+        }
+    }
+
+    #[test]
+    fn record_should_keep_previous_addresses_and_errors() {
+        let mut status = Status::default();
+        let mut change_set = ChangeSet::new(
+            "example.dynu.net",
+            addresses("192.0.2.1"),
+            &addresses("192.0.2.2"),
+        );
+        change_set.applied = true;
+        status.record("example.dynu.net", &Ok(change_set));
+        let domain = status.domains[0].clone();
+        assert_eq!(domain.ipv4.as_deref(), Some("192.0.2.2"));
+        assert_eq!(domain.previous_ipv4.as_deref(), Some("192.0.2.1"));
+        assert!(domain.last_change.is_some());
+        assert_eq!(status.events.len(), 1);
+
+        let error = SelfError::MsgError("dynu is down".to_string());
+        status.record("example.dynu.net", &Err(error));
+        assert_eq!(status.domains.len(), 1);
+        assert_eq!(
+            status.domains[0].error.as_deref(),
+            Some("MsgError(dynu is down)")
+        );
+        assert_eq!(status.domains[0].ipv4.as_deref(), Some("192.0.2.2"));
+        assert_eq!(status.errors.len(), 1);
+    }
+
+    #[test]
+    fn rate_limit_should_space_refreshes_of_each_domain() {
+        let mut limits = RateLimit {
+            interval: Duration::from_secs(60),
+            last: HashMap::new(),
+        };
+        let start = Instant::now();
+        assert!(limits.check("example.dynu.net", start).is_ok());
+        assert!(limits.check("other.dynu.net", start).is_ok());
+        assert_eq!(
+            limits.check("example.dynu.net", start + Duration::from_secs(20)),
+            Err(Duration::from_secs(40))
+        );
+        assert!(limits
+            .check("example.dynu.net", start + Duration::from_secs(60))
+            .is_ok());
+    }
+
+    #[test]
+    fn coalesce_should_merge_triggers() {
+        let domain = |d: &str| Some(d.to_string());
+        assert_eq!(
+            coalesce(vec![domain("a"), domain("b"), domain("a")].into_iter()),
+            Some(vec!["a".to_string(), "b".to_string()])
+        );
+        assert_eq!(coalesce(vec![domain("a"), None].into_iter()), None);
+    }
+}
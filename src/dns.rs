@@ -0,0 +1,476 @@
+use std::error::Error;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+pub const TYPE_A: u16 = 1;
+pub const TYPE_NS: u16 = 2;
+pub const TYPE_CNAME: u16 = 5;
+pub const TYPE_SOA: u16 = 6;
+pub const TYPE_PTR: u16 = 12;
+pub const TYPE_TXT: u16 = 16;
+pub const TYPE_SPF: u16 = 99;
+pub const TYPE_AAAA: u16 = 28;
+pub const TYPE_TSIG: u16 = 250;
+pub const TYPE_ANY: u16 = 255;
+pub const TYPE_CAA: u16 = 257;
+
+pub const CLASS_IN: u16 = 1;
+pub const CLASS_NONE: u16 = 254;
+pub const CLASS_ANY: u16 = 255;
+
+pub const OPCODE_UPDATE: u8 = 5;
+
+pub const RCODE_NOERROR: u8 = 0;
+pub const RCODE_FORMERR: u8 = 1;
+pub const RCODE_SERVFAIL: u8 = 2;
+pub const RCODE_NXDOMAIN: u8 = 3;
+pub const RCODE_NOTIMP: u8 = 4;
+pub const RCODE_YXDOMAIN: u8 = 6;
+pub const RCODE_YXRRSET: u8 = 7;
+pub const RCODE_NXRRSET: u8 = 8;
+pub const RCODE_NOTAUTH: u8 = 9;
+pub const RCODE_NOTZONE: u8 = 10;
+
+const FLAG_QR: u16 = 0x8000;
+const FLAG_TC: u16 = 0x0200;
+const FLAG_RD: u16 = 0x0100;
+const MAX_POINTERS: usize = 64;
+
+#[derive(Debug)]
+pub enum DnsError {
+    Malformed(String),
+}
+
+impl Error for DnsError {}
+
+impl fmt::Display for DnsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Malformed(msg) => write!(f, "Malformed({})", msg),
+        }
+    }
+}
+
+fn malformed<T>(what: &str) -> Result<T, DnsError> {
+    Err(DnsError::Malformed(what.to_string()))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Question {
+    pub name: String,
+    pub qtype: u16,
+    pub class: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceRecord {
+    pub name: String,
+    pub rtype: u16,
+    pub class: u16,
+    pub ttl: u32,
+    pub rdata: Vec<u8>,
+}
+
+// Sections are named after the query layout; in UPDATE messages they hold the
+// zone, prerequisites, updates and additional data respectively.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Message {
+    pub id: u16,
+    pub flags: u16,
+    pub questions: Vec<Question>,
+    pub answers: Vec<ResourceRecord>,
+    pub authorities: Vec<ResourceRecord>,
+    pub additionals: Vec<ResourceRecord>,
+}
+
+pub struct ParsedMessage {
+    pub message: Message,
+    // offset of the last additional record, which is where a TSIG record starts
+    pub last_additional_offset: Option<usize>,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn u8(&mut self) -> Result<u8, DnsError> {
+        let value = *self
+            .bytes
+            .get(self.position)
+            .ok_or_else(|| DnsError::Malformed("truncated message".to_string()))?;
+        self.position += 1;
+        Ok(value)
+    }
+
+    fn u16(&mut self) -> Result<u16, DnsError> {
+        Ok(u16::from_be_bytes([self.u8()?, self.u8()?]))
+    }
+
+    fn u32(&mut self) -> Result<u32, DnsError> {
+        Ok(u32::from_be_bytes([
+            self.u8()?,
+            self.u8()?,
+            self.u8()?,
+            self.u8()?,
+        ]))
+    }
+
+    fn slice(&mut self, len: usize) -> Result<&'a [u8], DnsError> {
+        let end = self.position + len;
+        if end > self.bytes.len() {
+            return malformed("truncated message");
+        }
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(slice)
+    }
+
+    fn name(&mut self) -> Result<String, DnsError> {
+        let (name, next) = read_name(self.bytes, self.position)?;
+        self.position = next;
+        Ok(name)
+    }
+}
+
+// Reads a possibly compressed name at offset, returning it without trailing dot
+// and the offset following it in the message.
+pub fn read_name(bytes: &[u8], offset: usize) -> Result<(String, usize), DnsError> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut position = offset;
+    let mut next: Option<usize> = None;
+    let mut pointers = 0;
+    loop {
+        let len = *bytes
+            .get(position)
+            .ok_or_else(|| DnsError::Malformed("truncated name".to_string()))?
+            as usize;
+        if len & 0xC0 == 0xC0 {
+            let low = *bytes
+                .get(position + 1)
+                .ok_or_else(|| DnsError::Malformed("truncated name pointer".to_string()))?;
+            if next.is_none() {
+                next = Some(position + 2);
+            }
+            pointers += 1;
+            if pointers > MAX_POINTERS {
+                return malformed("name compression loop");
+            }
+            position = ((len & 0x3F) << 8) | low as usize;
+            continue;
+        }
+        if len == 0 {
+            let name = labels.join(".");
+            return Ok((name, next.unwrap_or(position + 1)));
+        }
+        let label = bytes
+            .get(position + 1..position + 1 + len)
+            .ok_or_else(|| DnsError::Malformed("truncated label".to_string()))?;
+        labels.push(String::from_utf8_lossy(label).to_string());
+        position += 1 + len;
+    }
+}
+
+pub fn write_name(buffer: &mut Vec<u8>, name: &str) {
+    for label in name
+        .trim_end_matches('.')
+        .split('.')
+        .filter(|l| !l.is_empty())
+    {
+        buffer.push(label.len() as u8);
+        buffer.extend_from_slice(label.as_bytes());
+    }
+    buffer.push(0);
+}
+
+fn read_record(reader: &mut Reader) -> Result<ResourceRecord, DnsError> {
+    let name = reader.name()?;
+    let rtype = reader.u16()?;
+    let class = reader.u16()?;
+    let ttl = reader.u32()?;
+    let len = reader.u16()? as usize;
+    let rdata = reader.slice(len)?.to_vec();
+    Ok(ResourceRecord {
+        name,
+        rtype,
+        class,
+        ttl,
+        rdata,
+    })
+}
+
+fn write_record(buffer: &mut Vec<u8>, record: &ResourceRecord) {
+    write_name(buffer, &record.name);
+    buffer.extend_from_slice(&record.rtype.to_be_bytes());
+    buffer.extend_from_slice(&record.class.to_be_bytes());
+    buffer.extend_from_slice(&record.ttl.to_be_bytes());
+    buffer.extend_from_slice(&(record.rdata.len() as u16).to_be_bytes());
+    buffer.extend_from_slice(&record.rdata);
+}
+
+impl Message {
+    pub fn query(id: u16, name: &str, qtype: u16, recursion: bool) -> Message {
+        Message {
+            id,
+            flags: if recursion { FLAG_RD } else { 0 },
+            questions: vec![Question {
+                name: name.to_string(),
+                qtype,
+                class: CLASS_IN,
+            }],
+            ..Default::default()
+        }
+    }
+
+    pub fn opcode(&self) -> u8 {
+        ((self.flags >> 11) & 0x0F) as u8
+    }
+
+    pub fn rcode(&self) -> u8 {
+        (self.flags & 0x0F) as u8
+    }
+
+    // Answers too long for UDP are truncated, to be queried again over TCP.
+    pub fn truncated(&self) -> bool {
+        self.flags & FLAG_TC != 0
+    }
+
+    // Builds the response header for this message, echoing the question section
+    // as both queries and updates require.
+    pub fn response(&self, rcode: u8) -> Message {
+        Message {
+            id: self.id,
+            flags: FLAG_QR | ((self.opcode() as u16) << 11) | rcode as u16,
+            questions: self.questions.clone(),
+            ..Default::default()
+        }
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<ParsedMessage, DnsError> {
+        let mut reader = Reader { bytes, position: 0 };
+        let id = reader.u16()?;
+        let flags = reader.u16()?;
+        let counts = [reader.u16()?, reader.u16()?, reader.u16()?, reader.u16()?];
+        let mut message = Message {
+            id,
+            flags,
+            ..Default::default()
+        };
+        for _ in 0..counts[0] {
+            message.questions.push(Question {
+                name: reader.name()?,
+                qtype: reader.u16()?,
+                class: reader.u16()?,
+            });
+        }
+        for _ in 0..counts[1] {
+            message.answers.push(read_record(&mut reader)?);
+        }
+        for _ in 0..counts[2] {
+            message.authorities.push(read_record(&mut reader)?);
+        }
+        let mut last_additional_offset = None;
+        for _ in 0..counts[3] {
+            last_additional_offset = Some(reader.position);
+            message.additionals.push(read_record(&mut reader)?);
+        }
+        Ok(ParsedMessage {
+            message,
+            last_additional_offset,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(512);
+        buffer.extend_from_slice(&self.id.to_be_bytes());
+        buffer.extend_from_slice(&self.flags.to_be_bytes());
+        for count in [
+            self.questions.len(),
+            self.answers.len(),
+            self.authorities.len(),
+            self.additionals.len(),
+        ] {
+            buffer.extend_from_slice(&(count as u16).to_be_bytes());
+        }
+        for question in &self.questions {
+            write_name(&mut buffer, &question.name);
+            buffer.extend_from_slice(&question.qtype.to_be_bytes());
+            buffer.extend_from_slice(&question.class.to_be_bytes());
+        }
+        for record in self
+            .answers
+            .iter()
+            .chain(self.authorities.iter())
+            .chain(self.additionals.iter())
+        {
+            write_record(&mut buffer, record);
+        }
+        buffer
+    }
+}
+
+// Names of the NS records in the answer section of a message. NS data is
+// usually compressed, with pointers into the whole message, so the names are
+// read from the message rather than from the copied rdata.
+pub fn ns_names(bytes: &[u8]) -> Result<Vec<String>, DnsError> {
+    let mut reader = Reader { bytes, position: 4 };
+    let questions = reader.u16()?;
+    let answers = reader.u16()?;
+    reader.position = 12;
+    for _ in 0..questions {
+        reader.name()?;
+        reader.slice(4)?;
+    }
+    let mut names = Vec::new();
+    for _ in 0..answers {
+        reader.name()?;
+        let rtype = reader.u16()?;
+        reader.slice(6)?;
+        let len = reader.u16()? as usize;
+        if rtype == TYPE_NS {
+            names.push(read_name(bytes, reader.position)?.0);
+        }
+        reader.slice(len)?;
+    }
+    Ok(names)
+}
+
+pub fn a_rdata(address: &Ipv4Addr) -> Vec<u8> {
+    address.octets().to_vec()
+}
+
+pub fn parse_a(rdata: &[u8]) -> Result<Ipv4Addr, DnsError> {
+    let octets: [u8; 4] = rdata
+        .try_into()
+        .map_err(|_| DnsError::Malformed("A rdata must be 4 bytes".to_string()))?;
+    Ok(Ipv4Addr::from(octets))
+}
+
+pub fn aaaa_rdata(address: &Ipv6Addr) -> Vec<u8> {
+    address.octets().to_vec()
+}
+
+pub fn parse_aaaa(rdata: &[u8]) -> Result<Ipv6Addr, DnsError> {
+    let octets: [u8; 16] = rdata
+        .try_into()
+        .map_err(|_| DnsError::Malformed("AAAA rdata must be 16 bytes".to_string()))?;
+    Ok(Ipv6Addr::from(octets))
+}
+
+// TXT data longer than 255 bytes is split in several character strings, which
+// are concatenated back when parsing.
+pub fn txt_rdata(text: &str) -> Vec<u8> {
+    let mut rdata = Vec::with_capacity(text.len() + 1);
+    let bytes = text.as_bytes();
+    if bytes.is_empty() {
+        rdata.push(0);
+    }
+    for chunk in bytes.chunks(255) {
+        rdata.push(chunk.len() as u8);
+        rdata.extend_from_slice(chunk);
+    }
+    rdata
+}
+
+// RFC 8659: the flags, the tag preceded by its length, then the value up to
+// the end of the rdata.
+pub fn caa_rdata(flags: u8, tag: &str, value: &str) -> Vec<u8> {
+    let mut rdata = Vec::with_capacity(2 + tag.len() + value.len());
+    rdata.push(flags);
+    rdata.push(tag.len() as u8);
+    rdata.extend_from_slice(tag.as_bytes());
+    rdata.extend_from_slice(value.as_bytes());
+    rdata
+}
+
+pub fn parse_txt(rdata: &[u8]) -> Result<String, DnsError> {
+    let mut reader = Reader {
+        bytes: rdata,
+        position: 0,
+    };
+    let mut text = Vec::with_capacity(rdata.len());
+    while reader.position < rdata.len() {
+        let len = reader.u8()? as usize;
+        text.extend_from_slice(reader.slice(len)?);
+    }
+    Ok(String::from_utf8_lossy(&text).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_should_round_trip() {
+        let mut message = Message {
+            id: 42,
+            flags: 0x0100,
+            ..Default::default()
+        };
+        message.questions.push(Question {
+            name: "example.dynu.net".to_string(),
+            qtype: TYPE_TXT,
+            class: CLASS_IN,
+        });
+        message.answers.push(ResourceRecord {
+            name: "example.dynu.net".to_string(),
+            rtype: TYPE_TXT,
+            class: CLASS_IN,
+            ttl: 120,
+            rdata: txt_rdata("value"),
+        });
+        let parsed = Message::parse(&message.to_bytes()).unwrap();
+        assert_eq!(parsed.message, message);
+        assert_eq!(parsed.last_additional_offset, None);
+    }
+
+    #[test]
+    fn read_name_should_follow_pointers() {
+        let mut bytes = vec![0u8; 2];
+        write_name(&mut bytes, "example.dynu.net");
+        bytes.extend_from_slice(&[3, b'w', b'w', b'w', 0xC0, 2]);
+        let (name, next) = read_name(&bytes, 20).unwrap();
+        assert_eq!(name, "www.example.dynu.net");
+        assert_eq!(next, bytes.len());
+    }
+
+    #[test]
+    fn ns_names_should_follow_pointers_into_the_message() {
+        let mut message = Message::query(7, "example.dynu.net", TYPE_NS, true);
+        message.answers.push(ResourceRecord {
+            name: "example.dynu.net".to_string(),
+            rtype: TYPE_NS,
+            class: CLASS_IN,
+            ttl: 3600,
+            // ns1 followed by a pointer to "dynu.net" in the question
+            rdata: vec![3, b'n', b's', b'1', 0xC0, 12 + 8],
+        });
+        let bytes = message.to_bytes();
+        assert_eq!(ns_names(&bytes).unwrap(), vec!["ns1.dynu.net".to_string()]);
+        assert_eq!(Message::parse(&bytes).unwrap().message.rcode(), 0);
+    }
+
+    #[test]
+    fn read_name_should_reject_loops() {
+        let bytes = vec![0xC0, 0];
+        assert!(read_name(&bytes, 0).is_err());
+    }
+
+    #[test]
+    fn txt_rdata_should_split_long_values() {
+        let value = "a".repeat(300);
+        let rdata = txt_rdata(&value);
+        assert_eq!(rdata[0], 255);
+        assert_eq!(rdata[256], 45);
+        assert_eq!(parse_txt(&rdata).unwrap(), value);
+    }
+
+    #[test]
+    fn caa_rdata_should_prefix_the_tag_with_its_length() {
+        assert_eq!(
+            caa_rdata(128, "issue", "ca.example"),
+            b"\x80\x05issueca.example"
+        );
+    }
+}
@@ -0,0 +1,239 @@
+use clap::ValueEnum;
+use std::collections::BTreeMap;
+
+use crate::backup::{zone_diff, Difference, ZoneBackup};
+use crate::lease;
+use crate::names::{normalise_fqdn, same_name};
+use crate::notify::{Event, Sink, Status};
+use crate::state::StateDir;
+use crate::{SelfError, METADATA_NODE};
+
+const SNAPSHOTS_FILE: &str = "drift-snapshots.json";
+
+// What refresh --drift does with the changes made to a domain outside
+// dynuupdater: log them as warnings, or notify them as well.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum DriftAction {
+    Log,
+    Notify,
+}
+
+// Domains and records as left by the last refresh, so that the next one can
+// tell what was changed in between by someone else, e.g. in dynu's web UI.
+pub struct Snapshots<'a> {
+    state: &'a StateDir,
+}
+
+impl<'a> Snapshots<'a> {
+    pub fn new(state: &'a StateDir) -> Snapshots<'a> {
+        Snapshots { state }
+    }
+
+    fn snapshots(&self) -> Result<BTreeMap<String, ZoneBackup>, SelfError> {
+        Ok(self.state.read_json(SNAPSHOTS_FILE)?.unwrap_or_default())
+    }
+
+    // Changes from the snapshot of the zone to its current state, None when
+    // there is no snapshot to compare with.
+    pub fn drift(&self, current: &ZoneBackup) -> Result<Option<Vec<Difference>>, SelfError> {
+        Ok(self
+            .snapshots()?
+            .get(&normalise_fqdn(&current.domain.name))
+            .map(|snapshot| {
+                zone_diff(
+                    &without_own_records(snapshot),
+                    &without_own_records(current),
+                )
+            }))
+    }
+
+    pub fn store(&self, zone: ZoneBackup) -> Result<(), SelfError> {
+        let mut snapshots = self.snapshots()?;
+        snapshots.insert(normalise_fqdn(&zone.domain.name), zone);
+        self.state.write_json(SNAPSHOTS_FILE, &snapshots)
+    }
+
+    // Drops the snapshot of the domain, the next refresh storing a new one
+    // instead of comparing with it.
+    pub fn forget(&self, domain: &str) -> Result<(), SelfError> {
+        let mut snapshots = self.snapshots()?;
+        if snapshots.remove(&normalise_fqdn(domain)).is_some() {
+            self.state.write_json(SNAPSHOTS_FILE, &snapshots)?;
+        }
+        Ok(())
+    }
+}
+
+// Forgets the snapshot of the domains dynuupdater changes, e.g. with
+// txt-update or ensure between two refreshes, so that its own changes aren't
+// reported as drift. The changes made by others in the meantime go unreported
+// too, the snapshot only being compared with again after the next refresh.
+pub struct OwnWrites {
+    state: StateDir,
+}
+
+impl OwnWrites {
+    pub fn new(state: &StateDir) -> OwnWrites {
+        OwnWrites {
+            state: state.clone(),
+        }
+    }
+}
+
+impl Sink for OwnWrites {
+    fn name(&self) -> String {
+        "drift snapshots".to_string()
+    }
+
+    fn send(&self, event: &Event) -> Result<(), SelfError> {
+        // without a state directory there are no snapshots to forget
+        if event.status != Status::Updated
+            || event.kind == "drift"
+            || self.state.file(SNAPSHOTS_FILE).is_err()
+        {
+            return Ok(());
+        }
+        Snapshots::new(&self.state).forget(&event.domain)
+    }
+}
+
+// The zone without the records refresh rewrites by itself, the lease and the
+// metadata of the domain, so that renewing them isn't taken for drift.
+fn without_own_records(zone: &ZoneBackup) -> ZoneBackup {
+    ZoneBackup {
+        domain: zone.domain.clone(),
+        records: zone
+            .records
+            .iter()
+            .filter(|r| {
+                !same_name(r.node_name(), lease::NODE) && !same_name(r.node_name(), METADATA_NODE)
+            })
+            .cloned()
+            .collect(),
+    }
+}
+
+pub fn describe(difference: &Difference) -> String {
+    format!(
+        "warning: domain={} {} {} outside dynuupdater: {} -> {}",
+        difference.domain,
+        difference.what,
+        difference.change,
+        difference.before.as_deref().unwrap_or("-"),
+        difference.after.as_deref().unwrap_or("-")
+    )
+}
+
+pub fn event(difference: &Difference) -> Event {
+    let mut event = Event::new("drift", Status::Updated, &difference.domain)
+        .values(difference.before.clone(), difference.after.clone());
+    event.error = Some(format!("{} {}", difference.what, difference.change));
+    event
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynu::{DomainDTO, RecordDTO};
+
+    #[test]
+    fn drift_should_compare_with_the_last_snapshot() {
+        let dir = std::env::temp_dir().join("dynu_drift_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let state = StateDir::new(dir.to_str());
+        let snapshots = Snapshots::new(&state);
+        let mut zone = ZoneBackup {
+            domain: DomainDTO {
+                name: "example.dynu.net".to_string(),
+                ttl: 300,
+                ..Default::default()
+            },
+            records: vec![RecordDTO::txt_record("_acme-challenge", "a", 120, Some(1))],
+        };
+        assert_eq!(snapshots.drift(&zone).unwrap(), None);
+        snapshots.store(zone.clone()).unwrap();
+        assert_eq!(snapshots.drift(&zone).unwrap(), Some(vec![]));
+
+        zone.domain.ttl = 120;
+        zone.records[0] = RecordDTO::txt_record("_acme-challenge", "b", 120, Some(1));
+        let drift = snapshots.drift(&zone).unwrap().unwrap();
+        assert_eq!(drift.len(), 2);
+        assert_eq!(
+            describe(&drift[0]),
+            "warning: domain=example.dynu.net ttl modified outside dynuupdater: 300 -> 120"
+        );
+        assert_eq!(event(&drift[1]).new.as_deref(), Some("b ttl=120"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn drift_should_ignore_the_renewal_of_the_lease() {
+        let dir = std::env::temp_dir().join("dynu_drift_lease_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let state = StateDir::new(dir.to_str());
+        let snapshots = Snapshots::new(&state);
+        let zone = |lease: &str, metadata: &str| ZoneBackup {
+            domain: DomainDTO {
+                name: "example.dynu.net".to_string(),
+                ttl: 300,
+                ..Default::default()
+            },
+            records: vec![
+                RecordDTO::txt_record(lease::NODE, lease, 120, Some(1)),
+                RecordDTO::txt_record(METADATA_NODE, metadata, 120, Some(2)),
+            ],
+        };
+        snapshots
+            .store(zone(
+                "holder=nas expires=2026-10-16T08:10:00Z",
+                "updated=2026-10-16T08:00:00Z host=nas source=ipify",
+            ))
+            .unwrap();
+        let renewed = zone(
+            "holder=nas expires=2026-10-16T08:15:00Z",
+            "updated=2026-10-16T08:05:00Z host=nas source=ipify",
+        );
+        assert_eq!(snapshots.drift(&renewed).unwrap(), Some(vec![]));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn own_writes_should_not_be_reported_as_drift() {
+        let dir = std::env::temp_dir().join("dynu_drift_own_writes_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let state = StateDir::new(dir.to_str());
+        let snapshots = Snapshots::new(&state);
+        let mut zone = ZoneBackup {
+            domain: DomainDTO {
+                name: "example.dynu.net".to_string(),
+                ttl: 300,
+                ..Default::default()
+            },
+            records: vec![RecordDTO::txt_record("_acme-challenge", "a", 120, Some(1))],
+        };
+        snapshots.store(zone.clone()).unwrap();
+        let own_writes = OwnWrites::new(&state);
+        own_writes
+            .send(&Event::new("drift", Status::Updated, "example.dynu.net"))
+            .unwrap();
+        own_writes
+            .send(&Event::new(
+                "txt-update",
+                Status::Failed,
+                "example.dynu.net",
+            ))
+            .unwrap();
+        assert_eq!(snapshots.drift(&zone).unwrap(), Some(vec![]));
+
+        zone.records[0] = RecordDTO::txt_record("_acme-challenge", "b", 120, Some(1));
+        own_writes
+            .send(&Event::new(
+                "txt-update",
+                Status::Updated,
+                "Example.dynu.net.",
+            ))
+            .unwrap();
+        assert_eq!(snapshots.drift(&zone).unwrap(), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
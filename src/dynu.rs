@@ -6,16 +6,72 @@ use reqwest::{
     Error as ReqError, StatusCode,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::error::Error;
 use std::fmt;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-const DYNU_API: &str = "https://api.dynu.com";
+pub(crate) const DYNU_API: &str = "https://api.dynu.com";
+/// User-Agent of dynuupdater, naming its version and repository, e.g. for
+/// [`DynuClient::new`].
+pub const USER_AGENT: &str = concat!(
+    "dynuupdater/",
+    env!("CARGO_PKG_VERSION"),
+    " (+",
+    env!("CARGO_PKG_REPOSITORY"),
+    ")"
+);
+pub(crate) const RATE_LIMIT_PAUSE: Duration = Duration::from_secs(30);
+pub(crate) const RATE_LIMIT_ATTEMPTS: u32 = 3;
+// Requests in flight at once when creating records in bulk, unless the limits
+// of the client allow fewer.
+pub(crate) const BULK_WORKERS: usize = 4;
 
+/// Failure of a call to the Dynu API.
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum ClientError {
-    MsgError(String),
+    /// The request couldn't be sent or its answer read.
     HttpError(ReqError),
+    /// The API key or another header isn't a valid header value.
     HeaderValueError(InvalidHeaderValue),
+    /// Dynu answered with an error status, given with the request and the
+    /// message dynu answered with.
+    StatusError(u16, String),
+    /// The request would change the account but the client is read-only.
+    ReadOnly(String),
+    /// Dynu's answer lacks a field dynuupdater needs.
+    MalformedResponse(String),
+}
+
+impl ClientError {
+    /// Whether the failure is caused by Dynu being unreachable or temporarily
+    /// unable to serve requests, rather than by the request itself.
+    pub fn is_unavailable(&self) -> bool {
+        match self {
+            Self::HttpError(req) => req.is_connect() || req.is_timeout(),
+            Self::StatusError(status, _) => *status >= 500 || *status == 429,
+            _ => false,
+        }
+    }
+
+    /// Whether Dynu rejected the request because too many were sent.
+    pub fn is_rate_limited(&self) -> bool {
+        matches!(self, Self::StatusError(429, _))
+    }
+
+    /// Whether the write was rejected because another client changed the same
+    /// records in the meantime, which dynu reports either with a conflict status
+    /// or with a validation error about the record already existing.
+    pub fn is_conflict(&self) -> bool {
+        match self {
+            Self::StatusError(409, _) => true,
+            Self::StatusError(400, msg) => msg.to_lowercase().contains("already exist"),
+            _ => false,
+        }
+    }
 }
 
 impl Error for ClientError {}
@@ -24,8 +80,12 @@ impl fmt::Display for ClientError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::HttpError(req) => write!(f, "HttpError({})", req),
-            Self::MsgError(msg) => write!(f, "MsgError({})", msg),
             Self::HeaderValueError(req) => write!(f, "InvalidHeaderValue({})", req),
+            Self::StatusError(_, msg) => write!(f, "StatusError({})", msg),
+            Self::ReadOnly(request) => {
+                write!(f, "ReadOnly({} refused, the client is read-only)", request)
+            }
+            Self::MalformedResponse(msg) => write!(f, "MalformedResponse({})", msg),
         }
     }
 }
@@ -42,88 +102,313 @@ impl From<InvalidHeaderValue> for ClientError {
     }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+/// A domain of the account, as listed and updated by the API.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct DomainDTO {
+    /// Id dynu gives the domain, missing from domains not created yet.
     pub id: Option<u64>,
+    /// Name of the domain, in ASCII.
     pub name: String,
+    /// Name of the domain as written, for internationalised domains.
+    #[serde(default)]
     pub unicode_name: String,
+    /// Token of the domain for the IP update protocol.
     pub token: Option<String>,
+    /// State of the domain, e.g. Complete.
+    #[serde(default)]
     pub state: String,
+    /// Group the domain belongs to, empty without one.
+    #[serde(default)]
     pub group: String,
+    /// IPv4 address of the domain.
     pub ipv4_address: Option<String>,
+    /// IPv6 address of the domain.
     pub ipv6_address: Option<String>,
+    /// TTL of the addresses of the domain, in seconds.
     pub ttl: u64,
+    /// Whether the domain resolves to its IPv4 address.
+    #[serde(default)]
     pub ipv4: bool,
+    /// Whether the domain resolves to its IPv6 address.
+    #[serde(default)]
     pub ipv6: bool,
+    /// Whether every subdomain without records resolves to the IPv4 address.
+    #[serde(default)]
     pub ipv4_wildcard_alias: bool,
+    /// Whether every subdomain without records resolves to the IPv6 address.
+    #[serde(default)]
     pub ipv6_wildcard_alias: bool,
+    /// When the domain was created.
     pub created_on: Option<String>,
+    /// When the domain was last updated.
     pub updated_on: Option<String>,
+    /// Fields not modelled above, sent back as received so that updates don't
+    /// reset them to the API defaults.
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+impl DomainDTO {
+    /// The id dynu gives every domain, missing only from malformed answers.
+    pub fn require_id(&self) -> Result<u64, ClientError> {
+        self.id.ok_or_else(|| {
+            ClientError::MalformedResponse(format!("domain {} has no id", self.name))
+        })
+    }
+
+    /// The domain holding the addresses given, the families without one being
+    /// left as they are.
+    pub fn with_addresses(&self, ipv4: Option<&str>, ipv6: Option<&str>) -> DomainDTO {
+        let mut domain = self.clone();
+        if let Some(ipv4) = ipv4 {
+            domain.ipv4 = true;
+            domain.ipv4_address = Some(ipv4.to_string());
+        }
+        if let Some(ipv6) = ipv6 {
+            domain.ipv6 = true;
+            domain.ipv6_address = Some(ipv6.to_string());
+        }
+        domain
+    }
+}
+
+/// Domains by the group they belong to, the ones without a group being left
+/// out.
+pub fn groups(domains: Vec<DomainDTO>) -> BTreeMap<String, Vec<DomainDTO>> {
+    let mut groups: BTreeMap<String, Vec<DomainDTO>> = BTreeMap::new();
+    for domain in domains.into_iter().filter(|d| !d.group.is_empty()) {
+        groups.entry(domain.group.clone()).or_default().push(domain);
+    }
+    groups
+}
+
+/// The domains of the account.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct DomainsDTO {
+    /// HTTP status dynu answered with.
     pub status_code: u32,
+    /// Domains of the account.
     pub domains: Vec<DomainDTO>,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+/// A DNS record of a domain, one variant per record type. Every variant has
+/// the fields of the API, the same for all types: id, domain_id, domain_name,
+/// node_name (empty for the domain itself), hostname, ttl, state (whether the
+/// record is served), content and updated_on, then the fields of its type,
+/// e.g. ipv4_address for A records or text_data for TXT records.
+///
+/// Fields Dynu leaves out of some answers are defaulted, and the fields not
+/// modelled are kept in other and sent back as received, as for domains.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 #[serde(tag = "recordType")]
+#[allow(clippy::enum_variant_names, missing_docs)]
 pub enum RecordDTO {
     #[serde(rename = "TXT", rename_all = "camelCase")]
     TxtRecord {
         id: Option<u64>,
         domain_id: Option<u64>,
         domain_name: Option<String>,
+        #[serde(default)]
+        node_name: String,
+        hostname: Option<String>,
+        ttl: u64,
+        #[serde(default = "enabled")]
+        state: bool,
+        content: Option<String>,
+        updated_on: Option<String>,
+        text_data: String,
+        #[serde(flatten)]
+        other: serde_json::Map<String, serde_json::Value>,
+    },
+    #[serde(rename = "SPF", rename_all = "camelCase")]
+    SpfRecord {
+        id: Option<u64>,
+        domain_id: Option<u64>,
+        domain_name: Option<String>,
+        #[serde(default)]
         node_name: String,
         hostname: Option<String>,
         ttl: u64,
+        #[serde(default = "enabled")]
         state: bool,
         content: Option<String>,
         updated_on: Option<String>,
+        // the policy, e.g. v=spf1 mx -all, as a TXT record would hold it
         text_data: String,
+        #[serde(flatten)]
+        other: serde_json::Map<String, serde_json::Value>,
     },
     #[serde(rename = "SOA", rename_all = "camelCase")]
     SoaRecord {
         id: Option<u64>,
         domain_id: Option<u64>,
         domain_name: Option<String>,
+        #[serde(default)]
         node_name: String,
         hostname: Option<String>,
         ttl: u64,
+        #[serde(default = "enabled")]
         state: bool,
         content: Option<String>,
-        updated_on: String,
+        updated_on: Option<String>,
         master_name: String,
         responsible_name: String,
+        #[serde(default)]
         refresh: u64,
+        #[serde(default)]
         retry: u64,
+        #[serde(default)]
         expire: u64,
-        #[serde(rename = "negativeTTL")]
+        #[serde(rename = "negativeTTL", default)]
         negative_ttl: u64,
+        #[serde(flatten)]
+        other: serde_json::Map<String, serde_json::Value>,
     },
     #[serde(rename = "A", rename_all = "camelCase")]
     ARecord {
         id: Option<u64>,
         domain_id: Option<u64>,
         domain_name: Option<String>,
+        #[serde(default)]
         node_name: String,
         hostname: Option<String>,
         ttl: u64,
+        #[serde(default = "enabled")]
         state: bool,
         content: Option<String>,
         updated_on: Option<String>,
+        #[serde(default)]
         group: String,
+        ipv4_address: Option<String>,
+        #[serde(flatten)]
+        other: serde_json::Map<String, serde_json::Value>,
+    },
+    #[serde(rename = "AAAA", rename_all = "camelCase")]
+    AaaaRecord {
+        id: Option<u64>,
+        domain_id: Option<u64>,
+        domain_name: Option<String>,
+        #[serde(default)]
+        node_name: String,
+        hostname: Option<String>,
+        ttl: u64,
+        #[serde(default = "enabled")]
+        state: bool,
+        content: Option<String>,
+        updated_on: Option<String>,
+        #[serde(default)]
+        group: String,
+        ipv6_address: Option<String>,
+        #[serde(flatten)]
+        other: serde_json::Map<String, serde_json::Value>,
+    },
+    #[serde(rename = "CNAME", rename_all = "camelCase")]
+    CnameRecord {
+        id: Option<u64>,
+        domain_id: Option<u64>,
+        domain_name: Option<String>,
+        #[serde(default)]
+        node_name: String,
+        hostname: Option<String>,
+        ttl: u64,
+        #[serde(default = "enabled")]
+        state: bool,
+        content: Option<String>,
+        updated_on: Option<String>,
+        // the canonical name the node is an alias of
+        host: String,
+        #[serde(flatten)]
+        other: serde_json::Map<String, serde_json::Value>,
     },
+    #[serde(rename = "NS", rename_all = "camelCase")]
+    NsRecord {
+        id: Option<u64>,
+        domain_id: Option<u64>,
+        domain_name: Option<String>,
+        #[serde(default)]
+        node_name: String,
+        hostname: Option<String>,
+        ttl: u64,
+        #[serde(default = "enabled")]
+        state: bool,
+        content: Option<String>,
+        updated_on: Option<String>,
+        // a name server the node is delegated to
+        host: String,
+        #[serde(flatten)]
+        other: serde_json::Map<String, serde_json::Value>,
+    },
+    #[serde(rename = "PTR", rename_all = "camelCase")]
+    PtrRecord {
+        id: Option<u64>,
+        domain_id: Option<u64>,
+        domain_name: Option<String>,
+        #[serde(default)]
+        node_name: String,
+        hostname: Option<String>,
+        ttl: u64,
+        #[serde(default = "enabled")]
+        state: bool,
+        content: Option<String>,
+        updated_on: Option<String>,
+        // the name an address of a reverse zone points back to
+        host: String,
+        #[serde(flatten)]
+        other: serde_json::Map<String, serde_json::Value>,
+    },
+    #[serde(rename = "CAA", rename_all = "camelCase")]
+    CaaRecord {
+        id: Option<u64>,
+        domain_id: Option<u64>,
+        domain_name: Option<String>,
+        #[serde(default)]
+        node_name: String,
+        hostname: Option<String>,
+        ttl: u64,
+        #[serde(default = "enabled")]
+        state: bool,
+        content: Option<String>,
+        updated_on: Option<String>,
+        // 128 marks the tag as critical, to be understood by the CAs
+        #[serde(default)]
+        flags: u8,
+        // issue, issuewild or iodef
+        tag: String,
+        value: String,
+        #[serde(flatten)]
+        other: serde_json::Map<String, serde_json::Value>,
+    },
+    /// A record of a type not modelled above, e.g. MX or SRV, kept as dynu
+    /// sent it in other, so that zones holding such records can still be
+    /// listed and their records deleted. It is never created by dynuupdater.
+    #[serde(untagged, rename_all = "camelCase")]
+    Unknown {
+        id: Option<u64>,
+        #[serde(default)]
+        node_name: String,
+        record_type: String,
+        #[serde(default)]
+        ttl: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        updated_on: Option<String>,
+        #[serde(flatten)]
+        other: serde_json::Map<String, serde_json::Value>,
+    },
+}
+
+fn enabled() -> bool {
+    true
 }
 
 impl RecordDTO {
+    /// A TXT record of the node holding text_data, without an id when it is
+    /// to be created.
     pub fn txt_record(node_name: &str, text_data: &str, ttl: u64, id: Option<u64>) -> RecordDTO {
         RecordDTO::TxtRecord {
-            id: id,
+            id,
             domain_id: None,
             domain_name: None,
             node_name: node_name.to_string(),
@@ -133,34 +418,637 @@ impl RecordDTO {
             content: None,
             updated_on: None,
             text_data: text_data.to_string(),
+            other: serde_json::Map::new(),
+        }
+    }
+    /// An SPF record of the node holding the policy text_data.
+    pub fn spf_record(node_name: &str, text_data: &str, ttl: u64, id: Option<u64>) -> RecordDTO {
+        RecordDTO::SpfRecord {
+            id,
+            domain_id: None,
+            domain_name: None,
+            node_name: node_name.to_string(),
+            hostname: None,
+            ttl,
+            state: true,
+            content: None,
+            updated_on: None,
+            text_data: text_data.to_string(),
+            other: serde_json::Map::new(),
+        }
+    }
+    /// An A record of the node pointing to the IPv4 address.
+    pub fn a_record(node_name: &str, ipv4_address: &str, ttl: u64, id: Option<u64>) -> RecordDTO {
+        RecordDTO::ARecord {
+            id,
+            domain_id: None,
+            domain_name: None,
+            node_name: node_name.to_string(),
+            hostname: None,
+            ttl,
+            state: true,
+            content: None,
+            updated_on: None,
+            group: "".to_string(),
+            ipv4_address: Some(ipv4_address.to_string()),
+            other: serde_json::Map::new(),
         }
     }
+    /// An AAAA record of the node pointing to the IPv6 address.
+    pub fn aaaa_record(
+        node_name: &str,
+        ipv6_address: &str,
+        ttl: u64,
+        id: Option<u64>,
+    ) -> RecordDTO {
+        RecordDTO::AaaaRecord {
+            id,
+            domain_id: None,
+            domain_name: None,
+            node_name: node_name.to_string(),
+            hostname: None,
+            ttl,
+            state: true,
+            content: None,
+            updated_on: None,
+            group: "".to_string(),
+            ipv6_address: Some(ipv6_address.to_string()),
+            other: serde_json::Map::new(),
+        }
+    }
+    /// A CNAME record making the node an alias of host.
+    pub fn cname_record(node_name: &str, host: &str, ttl: u64, id: Option<u64>) -> RecordDTO {
+        RecordDTO::CnameRecord {
+            id,
+            domain_id: None,
+            domain_name: None,
+            node_name: node_name.to_string(),
+            hostname: None,
+            ttl,
+            state: true,
+            content: None,
+            updated_on: None,
+            host: host.to_string(),
+            other: serde_json::Map::new(),
+        }
+    }
+    /// An NS record delegating the node to the name server host.
+    pub fn ns_record(node_name: &str, host: &str, ttl: u64, id: Option<u64>) -> RecordDTO {
+        RecordDTO::NsRecord {
+            id,
+            domain_id: None,
+            domain_name: None,
+            node_name: node_name.to_string(),
+            hostname: None,
+            ttl,
+            state: true,
+            content: None,
+            updated_on: None,
+            host: host.to_string(),
+            other: serde_json::Map::new(),
+        }
+    }
+    /// A PTR record pointing the node of a reverse zone back to host.
+    pub fn ptr_record(node_name: &str, host: &str, ttl: u64, id: Option<u64>) -> RecordDTO {
+        RecordDTO::PtrRecord {
+            id,
+            domain_id: None,
+            domain_name: None,
+            node_name: node_name.to_string(),
+            hostname: None,
+            ttl,
+            state: true,
+            content: None,
+            updated_on: None,
+            host: host.to_string(),
+            other: serde_json::Map::new(),
+        }
+    }
+    /// A CAA record of the node, e.g. tag issue and value letsencrypt.org.
+    pub fn caa_record(
+        node_name: &str,
+        flags: u8,
+        tag: &str,
+        value: &str,
+        ttl: u64,
+        id: Option<u64>,
+    ) -> RecordDTO {
+        RecordDTO::CaaRecord {
+            id,
+            domain_id: None,
+            domain_name: None,
+            node_name: node_name.to_string(),
+            hostname: None,
+            ttl,
+            state: true,
+            content: None,
+            updated_on: None,
+            flags,
+            tag: tag.to_string(),
+            value: value.to_string(),
+            other: serde_json::Map::new(),
+        }
+    }
+    /// Id dynu gives the record, missing from records not created yet.
     pub fn id(&self) -> Option<u64> {
         match self {
-            RecordDTO::ARecord { id, .. } => id.clone(),
-            RecordDTO::SoaRecord { id, .. } => id.clone(),
-            RecordDTO::TxtRecord { id, .. } => id.clone(),
+            RecordDTO::ARecord { id, .. } => *id,
+            RecordDTO::AaaaRecord { id, .. } => *id,
+            RecordDTO::SoaRecord { id, .. } => *id,
+            RecordDTO::TxtRecord { id, .. } => *id,
+            RecordDTO::SpfRecord { id, .. } => *id,
+            RecordDTO::CnameRecord { id, .. } => *id,
+            RecordDTO::NsRecord { id, .. } => *id,
+            RecordDTO::PtrRecord { id, .. } => *id,
+            RecordDTO::CaaRecord { id, .. } => *id,
+            RecordDTO::Unknown { id, .. } => *id,
+        }
+    }
+    /// As for domains, records listed by dynu always have an id.
+    pub fn require_id(&self) -> Result<u64, ClientError> {
+        self.id().ok_or_else(|| {
+            ClientError::MalformedResponse(format!(
+                "{} record of node '{}' has no id",
+                self.record_type(),
+                self.node_name()
+            ))
+        })
+    }
+    /// Node of the record, empty for the domain itself.
+    pub fn node_name(&self) -> &str {
+        match self {
+            RecordDTO::ARecord { node_name, .. } => node_name,
+            RecordDTO::AaaaRecord { node_name, .. } => node_name,
+            RecordDTO::SoaRecord { node_name, .. } => node_name,
+            RecordDTO::TxtRecord { node_name, .. } => node_name,
+            RecordDTO::SpfRecord { node_name, .. } => node_name,
+            RecordDTO::CnameRecord { node_name, .. } => node_name,
+            RecordDTO::NsRecord { node_name, .. } => node_name,
+            RecordDTO::PtrRecord { node_name, .. } => node_name,
+            RecordDTO::CaaRecord { node_name, .. } => node_name,
+            RecordDTO::Unknown { node_name, .. } => node_name,
+        }
+    }
+    /// Type of the record as dynu names it, e.g. AAAA.
+    pub fn record_type(&self) -> &str {
+        match self {
+            RecordDTO::ARecord { .. } => "A",
+            RecordDTO::AaaaRecord { .. } => "AAAA",
+            RecordDTO::SoaRecord { .. } => "SOA",
+            RecordDTO::TxtRecord { .. } => "TXT",
+            RecordDTO::SpfRecord { .. } => "SPF",
+            RecordDTO::CnameRecord { .. } => "CNAME",
+            RecordDTO::NsRecord { .. } => "NS",
+            RecordDTO::PtrRecord { .. } => "PTR",
+            RecordDTO::CaaRecord { .. } => "CAA",
+            RecordDTO::Unknown { record_type, .. } => record_type,
+        }
+    }
+    /// TTL of the record, in seconds.
+    pub fn ttl(&self) -> u64 {
+        match self {
+            RecordDTO::ARecord { ttl, .. } => *ttl,
+            RecordDTO::AaaaRecord { ttl, .. } => *ttl,
+            RecordDTO::SoaRecord { ttl, .. } => *ttl,
+            RecordDTO::TxtRecord { ttl, .. } => *ttl,
+            RecordDTO::SpfRecord { ttl, .. } => *ttl,
+            RecordDTO::CnameRecord { ttl, .. } => *ttl,
+            RecordDTO::NsRecord { ttl, .. } => *ttl,
+            RecordDTO::PtrRecord { ttl, .. } => *ttl,
+            RecordDTO::CaaRecord { ttl, .. } => *ttl,
+            RecordDTO::Unknown { ttl, .. } => *ttl,
+        }
+    }
+    /// The record with its TTL replaced.
+    pub fn with_ttl(&self, new_ttl: u64) -> RecordDTO {
+        let mut record = self.clone();
+        match &mut record {
+            RecordDTO::ARecord { ttl, .. }
+            | RecordDTO::AaaaRecord { ttl, .. }
+            | RecordDTO::SoaRecord { ttl, .. }
+            | RecordDTO::TxtRecord { ttl, .. }
+            | RecordDTO::SpfRecord { ttl, .. }
+            | RecordDTO::CnameRecord { ttl, .. }
+            | RecordDTO::NsRecord { ttl, .. }
+            | RecordDTO::PtrRecord { ttl, .. }
+            | RecordDTO::CaaRecord { ttl, .. }
+            | RecordDTO::Unknown { ttl, .. } => *ttl = new_ttl,
+        }
+        record
+    }
+    /// When the record was last updated.
+    pub fn updated_on(&self) -> Option<&str> {
+        match self {
+            RecordDTO::ARecord { updated_on, .. } => updated_on.as_deref(),
+            RecordDTO::AaaaRecord { updated_on, .. } => updated_on.as_deref(),
+            RecordDTO::SoaRecord { updated_on, .. } => updated_on.as_deref(),
+            RecordDTO::TxtRecord { updated_on, .. } => updated_on.as_deref(),
+            RecordDTO::SpfRecord { updated_on, .. } => updated_on.as_deref(),
+            RecordDTO::CnameRecord { updated_on, .. } => updated_on.as_deref(),
+            RecordDTO::NsRecord { updated_on, .. } => updated_on.as_deref(),
+            RecordDTO::PtrRecord { updated_on, .. } => updated_on.as_deref(),
+            RecordDTO::CaaRecord { updated_on, .. } => updated_on.as_deref(),
+            RecordDTO::Unknown { updated_on, .. } => updated_on.as_deref(),
+        }
+    }
+    /// The data of the record as a zone file would present it, e.g. the
+    /// address of A records or the text of TXT records.
+    pub fn value(&self) -> String {
+        match self {
+            RecordDTO::ARecord { ipv4_address, .. } => ipv4_address.clone().unwrap_or_default(),
+            RecordDTO::AaaaRecord { ipv6_address, .. } => ipv6_address.clone().unwrap_or_default(),
+            RecordDTO::SoaRecord {
+                master_name,
+                responsible_name,
+                ..
+            } => format!("{} {}", master_name, responsible_name),
+            RecordDTO::TxtRecord { text_data, .. } | RecordDTO::SpfRecord { text_data, .. } => {
+                text_data.clone()
+            }
+            RecordDTO::CnameRecord { host, .. }
+            | RecordDTO::NsRecord { host, .. }
+            | RecordDTO::PtrRecord { host, .. } => host.clone(),
+            // the zone file presentation, e.g. 0 issue "letsencrypt.org"
+            RecordDTO::CaaRecord {
+                flags, tag, value, ..
+            } => format!("{} {} \"{}\"", flags, tag, value),
+            RecordDTO::Unknown { other, .. } => fields_value(other),
         }
     }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+/// The records of a domain.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct RecordsDTO {
+    /// HTTP status dynu answered with.
     pub status_code: u32,
+    /// Records of the domain.
     pub dns_records: Vec<RecordDTO>,
 }
 
+/// A record of any type, including the types RecordDTO doesn't model. The
+/// fields specific to the type (ipv6Address, host, priority...) are kept in
+/// other under the names dynu gives them.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct GenericRecordDTO {
+    /// Id dynu gives the record, missing from records not created yet.
+    pub id: Option<u64>,
+    /// Node of the record, empty for the domain itself.
+    #[serde(default)]
+    pub node_name: String,
+    /// Type of the record, e.g. MX.
+    pub record_type: String,
+    /// TTL of the record, in seconds.
+    pub ttl: u64,
+    /// Whether the record is served.
+    #[serde(default = "enabled")]
+    pub state: bool,
+    /// The record as a zone file line, filled in by dynu.
+    pub content: Option<String>,
+    /// When the record was last updated.
+    pub updated_on: Option<String>,
+    /// Fields specific to the type, under the names dynu gives them.
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+impl GenericRecordDTO {
+    /// As for domains, records listed by dynu always have an id.
+    pub fn require_id(&self) -> Result<u64, ClientError> {
+        self.id.ok_or_else(|| {
+            ClientError::MalformedResponse(format!(
+                "{} record of node '{}' has no id",
+                self.record_type, self.node_name
+            ))
+        })
+    }
+
+    /// The fields specific to the type, as key=value pairs sorted by name.
+    pub fn value(&self) -> String {
+        fields_value(&self.other)
+    }
+}
+
+// The fields specific to the type, as key=value pairs sorted by name.
+fn fields_value(other: &serde_json::Map<String, serde_json::Value>) -> String {
+    other
+        .iter()
+        .filter(|(key, _)| !GENERIC_MANAGED_FIELDS.contains(&key.as_str()))
+        .map(|(key, value)| match value {
+            serde_json::Value::String(value) => format!("{}={}", key, value),
+            value => format!("{}={}", key, value),
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Fields of the records filled in by dynu rather than describing the record.
+const GENERIC_MANAGED_FIELDS: [&str; 4] = ["domainId", "domainName", "hostname", "state"];
+
+/// The records of a domain, of any type.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct GenericRecordsDTO {
+    /// HTTP status dynu answered with.
+    pub status_code: u32,
+    /// Records of the domain.
+    pub dns_records: Vec<GenericRecordDTO>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ResponseWithId {
+    status_code: u32,
+    pub(crate) id: u64,
+}
+
+/// One address update received by Dynu. The history is only documented
+/// loosely, so every field is optional and the ones not modelled are kept.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct IpUpdateDTO {
+    /// Id of the update.
+    pub id: Option<u64>,
+    /// Domain the update was received for.
+    #[serde(alias = "domainName", alias = "domain")]
+    pub hostname: Option<String>,
+    /// IPv4 address sent by the update.
+    pub ipv4_address: Option<String>,
+    /// IPv6 address sent by the update.
+    pub ipv6_address: Option<String>,
+    /// When the update was received.
+    #[serde(alias = "dateUpdated", alias = "updatedOn")]
+    pub date: Option<String>,
+    /// Fields not modelled above.
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+/// The address updates received by Dynu.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct IpUpdateHistoryDTO {
+    /// HTTP status dynu answered with.
+    pub status_code: u32,
+    /// Updates received, as listed by dynu.
+    #[serde(default)]
+    pub ip_update_histories: Vec<IpUpdateDTO>,
+}
+
+/// Details of the account, whose answer is as loosely documented as the update
+/// history: the plan limits are optional, under any of their likely names.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountDTO {
+    /// HTTP status dynu answered with.
+    #[serde(default)]
+    pub status_code: u32,
+    /// Name of the user owning the account.
+    #[serde(alias = "userName")]
+    pub username: Option<String>,
+    /// Plan of the account, e.g. Free.
+    #[serde(alias = "membership", alias = "accountType", alias = "accountLevel")]
+    pub membership_level: Option<String>,
+    /// Number of domains the plan allows.
+    #[serde(alias = "domainLimit", alias = "maximumDomains")]
+    pub max_domains: Option<u64>,
+    /// Number of records the plan allows.
+    #[serde(alias = "recordLimit", alias = "maximumRecords")]
+    pub max_records: Option<u64>,
+    /// Fields not modelled above.
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+// Body of the answers to failed requests.
 #[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
-struct ResponseWithId {
+struct ErrorDTO {
+    #[serde(default)]
     status_code: u32,
-    id: u64,
+    #[serde(rename = "type", default)]
+    error_type: String,
+    #[serde(default)]
+    message: String,
 }
 
+/// Blocking client of the Dynu API, authenticated with an API key. It can be
+/// shared by several threads, its limits applying to all of them.
 pub struct DynuClient {
     _client: reqwest::blocking::Client,
     _api_key: String,
+    limiter: Limiter,
+    read_only: bool,
+}
+
+/// Endpoints the requests are budgeted by.
+pub const ENDPOINTS: [&str; 4] = ["domains", "records", "history", "account"];
+pub(crate) const BUDGET_WINDOW: Duration = Duration::from_secs(60);
+
+/// Limits of the requests sent to dynu, to stay within its quotas on big
+/// accounts: at most max_concurrent requests in flight among the threads
+/// sharing the client, and for each endpoint at most its budget of requests per
+/// minute. Requests beyond the limits wait for their turn instead of failing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Limits {
+    /// Requests in flight at once, unlimited when missing.
+    pub max_concurrent: Option<usize>,
+    /// Requests per minute to each of the [`ENDPOINTS`], at least 1.
+    pub budgets: BTreeMap<String, u32>,
+}
+
+/// Requests sent by a client per endpoint since it was created, shared by the
+/// clones so that they can be read while the client is in use, e.g. to report
+/// which commands spend the quotas of dynu.
+#[derive(Debug, Clone, Default)]
+pub struct ApiCalls(Arc<Mutex<BTreeMap<&'static str, u64>>>);
+
+impl ApiCalls {
+    fn record(&self, endpoint: &'static str) {
+        *self.0.lock().unwrap().entry(endpoint).or_default() += 1;
+    }
+
+    /// Requests sent so far to each endpoint which got any.
+    pub fn counts(&self) -> BTreeMap<String, u64> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(endpoint, count)| (endpoint.to_string(), *count))
+            .collect()
+    }
+}
+
+struct Limiter {
+    limits: Limits,
+    window: Duration,
+    in_flight: Mutex<usize>,
+    released: Condvar,
+    sent: Mutex<HashMap<&'static str, VecDeque<Instant>>>,
+    calls: ApiCalls,
+}
+
+// A request in flight, released when dropped.
+struct Permit<'a> {
+    limiter: &'a Limiter,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if self.limiter.limits.max_concurrent.is_some() {
+            *self.limiter.in_flight.lock().unwrap() -= 1;
+            self.limiter.released.notify_one();
+        }
+    }
+}
+
+// Records a request to endpoint when its budget allows it, or else returns how
+// long to wait for the oldest request of the window to expire.
+pub(crate) fn spend_budget(
+    sent: &Mutex<HashMap<&'static str, VecDeque<Instant>>>,
+    endpoint: &'static str,
+    budget: u32,
+    window: Duration,
+) -> Option<Duration> {
+    let mut sent = sent.lock().unwrap();
+    let sent = sent.entry(endpoint).or_default();
+    let now = Instant::now();
+    while sent
+        .front()
+        .is_some_and(|at| now.duration_since(*at) >= window)
+    {
+        sent.pop_front();
+    }
+    if sent.len() < budget as usize {
+        sent.push_back(now);
+        return None;
+    }
+    let wait = window - now.duration_since(sent[0]);
+    eprintln!(
+        "budget of {} requests to {} spent, pausing for {}",
+        budget,
+        endpoint,
+        humantime::format_duration(Duration::from_secs(wait.as_secs() + 1))
+    );
+    Some(wait)
+}
+
+impl Limiter {
+    fn new(limits: Limits, window: Duration) -> Limiter {
+        Limiter {
+            limits,
+            window,
+            in_flight: Mutex::new(0),
+            released: Condvar::new(),
+            sent: Mutex::new(HashMap::new()),
+            calls: ApiCalls::default(),
+        }
+    }
+
+    fn wait_for_budget(&self, endpoint: &'static str) {
+        let Some(budget) = self.limits.budgets.get(endpoint).copied() else {
+            return;
+        };
+        while let Some(wait) = spend_budget(&self.sent, endpoint, budget, self.window) {
+            thread::sleep(wait);
+        }
+    }
+
+    fn acquire(&self, endpoint: &'static str) -> Permit<'_> {
+        self.wait_for_budget(endpoint);
+        self.calls.record(endpoint);
+        if let Some(max_concurrent) = self.limits.max_concurrent {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            while *in_flight >= max_concurrent.max(1) {
+                in_flight = self.released.wait(in_flight).unwrap();
+            }
+            *in_flight += 1;
+        }
+        Permit { limiter: self }
+    }
+}
+
+// Calls rejected by Dynu's rate limiting are retried after a pause.
+fn rate_limited<T, F>(mut call: F) -> Result<T, ClientError>
+where
+    F: FnMut() -> Result<T, ClientError>,
+{
+    let mut attempt = 1;
+    loop {
+        match call() {
+            Err(e) if e.is_rate_limited() && attempt < RATE_LIMIT_ATTEMPTS => {
+                attempt += 1;
+                eprintln!(
+                    "rate limited by dynu, pausing for {}",
+                    humantime::format_duration(RATE_LIMIT_PAUSE)
+                );
+                thread::sleep(RATE_LIMIT_PAUSE);
+            }
+            result => return result,
+        }
+    }
+}
+
+// Calls call on every item from a few threads at once, the results being in
+// the order of the items.
+fn in_parallel<T, R, F>(items: &[T], workers: usize, call: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let workers = workers.clamp(1, items.len().max(1));
+    let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|worker| {
+                let call = &call;
+                scope.spawn(move || {
+                    items
+                        .iter()
+                        .enumerate()
+                        .skip(worker)
+                        .step_by(workers)
+                        .map(|(index, item)| (index, call(item)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Iterates over the records of several domains, fetching the records of each
+/// domain only once the records of the previous one have been consumed. Dynu
+/// answers with all the records of a domain at once, so at most one domain is
+/// held in memory.
+pub struct RecordsIter<'a> {
+    client: &'a DynuClient,
+    domain_ids: std::vec::IntoIter<u64>,
+    records: std::vec::IntoIter<RecordDTO>,
+}
+
+impl Iterator for RecordsIter<'_> {
+    type Item = Result<RecordDTO, ClientError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.records.next() {
+                return Some(Ok(record));
+            }
+            let domain_id = self.domain_ids.next()?;
+            match rate_limited(|| self.client.get_records(domain_id)) {
+                Ok(records) => self.records = records.dns_records.into_iter(),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
 }
 
 fn http_error<T>(
@@ -169,32 +1057,94 @@ fn http_error<T>(
     method: &str,
     status_code: &StatusCode,
 ) -> Result<T, ClientError> {
-    let error_body = response.text()?;
-    Err(ClientError::MsgError(format!(
-        "{} {}, status_code={}, body={}",
-        method,
-        url,
-        status_code.as_str(),
-        error_body
-    )))
+    let body = response.text()?;
+    Err(status_error(body, url, method, status_code))
+}
+
+pub(crate) fn check_writable(read_only: bool, method: &str, url: &str) -> Result<(), ClientError> {
+    if read_only {
+        return Err(ClientError::ReadOnly(format!("{} {}", method, url)));
+    }
+    Ok(())
+}
+
+// The error of a failed request, with the message dynu answered with when the
+// body holds one.
+pub(crate) fn status_error(
+    body: String,
+    url: &str,
+    method: &str,
+    status_code: &StatusCode,
+) -> ClientError {
+    let error_body = match serde_json::from_str::<ErrorDTO>(&body) {
+        Ok(error) if !error.message.is_empty() => {
+            format!("{}: {}", error.error_type, error.message)
+        }
+        _ => body,
+    };
+    ClientError::StatusError(
+        status_code.as_u16(),
+        format!(
+            "{} {}, status_code={}, body={}",
+            method,
+            url,
+            status_code.as_str(),
+            error_body
+        ),
+    )
 }
 
 impl DynuClient {
-    pub fn new(api_key: &str) -> Result<DynuClient, ClientError> {
+    /// A client authenticated with the API key, without limits.
+    pub fn new(api_key: &str, user_agent: &str) -> Result<DynuClient, ClientError> {
         let mut headers = HeaderMap::new();
         headers.insert(ACCEPT, "application/json".parse()?);
         headers.insert("api-key", api_key.parse()?);
         let client = reqwest::blocking::Client::builder()
             .default_headers(headers)
+            .user_agent(user_agent)
             .build()?;
         Ok(DynuClient {
             _client: client,
             _api_key: api_key.to_string(),
+            limiter: Limiter::new(Limits::default(), BUDGET_WINDOW),
+            read_only: false,
         })
     }
 
+    /// The client with its requests limited, keeping the calls counted so far.
+    pub fn with_limits(mut self, limits: Limits) -> DynuClient {
+        let calls = self.limiter.calls.clone();
+        self.limiter = Limiter::new(limits, BUDGET_WINDOW);
+        self.limiter.calls = calls;
+        self
+    }
+
+    /// The requests the client sent, updated as it sends more.
+    pub fn api_calls(&self) -> ApiCalls {
+        self.limiter.calls.clone()
+    }
+
+    /// A read-only client refuses every request changing the account, before
+    /// sending it.
+    pub fn with_read_only(mut self, read_only: bool) -> DynuClient {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Whether the client refuses the requests changing the account.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn check_writable(&self, method: &str, url: &str) -> Result<(), ClientError> {
+        check_writable(self.read_only, method, url)
+    }
+
+    /// The domains of the account.
     pub fn get_domains(&self) -> Result<DomainsDTO, ClientError> {
-        let url = format!("{}/v2/dns", DYNU_API.to_string());
+        let url = format!("{}/v2/dns", DYNU_API);
+        let _permit = self.limiter.acquire("domains");
         let response: Response = self._client.get(&url).send()?;
         let status = response.status();
         if !status.is_success() {
@@ -204,8 +1154,36 @@ impl DynuClient {
         Ok(result)
     }
 
+    /// Address updates received by Dynu for the domains of the account.
+    pub fn get_ip_update_history(&self) -> Result<IpUpdateHistoryDTO, ClientError> {
+        let url = format!("{}/v2/dns/ipUpdateHistory", DYNU_API);
+        let _permit = self.limiter.acquire("history");
+        let response: Response = self._client.get(&url).send()?;
+        let status = response.status();
+        if !status.is_success() {
+            return http_error(response, &url, "GET", &status);
+        }
+        let result: IpUpdateHistoryDTO = response.json()?;
+        Ok(result)
+    }
+
+    /// Details of the account, e.g. its plan.
+    pub fn get_account(&self) -> Result<AccountDTO, ClientError> {
+        let url = format!("{}/v2/account", DYNU_API);
+        let _permit = self.limiter.acquire("account");
+        let response: Response = self._client.get(&url).send()?;
+        let status = response.status();
+        if !status.is_success() {
+            return http_error(response, &url, "GET", &status);
+        }
+        let result: AccountDTO = response.json()?;
+        Ok(result)
+    }
+
+    /// The domain with the id, None when the account has no such domain.
     pub fn get_domain(&self, id: u64) -> Result<Option<DomainDTO>, ClientError> {
-        let url = format!("{}/v2/dns/{}", DYNU_API.to_string(), id);
+        let url = format!("{}/v2/dns/{}", DYNU_API, id);
+        let _permit = self.limiter.acquire("domains");
         let response: Response = self._client.get(&url).send()?;
         let status = response.status();
         if !status.is_success() {
@@ -215,8 +1193,12 @@ impl DynuClient {
         Ok(Some(result))
     }
 
+    /// Replaces the domain with the same id by domain_dto, e.g. to set its
+    /// addresses.
     pub fn update_domain(&self, domain_dto: &DomainDTO) -> Result<(), ClientError> {
-        let url = format!("{}/v2/dns/{}", DYNU_API, domain_dto.id.unwrap());
+        let url = format!("{}/v2/dns/{}", DYNU_API, domain_dto.require_id()?);
+        self.check_writable("POST", &url)?;
+        let _permit = self.limiter.acquire("domains");
         let result: Response = self
             ._client
             .post(&url)
@@ -230,14 +1212,92 @@ impl DynuClient {
         Ok(())
     }
 
+    /// Adds a domain to the account, e.g. a new hostname under one of dynu's
+    /// own domains, returning its id.
+    pub fn create_domain(&self, domain_dto: &DomainDTO) -> Result<u64, ClientError> {
+        let url = format!("{}/v2/dns", DYNU_API);
+        self.check_writable("POST", &url)?;
+        let _permit = self.limiter.acquire("domains");
+        let result: Response = self
+            ._client
+            .post(&url)
+            .headers(self.json_content_header()?)
+            .json(domain_dto)
+            .send()?;
+        let status = result.status();
+        if !status.is_success() {
+            return http_error(result, &url, "POST", &status);
+        }
+        let response: ResponseWithId = result.json()?;
+        Ok(response.id)
+    }
+
+    /// Removes the domain from the account, along with its records.
+    pub fn delete_domain(&self, id: u64) -> Result<(), ClientError> {
+        let url = format!("{}/v2/dns/{}", DYNU_API, id);
+        self.check_writable("DELETE", &url)?;
+        let _permit = self.limiter.acquire("domains");
+        let result: Response = self._client.delete(&url).send()?;
+        let status = result.status();
+        if !status.is_success() {
+            return http_error(result, &url, "DELETE", &status);
+        }
+        Ok(())
+    }
+
     fn json_content_header(&self) -> Result<HeaderMap, ClientError> {
         let mut headers = HeaderMap::new();
         headers.append(CONTENT_TYPE, "application/json".parse()?);
         Ok(headers)
     }
 
+    /// Groups gather the domains updated together, e.g. the hosts behind the
+    /// same connection. The API only exposes them as the group of each domain,
+    /// so they are read from the listing of the domains.
+    pub fn get_groups(&self) -> Result<BTreeMap<String, Vec<DomainDTO>>, ClientError> {
+        Ok(groups(self.get_domains()?.domains))
+    }
+
+    /// Sends the domains, e.g. the ones of a group given new addresses, with
+    /// concurrent requests: the API has no call updating a group at once. The
+    /// IP update protocol has one, through its location parameter, but it
+    /// authenticates with the username and IP update password of the account
+    /// rather than with the API key.
+    pub fn update_domains(&self, domains: &[DomainDTO]) -> Vec<Result<(), ClientError>> {
+        let workers = self.limiter.limits.max_concurrent.unwrap_or(BULK_WORKERS);
+        in_parallel(domains, workers, |domain| {
+            rate_limited(|| self.update_domain(domain))
+        })
+    }
+
+    /// The domains are listed by Dynu in a single answer.
+    pub fn domains_iter(&self) -> Result<std::vec::IntoIter<DomainDTO>, ClientError> {
+        Ok(rate_limited(|| self.get_domains())?.domains.into_iter())
+    }
+
+    /// Records of the domain, fetched once the iteration starts.
+    pub fn records_iter(&self, domain_id: u64) -> RecordsIter<'_> {
+        RecordsIter {
+            client: self,
+            domain_ids: vec![domain_id].into_iter(),
+            records: Vec::new().into_iter(),
+        }
+    }
+
+    /// Records of every domain of the account, domain after domain.
+    pub fn account_records_iter(&self) -> Result<RecordsIter<'_>, ClientError> {
+        let domain_ids: Vec<u64> = self.domains_iter()?.filter_map(|d| d.id).collect();
+        Ok(RecordsIter {
+            client: self,
+            domain_ids: domain_ids.into_iter(),
+            records: Vec::new().into_iter(),
+        })
+    }
+
+    /// The records of the domain.
     pub fn get_records(&self, domain_id: u64) -> Result<RecordsDTO, ClientError> {
         let url = format!("{}/v2/dns/{}/record", DYNU_API, domain_id);
+        let _permit = self.limiter.acquire("records");
         let result: Response = self._client.get(&url).send()?;
         let status = result.status();
         if !status.is_success() {
@@ -247,12 +1307,15 @@ impl DynuClient {
         Ok(response)
     }
 
+    /// The record with the id, None when dynu doesn't return it.
+    #[allow(dead_code)]
     pub fn get_record(
         &self,
         domain_id: u64,
         record_id: u64,
     ) -> Result<Option<RecordDTO>, ClientError> {
         let url = format!("{}/v2/dns/{}/record/{}", DYNU_API, domain_id, record_id);
+        let _permit = self.limiter.acquire("records");
         let result: Response = self._client.get(&url).send()?;
         let status = result.status();
         if !status.is_success() {
@@ -262,8 +1325,11 @@ impl DynuClient {
         Ok(Some(response))
     }
 
+    /// Deletes the record from the domain.
     pub fn delete_record(&self, domain_id: u64, record_id: u64) -> Result<(), ClientError> {
         let url = format!("{}/v2/dns/{}/record/{}", DYNU_API, domain_id, record_id);
+        self.check_writable("DELETE", &url)?;
+        let _permit = self.limiter.acquire("records");
         let result: Response = self._client.delete(&url).send()?;
         let status = result.status();
         if !status.is_success() {
@@ -272,43 +1338,125 @@ impl DynuClient {
         Ok(())
     }
 
+    /// Adds the record to the domain, returning the id dynu gave it.
     pub fn create_record(
         &self,
         domain_id: u64,
         record_dto: &RecordDTO,
     ) -> Result<u64, ClientError> {
         let url = format!("{}/v2/dns/{}/record", DYNU_API, domain_id);
-        let result: Response = self
-            ._client
-            .post(&url)
-            .headers(self.json_content_header()?)
-            .json(record_dto)
-            .send()?;
-        let status = result.status();
-        if !status.is_success() {
-            return http_error(result, &url, "POST", &status);
-        }
+        self.check_writable("POST", &url)?;
+        let result = self.post_record(&url, record_dto)?;
         let response: ResponseWithId = result.json()?;
         Ok(response.id)
     }
 
+    /// Dynu has no endpoint creating several records in one request, so the
+    /// records are created by a few requests in flight at once rather than one
+    /// after the other, within the limits of the client and retrying the ones
+    /// rejected by its rate limiting. The ids or errors are in the order of the
+    /// records, on_created being called as each request completes, e.g. to
+    /// report progress.
+    pub fn create_records(
+        &self,
+        domain_id: u64,
+        records: &[RecordDTO],
+        on_created: impl Fn() + Sync,
+    ) -> Vec<Result<u64, ClientError>> {
+        let workers = self.limiter.limits.max_concurrent.unwrap_or(BULK_WORKERS);
+        in_parallel(records, workers, |record| {
+            let result = rate_limited(|| self.create_record(domain_id, record));
+            on_created();
+            result
+        })
+    }
+
+    /// Replaces the record with the same id by record_dto.
     pub fn update_record(&self, domain_id: u64, record_dto: &RecordDTO) -> Result<(), ClientError> {
         let url = format!(
             "{}/v2/dns/{}/record/{}",
             DYNU_API,
             domain_id,
-            record_dto.id().unwrap()
+            record_dto.require_id()?
         );
+        self.check_writable("POST", &url)?;
+        self.post_record(&url, record_dto)?;
+        Ok(())
+    }
+
+    fn post_record<T: Serialize>(&self, url: &str, record: &T) -> Result<Response, ClientError> {
+        let _permit = self.limiter.acquire("records");
         let result: Response = self
             ._client
-            .post(&url)
+            .post(url)
             .headers(self.json_content_header()?)
-            .json(record_dto)
+            .json(record)
             .send()?;
         let status = result.status();
         if !status.is_success() {
-            return http_error(result, &url, "POST", &status);
+            return http_error(result, url, "POST", &status);
+        }
+        Ok(result)
+    }
+
+    /// Records of every type, for the generic record commands.
+    pub fn get_generic_records(&self, domain_id: u64) -> Result<GenericRecordsDTO, ClientError> {
+        let url = format!("{}/v2/dns/{}/record", DYNU_API, domain_id);
+        let _permit = self.limiter.acquire("records");
+        let result: Response = self._client.get(&url).send()?;
+        let status = result.status();
+        if !status.is_success() {
+            return http_error(result, &url, "GET", &status);
+        }
+        let response: GenericRecordsDTO = result.json()?;
+        Ok(response)
+    }
+
+    /// The record of any type with the id, None when dynu doesn't return it.
+    pub fn get_generic_record(
+        &self,
+        domain_id: u64,
+        record_id: u64,
+    ) -> Result<Option<GenericRecordDTO>, ClientError> {
+        let url = format!("{}/v2/dns/{}/record/{}", DYNU_API, domain_id, record_id);
+        let _permit = self.limiter.acquire("records");
+        let result: Response = self._client.get(&url).send()?;
+        let status = result.status();
+        if !status.is_success() {
+            return Ok(None);
         }
+        let response: GenericRecordDTO = result.json()?;
+        Ok(Some(response))
+    }
+
+    /// Adds the record of any type to the domain, returning the id dynu gave
+    /// it.
+    pub fn create_generic_record(
+        &self,
+        domain_id: u64,
+        record_dto: &GenericRecordDTO,
+    ) -> Result<u64, ClientError> {
+        let url = format!("{}/v2/dns/{}/record", DYNU_API, domain_id);
+        self.check_writable("POST", &url)?;
+        let result = self.post_record(&url, record_dto)?;
+        let response: ResponseWithId = result.json()?;
+        Ok(response.id)
+    }
+
+    /// Replaces the record of any type with the same id by record_dto.
+    pub fn update_generic_record(
+        &self,
+        domain_id: u64,
+        record_dto: &GenericRecordDTO,
+    ) -> Result<(), ClientError> {
+        let url = format!(
+            "{}/v2/dns/{}/record/{}",
+            DYNU_API,
+            domain_id,
+            record_dto.require_id()?
+        );
+        self.check_writable("POST", &url)?;
+        self.post_record(&url, record_dto)?;
         Ok(())
     }
 }
@@ -328,7 +1476,249 @@ mod tests {
 
     fn make_client() -> DynuClient {
         let api_key = env::var("DYNU_API_KEY").unwrap();
-        DynuClient::new(&api_key).unwrap()
+        DynuClient::new(&api_key, USER_AGENT).unwrap()
+    }
+
+    #[test]
+    fn is_conflict_should_recognise_existing_records() {
+        let error = |status, msg: &str| ClientError::StatusError(status, msg.to_string());
+        assert!(error(409, "POST /v2/dns/1/record, status_code=409").is_conflict());
+        assert!(error(400, "Argument Exception: Record already exists.").is_conflict());
+        assert!(!error(400, "Argument Exception: Invalid IPv4 address.").is_conflict());
+        assert!(!error(429, "").is_conflict());
+    }
+
+    #[test]
+    fn require_id_should_fail_on_malformed_answers() {
+        let domain = DomainDTO {
+            name: "example.dynu.net".to_string(),
+            ..DomainDTO::default()
+        };
+        let missing = domain.require_id().unwrap_err();
+        assert!(
+            matches!(missing, ClientError::MalformedResponse(_)),
+            "{}",
+            missing
+        );
+        let record = RecordDTO::txt_record("_test", "value", 120, Some(RECORD_ID));
+        assert_eq!(record.require_id().unwrap(), RECORD_ID);
+        assert!(DynuClient::new("key", USER_AGENT)
+            .unwrap()
+            .update_domain(&domain)
+            .is_err());
+    }
+
+    #[test]
+    fn read_only_client_should_refuse_writes() {
+        let client = DynuClient::new("key", USER_AGENT)
+            .unwrap()
+            .with_read_only(true);
+        let refused = client.delete_record(DOMAIN_ID, RECORD_ID).unwrap_err();
+        assert!(matches!(refused, ClientError::ReadOnly(_)), "{}", refused);
+        let record = RecordDTO::txt_record("_test", "value", 120, Some(RECORD_ID));
+        assert!(matches!(
+            client.update_record(DOMAIN_ID, &record),
+            Err(ClientError::ReadOnly(_))
+        ));
+        assert!(matches!(
+            client.create_record(DOMAIN_ID, &record),
+            Err(ClientError::ReadOnly(_))
+        ));
+        assert!(matches!(
+            client.create_domain(&DomainDTO::default()),
+            Err(ClientError::ReadOnly(_))
+        ));
+        assert!(matches!(
+            client.delete_domain(DOMAIN_ID),
+            Err(ClientError::ReadOnly(_))
+        ));
+    }
+
+    #[test]
+    fn limiter_should_wait_once_the_budget_is_spent() {
+        let window = Duration::from_millis(200);
+        let limiter = Limiter::new(
+            Limits {
+                max_concurrent: Some(2),
+                budgets: BTreeMap::from([("records".to_string(), 2)]),
+            },
+            window,
+        );
+        let start = Instant::now();
+        for _ in 0..2 {
+            drop(limiter.acquire("records"));
+            drop(limiter.acquire("domains"));
+        }
+        assert!(start.elapsed() < window);
+        let permit = limiter.acquire("records");
+        assert!(start.elapsed() >= window);
+        assert_eq!(*limiter.in_flight.lock().unwrap(), 1);
+        drop(permit);
+        assert_eq!(*limiter.in_flight.lock().unwrap(), 0);
+        assert_eq!(
+            limiter.calls.counts(),
+            BTreeMap::from([("domains".to_string(), 2), ("records".to_string(), 3)])
+        );
+    }
+
+    #[test]
+    fn in_parallel_should_keep_the_order_of_the_items() {
+        let items: Vec<u64> = (0..10).collect();
+        let calls = Mutex::new(0);
+        let results = in_parallel(&items, 4, |item| {
+            *calls.lock().unwrap() += 1;
+            item * 2
+        });
+        assert_eq!(results, (0..10).map(|i| i * 2).collect::<Vec<u64>>());
+        assert_eq!(*calls.lock().unwrap(), 10);
+        assert!(in_parallel(&[] as &[u64], 4, |item| *item).is_empty());
+    }
+
+    #[test]
+    fn groups_should_gather_the_domains_of_each_group() {
+        let domain = |name: &str, group: &str| DomainDTO {
+            name: name.to_string(),
+            group: group.to_string(),
+            ipv4: true,
+            ipv4_address: Some("192.0.2.1".to_string()),
+            ..Default::default()
+        };
+        let groups = groups(vec![
+            domain("a.dynu.net", "home"),
+            domain("b.dynu.net", ""),
+            domain("c.dynu.net", "home"),
+            domain("d.dynu.net", "office"),
+        ]);
+        assert_eq!(groups.keys().collect::<Vec<_>>(), vec!["home", "office"]);
+        assert_eq!(groups["home"][1].name, "c.dynu.net");
+        let updated = groups["home"][0].with_addresses(None, Some("2001:db8::1"));
+        assert_eq!(updated.ipv4_address.as_deref(), Some("192.0.2.1"));
+        assert!(updated.ipv6);
+        assert_eq!(updated.ipv6_address.as_deref(), Some("2001:db8::1"));
+    }
+
+    mod model {
+        use super::*;
+
+        #[test]
+        fn domain_should_round_trip_unknown_fields() {
+            let json = serde_json::json!({
+                "id": 1, "name": "example.dynu.net", "unicodeName": "example.dynu.net",
+                "token": null, "state": "Complete", "group": "", "ipv4Address": null,
+                "ipv6Address": null, "ttl": 90, "ipv4": false, "ipv6": false,
+                "ipv4WildcardAlias": true, "ipv6WildcardAlias": false,
+                "createdOn": null, "updatedOn": null, "allowZoneTransfer": true
+            });
+            let domain: DomainDTO = serde_json::from_value(json.clone()).unwrap();
+            assert_eq!(serde_json::to_value(&domain).unwrap(), json);
+        }
+
+        fn fixture(name: &str) -> serde_json::Value {
+            let path = format!("{}/fixtures/dynu/{}", env!("CARGO_MANIFEST_DIR"), name);
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+        }
+
+        #[test]
+        fn fixtures_should_round_trip() {
+            let json = fixture("domains.json");
+            let domains: DomainsDTO = serde_json::from_value(json.clone()).unwrap();
+            assert_eq!(serde_json::to_value(&domains).unwrap(), json);
+
+            let json = fixture("records.json");
+            let records: RecordsDTO = serde_json::from_value(json.clone()).unwrap();
+            let types: Vec<&str> = records
+                .dns_records
+                .iter()
+                .map(|r| r.record_type())
+                .collect();
+            assert_eq!(types, vec!["SOA", "TXT", "A"]);
+            assert_eq!(serde_json::to_value(&records).unwrap(), json);
+
+            let json = fixture("record_created.json");
+            let created: ResponseWithId = serde_json::from_value(json.clone()).unwrap();
+            assert_eq!(created.id, 10395751);
+            assert_eq!(serde_json::to_value(&created).unwrap(), json);
+        }
+
+        #[test]
+        fn fixtures_should_tolerate_omitted_fields_and_unsupported_types() {
+            let domain: DomainDTO = serde_json::from_value(fixture("domain_minimal.json")).unwrap();
+            assert_eq!(domain.name, "example.dynu.net");
+            assert_eq!(domain.group, "");
+
+            let records: RecordsDTO =
+                serde_json::from_value(fixture("records_unsupported.json")).unwrap();
+            assert_eq!(records.dns_records.len(), 7);
+            let mx = &records.dns_records[0];
+            assert_eq!(mx.record_type(), "MX");
+            assert_eq!(mx.require_id().unwrap(), 10395748);
+            assert_eq!(mx.value(), "host=mail.example.dynu.net priority=10");
+            assert_eq!(
+                serde_json::to_value(mx).unwrap(),
+                fixture("records_unsupported.json")["dnsRecords"][0]
+            );
+            let record = &records.dns_records[1];
+            assert_eq!(record.value(), "some-challenge");
+            assert!(matches!(record, RecordDTO::TxtRecord { state: true, .. }));
+            assert_eq!(
+                records.dns_records[2],
+                RecordDTO::cname_record("blog", "example.github.io", 300, Some(10395750))
+            );
+            let caa = &records.dns_records[3];
+            assert_eq!(
+                caa,
+                &RecordDTO::caa_record("", 0, "issue", "letsencrypt.org", 300, Some(10395751))
+            );
+            assert_eq!(caa.value(), "0 issue \"letsencrypt.org\"");
+            assert_eq!(
+                records.dns_records[4],
+                RecordDTO::ns_record("lab", "ns1.example.net", 3600, Some(10395752))
+            );
+            assert_eq!(
+                records.dns_records[5],
+                RecordDTO::aaaa_record("www", "2001:db8::1", 300, Some(10395753))
+            );
+            assert_eq!(
+                records.dns_records[6],
+                RecordDTO::spf_record("", "v=spf1 mx -all", 3600, Some(10395754))
+            );
+        }
+
+        #[test]
+        fn ip_update_history_fixture_should_deserialise() {
+            let history: IpUpdateHistoryDTO =
+                serde_json::from_value(fixture("ip_update_history.json")).unwrap();
+            let update = &history.ip_update_histories[0];
+            assert_eq!(update.hostname.as_deref(), Some("example.dynu.net"));
+            assert_eq!(update.ipv4_address.as_deref(), Some("192.0.2.1"));
+            assert_eq!(update.date.as_deref(), Some("2024-05-01T10:00:00"));
+            assert_eq!(history.ip_update_histories.len(), 2);
+        }
+
+        #[test]
+        fn account_fixture_should_deserialise() {
+            let account: AccountDTO = serde_json::from_value(fixture("account.json")).unwrap();
+            assert_eq!(account.membership_level.as_deref(), Some("Basic"));
+            assert_eq!(account.max_domains, Some(4));
+            assert_eq!(account.max_records, None);
+            assert!(account.other.contains_key("emailAddress"));
+        }
+
+        #[test]
+        fn error_fixtures_should_deserialise() {
+            for (name, status_code, error_type) in [
+                ("error_authentication.json", 401, "Authentication Exception"),
+                ("error_validation.json", 400, "Argument Exception"),
+            ] {
+                let json = fixture(name);
+                let error: ErrorDTO = serde_json::from_value(json.clone()).unwrap();
+                assert_eq!(
+                    (error.status_code, error.error_type.as_str()),
+                    (status_code, error_type)
+                );
+                assert_eq!(serde_json::to_value(&error).unwrap(), json);
+            }
+        }
     }
 
     mod get {
@@ -343,6 +1733,19 @@ mod tests {
             assert!(result.dns_records.len() > 1);
         }
 
+        #[test]
+        #[ignore]
+        fn account_records_iter_should_include_domain_records() {
+            let client = make_client();
+            let records: Vec<RecordDTO> = client
+                .account_records_iter()
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+
+            assert!(records.len() >= client.get_records(DOMAIN_ID).unwrap().dns_records.len());
+        }
+
         #[test]
         #[ignore]
         fn get_record_should_deserialise() {
@@ -0,0 +1,492 @@
+use reqwest::header::{HeaderMap, ACCEPT, CONTENT_TYPE};
+use reqwest::{Client, Response};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::dynu::{
+    check_writable, groups, spend_budget, status_error, AccountDTO, ClientError, DomainDTO,
+    DomainsDTO, GenericRecordDTO, GenericRecordsDTO, IpUpdateHistoryDTO, Limits, RecordDTO,
+    RecordsDTO, ResponseWithId, BUDGET_WINDOW, BULK_WORKERS, DYNU_API, RATE_LIMIT_ATTEMPTS,
+    RATE_LIMIT_PAUSE,
+};
+
+/// Counterpart of DynuClient for async services, with the same endpoints and
+/// limits. It needs a tokio runtime with the time driver enabled.
+pub struct AsyncDynuClient {
+    client: Client,
+    limiter: Limiter,
+    read_only: bool,
+}
+
+struct Limiter {
+    limits: Limits,
+    window: Duration,
+    in_flight: Option<Semaphore>,
+    sent: Mutex<HashMap<&'static str, VecDeque<Instant>>>,
+}
+
+impl Limiter {
+    fn new(limits: Limits, window: Duration) -> Limiter {
+        Limiter {
+            in_flight: limits.max_concurrent.map(|max| Semaphore::new(max.max(1))),
+            limits,
+            window,
+            sent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn acquire(&self, endpoint: &'static str) -> Option<SemaphorePermit<'_>> {
+        if let Some(budget) = self.limits.budgets.get(endpoint).copied() {
+            while let Some(wait) = spend_budget(&self.sent, endpoint, budget, self.window) {
+                tokio::time::sleep(wait).await;
+            }
+        }
+        match &self.in_flight {
+            // the semaphore is never closed
+            Some(in_flight) => in_flight.acquire().await.ok(),
+            None => None,
+        }
+    }
+}
+
+async fn http_error<T>(response: Response, url: &str, method: &str) -> Result<T, ClientError> {
+    let status = response.status();
+    let body = response.text().await?;
+    Err(status_error(body, url, method, &status))
+}
+
+// Calls rejected by Dynu's rate limiting are retried after a pause, as by the
+// blocking client.
+async fn rate_limited<T, F, R>(mut call: F) -> Result<T, ClientError>
+where
+    F: FnMut() -> R,
+    R: Future<Output = Result<T, ClientError>>,
+{
+    let mut attempt = 1;
+    loop {
+        match call().await {
+            Err(e) if e.is_rate_limited() && attempt < RATE_LIMIT_ATTEMPTS => {
+                attempt += 1;
+                eprintln!(
+                    "rate limited by dynu, pausing for {}",
+                    humantime::format_duration(RATE_LIMIT_PAUSE)
+                );
+                tokio::time::sleep(RATE_LIMIT_PAUSE).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+// Calls call on every item with at most workers calls in flight at once, the
+// results being in the order of the items. The calls borrow the client, so
+// they are polled together by the caller's task rather than spawned.
+async fn in_parallel<'a, T, R, F, Fut>(items: &'a [T], workers: usize, call: F) -> Vec<R>
+where
+    F: Fn(&'a T) -> Fut,
+    Fut: Future<Output = R>,
+{
+    let workers = Semaphore::new(workers.max(1));
+    let mut calls: Vec<Pin<Box<_>>> = items
+        .iter()
+        .map(|item| {
+            let workers = &workers;
+            let call = &call;
+            Box::pin(async move {
+                // the semaphore is never closed
+                let _worker = workers.acquire().await.ok();
+                call(item).await
+            })
+        })
+        .collect();
+    let mut results: Vec<Option<R>> = items.iter().map(|_| None).collect();
+    poll_fn(|context| {
+        let mut pending = false;
+        for (call, result) in calls.iter_mut().zip(results.iter_mut()) {
+            if result.is_none() {
+                match call.as_mut().poll(context) {
+                    Poll::Ready(value) => *result = Some(value),
+                    Poll::Pending => pending = true,
+                }
+            }
+        }
+        if pending {
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    })
+    .await;
+    results.into_iter().map(Option::unwrap).collect()
+}
+
+fn json_content_header() -> Result<HeaderMap, ClientError> {
+    let mut headers = HeaderMap::new();
+    headers.append(CONTENT_TYPE, "application/json".parse()?);
+    Ok(headers)
+}
+
+impl AsyncDynuClient {
+    /// A client authenticated with the API key, without limits.
+    pub fn new(api_key: &str, user_agent: &str) -> Result<AsyncDynuClient, ClientError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, "application/json".parse()?);
+        headers.insert("api-key", api_key.parse()?);
+        let client = Client::builder()
+            .default_headers(headers)
+            .user_agent(user_agent)
+            .build()?;
+        Ok(AsyncDynuClient {
+            client,
+            limiter: Limiter::new(Limits::default(), BUDGET_WINDOW),
+            read_only: false,
+        })
+    }
+
+    /// The client with its requests limited, as [`DynuClient::with_limits`](crate::dynu::DynuClient::with_limits).
+    pub fn with_limits(mut self, limits: Limits) -> AsyncDynuClient {
+        self.limiter = Limiter::new(limits, BUDGET_WINDOW);
+        self
+    }
+
+    /// A read-only client refuses every request changing the account, before
+    /// sending it.
+    pub fn with_read_only(mut self, read_only: bool) -> AsyncDynuClient {
+        self.read_only = read_only;
+        self
+    }
+
+    /// As [`DynuClient::get_domains`](crate::dynu::DynuClient::get_domains).
+    pub async fn get_domains(&self) -> Result<DomainsDTO, ClientError> {
+        let url = format!("{}/v2/dns", DYNU_API);
+        let _permit = self.limiter.acquire("domains").await;
+        let response = self.client.get(&url).send().await?;
+        if !response.status().is_success() {
+            return http_error(response, &url, "GET").await;
+        }
+        Ok(response.json().await?)
+    }
+
+    /// As [`DynuClient::update_domains`](crate::dynu::DynuClient::update_domains).
+    pub async fn update_domains(&self, domains: &[DomainDTO]) -> Vec<Result<(), ClientError>> {
+        let workers = self.limiter.limits.max_concurrent.unwrap_or(BULK_WORKERS);
+        in_parallel(domains, workers, |domain| {
+            rate_limited(move || self.update_domain(domain))
+        })
+        .await
+    }
+
+    /// As [`DynuClient::get_groups`](crate::dynu::DynuClient::get_groups).
+    pub async fn get_groups(&self) -> Result<BTreeMap<String, Vec<DomainDTO>>, ClientError> {
+        Ok(groups(self.get_domains().await?.domains))
+    }
+
+    /// As [`DynuClient::get_ip_update_history`](crate::dynu::DynuClient::get_ip_update_history).
+    pub async fn get_ip_update_history(&self) -> Result<IpUpdateHistoryDTO, ClientError> {
+        let url = format!("{}/v2/dns/ipUpdateHistory", DYNU_API);
+        let _permit = self.limiter.acquire("history").await;
+        let response = self.client.get(&url).send().await?;
+        if !response.status().is_success() {
+            return http_error(response, &url, "GET").await;
+        }
+        Ok(response.json().await?)
+    }
+
+    /// As [`DynuClient::get_account`](crate::dynu::DynuClient::get_account).
+    pub async fn get_account(&self) -> Result<AccountDTO, ClientError> {
+        let url = format!("{}/v2/account", DYNU_API);
+        let _permit = self.limiter.acquire("account").await;
+        let response = self.client.get(&url).send().await?;
+        if !response.status().is_success() {
+            return http_error(response, &url, "GET").await;
+        }
+        Ok(response.json().await?)
+    }
+
+    /// As [`DynuClient::get_domain`](crate::dynu::DynuClient::get_domain).
+    pub async fn get_domain(&self, id: u64) -> Result<Option<DomainDTO>, ClientError> {
+        let url = format!("{}/v2/dns/{}", DYNU_API, id);
+        let _permit = self.limiter.acquire("domains").await;
+        let response = self.client.get(&url).send().await?;
+        if !response.status().is_success() {
+            return Ok(None);
+        }
+        Ok(Some(response.json().await?))
+    }
+
+    /// As [`DynuClient::update_domain`](crate::dynu::DynuClient::update_domain).
+    pub async fn update_domain(&self, domain_dto: &DomainDTO) -> Result<(), ClientError> {
+        let url = format!("{}/v2/dns/{}", DYNU_API, domain_dto.require_id()?);
+        check_writable(self.read_only, "POST", &url)?;
+        let _permit = self.limiter.acquire("domains").await;
+        let response = self
+            .client
+            .post(&url)
+            .headers(json_content_header()?)
+            .json(domain_dto)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return http_error(response, &url, "POST").await;
+        }
+        Ok(())
+    }
+
+    /// As [`DynuClient::create_domain`](crate::dynu::DynuClient::create_domain).
+    pub async fn create_domain(&self, domain_dto: &DomainDTO) -> Result<u64, ClientError> {
+        let url = format!("{}/v2/dns", DYNU_API);
+        check_writable(self.read_only, "POST", &url)?;
+        let _permit = self.limiter.acquire("domains").await;
+        let response = self
+            .client
+            .post(&url)
+            .headers(json_content_header()?)
+            .json(domain_dto)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return http_error(response, &url, "POST").await;
+        }
+        let created: ResponseWithId = response.json().await?;
+        Ok(created.id)
+    }
+
+    /// As [`DynuClient::delete_domain`](crate::dynu::DynuClient::delete_domain).
+    pub async fn delete_domain(&self, id: u64) -> Result<(), ClientError> {
+        let url = format!("{}/v2/dns/{}", DYNU_API, id);
+        check_writable(self.read_only, "DELETE", &url)?;
+        let _permit = self.limiter.acquire("domains").await;
+        let response = self.client.delete(&url).send().await?;
+        if !response.status().is_success() {
+            return http_error(response, &url, "DELETE").await;
+        }
+        Ok(())
+    }
+
+    /// As [`DynuClient::get_records`](crate::dynu::DynuClient::get_records).
+    pub async fn get_records(&self, domain_id: u64) -> Result<RecordsDTO, ClientError> {
+        let url = format!("{}/v2/dns/{}/record", DYNU_API, domain_id);
+        let _permit = self.limiter.acquire("records").await;
+        let response = self.client.get(&url).send().await?;
+        if !response.status().is_success() {
+            return http_error(response, &url, "GET").await;
+        }
+        Ok(response.json().await?)
+    }
+
+    /// As [`DynuClient::get_record`](crate::dynu::DynuClient::get_record).
+    pub async fn get_record(
+        &self,
+        domain_id: u64,
+        record_id: u64,
+    ) -> Result<Option<RecordDTO>, ClientError> {
+        let url = format!("{}/v2/dns/{}/record/{}", DYNU_API, domain_id, record_id);
+        let _permit = self.limiter.acquire("records").await;
+        let response = self.client.get(&url).send().await?;
+        if !response.status().is_success() {
+            return Ok(None);
+        }
+        Ok(Some(response.json().await?))
+    }
+
+    /// As [`DynuClient::delete_record`](crate::dynu::DynuClient::delete_record).
+    pub async fn delete_record(&self, domain_id: u64, record_id: u64) -> Result<(), ClientError> {
+        let url = format!("{}/v2/dns/{}/record/{}", DYNU_API, domain_id, record_id);
+        check_writable(self.read_only, "DELETE", &url)?;
+        let _permit = self.limiter.acquire("records").await;
+        let response = self.client.delete(&url).send().await?;
+        if !response.status().is_success() {
+            return http_error(response, &url, "DELETE").await;
+        }
+        Ok(())
+    }
+
+    /// As [`DynuClient::create_record`](crate::dynu::DynuClient::create_record).
+    pub async fn create_record(
+        &self,
+        domain_id: u64,
+        record_dto: &RecordDTO,
+    ) -> Result<u64, ClientError> {
+        let url = format!("{}/v2/dns/{}/record", DYNU_API, domain_id);
+        check_writable(self.read_only, "POST", &url)?;
+        let response = self.post_record(&url, record_dto).await?;
+        let created: ResponseWithId = response.json().await?;
+        Ok(created.id)
+    }
+
+    /// As [`DynuClient::create_records`](crate::dynu::DynuClient::create_records).
+    pub async fn create_records(
+        &self,
+        domain_id: u64,
+        records: &[RecordDTO],
+        on_created: impl Fn() + Sync,
+    ) -> Vec<Result<u64, ClientError>> {
+        let workers = self.limiter.limits.max_concurrent.unwrap_or(BULK_WORKERS);
+        let on_created = &on_created;
+        in_parallel(records, workers, |record| async move {
+            let result = rate_limited(|| self.create_record(domain_id, record)).await;
+            on_created();
+            result
+        })
+        .await
+    }
+
+    /// As [`DynuClient::update_record`](crate::dynu::DynuClient::update_record).
+    pub async fn update_record(
+        &self,
+        domain_id: u64,
+        record_dto: &RecordDTO,
+    ) -> Result<(), ClientError> {
+        let url = format!(
+            "{}/v2/dns/{}/record/{}",
+            DYNU_API,
+            domain_id,
+            record_dto.require_id()?
+        );
+        check_writable(self.read_only, "POST", &url)?;
+        self.post_record(&url, record_dto).await?;
+        Ok(())
+    }
+
+    async fn post_record<T: Serialize>(
+        &self,
+        url: &str,
+        record: &T,
+    ) -> Result<Response, ClientError> {
+        let _permit = self.limiter.acquire("records").await;
+        let response = self
+            .client
+            .post(url)
+            .headers(json_content_header()?)
+            .json(record)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return http_error(response, url, "POST").await;
+        }
+        Ok(response)
+    }
+
+    /// As [`DynuClient::get_generic_records`](crate::dynu::DynuClient::get_generic_records).
+    pub async fn get_generic_records(
+        &self,
+        domain_id: u64,
+    ) -> Result<GenericRecordsDTO, ClientError> {
+        let url = format!("{}/v2/dns/{}/record", DYNU_API, domain_id);
+        let _permit = self.limiter.acquire("records").await;
+        let response = self.client.get(&url).send().await?;
+        if !response.status().is_success() {
+            return http_error(response, &url, "GET").await;
+        }
+        Ok(response.json().await?)
+    }
+
+    /// As [`DynuClient::get_generic_record`](crate::dynu::DynuClient::get_generic_record).
+    pub async fn get_generic_record(
+        &self,
+        domain_id: u64,
+        record_id: u64,
+    ) -> Result<Option<GenericRecordDTO>, ClientError> {
+        let url = format!("{}/v2/dns/{}/record/{}", DYNU_API, domain_id, record_id);
+        let _permit = self.limiter.acquire("records").await;
+        let response = self.client.get(&url).send().await?;
+        if !response.status().is_success() {
+            return Ok(None);
+        }
+        Ok(Some(response.json().await?))
+    }
+
+    /// As [`DynuClient::create_generic_record`](crate::dynu::DynuClient::create_generic_record).
+    pub async fn create_generic_record(
+        &self,
+        domain_id: u64,
+        record_dto: &GenericRecordDTO,
+    ) -> Result<u64, ClientError> {
+        let url = format!("{}/v2/dns/{}/record", DYNU_API, domain_id);
+        check_writable(self.read_only, "POST", &url)?;
+        let response = self.post_record(&url, record_dto).await?;
+        let created: ResponseWithId = response.json().await?;
+        Ok(created.id)
+    }
+
+    /// As [`DynuClient::update_generic_record`](crate::dynu::DynuClient::update_generic_record).
+    pub async fn update_generic_record(
+        &self,
+        domain_id: u64,
+        record_dto: &GenericRecordDTO,
+    ) -> Result<(), ClientError> {
+        let url = format!(
+            "{}/v2/dns/{}/record/{}",
+            DYNU_API,
+            domain_id,
+            record_dto.require_id()?
+        );
+        check_writable(self.read_only, "POST", &url)?;
+        self.post_record(&url, record_dto).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn limiter_should_wait_once_the_budget_is_spent() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let window = Duration::from_millis(200);
+        let limiter = Limiter::new(
+            Limits {
+                max_concurrent: Some(1),
+                budgets: BTreeMap::from([("records".to_string(), 2)]),
+            },
+            window,
+        );
+        runtime.block_on(async {
+            let start = Instant::now();
+            for _ in 0..2 {
+                drop(limiter.acquire("records").await);
+            }
+            assert!(start.elapsed() < window);
+            let permit = limiter.acquire("records").await;
+            assert!(start.elapsed() >= window);
+            assert_eq!(limiter.in_flight.as_ref().unwrap().available_permits(), 0);
+            drop(permit);
+            assert_eq!(limiter.in_flight.as_ref().unwrap().available_permits(), 1);
+        });
+    }
+
+    #[test]
+    fn in_parallel_should_keep_the_order_of_the_items() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let in_flight = Mutex::new((0, 0));
+        let results = runtime.block_on(in_parallel(&[30u64, 10, 20, 0], 2, |delay| {
+            let in_flight = &in_flight;
+            async move {
+                {
+                    let mut in_flight = in_flight.lock().unwrap();
+                    in_flight.0 += 1;
+                    in_flight.1 = in_flight.1.max(in_flight.0);
+                }
+                tokio::time::sleep(Duration::from_millis(*delay)).await;
+                in_flight.lock().unwrap().0 -= 1;
+                delay * 2
+            }
+        }));
+        assert_eq!(results, vec![60, 20, 40, 0]);
+        assert_eq!(in_flight.lock().unwrap().1, 2);
+    }
+}
@@ -0,0 +1,217 @@
+use clap::ValueEnum;
+
+use crate::content::same_content;
+use crate::dynu::{DynuClient, RecordDTO};
+use crate::names::same_name;
+use crate::SelfError;
+
+pub const DEFAULT_TTL: u64 = 300;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum RecordKind {
+    A,
+    Txt,
+}
+
+impl RecordKind {
+    fn record_type(&self) -> &'static str {
+        match self {
+            RecordKind::A => "A",
+            RecordKind::Txt => "TXT",
+        }
+    }
+}
+
+// State a node should be in. A node holds exactly the given address, since
+// several addresses would be served in turn, while a TXT value is added next
+// to the other values of the node, which commonly hold unrelated tokens.
+#[derive(Debug, PartialEq)]
+pub enum Desired {
+    Address(String),
+    Text(String),
+    Absent(RecordKind, Option<String>),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Step {
+    Create(RecordDTO),
+    // the current record is boxed, RecordDTO being large
+    Update(Box<RecordDTO>, RecordDTO),
+    Delete(RecordDTO),
+}
+
+impl Step {
+    pub fn describe(&self) -> String {
+        let describe = |r: &RecordDTO| {
+            format!(
+                "{} node='{}' value={} ttl={}",
+                r.record_type(),
+                r.node_name(),
+                r.value(),
+                r.ttl()
+            )
+        };
+        match self {
+            Step::Create(record) => format!("create {}", describe(record)),
+            Step::Update(old, new) => format!("update {} to {}", describe(old), describe(new)),
+            Step::Delete(record) => format!("delete {}", describe(record)),
+        }
+    }
+}
+
+fn on_node<'a>(
+    records: &'a [RecordDTO],
+    node: &'a str,
+    kind: RecordKind,
+) -> impl Iterator<Item = &'a RecordDTO> {
+    records
+        .iter()
+        .filter(move |r| r.record_type() == kind.record_type() && same_name(r.node_name(), node))
+}
+
+// Steps bringing the records of node to the desired state, none when it is
+// already there. The TTL is only enforced when given.
+pub fn plan(records: &[RecordDTO], node: &str, desired: &Desired, ttl: Option<u64>) -> Vec<Step> {
+    let ttl_matches = |r: &RecordDTO| ttl.is_none() || ttl == Some(r.ttl());
+    match desired {
+        Desired::Address(address) => {
+            let existing: Vec<&RecordDTO> = on_node(records, node, RecordKind::A).collect();
+            let keep = existing
+                .iter()
+                .position(|r| same_content("A", &r.value(), address));
+            let mut steps = Vec::new();
+            match (keep, existing.first()) {
+                (Some(index), _) if ttl_matches(existing[index]) => {}
+                (Some(index), _) => {
+                    let current = existing[index];
+                    steps.push(Step::Update(
+                        Box::new(current.clone()),
+                        RecordDTO::a_record(node, address, ttl.unwrap(), current.id()),
+                    ));
+                }
+                (None, Some(first)) => steps.push(Step::Update(
+                    Box::new((*first).clone()),
+                    RecordDTO::a_record(node, address, ttl.unwrap_or(first.ttl()), first.id()),
+                )),
+                (None, None) => steps.push(Step::Create(RecordDTO::a_record(
+                    node,
+                    address,
+                    ttl.unwrap_or(DEFAULT_TTL),
+                    None,
+                ))),
+            }
+            let kept = keep.unwrap_or(0);
+            for (index, record) in existing.iter().enumerate() {
+                if index != kept {
+                    steps.push(Step::Delete((*record).clone()));
+                }
+            }
+            steps
+        }
+        Desired::Text(value) => {
+            let existing = on_node(records, node, RecordKind::Txt)
+                .find(|r| same_content("TXT", &r.value(), value));
+            match existing {
+                Some(record) if ttl_matches(record) => vec![],
+                Some(record) => vec![Step::Update(
+                    Box::new(record.clone()),
+                    RecordDTO::txt_record(node, &record.value(), ttl.unwrap(), record.id()),
+                )],
+                None => vec![Step::Create(RecordDTO::txt_record(
+                    node,
+                    value,
+                    ttl.unwrap_or(DEFAULT_TTL),
+                    None,
+                ))],
+            }
+        }
+        Desired::Absent(kind, value) => on_node(records, node, *kind)
+            .filter(|r| match value {
+                Some(value) => same_content(kind.record_type(), &r.value(), value),
+                None => true,
+            })
+            .map(|r| Step::Delete(r.clone()))
+            .collect(),
+    }
+}
+
+pub fn apply(dynu_client: &DynuClient, domain_id: u64, steps: &[Step]) -> Result<(), SelfError> {
+    for step in steps {
+        match step {
+            Step::Create(record) => {
+                dynu_client.create_record(domain_id, record)?;
+            }
+            Step::Update(_, record) => dynu_client.update_record(domain_id, record)?,
+            Step::Delete(record) => dynu_client.delete_record(domain_id, record.require_id()?)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records() -> Vec<RecordDTO> {
+        vec![
+            RecordDTO::a_record("vpn", "203.0.113.9", 300, Some(1)),
+            RecordDTO::a_record("lb", "203.0.113.1", 300, Some(2)),
+            RecordDTO::a_record("lb", "203.0.113.2", 300, Some(3)),
+            RecordDTO::txt_record("_old-token", "a", 120, Some(4)),
+            RecordDTO::txt_record("_old-token", "b", 120, Some(5)),
+        ]
+    }
+
+    #[test]
+    fn plan_should_be_empty_when_already_in_state() {
+        let records = records();
+        let address = Desired::Address("203.0.113.9".to_string());
+        assert_eq!(plan(&records, "vpn", &address, None), vec![]);
+        assert_eq!(plan(&records, "vpn", &address, Some(300)), vec![]);
+        let text = Desired::Text("\"a\"".to_string());
+        assert_eq!(plan(&records, "_old-token", &text, None), vec![]);
+        let absent = Desired::Absent(RecordKind::Txt, None);
+        assert_eq!(plan(&records, "missing", &absent, None), vec![]);
+    }
+
+    #[test]
+    fn plan_should_leave_a_single_address() {
+        let records = records();
+        let steps = plan(
+            &records,
+            "lb",
+            &Desired::Address("203.0.113.2".to_string()),
+            None,
+        );
+        assert_eq!(steps, vec![Step::Delete(records[1].clone())]);
+
+        let steps = plan(
+            &records,
+            "lb",
+            &Desired::Address("203.0.113.3".to_string()),
+            Some(60),
+        );
+        assert_eq!(
+            steps,
+            vec![
+                Step::Update(
+                    Box::new(records[1].clone()),
+                    RecordDTO::a_record("lb", "203.0.113.3", 60, Some(2))
+                ),
+                Step::Delete(records[2].clone()),
+            ]
+        );
+    }
+
+    #[test]
+    fn plan_should_delete_only_matching_values() {
+        let records = records();
+        let absent = Desired::Absent(RecordKind::Txt, Some("b".to_string()));
+        assert_eq!(
+            plan(&records, "_old-token", &absent, None),
+            vec![Step::Delete(records[4].clone())]
+        );
+        let absent = Desired::Absent(RecordKind::Txt, None);
+        assert_eq!(plan(&records, "_old-token", &absent, None).len(), 2);
+    }
+}
@@ -0,0 +1,405 @@
+use clap::ValueEnum;
+use serde_json::json;
+use std::collections::BTreeMap;
+
+use crate::dynu::RecordDTO;
+
+// dnscontrol zones are exported as the D() call of a dnsconfig.js, or as the
+// JSON printed by dnscontrol print-ir.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Terraform,
+    Dnscontrol,
+    DnscontrolJson,
+}
+
+// Resources the records are exported as: the record sets of the generic
+// hashicorp/dns provider, which manages zones over RFC 2136 (see serve
+// rfc2136), or one dynu_dns_record per record following the naming of a dynu
+// provider.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum TerraformProvider {
+    Dns,
+    Dynu,
+}
+
+fn hcl_string(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace("${", "$${")
+        .replace("%{", "%%{");
+    format!("\"{}\"", escaped)
+}
+
+// Terraform identifiers built from the node and type, unique within the export.
+struct Labels {
+    used: BTreeMap<String, usize>,
+}
+
+impl Labels {
+    fn new() -> Labels {
+        Labels {
+            used: BTreeMap::new(),
+        }
+    }
+
+    fn next(&mut self, node: &str, record_type: &str) -> String {
+        let node = if node.is_empty() { "apex" } else { node };
+        let mut label: String = format!("{}_{}", node, record_type)
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        if label.starts_with(|c: char| c.is_ascii_digit() || c == '_') {
+            label = format!("r{}", label);
+        }
+        let count = self.used.entry(label.clone()).or_insert(0);
+        *count += 1;
+        match *count {
+            1 => label,
+            n => format!("{}_{}", label, n),
+        }
+    }
+}
+
+// SOA records, and the NS records of the domain itself, are managed by dynu
+// and never exported, nor are the records of types dynuupdater doesn't model.
+fn exported(records: &[RecordDTO]) -> impl Iterator<Item = &RecordDTO> {
+    records.iter().filter(|r| match r.record_type() {
+        _ if matches!(r, RecordDTO::Unknown { .. }) => false,
+        "SOA" => false,
+        "NS" => !r.node_name().is_empty(),
+        _ => true,
+    })
+}
+
+// Targets of CNAME, NS and PTR records are exported fully qualified, dynu
+// storing them without the trailing dot.
+fn exported_value(record: &RecordDTO) -> String {
+    match record.record_type() {
+        "CNAME" | "NS" | "PTR" => format!("{}.", record.value().trim_end_matches('.')),
+        _ => record.value(),
+    }
+}
+
+fn dns_provider(zone: &str, records: &[RecordDTO]) -> String {
+    // record sets group the values of a node and type, with the TTL of the
+    // first record of the set
+    let mut sets: Vec<(&str, &str, u64, Vec<String>)> = Vec::new();
+    // the dns provider has no resource for CAA and SPF records
+    for record in exported(records).filter(|r| !matches!(r.record_type(), "CAA" | "SPF")) {
+        let key = (record.node_name(), record.record_type());
+        match sets.iter_mut().find(|s| (s.0, s.1) == key) {
+            Some(set) => set.3.push(exported_value(record)),
+            None => sets.push((key.0, key.1, record.ttl(), vec![exported_value(record)])),
+        }
+    }
+    let mut labels = Labels::new();
+    sets.into_iter()
+        .map(|(node, record_type, ttl, values)| {
+            let (resource, attribute) = match record_type {
+                "A" => ("dns_a_record_set", "addresses"),
+                "AAAA" => ("dns_aaaa_record_set", "addresses"),
+                "CNAME" => ("dns_cname_record", "cname"),
+                "NS" => ("dns_ns_record_set", "nameservers"),
+                "PTR" => ("dns_ptr_record", "ptr"),
+                _ => ("dns_txt_record_set", "txt"),
+            };
+            let values: Vec<String> = values.iter().map(|v| hcl_string(v)).collect();
+            // a node has a single CNAME or PTR, which isn't a set
+            let values = match record_type {
+                "CNAME" | "PTR" => values.join(", "),
+                _ => format!("[{}]", values.join(", ")),
+            };
+            let mut block = format!(
+                "resource \"{}\" \"{}\" {{\n  zone = {}\n",
+                resource,
+                labels.next(node, record_type),
+                hcl_string(&format!("{}.", zone))
+            );
+            if !node.is_empty() {
+                block.push_str(&format!("  name = {}\n", hcl_string(node)));
+            }
+            block.push_str(&format!(
+                "  {} = {}\n  ttl = {}\n}}\n",
+                attribute, values, ttl
+            ));
+            block
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn dynu_provider(domain_id: u64, records: &[RecordDTO]) -> String {
+    let mut labels = Labels::new();
+    exported(records)
+        .map(|record| {
+            let value = match record {
+                RecordDTO::CaaRecord {
+                    flags, tag, value, ..
+                } => format!(
+                    "flags = {}\n  tag = {}\n  value = {}",
+                    flags,
+                    hcl_string(tag),
+                    hcl_string(value)
+                ),
+                _ => {
+                    let value_attribute = match record.record_type() {
+                        "A" => "ipv4_address",
+                        "AAAA" => "ipv6_address",
+                        "CNAME" | "NS" | "PTR" => "host",
+                        _ => "text_data",
+                    };
+                    format!("{} = {}", value_attribute, hcl_string(&record.value()))
+                }
+            };
+            format!(
+                "resource \"dynu_dns_record\" \"{}\" {{\n  domain_id = {}\n  node_name = {}\n  record_type = {}\n  {}\n  ttl = {}\n}}\n",
+                labels.next(record.node_name(), record.record_type()),
+                domain_id,
+                hcl_string(record.node_name()),
+                hcl_string(record.record_type()),
+                value,
+                record.ttl()
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn dnscontrol_name(node: &str) -> &str {
+    if node.is_empty() {
+        "@"
+    } else {
+        node
+    }
+}
+
+// JSON strings are valid javascript string literals.
+fn js_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap()
+}
+
+// dnscontrol has no dynu provider: the zone is declared with placeholders for
+// the registrar and the DNS provider it is actually managed with.
+pub fn dnscontrol(zone: &str, records: &[RecordDTO]) -> String {
+    let mut lines = vec![
+        "var REG_NONE = NewRegistrar(\"none\");".to_string(),
+        "var DSP_DYNU = NewDnsProvider(\"dynu\");".to_string(),
+        "".to_string(),
+        format!("D({}, REG_NONE, DnsProvider(DSP_DYNU),", js_string(zone)),
+    ];
+    // dnscontrol only writes SPF policies as TXT records, as RFC 7208 does
+    let calls: Vec<String> = exported(records)
+        .filter(|r| r.record_type() != "SPF")
+        .map(|record| {
+            // CAA records take the tag before the value, the critical flag
+            // being a modifier
+            let arguments = match record {
+                RecordDTO::CaaRecord {
+                    flags, tag, value, ..
+                } => format!(
+                    "{}, {}{}",
+                    js_string(tag),
+                    js_string(value),
+                    if flags & 128 != 0 {
+                        ", CAA_CRITICAL"
+                    } else {
+                        ""
+                    }
+                ),
+                _ => js_string(&exported_value(record)),
+            };
+            format!(
+                "    {}({}, {}, TTL({}))",
+                record.record_type(),
+                js_string(dnscontrol_name(record.node_name())),
+                arguments,
+                record.ttl()
+            )
+        })
+        .collect();
+    lines.push(calls.join(",\n"));
+    lines.push(");".to_string());
+    format!("{}\n", lines.join("\n"))
+}
+
+pub fn dnscontrol_json(zone: &str, records: &[RecordDTO]) -> String {
+    let records: Vec<_> = exported(records)
+        .filter(|r| r.record_type() != "SPF")
+        .map(|record| match record {
+            RecordDTO::CaaRecord {
+                flags, tag, value, ..
+            } => json!({
+                "type": "CAA",
+                "name": dnscontrol_name(record.node_name()),
+                "target": value,
+                "caaflag": flags,
+                "caatag": tag,
+                "ttl": record.ttl(),
+            }),
+            _ => json!({
+                "type": record.record_type(),
+                "name": dnscontrol_name(record.node_name()),
+                "target": exported_value(record),
+                "ttl": record.ttl(),
+            }),
+        })
+        .collect();
+    let document = json!({ "domains": [{ "name": zone, "records": records }] });
+    format!("{}\n", serde_json::to_string_pretty(&document).unwrap())
+}
+
+pub fn terraform(
+    provider: TerraformProvider,
+    zone: &str,
+    domain_id: u64,
+    records: &[RecordDTO],
+) -> String {
+    match provider {
+        TerraformProvider::Dns => dns_provider(zone, records),
+        TerraformProvider::Dynu => dynu_provider(domain_id, records),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records() -> Vec<RecordDTO> {
+        vec![
+            RecordDTO::a_record("", "192.0.2.1", 300, Some(1)),
+            RecordDTO::txt_record("_acme-challenge", "token-1", 120, Some(2)),
+            RecordDTO::txt_record("_acme-challenge", "token-${2}", 120, Some(3)),
+        ]
+    }
+
+    #[test]
+    fn dns_provider_should_group_record_sets() {
+        assert_eq!(
+            terraform(TerraformProvider::Dns, "example.dynu.net", 7, &records()),
+            r#"resource "dns_a_record_set" "apex_a" {
+  zone = "example.dynu.net."
+  addresses = ["192.0.2.1"]
+  ttl = 300
+}
+
+resource "dns_txt_record_set" "r_acme_challenge_txt" {
+  zone = "example.dynu.net."
+  name = "_acme-challenge"
+  txt = ["token-1", "token-$${2}"]
+  ttl = 120
+}
+"#
+        );
+    }
+
+    #[test]
+    fn cname_targets_should_be_exported_fully_qualified() {
+        let records = vec![RecordDTO::cname_record(
+            "blog",
+            "example.github.io",
+            300,
+            Some(4),
+        )];
+        assert_eq!(
+            terraform(TerraformProvider::Dns, "example.dynu.net", 7, &records),
+            r#"resource "dns_cname_record" "blog_cname" {
+  zone = "example.dynu.net."
+  name = "blog"
+  cname = "example.github.io."
+  ttl = 300
+}
+"#
+        );
+        assert!(dnscontrol("example.dynu.net", &records)
+            .contains(r#"CNAME("blog", "example.github.io.", TTL(300))"#));
+    }
+
+    #[test]
+    fn delegations_should_be_exported_as_name_server_sets() {
+        let records = vec![
+            RecordDTO::ns_record("", "ns1.dynu.com", 3600, Some(5)),
+            RecordDTO::ns_record("lab", "ns1.example.net", 3600, Some(6)),
+            RecordDTO::ns_record("lab", "ns2.example.net.", 3600, Some(7)),
+        ];
+        assert_eq!(
+            terraform(TerraformProvider::Dns, "example.dynu.net", 7, &records),
+            r#"resource "dns_ns_record_set" "lab_ns" {
+  zone = "example.dynu.net."
+  name = "lab"
+  nameservers = ["ns1.example.net.", "ns2.example.net."]
+  ttl = 3600
+}
+"#
+        );
+    }
+
+    #[test]
+    fn caa_records_should_be_exported_with_their_tag() {
+        let records = vec![RecordDTO::caa_record(
+            "",
+            128,
+            "issue",
+            "letsencrypt.org",
+            300,
+            Some(5),
+        )];
+        assert_eq!(
+            terraform(TerraformProvider::Dns, "example.dynu.net", 7, &records),
+            ""
+        );
+        assert!(
+            terraform(TerraformProvider::Dynu, "example.dynu.net", 7, &records)
+                .contains("  flags = 128\n  tag = \"issue\"\n  value = \"letsencrypt.org\"\n")
+        );
+        assert!(dnscontrol("example.dynu.net", &records)
+            .contains(r#"CAA("@", "issue", "letsencrypt.org", CAA_CRITICAL, TTL(300))"#));
+        let exported: serde_json::Value =
+            serde_json::from_str(&dnscontrol_json("example.dynu.net", &records)).unwrap();
+        assert_eq!(exported["domains"][0]["records"][0]["caatag"], "issue");
+    }
+
+    #[test]
+    fn spf_records_should_only_be_exported_to_the_dynu_provider() {
+        let records = vec![RecordDTO::spf_record("", "v=spf1 mx -all", 3600, Some(9))];
+        assert_eq!(
+            terraform(TerraformProvider::Dns, "example.dynu.net", 7, &records),
+            ""
+        );
+        assert!(
+            terraform(TerraformProvider::Dynu, "example.dynu.net", 7, &records)
+                .contains("  record_type = \"SPF\"\n  text_data = \"v=spf1 mx -all\"\n")
+        );
+        assert!(!dnscontrol("example.dynu.net", &records).contains("SPF"));
+    }
+
+    #[test]
+    fn dynu_provider_should_export_each_record_with_unique_labels() {
+        let exported = terraform(TerraformProvider::Dynu, "example.dynu.net", 7, &records());
+        assert!(exported.contains("resource \"dynu_dns_record\" \"r_acme_challenge_txt\" {"));
+        assert!(exported.contains("resource \"dynu_dns_record\" \"r_acme_challenge_txt_2\" {"));
+        assert!(exported.contains("  domain_id = 7\n  node_name = \"\"\n  record_type = \"A\"\n  ipv4_address = \"192.0.2.1\"\n"));
+    }
+
+    #[test]
+    fn dnscontrol_should_declare_the_zone() {
+        let exported = dnscontrol("example.dynu.net", &records());
+        assert!(exported.ends_with(
+            r#"D("example.dynu.net", REG_NONE, DnsProvider(DSP_DYNU),
+    A("@", "192.0.2.1", TTL(300)),
+    TXT("_acme-challenge", "token-1", TTL(120)),
+    TXT("_acme-challenge", "token-${2}", TTL(120))
+);
+"#
+        ));
+        let exported: serde_json::Value =
+            serde_json::from_str(&dnscontrol_json("example.dynu.net", &records())).unwrap();
+        assert_eq!(
+            exported["domains"][0]["records"][1],
+            json!({"type": "TXT", "name": "_acme-challenge", "target": "token-1", "ttl": 120})
+        );
+    }
+}
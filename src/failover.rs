@@ -0,0 +1,199 @@
+use std::net::{TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::Duration;
+
+use crate::netutils;
+use crate::SelfError;
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Check {
+    Tcp(String),
+    Http(String),
+}
+
+impl Check {
+    // tcp:<host>:<port> connects to the port, http(s) urls must answer with a
+    // success status.
+    pub fn parse(spec: &str) -> Result<Check, SelfError> {
+        if let Some(address) = spec.strip_prefix("tcp:") {
+            if address
+                .rsplit_once(':')
+                .is_some_and(|(_, port)| port.parse::<u16>().is_ok())
+            {
+                return Ok(Check::Tcp(address.to_string()));
+            }
+        } else if spec.starts_with("http://") || spec.starts_with("https://") {
+            return Ok(Check::Http(spec.to_string()));
+        }
+        Err(SelfError::MsgError(format!(
+            "invalid health check {}, expected tcp:<host>:<port> or an http(s) url",
+            spec
+        )))
+    }
+
+    pub fn run(&self, http: &reqwest::blocking::Client) -> Result<(), String> {
+        match self {
+            Check::Tcp(address) => {
+                let addresses = address.to_socket_addrs().map_err(|e| e.to_string())?;
+                let mut error = format!("{} did not resolve", address);
+                for socket in addresses {
+                    match TcpStream::connect_timeout(&socket, CHECK_TIMEOUT) {
+                        Ok(_) => return Ok(()),
+                        Err(e) => error = format!("{}: {}", socket, e),
+                    }
+                }
+                Err(error)
+            }
+            Check::Http(url) => http
+                .get(url)
+                .send()
+                .and_then(|r| r.error_for_status())
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Primary,
+    Backup,
+}
+
+// Hysteresis on the health checks: the record is only switched to the backup
+// after `failures` consecutive failed checks, and back to the primary after
+// `recoveries` consecutive successful ones, so that a flapping endpoint doesn't
+// flap the record as well.
+pub struct Monitor {
+    active: Target,
+    failures: u32,
+    recoveries: u32,
+    streak: u32,
+}
+
+impl Monitor {
+    pub fn new(active: Target, failures: u32, recoveries: u32) -> Monitor {
+        Monitor {
+            active,
+            failures: failures.max(1),
+            recoveries: recoveries.max(1),
+            streak: 0,
+        }
+    }
+
+    // Returns the target to switch to when the check result completes a streak.
+    pub fn observe(&mut self, healthy: bool) -> Option<Target> {
+        let (candidate, threshold) = match (self.active, healthy) {
+            (Target::Primary, false) => (Target::Backup, self.failures),
+            (Target::Backup, true) => (Target::Primary, self.recoveries),
+            _ => {
+                self.streak = 0;
+                return None;
+            }
+        };
+        self.streak += 1;
+        if self.streak < threshold {
+            return None;
+        }
+        self.streak = 0;
+        self.active = candidate;
+        Some(candidate)
+    }
+}
+
+pub struct FailoverOptions {
+    pub domain: String,
+    pub primary: String,
+    pub backup: String,
+    pub check: Check,
+    pub interval: Duration,
+    pub failures: u32,
+    pub recoveries: u32,
+}
+
+impl FailoverOptions {
+    pub fn address(&self, target: Target) -> &str {
+        match target {
+            Target::Primary => &self.primary,
+            Target::Backup => &self.backup,
+        }
+    }
+}
+
+// Checks the primary endpoint every interval forever, calling switch with the
+// target whenever the monitor decides the record has to change. A failed switch
+// is retried on the next check.
+pub fn run<F>(options: &FailoverOptions, initial: Target, mut switch: F) -> Result<(), SelfError>
+where
+    F: FnMut(Target) -> Result<(), SelfError>,
+{
+    let http = netutils::http_client()
+        .timeout(CHECK_TIMEOUT)
+        .build()
+        .map_err(|e| SelfError::MsgError(format!("{}", e)))?;
+    let mut monitor = Monitor::new(initial, options.failures, options.recoveries);
+    let mut pending: Option<Target> = None;
+    eprintln!(
+        "monitoring {:?} for domain={}, currently on {:?}",
+        options.check, options.domain, initial
+    );
+    loop {
+        let result = options.check.run(&http);
+        if let Err(e) = &result {
+            eprintln!("health check of domain={} failed: {}", options.domain, e);
+        }
+        if let Some(target) = monitor.observe(result.is_ok()) {
+            pending = Some(target);
+        }
+        if let Some(target) = pending {
+            match switch(target) {
+                Ok(()) => pending = None,
+                Err(e) => eprintln!(
+                    "cannot switch domain={} to {:?}, retrying on the next check: {}",
+                    options.domain, target, e
+                ),
+            }
+        }
+        thread::sleep(options.interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_should_accept_tcp_and_http_checks() {
+        assert_eq!(
+            Check::parse("tcp:192.0.2.1:443").unwrap(),
+            Check::Tcp("192.0.2.1:443".to_string())
+        );
+        assert_eq!(
+            Check::parse("tcp:[2001:db8::1]:22").unwrap(),
+            Check::Tcp("[2001:db8::1]:22".to_string())
+        );
+        assert!(matches!(
+            Check::parse("https://example.com/health").unwrap(),
+            Check::Http(_)
+        ));
+        assert!(Check::parse("tcp:192.0.2.1").is_err());
+        assert!(Check::parse("ftp://example.com").is_err());
+    }
+
+    #[test]
+    fn monitor_should_switch_after_consecutive_results_only() {
+        let mut monitor = Monitor::new(Target::Primary, 2, 3);
+        assert_eq!(monitor.observe(false), None);
+        assert_eq!(monitor.observe(true), None);
+        assert_eq!(monitor.observe(false), None);
+        assert_eq!(monitor.observe(false), Some(Target::Backup));
+        assert_eq!(monitor.observe(true), None);
+        assert_eq!(monitor.observe(true), None);
+        assert_eq!(monitor.observe(false), None);
+        assert_eq!(monitor.observe(true), None);
+        assert_eq!(monitor.observe(true), None);
+        assert_eq!(monitor.observe(true), Some(Target::Primary));
+    }
+}
@@ -0,0 +1,623 @@
+use clap::ValueEnum;
+use serde_json::Value;
+
+use crate::content::normalise_txt;
+use crate::dynu::RecordDTO;
+use crate::names::{node_of, normalise_fqdn};
+use crate::SelfError;
+
+// TTL of the records exported without one, or with cloudflare's automatic TTL.
+pub const DEFAULT_TTL: u64 = 300;
+
+// Cloudflare exports the records as the answer of its list records API,
+// dnscontrol describes them in a dnsconfig.js or the JSON of print-ir, the
+// generic formats hold one record per object or row with a name, a type, a
+// value and optionally a TTL.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ImportFormat {
+    Cloudflare,
+    Dnscontrol,
+    Json,
+    Csv,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ForeignRecord {
+    pub name: String,
+    pub record_type: String,
+    pub value: String,
+    pub ttl: Option<u64>,
+}
+
+const NAME_KEYS: [&str; 4] = ["name", "node", "node_name", "hostname"];
+const TYPE_KEYS: [&str; 3] = ["type", "record_type", "recordtype"];
+const VALUE_KEYS: [&str; 4] = ["value", "content", "data", "address"];
+
+fn import_error(message: String) -> SelfError {
+    SelfError::MsgError(format!("import: {}", message))
+}
+
+pub fn detect(path: &str, content: &str) -> ImportFormat {
+    let path = path.to_lowercase();
+    if path.ends_with(".csv") {
+        return ImportFormat::Csv;
+    }
+    if path.ends_with(".js") {
+        return ImportFormat::Dnscontrol;
+    }
+    match serde_json::from_str::<Value>(content) {
+        Ok(Value::Object(object)) if object.contains_key("result") => ImportFormat::Cloudflare,
+        Ok(Value::Object(object)) if object.contains_key("domains") => ImportFormat::Dnscontrol,
+        Ok(_) => ImportFormat::Json,
+        Err(_) => ImportFormat::Csv,
+    }
+}
+
+fn field<'a>(lookup: impl Fn(&str) -> Option<&'a str>, keys: &[&str]) -> Option<&'a str> {
+    keys.iter().find_map(|key| lookup(key))
+}
+
+fn json_record(index: usize, value: &Value) -> Result<ForeignRecord, SelfError> {
+    let object = value
+        .as_object()
+        .ok_or_else(|| import_error(format!("record {} is not an object", index + 1)))?;
+    let lookup = |key: &str| object.get(key).and_then(|v| v.as_str());
+    let required = |keys: &[&str]| {
+        field(lookup, keys)
+            .map(|v| v.to_string())
+            .ok_or_else(|| import_error(format!("record {} has no {}", index + 1, keys[0])))
+    };
+    Ok(ForeignRecord {
+        name: required(&NAME_KEYS)?,
+        record_type: required(&TYPE_KEYS)?.to_uppercase(),
+        value: required(&VALUE_KEYS)?,
+        ttl: object.get("ttl").and_then(|ttl| match ttl {
+            Value::String(ttl) => ttl.parse().ok(),
+            ttl => ttl.as_u64(),
+        }),
+    })
+}
+
+fn parse_json(content: &str, cloudflare: bool) -> Result<Vec<ForeignRecord>, SelfError> {
+    let document: Value =
+        serde_json::from_str(content).map_err(|e| import_error(format!("invalid json: {}", e)))?;
+    let records = match &document {
+        Value::Object(object) if cloudflare || object.contains_key("result") => {
+            object.get("result")
+        }
+        Value::Object(object) => object.get("records"),
+        array => Some(array),
+    }
+    .and_then(|records| records.as_array())
+    .ok_or_else(|| import_error("no array of records found".to_string()))?;
+    let mut parsed: Vec<ForeignRecord> = records
+        .iter()
+        .enumerate()
+        .map(|(i, record)| json_record(i, record))
+        .collect::<Result<_, _>>()?;
+    if cloudflare {
+        // a TTL of 1 is cloudflare's automatic TTL
+        for record in parsed.iter_mut().filter(|r| r.ttl == Some(1)) {
+            record.ttl = None;
+        }
+    }
+    Ok(parsed)
+}
+
+// RFC 4180 rows, with quoted fields possibly holding separators and newlines.
+fn csv_rows(content: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            (c, _) => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows.retain(|row| row.iter().any(|f| !f.trim().is_empty()));
+    rows
+}
+
+fn parse_csv(content: &str) -> Result<Vec<ForeignRecord>, SelfError> {
+    let mut rows = csv_rows(content).into_iter();
+    let header: Vec<String> = rows
+        .next()
+        .ok_or_else(|| import_error("empty csv".to_string()))?
+        .iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect();
+    let column = |keys: &[&str]| header.iter().position(|h| keys.contains(&h.as_str()));
+    let missing = |keys: &[&str]| import_error(format!("csv header has no {} column", keys[0]));
+    let name = column(&NAME_KEYS).ok_or_else(|| missing(&NAME_KEYS))?;
+    let record_type = column(&TYPE_KEYS).ok_or_else(|| missing(&TYPE_KEYS))?;
+    let value = column(&VALUE_KEYS).ok_or_else(|| missing(&VALUE_KEYS))?;
+    let ttl = column(&["ttl"]);
+    rows.enumerate()
+        .map(|(i, row)| {
+            let cell = |index: usize| {
+                row.get(index).map(|c| c.trim().to_string()).ok_or_else(|| {
+                    import_error(format!("row {} has only {} columns", i + 2, row.len()))
+                })
+            };
+            Ok(ForeignRecord {
+                name: cell(name)?,
+                record_type: cell(record_type)?.to_uppercase(),
+                value: cell(value)?,
+                ttl: ttl
+                    .and_then(|t| row.get(t))
+                    .and_then(|t| t.trim().parse().ok()),
+            })
+        })
+        .collect()
+}
+
+// Record types of dnscontrol, whose first argument is the name and second the
+// value.
+const DNSCONTROL_RECORDS: [&str; 10] = [
+    "A", "AAAA", "ALIAS", "CAA", "CNAME", "MX", "NS", "PTR", "SRV", "TXT",
+];
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Number(String),
+    Punct(char),
+}
+
+// Tokens of the javascript of a dnsconfig.js, enough to find the record calls
+// and their literal arguments.
+fn js_tokens(content: &str) -> Result<Vec<Token>, SelfError> {
+    let mut tokens = Vec::new();
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+            }
+            '"' | '\'' | '`' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some(escaped) => value.push(escaped),
+                            None => break,
+                        },
+                        Some(other) => value.push(other),
+                        None => return Err(import_error("unterminated string".to_string())),
+                    }
+                }
+                tokens.push(Token::Str(value));
+            }
+            c if c.is_ascii_alphanumeric() || c == '_' || c == '$' => {
+                let mut word = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if !(next.is_ascii_alphanumeric() || next == '_' || next == '$' || next == '.')
+                    {
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+                tokens.push(if c.is_ascii_digit() {
+                    Token::Number(word)
+                } else {
+                    Token::Ident(word)
+                });
+            }
+            c => tokens.push(Token::Punct(c)),
+        }
+    }
+    Ok(tokens)
+}
+
+// Top level arguments of the call whose arguments start at start, and the
+// index following its closing parenthesis.
+fn call_arguments(tokens: &[Token], start: usize) -> (Vec<&[Token]>, usize) {
+    let mut arguments = Vec::new();
+    let mut depth = 0;
+    let mut from = start;
+    for (i, token) in tokens.iter().enumerate().skip(start) {
+        match token {
+            Token::Punct('(' | '[' | '{') => depth += 1,
+            Token::Punct(')' | ']' | '}') if depth == 0 => {
+                if i > from {
+                    arguments.push(&tokens[from..i]);
+                }
+                return (arguments, i + 1);
+            }
+            Token::Punct(')' | ']' | '}') => depth -= 1,
+            Token::Punct(',') if depth == 0 => {
+                arguments.push(&tokens[from..i]);
+                from = i + 1;
+            }
+            _ => {}
+        }
+    }
+    (arguments, tokens.len())
+}
+
+// TTLs are seconds or durations such as "5m".
+fn dnscontrol_ttl(value: &str) -> Option<u64> {
+    value
+        .parse()
+        .ok()
+        .or_else(|| humantime::parse_duration(value).ok().map(|d| d.as_secs()))
+}
+
+// The TTL of a TTL() or DefaultTTL() modifier.
+fn ttl_modifier(tokens: &[Token], name: &str) -> Option<u64> {
+    match tokens {
+        [Token::Ident(modifier), Token::Punct('('), Token::Number(ttl) | Token::Str(ttl), Token::Punct(')')]
+            if modifier == name =>
+        {
+            dnscontrol_ttl(ttl)
+        }
+        _ => None,
+    }
+}
+
+fn dnscontrol_record(
+    record_type: &str,
+    arguments: &[&[Token]],
+) -> Result<ForeignRecord, SelfError> {
+    let name = match arguments.first() {
+        Some([Token::Str(name)]) => name.clone(),
+        _ => return Err(import_error(format!("{}() without a name", record_type))),
+    };
+    // TXT values may be split in several strings, MX values start with the
+    // priority
+    let value: String = arguments
+        .get(1)
+        .map(|argument| {
+            argument
+                .iter()
+                .filter_map(|token| match token {
+                    Token::Str(value) | Token::Number(value) => Some(value.as_str()),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let ttl = arguments
+        .iter()
+        .skip(2)
+        .find_map(|argument| ttl_modifier(argument, "TTL"));
+    Ok(ForeignRecord {
+        name,
+        record_type: record_type.to_string(),
+        value,
+        ttl,
+    })
+}
+
+fn parse_dnsconfig(content: &str) -> Result<Vec<ForeignRecord>, SelfError> {
+    let tokens = js_tokens(content)?;
+    let domains = tokens
+        .windows(2)
+        .filter(|w| w[0] == Token::Ident("D".to_string()) && w[1] == Token::Punct('('))
+        .count();
+    if domains > 1 {
+        return Err(import_error(format!(
+            "{} domains declared, keep only the one to import",
+            domains
+        )));
+    }
+    let mut records = Vec::new();
+    let mut i = 0;
+    while i + 1 < tokens.len() {
+        match (&tokens[i], &tokens[i + 1]) {
+            (Token::Ident(record_type), Token::Punct('('))
+                if DNSCONTROL_RECORDS.contains(&record_type.as_str()) =>
+            {
+                let (arguments, end) = call_arguments(&tokens, i + 2);
+                records.push(dnscontrol_record(record_type, &arguments)?);
+                i = end;
+            }
+            _ => i += 1,
+        }
+    }
+    if let Some(default) = tokens
+        .windows(4)
+        .find_map(|tokens| ttl_modifier(tokens, "DefaultTTL"))
+    {
+        for record in records.iter_mut().filter(|r| r.ttl.is_none()) {
+            record.ttl = Some(default);
+        }
+    }
+    Ok(records)
+}
+
+// The JSON of dnscontrol print-ir, TXT values being split in txtstrings by
+// older versions.
+fn parse_dnscontrol_json(content: &str) -> Result<Vec<ForeignRecord>, SelfError> {
+    let document: Value =
+        serde_json::from_str(content).map_err(|e| import_error(format!("invalid json: {}", e)))?;
+    let domains = document
+        .get("domains")
+        .and_then(|domains| domains.as_array())
+        .ok_or_else(|| import_error("no array of domains found".to_string()))?;
+    let [domain] = domains.as_slice() else {
+        return Err(import_error(format!(
+            "{} domains declared, keep only the one to import",
+            domains.len()
+        )));
+    };
+    let records = domain
+        .get("records")
+        .and_then(|records| records.as_array())
+        .ok_or_else(|| import_error("no array of records found".to_string()))?;
+    records
+        .iter()
+        .enumerate()
+        .map(|(i, record)| {
+            let text = |key: &str| record.get(key).and_then(|v| v.as_str());
+            let missing = |key: &str| import_error(format!("record {} has no {}", i + 1, key));
+            let value = match record.get("txtstrings").and_then(|v| v.as_array()) {
+                Some(strings) => strings.iter().filter_map(|s| s.as_str()).collect(),
+                None => text("target").ok_or_else(|| missing("target"))?.to_string(),
+            };
+            Ok(ForeignRecord {
+                name: text("name").ok_or_else(|| missing("name"))?.to_string(),
+                record_type: text("type").ok_or_else(|| missing("type"))?.to_uppercase(),
+                value,
+                ttl: record.get("ttl").and_then(|ttl| ttl.as_u64()),
+            })
+        })
+        .collect()
+}
+
+pub fn parse(format: ImportFormat, content: &str) -> Result<Vec<ForeignRecord>, SelfError> {
+    match format {
+        ImportFormat::Cloudflare => parse_json(content, true),
+        ImportFormat::Dnscontrol if content.trim_start().starts_with('{') => {
+            parse_dnscontrol_json(content)
+        }
+        ImportFormat::Dnscontrol => parse_dnsconfig(content),
+        ImportFormat::Json => parse_json(content, false),
+        ImportFormat::Csv => parse_csv(content),
+    }
+}
+
+// Names may be fully qualified, relative to the zone or "@" for the apex.
+fn node(name: &str, zone: &str) -> String {
+    let name = normalise_fqdn(name);
+    let zone = normalise_fqdn(zone);
+    if name == "@" {
+        return "".to_string();
+    }
+    node_of(&name, &zone).unwrap_or(name)
+}
+
+// CAA values are given in the zone file presentation, e.g.
+// 0 issue "letsencrypt.org".
+fn caa_fields(value: &str) -> Option<(u8, &str, String)> {
+    let mut parts = value.trim().splitn(3, char::is_whitespace);
+    let flags = parts.next()?.parse().ok()?;
+    let tag = parts.next()?;
+    let value = parts.next()?.trim();
+    if tag.is_empty() || value.is_empty() {
+        return None;
+    }
+    Some((flags, tag, normalise_txt(value)))
+}
+
+// Maps the foreign records to the dynu records they correspond to, returning
+// alongside them the reason each record that cannot be imported was skipped.
+// Dynu manages the SOA and NS records of its zones itself, NS records being
+// only imported for the subdomains they delegate.
+pub fn to_dynu(records: &[ForeignRecord], zone: &str) -> (Vec<RecordDTO>, Vec<String>) {
+    let mut imported = Vec::new();
+    let mut skipped = Vec::new();
+    for record in records {
+        let node = node(&record.name, zone);
+        let ttl = record.ttl.unwrap_or(DEFAULT_TTL);
+        match record.record_type.as_str() {
+            "A" => imported.push(RecordDTO::a_record(&node, record.value.trim(), ttl, None)),
+            "AAAA" => imported.push(RecordDTO::aaaa_record(
+                &node,
+                record.value.trim(),
+                ttl,
+                None,
+            )),
+            "TXT" => imported.push(RecordDTO::txt_record(
+                &node,
+                &normalise_txt(&record.value),
+                ttl,
+                None,
+            )),
+            "SPF" => imported.push(RecordDTO::spf_record(
+                &node,
+                &normalise_txt(&record.value),
+                ttl,
+                None,
+            )),
+            "CNAME" => imported.push(RecordDTO::cname_record(
+                &node,
+                record.value.trim().trim_end_matches('.'),
+                ttl,
+                None,
+            )),
+            "CAA" => match caa_fields(&record.value) {
+                Some((flags, tag, value)) => {
+                    imported.push(RecordDTO::caa_record(&node, flags, tag, &value, ttl, None))
+                }
+                None => skipped.push(format!(
+                    "{}: invalid CAA value {}",
+                    record.name, record.value
+                )),
+            },
+            "PTR" => imported.push(RecordDTO::ptr_record(
+                &node,
+                record.value.trim().trim_end_matches('.'),
+                ttl,
+                None,
+            )),
+            "NS" if !node.is_empty() => imported.push(RecordDTO::ns_record(
+                &node,
+                record.value.trim().trim_end_matches('.'),
+                ttl,
+                None,
+            )),
+            "SOA" | "NS" => skipped.push(format!(
+                "{}: {} records are managed by dynu",
+                record.name, record.record_type
+            )),
+            other => skipped.push(format!(
+                "{}: unsupported record type {}",
+                record.name, other
+            )),
+        }
+    }
+    (imported, skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_should_read_cloudflare_exports() {
+        let content = r#"{"success": true, "result": [
+            {"id": "1", "type": "A", "name": "www.example.com", "content": "192.0.2.1", "ttl": 1, "proxied": false},
+            {"id": "2", "type": "TXT", "name": "example.com", "content": "\"v=spf1 -all\"", "ttl": 3600},
+            {"id": "3", "type": "CAA", "name": "example.com", "content": "0 issue \"letsencrypt.org\"", "ttl": 1}
+        ]}"#;
+        assert_eq!(detect("zone.json", content), ImportFormat::Cloudflare);
+        let records = parse(ImportFormat::Cloudflare, content).unwrap();
+        assert_eq!(
+            records[0],
+            ForeignRecord {
+                name: "www.example.com".to_string(),
+                record_type: "A".to_string(),
+                value: "192.0.2.1".to_string(),
+                ttl: None,
+            }
+        );
+        let (imported, skipped) = to_dynu(&records, "example.com");
+        assert!(skipped.is_empty());
+        assert_eq!(imported[0].node_name(), "www");
+        assert_eq!(imported[0].ttl(), DEFAULT_TTL);
+        assert_eq!(imported[1].node_name(), "");
+        assert_eq!(imported[1].value(), "v=spf1 -all");
+        assert_eq!(imported[1].ttl(), 3600);
+        assert_eq!(
+            imported[2],
+            RecordDTO::caa_record("", 0, "issue", "letsencrypt.org", DEFAULT_TTL, None)
+        );
+    }
+
+    #[test]
+    fn parse_should_read_csv_with_quoted_fields() {
+        let content = "Name,Type,Value,TTL\r\n@,txt,\"a, \"\"quoted\"\" value\",60\nmail,MX,10 mx.example.com,\n@,NS,ns1.dynu.com,\nlab,NS,ns1.example.net.,\n";
+        assert_eq!(detect("zone.csv", content), ImportFormat::Csv);
+        let records = parse(ImportFormat::Csv, content).unwrap();
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0].value, "a, \"quoted\" value");
+        assert_eq!(records[0].ttl, Some(60));
+        assert_eq!(records[1].ttl, None);
+        let (imported, skipped) = to_dynu(&records, "example.com");
+        assert_eq!(imported.len(), 2);
+        assert_eq!(
+            imported[1],
+            RecordDTO::ns_record("lab", "ns1.example.net", DEFAULT_TTL, None)
+        );
+        assert_eq!(
+            skipped,
+            vec![
+                "mail: unsupported record type MX".to_string(),
+                "@: NS records are managed by dynu".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_should_read_dnscontrol_zones() {
+        let content = r#"// managed by dnscontrol
+var REG_NONE = NewRegistrar("none");
+var DSP_DYNU = NewDnsProvider("dynu");
+
+D("example.com", REG_NONE, DnsProvider(DSP_DYNU), DefaultTTL(600),
+    A("@", "192.0.2.1", TTL(300)),
+    TXT("_dmarc", ["v=DMARC1; ", 'p=none'], TTL("1h")),
+    MX("@", 10, "mx.example.com."),
+    /* CNAME("old", "www") */
+    A("www", "192.0.2.2")
+);
+"#;
+        assert_eq!(detect("dnsconfig.js", content), ImportFormat::Dnscontrol);
+        let records = parse(ImportFormat::Dnscontrol, content).unwrap();
+        assert_eq!(records.len(), 4);
+        assert_eq!(
+            records[1],
+            ForeignRecord {
+                name: "_dmarc".to_string(),
+                record_type: "TXT".to_string(),
+                value: "v=DMARC1; p=none".to_string(),
+                ttl: Some(3600),
+            }
+        );
+        assert_eq!(records[3].ttl, Some(600));
+        let (imported, skipped) = to_dynu(&records, "example.com");
+        assert_eq!(imported[0].node_name(), "");
+        assert_eq!(imported[0].ttl(), 300);
+        assert_eq!(skipped, vec!["@: unsupported record type MX".to_string()]);
+
+        let two = format!("{}D(\"other.com\", REG_NONE);", content);
+        assert!(parse(ImportFormat::Dnscontrol, &two).is_err());
+    }
+
+    #[test]
+    fn parse_should_read_dnscontrol_json() {
+        let content = r#"{"domains": [{"name": "example.com", "records": [
+            {"type": "A", "name": "www", "target": "192.0.2.1", "ttl": 300},
+            {"type": "TXT", "name": "@", "txtstrings": ["v=spf1 ", "-all"], "ttl": 300}
+        ]}]}"#;
+        assert_eq!(detect("ir.json", content), ImportFormat::Dnscontrol);
+        let records = parse(ImportFormat::Dnscontrol, content).unwrap();
+        assert_eq!(records[0].value, "192.0.2.1");
+        assert_eq!(records[1].value, "v=spf1 -all");
+    }
+
+    #[test]
+    fn parse_should_report_missing_fields() {
+        assert!(parse(ImportFormat::Json, r#"[{"name": "www", "type": "A"}]"#).is_err());
+        assert!(parse(ImportFormat::Csv, "name,type\nwww,A\n").is_err());
+    }
+}
@@ -0,0 +1,309 @@
+use clap::ValueEnum;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config::CONFIG_VERSION;
+use crate::dynu::DynuClient;
+use crate::netutils;
+use crate::SelfError;
+
+const API_KEY_FILE: &str = "api_key";
+
+// Where init stores the API key: in a file only readable by the user, which
+// the configuration points to, in the configuration itself, or nowhere, the
+// key being then given through DYNU_API_KEY.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SecretBackend {
+    File,
+    Config,
+    Env,
+}
+
+// Questions are written to stderr, so that nothing but the configuration
+// path ends up on stdout.
+struct Prompt<R, W> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Prompt<R, W> {
+    fn say(&mut self, line: &str) -> Result<(), SelfError> {
+        writeln!(self.output, "{}", line)?;
+        Ok(())
+    }
+
+    // Asks until an answer is given, an empty answer taking the default.
+    fn ask(&mut self, question: &str, default: Option<&str>) -> Result<String, SelfError> {
+        loop {
+            match default {
+                Some(default) => write!(self.output, "{} [{}]: ", question, default)?,
+                None => write!(self.output, "{}: ", question)?,
+            }
+            self.output.flush()?;
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Err(SelfError::MsgError(
+                    "init is interactive, its answers are read from stdin".to_string(),
+                ));
+            }
+            match (line.trim(), default) {
+                ("", Some(default)) => return Ok(default.to_string()),
+                ("", None) => continue,
+                (answer, _) => return Ok(answer.to_string()),
+            }
+        }
+    }
+}
+
+// Domains picked by their number in the listing, e.g. 1,3, all of them with
+// all and none with none.
+fn pick(answer: &str, domains: &[String]) -> Result<Vec<String>, String> {
+    match answer {
+        "all" => return Ok(domains.to_vec()),
+        "none" => return Ok(vec![]),
+        _ => {}
+    }
+    answer
+        .split(',')
+        .map(|number| {
+            number
+                .trim()
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| n.checked_sub(1))
+                .and_then(|index| domains.get(index).cloned())
+                .ok_or_else(|| format!("no domain numbered {}", number.trim()))
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+struct Answers {
+    api_key: String,
+    backend: SecretBackend,
+    domains: Vec<String>,
+}
+
+fn ask_answers<R: BufRead, W: Write>(
+    prompt: &mut Prompt<R, W>,
+    backend: Option<SecretBackend>,
+    list_domains: impl Fn(&str) -> Result<Vec<String>, SelfError>,
+) -> Result<Answers, SelfError> {
+    prompt.say("The API key is shown under API Credentials in the control panel of dynu.")?;
+    let (api_key, account_domains) = loop {
+        let api_key = prompt.ask("API key", None)?;
+        match list_domains(&api_key) {
+            Ok(domains) => break (api_key, domains),
+            Err(e) => prompt.say(&format!("dynu refused the API key: {}", e))?,
+        }
+    };
+    let backend = match backend {
+        Some(backend) => backend,
+        None => loop {
+            let answer = prompt.ask(
+                "Store the API key in a file, the config or the env",
+                Some("file"),
+            )?;
+            match SecretBackend::from_str(&answer, true) {
+                Ok(backend) => break backend,
+                Err(_) => prompt.say("expected file, config or env")?,
+            }
+        },
+    };
+    if account_domains.is_empty() {
+        prompt.say("The account has no domain yet, add them to the configuration later.")?;
+        return Ok(Answers {
+            api_key,
+            backend,
+            domains: vec![],
+        });
+    }
+    for (index, domain) in account_domains.iter().enumerate() {
+        prompt.say(&format!("{:>3}. {}", index + 1, domain))?;
+    }
+    let domains = loop {
+        let answer = prompt.ask(
+            "Domains refreshed by this host, by number (e.g. 1,3), all or none",
+            Some("1"),
+        )?;
+        match pick(&answer, &account_domains) {
+            Ok(domains) => break domains,
+            Err(e) => prompt.say(&e)?,
+        }
+    };
+    Ok(Answers {
+        api_key,
+        backend,
+        domains,
+    })
+}
+
+// The starter configuration, the first domain picked being the default one of
+// the record commands.
+fn starter_config(answers: &Answers, key_file: &Path) -> String {
+    let mut table = toml::Table::new();
+    table.insert("version".to_string(), CONFIG_VERSION.into());
+    match answers.backend {
+        SecretBackend::File => {
+            table.insert(
+                "api_key_file".to_string(),
+                key_file.display().to_string().into(),
+            );
+        }
+        SecretBackend::Config => {
+            table.insert("api_key".to_string(), answers.api_key.clone().into());
+        }
+        SecretBackend::Env => {}
+    }
+    if let Some(domain) = answers.domains.first() {
+        let mut defaults = toml::Table::new();
+        defaults.insert("domain".to_string(), domain.clone().into());
+        table.insert("defaults".to_string(), defaults.into());
+        let domains: toml::Table = answers
+            .domains
+            .iter()
+            .map(|domain| (domain.clone(), toml::Table::new().into()))
+            .collect();
+        table.insert("domains".to_string(), domains.into());
+    }
+    format!(
+        "# Written by dynuupdater init, see the README for the other settings.\n{}",
+        toml::to_string_pretty(&table).unwrap()
+    )
+}
+
+// Writes a file holding the API key, readable by its owner only.
+#[cfg(unix)]
+fn write_secret(path: &Path, content: &str) -> io::Result<()> {
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(content.as_bytes())
+}
+
+#[cfg(not(unix))]
+fn write_secret(path: &Path, content: &str) -> io::Result<()> {
+    fs::write(path, content)
+}
+
+// Asks for the API key, where to store it unless given, and the domains of the account to refresh, then writes
+// the starter configuration to path, which is only overwritten with force.
+pub fn run(
+    path: Option<PathBuf>,
+    backend: Option<SecretBackend>,
+    force: bool,
+) -> Result<(), SelfError> {
+    let path = path.ok_or_else(|| {
+        SelfError::MsgError(
+            "cannot determine the configuration file, provide 'config' argument".to_string(),
+        )
+    })?;
+    if path.exists() && !force {
+        return Err(SelfError::MsgError(format!(
+            "{} already exists, edit it or run init --force to replace it",
+            path.display()
+        )));
+    }
+    let key_file = path.with_file_name(API_KEY_FILE);
+    let mut prompt = Prompt {
+        input: io::stdin().lock(),
+        output: io::stderr(),
+    };
+    let answers = ask_answers(&mut prompt, backend, |api_key| {
+        let client = DynuClient::new(api_key, netutils::user_agent())?;
+        Ok(client
+            .get_domains()?
+            .domains
+            .into_iter()
+            .map(|d| d.name)
+            .collect())
+    })?;
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    if answers.backend == SecretBackend::File {
+        write_secret(&key_file, &format!("{}\n", answers.api_key))?;
+        prompt.say(&format!("API key written to {}", key_file.display()))?;
+    }
+    let content = starter_config(&answers, &key_file);
+    if answers.backend == SecretBackend::Config {
+        write_secret(&path, &content)?;
+    } else {
+        fs::write(&path, content)?;
+    }
+    if answers.backend == SecretBackend::Env {
+        prompt.say("Export DYNU_API_KEY, or DYNU_API_KEY_FILE, before running dynuupdater.")?;
+    }
+    prompt.say("Run dynuupdater refresh --dry-run to check the setup.")?;
+    println!("{}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ask_answers_should_retry_until_the_answers_are_valid() {
+        let input = "bad-key\ngood-key\nvault\nconfig\n4\n2,1\n";
+        let mut prompt = Prompt {
+            input: input.as_bytes(),
+            output: Vec::new(),
+        };
+        let answers = ask_answers(&mut prompt, None, |api_key| match api_key {
+            "good-key" => Ok(vec!["a.dynu.net".to_string(), "b.dynu.net".to_string()]),
+            _ => Err(SelfError::MsgError("unauthorized".to_string())),
+        })
+        .unwrap();
+        assert_eq!(
+            answers,
+            Answers {
+                api_key: "good-key".to_string(),
+                backend: SecretBackend::Config,
+                domains: vec!["b.dynu.net".to_string(), "a.dynu.net".to_string()],
+            }
+        );
+        let output = String::from_utf8(prompt.output.clone()).unwrap();
+        assert!(output.contains("dynu refused the API key"));
+        assert!(output.contains("no domain numbered 4"));
+        assert!(ask_answers(&mut prompt, None, |_| Ok(vec![])).is_err());
+    }
+
+    #[test]
+    fn starter_config_should_be_a_valid_configuration() {
+        let answers = Answers {
+            api_key: "secret".to_string(),
+            backend: SecretBackend::File,
+            domains: vec!["b.dynu.net".to_string(), "a.dynu.net".to_string()],
+        };
+        let content = starter_config(&answers, Path::new("/etc/dynuupdater/api_key"));
+        assert!(!content.contains("secret"));
+        let config: crate::config::Config = toml::from_str(&content).unwrap();
+        assert_eq!(
+            config.api_key_file.as_deref(),
+            Some("/etc/dynuupdater/api_key")
+        );
+        assert_eq!(config.defaults.domain.as_deref(), Some("b.dynu.net"));
+        assert_eq!(config.domains.len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_secret_should_keep_the_file_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = std::env::temp_dir().join("dynu_init_test_secret.toml");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        write_secret(&path, "api_key = \"secret\"\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "api_key = \"secret\"\n");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        fs::remove_file(&path).unwrap();
+    }
+}
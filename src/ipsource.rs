@@ -0,0 +1,227 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::Read;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::netutils::{ip, Addresses, IP};
+use crate::SelfError;
+
+// IP sources other than the builtin one are executables on PATH named
+// dynuupdater-ipsource-<name>, printing {"v4": ..., "v6": ...} on stdout, or
+// WASM plugins when built with the wasm feature.
+pub const PLUGIN_PREFIX: &str = "dynuupdater-ipsource-";
+pub const BUILTIN: &str = "ipify";
+// Plugins still running after the timeout are killed, so that a hung plugin
+// cannot stall refresh or the daemon.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+static TIMEOUT: OnceLock<Duration> = OnceLock::new();
+
+pub fn set_timeout(timeout: Duration) {
+    let _ = TIMEOUT.set(timeout);
+}
+
+fn timeout() -> Duration {
+    TIMEOUT.get().copied().unwrap_or(DEFAULT_TIMEOUT)
+}
+
+#[derive(Deserialize, Debug)]
+struct PluginOutput {
+    v4: Option<String>,
+    v6: Option<String>,
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+// Plugins found in the directories of path, the first directory providing a
+// name wins as it would when running the executable from a shell.
+pub fn discover_in(path: &OsStr) -> BTreeMap<String, PathBuf> {
+    let mut plugins = BTreeMap::new();
+    for directory in env::split_paths(path) {
+        let Ok(entries) = fs::read_dir(&directory) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if let Some(name) = file_name.strip_prefix(PLUGIN_PREFIX) {
+                if !name.is_empty() && is_executable(&entry.path()) {
+                    plugins.entry(name.to_string()).or_insert(entry.path());
+                }
+            }
+        }
+    }
+    plugins
+}
+
+pub fn discover() -> BTreeMap<String, PathBuf> {
+    env::var_os("PATH")
+        .map(|path| discover_in(&path))
+        .unwrap_or_default()
+}
+
+fn check_address(
+    value: Option<String>,
+    v4: bool,
+    source: &str,
+) -> Result<Option<String>, SelfError> {
+    let Some(value) = value.filter(|v| !v.trim().is_empty()) else {
+        return Ok(None);
+    };
+    match value.trim().parse::<IpAddr>() {
+        Ok(address) if address.is_ipv4() == v4 => Ok(Some(address.to_string())),
+        _ => Err(SelfError::MsgError(format!(
+            "ip source {} returned invalid {} address {}",
+            source,
+            if v4 { "ipv4" } else { "ipv6" },
+            value
+        ))),
+    }
+}
+
+fn run_plugin(plugin: &Path, timeout: Duration) -> Result<Addresses, SelfError> {
+    let mut child = Command::new(plugin)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()?;
+    // Reading on another thread, so that a plugin filling the pipe doesn't
+    // block before exiting.
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let reader = thread::spawn(move || {
+        let mut output = Vec::new();
+        stdout.read_to_end(&mut output).map(|_| output)
+    });
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(SelfError::MsgError(format!(
+                "ip source {} killed after not finishing within {}",
+                plugin.display(),
+                humantime::format_duration(timeout)
+            )));
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+    let output = reader.join().map_err(|_| {
+        SelfError::MsgError(format!("reading ip source {} failed", plugin.display()))
+    })??;
+    if !status.success() {
+        return Err(SelfError::MsgError(format!(
+            "ip source {} failed with {}",
+            plugin.display(),
+            status
+        )));
+    }
+    parse_output(&output, &plugin.display().to_string())
+}
+
+pub fn parse_output(output: &[u8], source: &str) -> Result<Addresses, SelfError> {
+    let parsed: PluginOutput = serde_json::from_slice(output).map_err(|e| {
+        SelfError::MsgError(format!("ip source {} returned invalid JSON: {}", source, e))
+    })?;
+    Ok(Addresses {
+        v4: check_address(parsed.v4, true, source)?,
+        v6: check_address(parsed.v6, false, source)?,
+    })
+}
+
+pub fn detect(source: &str) -> Result<Addresses, SelfError> {
+    if source == BUILTIN {
+        return Ok(Addresses {
+            v4: ip(IP::V4),
+            v6: ip(IP::V6),
+        });
+    }
+    #[cfg(feature = "wasm")]
+    if let Some(plugin) = crate::wasm::ip_source(source)? {
+        return plugin.detect();
+    }
+    let plugins = discover();
+    let plugin = plugins.get(source).ok_or_else(|| {
+        SelfError::MsgError(format!(
+            "ip source {} not found, no executable named {}{} on PATH",
+            source, PLUGIN_PREFIX, source
+        ))
+    })?;
+    run_plugin(plugin, timeout())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn plugin_dir(name: &str, script: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("dynu_ipsource_test_{}", name));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let plugin = directory.join(format!("{}{}", PLUGIN_PREFIX, name));
+        fs::write(&plugin, script).unwrap();
+        fs::set_permissions(&plugin, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(
+            directory.join(format!("{}not-executable", PLUGIN_PREFIX)),
+            "",
+        )
+        .unwrap();
+        directory
+    }
+
+    #[test]
+    fn discover_should_find_executable_plugins_only() {
+        let directory = plugin_dir("found", "#!/bin/sh\n");
+        let plugins = discover_in(directory.as_os_str());
+        assert_eq!(plugins.keys().collect::<Vec<_>>(), vec!["found"]);
+    }
+
+    #[test]
+    fn run_plugin_should_parse_and_validate_addresses() {
+        let directory = plugin_dir(
+            "valid",
+            "#!/bin/sh\necho '{\"v4\": \"192.0.2.1\", \"v6\": \"2001:DB8::1\"}'\n",
+        );
+        let plugin = discover_in(directory.as_os_str()).remove("valid").unwrap();
+        let addresses = run_plugin(&plugin, DEFAULT_TIMEOUT).unwrap();
+        assert_eq!(addresses.v4.as_deref(), Some("192.0.2.1"));
+        assert_eq!(addresses.v6.as_deref(), Some("2001:db8::1"));
+
+        let directory = plugin_dir("swapped", "#!/bin/sh\necho '{\"v4\": \"2001:db8::1\"}'\n");
+        let plugin = discover_in(directory.as_os_str())
+            .remove("swapped")
+            .unwrap();
+        assert!(run_plugin(&plugin, DEFAULT_TIMEOUT).is_err());
+    }
+
+    #[test]
+    fn run_plugin_should_kill_plugins_exceeding_the_timeout() {
+        let directory = plugin_dir("hung", "#!/bin/sh\nexec sleep 30\n");
+        let plugin = discover_in(directory.as_os_str()).remove("hung").unwrap();
+        let started = Instant::now();
+        let result = run_plugin(&plugin, Duration::from_millis(200));
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(matches!(result, Err(SelfError::MsgError(msg)) if msg.contains("killed")));
+    }
+}
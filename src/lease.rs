@@ -0,0 +1,79 @@
+use std::time::{Duration, SystemTime};
+
+// Node whose TXT record holds the lease of a domain, e.g.
+// holder=nas expires=2026-10-16T08:10:00Z.
+pub const NODE: &str = "_dynuupdater-lease";
+
+// When several hosts update the same domain, e.g. over two WAN connections,
+// only the host holding its lease refreshes it, so that the domain doesn't
+// flap between their addresses. The holder renews the lease at each refresh
+// and the other hosts take it over once it has expired.
+#[derive(Debug, PartialEq)]
+pub struct Lease {
+    pub holder: String,
+    pub expires: SystemTime,
+}
+
+impl Lease {
+    pub fn new(holder: &str, now: SystemTime, duration: Duration) -> Lease {
+        Lease {
+            holder: holder.to_string(),
+            expires: now + duration,
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Lease> {
+        let mut holder = None;
+        let mut expires = None;
+        for pair in value.split_whitespace() {
+            match pair.split_once('=') {
+                Some(("holder", value)) => holder = Some(value.to_string()),
+                Some(("expires", value)) => expires = humantime::parse_rfc3339(value).ok(),
+                _ => {}
+            }
+        }
+        Some(Lease {
+            holder: holder?,
+            expires: expires?,
+        })
+    }
+
+    pub fn value(&self) -> String {
+        format!(
+            "holder={} expires={}",
+            self.holder,
+            humantime::format_rfc3339_seconds(self.expires)
+        )
+    }
+
+    pub fn is_held_by(&self, host: &str, now: SystemTime) -> bool {
+        self.holder == host && self.expires > now
+    }
+
+    pub fn is_held_by_other(&self, host: &str, now: SystemTime) -> bool {
+        self.holder != host && self.expires > now
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn lease_should_only_exclude_other_hosts_until_it_expires() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_800_000_000);
+        let lease = Lease::new("nas", now, Duration::from_secs(600));
+        assert_eq!(lease.value(), "holder=nas expires=2027-01-15T08:10:00Z");
+        assert_eq!(Lease::parse(&lease.value()), Some(lease));
+
+        let lease = Lease::parse("holder=nas expires=2027-01-15T08:10:00Z").unwrap();
+        assert!(lease.is_held_by_other("router", now));
+        assert!(!lease.is_held_by_other("nas", now));
+        assert!(!lease.is_held_by_other("router", now + Duration::from_secs(600)));
+        assert!(lease.is_held_by("nas", now));
+        assert!(!lease.is_held_by("router", now));
+        assert!(!lease.is_held_by("nas", now + Duration::from_secs(600)));
+        assert_eq!(Lease::parse("holder=nas"), None);
+    }
+}
@@ -0,0 +1,92 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::env;
+
+use crate::acme;
+use crate::dynu::DynuClient;
+use crate::output;
+use crate::SelfError;
+
+const DEFAULT_PROPAGATION_TIMEOUT: u64 = 60;
+const DEFAULT_POLLING_INTERVAL: u64 = 2;
+
+pub enum Action {
+    Present,
+    Cleanup,
+}
+
+#[derive(Serialize, Debug)]
+struct Timeout {
+    timeout: u64,
+    interval: u64,
+}
+
+// In the default mode lego passes the fqdn of the challenge record and its
+// value, while in RAW mode it passes the domain, the token and the key
+// authorization, leaving the computation of the record to the program.
+fn challenge(args: &[String]) -> Result<(String, String), SelfError> {
+    let raw = env::var("EXEC_MODE").map(|m| m == "RAW").unwrap_or(false);
+    match (raw, args) {
+        (false, [fqdn, value]) => Ok((fqdn.clone(), value.clone())),
+        (true, [domain, _token, key_auth]) => Ok((
+            format!("_acme-challenge.{}.", domain.trim_end_matches('.')),
+            key_authorization_digest(key_auth),
+        )),
+        (false, _) => Err(SelfError::MsgError(
+            "expected arguments <FQDN> <VALUE>".to_string(),
+        )),
+        (true, _) => Err(SelfError::MsgError(
+            "expected arguments <DOMAIN> <TOKEN> <KEY_AUTH> with EXEC_MODE=RAW".to_string(),
+        )),
+    }
+}
+
+fn key_authorization_digest(key_auth: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(key_auth.as_bytes()))
+}
+
+fn env_seconds(name: &str, default: u64) -> Result<u64, SelfError> {
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map_err(|_| SelfError::MsgError(format!("{}={} is not a number", name, value))),
+        Err(_) => Ok(default),
+    }
+}
+
+pub fn run(dynu_client: DynuClient, action: Action, args: &[String]) -> Result<(), SelfError> {
+    let (fqdn, value) = challenge(args)?;
+    match action {
+        Action::Present => acme::present(
+            &dynu_client,
+            &fqdn,
+            None,
+            &value,
+            acme::DEFAULT_CHALLENGE_TTL,
+        ),
+        Action::Cleanup => acme::cleanup(&dynu_client, &fqdn, None, &value),
+    }
+}
+
+pub fn timeout() -> Result<(), SelfError> {
+    let timeout = Timeout {
+        timeout: env_seconds("EXEC_PROPAGATION_TIMEOUT", DEFAULT_PROPAGATION_TIMEOUT)?,
+        interval: env_seconds("EXEC_POLLING_INTERVAL", DEFAULT_POLLING_INTERVAL)?,
+    };
+    output::print_json(&timeout);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_authorization_digest_should_be_unpadded_base64url_sha256() {
+        assert_eq!(
+            key_authorization_digest("token.thumbprint"),
+            "61rBZ_4knHblO0MNoxFsXZ_eTFUHum0B6IVRbhvUn5I"
+        );
+    }
+}
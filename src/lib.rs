@@ -0,0 +1,34 @@
+//! Client of the [Dynu](https://www.dynu.com) API, usable by other crates
+//! without the command line, which is built on top of it:
+//!
+//! - [`dynu`]: the blocking API client, with the DTOs of the domains, records
+//!   and account it reads and writes
+//! - [`record`]: a flattened view of the records, easier to build and to match
+//!   on than the DTOs
+//! - `dynu_async`: `AsyncDynuClient`, the same client with async methods for
+//!   tokio based services, behind the `async` feature
+//! - [`netutils`]: detection of the public addresses of the host through
+//!   ipify, and resolution of the addresses a domain currently has
+//!
+//! ```no_run
+//! use dynuupdater::dynu::{DynuClient, USER_AGENT};
+//! use dynuupdater::netutils::{ip, IP};
+//!
+//! let client = DynuClient::new("api key", USER_AGENT)?;
+//! for domain in client.get_domains()?.domains {
+//!     println!("{} {:?}", domain.name, domain.ipv4_address);
+//! }
+//! println!("public address {:?}", ip(IP::V4));
+//! # Ok::<(), dynuupdater::dynu::ClientError>(())
+//! ```
+#![warn(missing_docs)]
+
+/// Blocking client of the Dynu API and the DTOs it reads and writes.
+pub mod dynu;
+/// Async client of the Dynu API, behind the `async` feature.
+#[cfg(feature = "async")]
+pub mod dynu_async;
+/// Public addresses of the host and of domains.
+pub mod netutils;
+/// Flattened view of the records.
+pub mod record;
@@ -0,0 +1,302 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::dynu::{DomainDTO, RecordDTO};
+use crate::names::{node_of, normalise_fqdn, same_name};
+use crate::SelfError;
+
+// TTLs outside these bounds are reported by default: lower ones multiply the
+// queries reaching the name servers, higher ones delay changes for days.
+pub const LOW_TTL: u64 = 60;
+pub const HIGH_TTL: u64 = 86400;
+
+const CNAME_AT_APEX: &str = "the domain itself can't be a CNAME, it holds the SOA and NS records";
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    // the zone is broken for resolvers
+    Error,
+    // the zone works but likely not as intended
+    Warning,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+// A problem of the zone, check naming the kind of problem so that scripts can
+// filter on it, e.g. cname-at-apex.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub check: &'static str,
+    pub node: String,
+    pub message: String,
+}
+
+impl Finding {
+    fn new(severity: Severity, check: &'static str, node: &str, message: String) -> Finding {
+        Finding {
+            severity,
+            check,
+            node: node.to_string(),
+            message,
+        }
+    }
+}
+
+// Whether a name of the zone is served, by a record of its node, a wildcard
+// covering it or the wildcard aliases of the domain.
+fn is_served(domain: &DomainDTO, nodes: &BTreeMap<String, Vec<&RecordDTO>>, node: &str) -> bool {
+    node.is_empty()
+        || domain.ipv4_wildcard_alias
+        || domain.ipv6_wildcard_alias
+        || nodes.contains_key(node)
+        || nodes
+            .keys()
+            .any(|candidate| match candidate.strip_prefix('*') {
+                Some("") => true,
+                Some(suffix) => suffix.starts_with('.') && node.ends_with(suffix),
+                None => false,
+            })
+}
+
+// Why node can't hold a CNAME given the records of the zone, as RFC 1034
+// allows no other record next to it, None when it can. The CNAME already on the
+// node, being replaced, doesn't count.
+pub fn cname_refused(node: &str, records: &[RecordDTO]) -> Option<String> {
+    if normalise_fqdn(node).is_empty() {
+        return Some(CNAME_AT_APEX.to_string());
+    }
+    let others: Vec<&str> = records
+        .iter()
+        .filter(|r| same_name(r.node_name(), node))
+        .map(|r| r.record_type())
+        .filter(|t| *t != "CNAME")
+        .collect();
+    (!others.is_empty()).then(|| {
+        format!(
+            "a CNAME can't be next to other records ({})",
+            others.join(", ")
+        )
+    })
+}
+
+// Whether a name exists, failing when it can't be looked up.
+pub type Resolve = dyn Fn(&str) -> Result<bool, SelfError>;
+
+// Checks the domain and its records for common problems, ordered by severity
+// then node. resolve tells whether a name outside the zone exists, the CNAME
+// targets outside the zone being left alone without it; a target which can't
+// be looked up at all, e.g. without network, is only warned about.
+pub fn lint(
+    domain: &DomainDTO,
+    records: &[RecordDTO],
+    low_ttl: u64,
+    high_ttl: u64,
+    resolve: Option<&Resolve>,
+) -> Vec<Finding> {
+    let zone = normalise_fqdn(&domain.name);
+    let mut nodes: BTreeMap<String, Vec<&RecordDTO>> = BTreeMap::new();
+    for record in records {
+        nodes
+            .entry(normalise_fqdn(record.node_name()))
+            .or_default()
+            .push(record);
+    }
+    let mut findings = Vec::new();
+    for (node, records) in &nodes {
+        let cnames: Vec<&str> = records
+            .iter()
+            .filter_map(|r| match r {
+                RecordDTO::CnameRecord { host, .. } => Some(host.as_str()),
+                _ => None,
+            })
+            .collect();
+        if cnames.is_empty() {
+            continue;
+        }
+        if node.is_empty() {
+            findings.push(Finding::new(
+                Severity::Error,
+                "cname-at-apex",
+                node,
+                CNAME_AT_APEX.to_string(),
+            ));
+        } else if records.len() > 1 {
+            let others: Vec<&str> = records
+                .iter()
+                .map(|r| r.record_type())
+                .filter(|t| *t != "CNAME")
+                .collect();
+            findings.push(Finding::new(
+                Severity::Error,
+                "cname-conflict",
+                node,
+                if others.is_empty() {
+                    format!("{} CNAME records on the same node", cnames.len())
+                } else {
+                    format!("CNAME next to other records ({})", others.join(", "))
+                },
+            ));
+        }
+        for target in cnames {
+            let target = normalise_fqdn(target);
+            let dangling = match node_of(&target, &zone) {
+                Some(target_node) => !is_served(domain, &nodes, &target_node),
+                None => match resolve.map(|resolve| resolve(&target)) {
+                    Some(Ok(exists)) => !exists,
+                    Some(Err(e)) => {
+                        findings.push(Finding::new(
+                            Severity::Warning,
+                            "cname-unresolved",
+                            node,
+                            format!("the target {} couldn't be looked up: {}", target, e),
+                        ));
+                        false
+                    }
+                    None => false,
+                },
+            };
+            if dangling {
+                findings.push(Finding::new(
+                    Severity::Error,
+                    "dangling-cname",
+                    node,
+                    format!("the target {} doesn't resolve", target),
+                ));
+            }
+        }
+    }
+    if domain.ipv6 && domain.ipv6_address.is_none() {
+        findings.push(Finding::new(
+            Severity::Warning,
+            "missing-aaaa",
+            "",
+            "ipv6 is enabled on the domain but it has no IPv6 address".to_string(),
+        ));
+    }
+    if domain.ipv6 && domain.ipv6_address.is_some() {
+        for (node, records) in &nodes {
+            let has = |record_type: &str| records.iter().any(|r| r.record_type() == record_type);
+            if !node.is_empty() && has("A") && !has("AAAA") {
+                findings.push(Finding::new(
+                    Severity::Warning,
+                    "missing-aaaa",
+                    node,
+                    "A record without AAAA record while the domain has an IPv6 address".to_string(),
+                ));
+            }
+        }
+    }
+    let ttls = std::iter::once(("", "domain", domain.ttl)).chain(
+        records
+            .iter()
+            .filter(|r| r.record_type() != "SOA")
+            .map(|r| (r.node_name(), r.record_type(), r.ttl())),
+    );
+    for (node, what, ttl) in ttls {
+        if ttl < low_ttl {
+            findings.push(Finding::new(
+                Severity::Warning,
+                "ttl-low",
+                node,
+                format!("{} TTL {} is lower than {}", what, ttl, low_ttl),
+            ));
+        } else if ttl > high_ttl {
+            findings.push(Finding::new(
+                Severity::Warning,
+                "ttl-high",
+                node,
+                format!("{} TTL {} is higher than {}", what, ttl, high_ttl),
+            ));
+        }
+    }
+    findings.sort_by(|a, b| (a.severity, &a.node).cmp(&(b.severity, &b.node)));
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checks(findings: &[Finding]) -> Vec<(&str, &str)> {
+        findings
+            .iter()
+            .map(|f| (f.check, f.node.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn lint_should_report_the_problems_of_the_zone() {
+        let domain = DomainDTO {
+            name: "example.dynu.net".to_string(),
+            ipv4: true,
+            ipv4_address: Some("192.0.2.1".to_string()),
+            ipv6: true,
+            ipv6_address: Some("2001:db8::1".to_string()),
+            ttl: 120,
+            ..Default::default()
+        };
+        let records = vec![
+            RecordDTO::cname_record("", "other.example.com", 300, Some(1)),
+            RecordDTO::cname_record("www", "example.dynu.net", 300, Some(2)),
+            RecordDTO::txt_record("www", "token", 300, Some(3)),
+            RecordDTO::cname_record("old", "gone.example.dynu.net", 300, Some(4)),
+            RecordDTO::cname_record("cdn", "cdn.example.com", 300, Some(5)),
+            RecordDTO::a_record("vpn", "192.0.2.9", 30, Some(6)),
+            RecordDTO::cname_record("api", "api.example.org", 300, Some(7)),
+        ];
+        let resolve = |name: &str| match name {
+            "api.example.org" => Err(SelfError::MsgError("timed out".to_string())),
+            name => Ok(name == "cdn.example.com"),
+        };
+        let findings = lint(&domain, &records, LOW_TTL, HIGH_TTL, Some(&resolve));
+        assert_eq!(
+            checks(&findings),
+            vec![
+                ("cname-at-apex", ""),
+                ("dangling-cname", ""),
+                ("dangling-cname", "old"),
+                ("cname-conflict", "www"),
+                ("cname-unresolved", "api"),
+                ("missing-aaaa", "vpn"),
+                ("ttl-low", "vpn"),
+            ]
+        );
+        assert_eq!(findings[3].message, "CNAME next to other records (TXT)");
+        assert_eq!(
+            findings[4].message,
+            "the target api.example.org couldn't be looked up: MsgError(timed out)"
+        );
+
+        assert_eq!(cname_refused("", &records).as_deref(), Some(CNAME_AT_APEX));
+        assert_eq!(
+            cname_refused("WWW", &records).as_deref(),
+            Some("a CNAME can't be next to other records (TXT)")
+        );
+        assert_eq!(cname_refused("old", &records), None);
+        assert_eq!(cname_refused("new", &records), None);
+
+        let findings = lint(&domain, &records[1..2], LOW_TTL, HIGH_TTL, None);
+        assert!(findings.is_empty());
+        let wildcard = RecordDTO::a_record("*", "192.0.2.9", 300, Some(8));
+        let records = [records[3].clone(), wildcard];
+        let findings = lint(&domain, &records, LOW_TTL, 100, None);
+        assert_eq!(
+            checks(&findings),
+            vec![
+                ("ttl-high", ""),
+                ("missing-aaaa", "*"),
+                ("ttl-high", "*"),
+                ("ttl-high", "old")
+            ]
+        );
+    }
+}
@@ -1,21 +1,109 @@
-mod dynu;
-mod netutils;
-use clap::{Parser, Subcommand};
+mod account;
+mod acme;
+mod alias;
+mod allow;
+mod ansible;
+mod apicalls;
+mod backup;
+mod cache;
+mod certmanager;
+mod changes;
+mod config;
+mod constraints;
+mod content;
+mod crash;
+mod daemon;
+mod dashboard;
+mod dns;
+mod drift;
+mod ensure;
+mod export;
+mod failover;
+mod import;
+mod init;
+mod ipsource;
+mod lease;
+mod lego;
+mod lint;
+mod mqtt;
+mod names;
+mod netwatch;
+mod notify;
+mod output;
+mod payload;
+mod ping;
+mod progress;
+mod propagation;
+mod queue;
+mod reachability;
+mod rfc2136;
+mod server;
+mod staleness;
+mod state;
+mod suggest;
+mod templates;
+mod ttl;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+#[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+compile_error!("either the native-tls or the rustls feature must be enabled");
+use clap::{ArgGroup, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use core::fmt;
-use std::{
-    env::{self, VarError},
-    error::Error,
-    io,
-};
+use dynuupdater::{dynu, netutils};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use std::{env, env::VarError, error::Error, io};
 
+use crate::ansible::{Recorder, Report};
+use crate::backup::Backup;
+use crate::cache::ZoneCache;
+use crate::certmanager::CertManagerOptions;
+use crate::changes::{results_table, Action, ChangeSet, DomainResult, Family, Outcome};
+use crate::config::{Config, DomainConfig};
+use crate::constraints::Constraints;
+use crate::content::same_content;
+use crate::dashboard::DashboardOptions;
+use crate::dns::{TYPE_A, TYPE_AAAA};
+use crate::drift::{DriftAction, OwnWrites, Snapshots};
 use crate::dynu::RecordDTO;
+use crate::ensure::{Desired, RecordKind, Step};
+use crate::export::{ExportFormat, TerraformProvider};
+use crate::failover::{Check, FailoverOptions, Target};
+use crate::import::ImportFormat;
+use crate::lease::Lease;
+use crate::mqtt::Mqtt;
+use crate::names::{check_node, normalise_fqdn, same_name, to_ascii};
+use crate::notify::{Event, Notifier, Sink, Status, Webhook};
+use crate::output::{field_diff, or_dash, print_json, print_text, Listing, OutputFormat, Table};
+use crate::payload::RecordFields;
+use crate::progress::Progress;
+use crate::propagation::{Resolver, Verification};
+use crate::queue::{PendingChange, Queue, ReplayOutcome};
+use crate::reachability::{Probe, Reachability};
+use crate::rfc2136::{Rfc2136Options, TsigKey};
+use crate::server::{Auth, ServerOptions, Tls, TlsFiles};
+use crate::staleness::LastUpdates;
+use crate::state::StateDir;
+use crate::suggest::did_you_mean;
 use crate::SelfError::MsgError;
-use dynu::{ClientError, DomainDTO, DomainsDTO, DynuClient};
-use netutils::{ip, public_ip_of, IP};
+use dynu::{ClientError, DomainDTO, DomainsDTO, DynuClient, GenericRecordDTO, IpUpdateDTO};
+use netutils::{public_ip_of, Addresses};
 
 const API_KEY_NAME: &str = "DYNU_API_KEY";
+const TSIG_KEY_NAME: &str = "DYNU_TSIG_KEY";
+const SERVER_TOKEN_NAME: &str = "DYNU_SERVER_TOKEN";
+const SERVER_BASIC_AUTH_NAME: &str = "DYNU_SERVER_BASIC_AUTH";
+const WEBHOOK_URL_NAME: &str = "DYNU_WEBHOOK_URL";
+const MQTT_URL_NAME: &str = "DYNU_MQTT_URL";
+const DEFAULT_CONTENT_TYPE: &str = "application/json";
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 enum SelfError {
     MsgError(String),
     ClientError(ClientError),
@@ -58,9 +146,127 @@ impl From<VarError> for SelfError {
 struct MainArguments {
     #[arg(
         long,
-        help = "API KEY for dynu, used with priority over the DYNU_API_KEY environment variable"
+        help = "API KEY for dynu, used with priority over the DYNU_API_KEY and DYNU_API_KEY_FILE environment variables"
     )]
     api_key: Option<String>,
+    #[arg(
+        long,
+        help = "URL to POST notifications of updates and failures to, used with priority over the DYNU_WEBHOOK_URL and DYNU_WEBHOOK_URL_FILE environment variables"
+    )]
+    webhook_url: Option<String>,
+    #[arg(
+        long,
+        env = "DYNU_WEBHOOK_TEMPLATE",
+        help = "minijinja template file rendering the body of the notifications, which is the JSON event by default"
+    )]
+    webhook_template: Option<String>,
+    #[arg(
+        long,
+        env = "DYNU_WEBHOOK_CONTENT_TYPE",
+        help = "Content type of the body of the notifications [default: application/json]"
+    )]
+    webhook_content_type: Option<String>,
+    #[arg(
+        long,
+        help = "MQTT broker to publish notifications to as mqtt://[user[:password]@]host[:port][/topic], used with priority over the DYNU_MQTT_URL and DYNU_MQTT_URL_FILE environment variables"
+    )]
+    mqtt_url: Option<String>,
+    #[arg(
+        long,
+        env = "DYNU_MQTT_DISCOVERY_PREFIX",
+        help = "Topic prefix of the Home Assistant discovery configurations published to MQTT [default: homeassistant]"
+    )]
+    mqtt_discovery_prefix: Option<String>,
+    #[arg(
+        long,
+        env = "DYNU_STATE_DIR",
+        help = "Directory where state is persisted between runs [default: $XDG_STATE_HOME/dynuupdater]"
+    )]
+    state_dir: Option<String>,
+    #[arg(
+        long,
+        help = "Show the field level changes sent to dynu when updating a domain"
+    )]
+    verbose: bool,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "text",
+        help = "Format of the results printed on stdout"
+    )]
+    output: OutputFormat,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Comma separated columns of the listings to print, e.g. id,node,type,value"
+    )]
+    fields: Vec<String>,
+    #[arg(
+        long,
+        help = "Print the outcome as the JSON of an ansible module, with changed, msg and diff, and exit with 1 only on failures"
+    )]
+    ansible: bool,
+    #[arg(
+        long,
+        env = "DYNU_IP_SOURCE",
+        help = "Source detecting the public ip, either the builtin ipify or a dynuupdater-ipsource-<name> executable on PATH [default: ipify]"
+    )]
+    ip_source: Option<String>,
+    #[arg(
+        long,
+        env = "DYNU_IP_SOURCE_TIMEOUT",
+        help = "Kill a dynuupdater-ipsource-<name> executable still running after this, e.g. 10s [default: 30s]"
+    )]
+    ip_source_timeout: Option<humantime::Duration>,
+    #[arg(
+        long,
+        global = true,
+        env = "DYNU_READ_ONLY",
+        help = "Refuse every request changing the dynu account, e.g. to explore a production account safely"
+    )]
+    read_only: bool,
+    #[arg(
+        long,
+        global = true,
+        env = "DYNU_MAX_RUNTIME",
+        help = "Abort the whole run, exiting with 4, when it takes longer than this, e.g. 60s"
+    )]
+    max_runtime: Option<humantime::Duration>,
+    #[arg(
+        long,
+        global = true,
+        env = "DYNU_CONFIG",
+        help = "Configuration file providing the settings not given as arguments or environment variables [default: $XDG_CONFIG_HOME/dynuupdater/config.toml]"
+    )]
+    config: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        env = "DYNU_PROFILE",
+        help = "Profile of the configuration file whose settings override the top-level ones"
+    )]
+    profile: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        env = "DYNU_ENV",
+        help = "Environment whose overrides of the configuration file apply over the profile"
+    )]
+    env: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        env = "DYNU_API_CALLS",
+        help = "Print on stderr how many requests to dynu the command sent, per endpoint"
+    )]
+    api_calls: bool,
+    #[arg(
+        long,
+        global = true,
+        env = "DYNU_API_CALLS_FILE",
+        help = "Add the requests to dynu the command sent to the counters of this file, in the Prometheus text format"
+    )]
+    api_calls_file: Option<String>,
     #[command(subcommand)]
     cmd: Commands,
 }
@@ -70,163 +276,4978 @@ enum Commands {
     #[command(
         about = "Update a dynu domain using the public ip of the system running the process"
     )]
+    #[command(visible_alias = "rf")]
     Refresh {
-        #[arg(help = "Domain to update")]
-        domain: String,
+        #[arg(
+            help = "Domain to update, the domains declared in the configuration or else its default domain when missing"
+        )]
+        domain: Option<String>,
+        #[arg(
+            long,
+            conflicts_with = "domain",
+            help = "Update every domain of the dynu account"
+        )]
+        all: bool,
+        #[arg(
+            long,
+            help = "Show the changes that would be sent to dynu without updating the domain"
+        )]
+        dry_run: bool,
+        #[arg(long, help = "Also set the TTL of the domain")]
+        ttl: Option<u64>,
+        #[arg(long, help = "Also set the group of the domain")]
+        group: Option<String>,
+        #[arg(long, help = "Only publish addresses on which this port is reachable")]
+        require_port: Option<u16>,
+        #[arg(
+            long,
+            requires = "require_port",
+            default_value = "direct",
+            help = "How the required port is probed: direct, loopback, or the url of an external checker with {address} and {port} placeholders"
+        )]
+        probe: String,
+        #[arg(
+            long,
+            help = "Update the domain even when its addresses are current if it was last updated longer ago, e.g. 7d"
+        )]
+        max_age: Option<humantime::Duration>,
+        #[arg(
+            long,
+            value_enum,
+            help = "Warn about the changes made to the domain outside dynuupdater since the last refresh, and notify them with notify"
+        )]
+        drift: Option<DriftAction>,
+    },
+
+    #[command(
+        about = "Send the current addresses of a dynu domain back unchanged, resetting dynu's inactivity timer"
+    )]
+    Touch {
+        #[arg(help = "Domain to touch, the default domain of the configuration when missing")]
+        domain: Option<String>,
+    },
+
+    #[command(about = "Keep refreshing dynu domains periodically, without the need for cron")]
+    Daemon {
+        #[arg(
+            help = "Domains to refresh, the domains declared in the configuration or else its default domain when missing"
+        )]
+        domains: Vec<String>,
+        #[arg(
+            long,
+            conflicts_with = "domains",
+            help = "Refresh every domain of the dynu account"
+        )]
+        all: bool,
+        #[arg(long, help = "Time between refreshes", default_value = "5m")]
+        interval: humantime::Duration,
+        #[arg(
+            long,
+            help = "Also refresh as soon as the network configuration of the host changes, only supported on macOS"
+        )]
+        watch_network: bool,
+        #[arg(
+            long,
+            help = "Update the domains even when their addresses are current if they were last updated longer ago, e.g. 7d"
+        )]
+        max_age: Option<humantime::Duration>,
+        #[arg(
+            long,
+            value_enum,
+            help = "Warn about the changes made to the domains outside dynuupdater since the last refresh, and notify them with notify"
+        )]
+        drift: Option<DriftAction>,
+        #[arg(
+            long,
+            help = "Also back up the domains into the state directory with this frequency, e.g. 1d"
+        )]
+        backup_every: Option<humantime::Duration>,
+        #[arg(
+            long,
+            requires = "backup_every",
+            default_value_t = 7,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+            help = "Number of backups kept, the oldest ones being deleted"
+        )]
+        backup_keep: usize,
     },
 
     #[command(about = "Update or create a dynu domain TXT record with provided value")]
-    #[command(name = "txt-update")]
+    #[command(name = "txt-update", visible_alias = "tu")]
+    #[command(group(
+        ArgGroup::new("source")
+            .required(true)
+            .args(["value", "value_file", "value_stdin"])
+    ))]
     UpdateTxtRecord {
-        #[arg(long, help = "DNS record key to update")]
-        name: String,
-        #[arg(long, help = "TTL for the record entry", default_value = "120")]
-        ttl: u64,
+        #[arg(
+            long,
+            help = "DNS record key to update, when missing DOMAIN split into the dynu domain and the key, or the default node of the configuration for the default domain"
+        )]
+        name: Option<String>,
+        #[arg(
+            long,
+            help = "TTL for the record entry, the default TTL of the configuration or 120 when missing"
+        )]
+        ttl: Option<u64>,
         #[arg(long, help = "DNS record value to update")]
-        value: String,
-        #[arg(help = "Domain to update")]
-        domain: String,
+        value: Option<String>,
+        #[arg(
+            long,
+            help = "Read the DNS record value from this file, without the trailing newline"
+        )]
+        value_file: Option<String>,
+        #[arg(
+            long,
+            help = "Read the DNS record value from the standard input, without the trailing newline"
+        )]
+        value_stdin: bool,
+        #[arg(
+            help = "Domain to update, or full hostname of the record, the default domain of the configuration when missing"
+        )]
+        domain: Option<String>,
+        #[arg(
+            long,
+            help = "Append the hostname of the system to the record key, so that several hosts can set their own record"
+        )]
+        unique_suffix: bool,
     },
 
     #[command(about = "Delete a dynu domain TXT record")]
     #[command(name = "txt-delete")]
     DeleteTxtRecord {
-        #[arg(help = "Domain to update")]
+        #[arg(
+            help = "Domain to update, or full hostname of the record, the default domain of the configuration when missing"
+        )]
+        domain: Option<String>,
+        #[arg(
+            help = "DNS record key to delete, when missing DOMAIN split into the dynu domain and the key, or the default node of the configuration for the default domain"
+        )]
+        name: Option<String>,
+        #[arg(
+            long,
+            help = "Only delete the TXT records holding this value, leaving the other records of the key in place"
+        )]
+        value: Option<String>,
+        #[arg(
+            long,
+            help = "Append the hostname of the system to the record key, deleting the record set by txt-update --unique-suffix"
+        )]
+        unique_suffix: bool,
+        #[arg(
+            long,
+            help = "After deleting, wait until no authoritative nameserver of the domain serves the deleted values"
+        )]
+        verify: bool,
+        #[arg(
+            long,
+            requires = "verify",
+            default_value = "2m",
+            help = "How long to wait for the deletion to propagate before failing"
+        )]
+        verify_timeout: humantime::Duration,
+        #[arg(
+            long = "resolver",
+            requires = "verify",
+            help = "Resolver to verify the deletion on, repeatable: authoritative (the default), system, an address or a DNS over HTTPS URL"
+        )]
+        resolvers: Vec<Resolver>,
+    },
+
+    #[command(
+        about = "Add the TXT value rendered from a template of the configuration to a dynu domain"
+    )]
+    #[command(name = "txt-apply-template")]
+    TxtApplyTemplate {
+        #[arg(help = "Domain to add the TXT value to")]
         domain: String,
-        #[arg(help = "DNS record key to delete")]
-        name: String,
+        #[arg(help = "Name of the template in the [txt_templates] table of the configuration")]
+        template: String,
+        #[arg(long, help = "Value the template is rendered with, as {{ value }}")]
+        value: String,
+        #[arg(
+            long,
+            help = "DNS record key of the TXT value, the node of the template or the domain itself when missing"
+        )]
+        name: Option<String>,
+        #[arg(
+            long,
+            help = "TTL for the record entry, the TTL of the template or the default TTL of the configuration when missing"
+        )]
+        ttl: Option<u64>,
+        #[arg(
+            long,
+            help = "Only print the value and the changes, without making them"
+        )]
+        dry_run: bool,
+    },
+
+    #[command(about = "Update or create the A record of a node of a dynu domain")]
+    #[command(name = "a-update")]
+    AUpdate {
+        #[arg(long, help = "Address the record must hold")]
+        address: Ipv4Addr,
+        #[arg(long, help = "TTL for the record entry, 300 when missing")]
+        ttl: Option<u64>,
+        #[command(flatten)]
+        node: RecordNode,
+    },
+
+    #[command(about = "Delete the A records of a node of a dynu domain")]
+    #[command(name = "a-delete")]
+    ADelete {
+        #[arg(
+            long,
+            help = "Only delete the A record holding this address, leaving the other records of the node in place"
+        )]
+        address: Option<Ipv4Addr>,
+        #[command(flatten)]
+        node: RecordNode,
+    },
+
+    #[command(about = "Update or create the AAAA record of a node of a dynu domain")]
+    #[command(name = "aaaa-update")]
+    AaaaUpdate {
+        #[arg(long, help = "Address the record must hold")]
+        address: Ipv6Addr,
+        #[arg(long, help = "TTL for the record entry, 300 when missing")]
+        ttl: Option<u64>,
+        #[command(flatten)]
+        node: RecordNode,
+    },
+
+    #[command(about = "Delete the AAAA records of a node of a dynu domain")]
+    #[command(name = "aaaa-delete")]
+    AaaaDelete {
+        #[arg(
+            long,
+            help = "Only delete the AAAA record holding this address, leaving the other records of the node in place"
+        )]
+        address: Option<Ipv6Addr>,
+        #[command(flatten)]
+        node: RecordNode,
+    },
+
+    #[command(
+        about = "Update or create the SPF record holding the policy of a node of a dynu domain"
+    )]
+    #[command(name = "spf-update")]
+    SpfUpdate {
+        #[arg(long, help = "SPF policy of the node, e.g. 'v=spf1 mx -all'")]
+        policy: String,
+        #[arg(long, help = "TTL for the record entry, 300 when missing")]
+        ttl: Option<u64>,
+        #[command(flatten)]
+        node: RecordNode,
+    },
+
+    #[command(about = "Delete the SPF record of a node of a dynu domain")]
+    #[command(name = "spf-delete")]
+    SpfDelete {
+        #[command(flatten)]
+        node: RecordNode,
+    },
+
+    #[command(about = "Update or create the CNAME record making a node of a dynu domain an alias")]
+    #[command(name = "cname-update")]
+    CnameUpdate {
+        #[arg(
+            long,
+            help = "Canonical name the node is an alias of, e.g. example.github.io"
+        )]
+        target: String,
+        #[arg(long, help = "TTL for the record entry, 300 when missing")]
+        ttl: Option<u64>,
+        #[command(flatten)]
+        node: RecordNode,
+    },
+
+    #[command(about = "Delete the CNAME record of a node of a dynu domain")]
+    #[command(name = "cname-delete")]
+    CnameDelete {
+        #[command(flatten)]
+        node: RecordNode,
+    },
+
+    #[command(
+        about = "Update or create the PTR record of an address in a reverse zone hosted by dynu"
+    )]
+    #[command(name = "ptr-update")]
+    PtrUpdate {
+        #[arg(
+            long,
+            help = "Hostname the address points back to, e.g. nas.example.dynu.net"
+        )]
+        target: String,
+        #[arg(long, help = "TTL for the record entry, 300 when missing")]
+        ttl: Option<u64>,
+        #[arg(
+            help = "Address whose PTR record is set, e.g. 192.0.2.1, or its name in the reverse zone"
+        )]
+        address: String,
+    },
+
+    #[command(about = "Delete the PTR record of an address in a reverse zone hosted by dynu")]
+    #[command(name = "ptr-delete")]
+    PtrDelete {
+        #[arg(
+            help = "Address whose PTR record is deleted, e.g. 192.0.2.1, or its name in the reverse zone"
+        )]
+        address: String,
+    },
+
+    #[command(about = "Delegate a node of a dynu domain to name servers, setting its NS records")]
+    #[command(name = "ns-update")]
+    NsUpdate {
+        #[arg(
+            long = "nameserver",
+            required = true,
+            help = "Name server the node is delegated to, repeated for each of them"
+        )]
+        nameservers: Vec<String>,
+        #[arg(long, help = "TTL for the record entries, 300 when missing")]
+        ttl: Option<u64>,
+        #[command(flatten)]
+        node: RecordNode,
+    },
+
+    #[command(about = "Delete the NS records of a node of a dynu domain, ending its delegation")]
+    #[command(name = "ns-delete")]
+    NsDelete {
+        #[arg(
+            long = "nameserver",
+            help = "Only delete the record of this name server, repeated for each of them"
+        )]
+        nameservers: Vec<String>,
+        #[command(flatten)]
+        node: RecordNode,
+    },
+
+    #[command(
+        about = "Update or create a CAA record restricting the certificate authorities of a node of a dynu domain"
+    )]
+    #[command(name = "caa-update")]
+    CaaUpdate {
+        #[arg(
+            long,
+            default_value = "issue",
+            help = "Tag of the record: issue, issuewild or iodef"
+        )]
+        tag: String,
+        #[arg(
+            long,
+            help = "Value of the record, e.g. letsencrypt.org for issue or mailto:security@example.com for iodef"
+        )]
+        value: String,
+        #[arg(
+            long,
+            default_value_t = 0,
+            help = "Flags of the record, 128 when the tag is critical"
+        )]
+        flags: u8,
+        #[arg(long, help = "TTL for the record entry, 300 when missing")]
+        ttl: Option<u64>,
+        #[command(flatten)]
+        node: RecordNode,
+    },
+
+    #[command(about = "Delete the CAA records of a node of a dynu domain")]
+    #[command(name = "caa-delete")]
+    CaaDelete {
+        #[arg(long, help = "Only delete the records with this tag")]
+        tag: Option<String>,
+        #[arg(long, help = "Only delete the records with this value")]
+        value: Option<String>,
+        #[command(flatten)]
+        node: RecordNode,
+    },
+
+    #[command(about = "Print the version of dynuupdater")]
+    Version {
+        #[arg(
+            long,
+            help = "Also print the commit, build date, enabled features and TLS backend"
+        )]
+        verbose: bool,
+    },
+
+    #[command(about = "Measure the latency and error rate of authenticated requests to dynu")]
+    #[command(name = "ping-api")]
+    PingApi {
+        #[arg(long, default_value = "10", help = "Number of requests to send")]
+        count: u32,
+        #[arg(long, default_value = "1s", help = "Pause between the requests")]
+        interval: humantime::Duration,
+    },
+
+    #[command(about = "List the available sources detecting the public ip")]
+    #[command(name = "ip-sources")]
+    IpSources,
+
+    #[command(
+        about = "Show the membership level of the dynu account and its usage against the plan limits"
+    )]
+    Account {
+        #[arg(
+            long,
+            help = "Fail unless the plan allows creating this many more domains"
+        )]
+        require_free_domains: Option<u64>,
+        #[arg(
+            long,
+            help = "Fail unless the plan allows creating this many more records"
+        )]
+        require_free_records: Option<u64>,
+    },
+
+    #[command(about = "List the domains of the dynu account")]
+    #[command(args_conflicts_with_subcommands = true)]
+    Domains {
+        #[arg(
+            long,
+            help = "Show the domains cached by the last online listing instead of querying dynu"
+        )]
+        cached: bool,
+        #[command(subcommand)]
+        action: Option<DomainsAction>,
+    },
+
+    #[command(
+        about = "Bring the records of a host to a state, printing changed or unchanged and exiting with 2 when changed"
+    )]
+    Ensure {
+        #[arg(
+            long,
+            global = true,
+            help = "Only report whether the records would change, without changing them"
+        )]
+        check: bool,
+        #[arg(
+            long,
+            global = true,
+            help = "TTL the records must have, kept as is when missing and 300 for new records"
+        )]
+        ttl: Option<u64>,
+        #[arg(
+            long,
+            global = true,
+            help = "Reuse the records cached by the last run while dynu reports the domain unchanged"
+        )]
+        reuse_records: bool,
+        #[command(subcommand)]
+        state: EnsureState,
+    },
+
+    #[command(about = "Inspect a dynu domain")]
+    Domain {
+        #[command(subcommand)]
+        action: DomainAction,
+    },
+
+    #[command(about = "List and update the groups of domains of the dynu account")]
+    Group {
+        #[command(subcommand)]
+        action: GroupAction,
+    },
+
+    #[command(
+        about = "Check a dynu domain for common zone problems, exiting with 1 when some are errors"
+    )]
+    Lint {
+        #[arg(help = "Domain to check, the default domain of the configuration when missing")]
+        domain: Option<String>,
+        #[arg(
+            long,
+            default_value_t = lint::LOW_TTL,
+            help = "TTLs lower than this are reported"
+        )]
+        min_ttl: u64,
+        #[arg(
+            long,
+            default_value_t = lint::HIGH_TTL,
+            help = "TTLs higher than this are reported"
+        )]
+        max_ttl: u64,
+        #[arg(
+            long,
+            help = "Don't resolve the CNAME targets outside the domain, only checking the ones inside it"
+        )]
+        no_resolve: bool,
+    },
+
+    #[command(about = "List the DNS records of a dynu domain")]
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    #[command(visible_alias = "ls")]
+    Records {
+        #[arg(required = true, help = "Domain to list the records of")]
+        domain: Option<String>,
+        #[arg(
+            long,
+            help = "Show the records cached by the last online listing instead of querying dynu"
+        )]
+        cached: bool,
+        #[command(flatten)]
+        order: RecordOrder,
+        #[command(subcommand)]
+        action: Option<RecordsAction>,
+    },
+
+    #[command(about = "Manage single DNS records of any type in a dynu domain")]
+    Record {
+        #[command(subcommand)]
+        action: RecordAction,
+    },
+
+    #[command(about = "Export the DNS records of a dynu domain as infrastructure as code")]
+    Export {
+        #[arg(help = "Domain to export the records of")]
+        domain: String,
+        #[arg(
+            long,
+            value_enum,
+            default_value = "terraform",
+            help = "Format of the export"
+        )]
+        format: ExportFormat,
+        #[arg(
+            long,
+            value_enum,
+            default_value = "dns",
+            help = "Terraform provider the resources belong to"
+        )]
+        provider: TerraformProvider,
+    },
+
+    #[command(about = "Import DNS records exported from another provider into a dynu domain")]
+    Import {
+        #[arg(help = "Domain to import the records into")]
+        domain: String,
+        #[arg(long, help = "File holding the exported records")]
+        from: String,
+        #[arg(
+            long,
+            value_enum,
+            help = "Format of the export, detected from the file when missing"
+        )]
+        format: Option<ImportFormat>,
+        #[arg(
+            long,
+            help = "Show the records that would be created without creating them"
+        )]
+        dry_run: bool,
+    },
+
+    #[command(about = "Take snapshots of dynu domains and their records, and compare them")]
+    Backup {
+        #[command(subcommand)]
+        action: BackupAction,
+    },
+
+    #[command(about = "Create the TXT record of an ACME challenge, as a lego exec provider")]
+    Present {
+        #[arg(
+            required = true,
+            allow_hyphen_values = true,
+            help = "<FQDN> <VALUE>, or <DOMAIN> <TOKEN> <KEY_AUTH> with EXEC_MODE=RAW"
+        )]
+        args: Vec<String>,
+    },
+
+    #[command(about = "Delete the TXT record of an ACME challenge, as a lego exec provider")]
+    Cleanup {
+        #[arg(
+            required = true,
+            allow_hyphen_values = true,
+            help = "<FQDN> <VALUE>, or <DOMAIN> <TOKEN> <KEY_AUTH> with EXEC_MODE=RAW"
+        )]
+        args: Vec<String>,
+    },
+
+    #[command(
+        about = "Print the propagation timeout and polling interval as JSON, as a lego exec provider"
+    )]
+    Timeout,
+
+    #[command(
+        name = "migrate-ip",
+        about = "Switch the addresses of a dynu domain with its TTL lowered beforehand, restoring the TTL once the switch propagated"
+    )]
+    MigrateIp {
+        #[arg(help = "Domain to switch the addresses of")]
+        domain: String,
+        #[arg(long, required_unless_present = "ipv6", help = "New IPv4 address")]
+        ipv4: Option<Ipv4Addr>,
+        #[arg(long, help = "New IPv6 address")]
+        ipv6: Option<Ipv6Addr>,
+        #[arg(
+            long,
+            default_value_t = constraints::FREE_MIN_TTL,
+            help = "TTL the domain has while switching, the lowest one every plan accepts by default"
+        )]
+        low_ttl: u64,
+        #[arg(
+            long,
+            default_value = "10m",
+            help = "How long to wait for the resolvers to serve the new addresses"
+        )]
+        verify_timeout: humantime::Duration,
+        #[arg(
+            long = "resolver",
+            help = "Resolver to verify the switch on, repeatable: authoritative (the default), system, an address or a DNS over HTTPS URL"
+        )]
+        resolvers: Vec<Resolver>,
+    },
+
+    #[command(
+        about = "Monitor a primary endpoint and switch the A record of a domain to a backup address while it is down"
+    )]
+    Failover {
+        #[arg(help = "Domain to switch")]
+        domain: String,
+        #[arg(long, help = "Address published while the primary endpoint is healthy")]
+        primary: Ipv4Addr,
+        #[arg(long, help = "Address published while the primary endpoint is down")]
+        backup: Ipv4Addr,
+        #[arg(
+            long,
+            help = "Health check of the primary endpoint, tcp:<host>:<port> or an http(s) url"
+        )]
+        check: String,
+        #[arg(long, help = "Time between health checks", default_value = "30s")]
+        interval: humantime::Duration,
+        #[arg(
+            long,
+            help = "Consecutive failed checks switching to the backup address",
+            default_value = "3"
+        )]
+        failures: u32,
+        #[arg(
+            long,
+            help = "Consecutive successful checks switching back to the primary address",
+            default_value = "3"
+        )]
+        recoveries: u32,
+    },
+
+    #[command(about = "Run a long-lived server integrating dynu with other systems")]
+    Serve {
+        #[command(subcommand)]
+        mode: ServeMode,
+    },
+
+    #[command(about = "Maintain the ACME challenge records")]
+    Acme {
+        #[command(subcommand)]
+        action: AcmeAction,
+    },
+
+    #[command(about = "Manage the configuration file")]
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
+    #[command(
+        about = "Interactively ask for the API key and the domains to refresh, and write a starter configuration file"
+    )]
+    Init {
+        #[arg(long, help = "Store the API key with this backend instead of asking")]
+        secret_backend: Option<init::SecretBackend>,
+        #[arg(long, help = "Replace the configuration file when it already exists")]
+        force: bool,
     },
 }
 
-fn get_api_key(args: &MainArguments) -> Result<String, SelfError> {
-    match &args.api_key {
-        Some(value) => Ok(value.clone()),
-        None => env::var(API_KEY_NAME).map_err(|_| {
-            SelfError::MsgError(format!(
-                "provide 'api-key' argument or define environment variable {}",
-                API_KEY_NAME
-            ))
-        }),
-    }
+#[derive(Args, Debug)]
+struct RecordNode {
+    #[arg(
+        long,
+        help = "Node of the record, e.g. nas, when missing DOMAIN split into the dynu domain and the node"
+    )]
+    name: Option<String>,
+    #[arg(
+        help = "Domain of the record, or full hostname of the record, the default domain of the configuration when missing"
+    )]
+    domain: Option<String>,
 }
 
-fn or_empty(option: &Option<String>) -> String {
-    option
-        .as_ref()
-        .map(|s| s.to_string())
-        .unwrap_or("".to_string())
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum RecordSort {
+    Node,
+    Type,
+    Ttl,
+    Updated,
 }
 
-fn find_domain_id(dynu_client: &DynuClient, domain: &str) -> Result<DomainDTO, SelfError> {
-    let body: DomainsDTO = dynu_client.get_domains()?;
-    let maybe_domain = body.domains.into_iter().find(|d| d.name == domain);
-    if maybe_domain.is_none() {
-        return Err(SelfError::MsgError(format!(
-            "domain={} cannot be found in dynu",
-            domain
-        )));
-    }
-    Ok(maybe_domain.unwrap())
+#[derive(Args, Debug)]
+struct RecordOrder {
+    #[arg(long, value_enum, help = "Sort the records by this column")]
+    sort: Option<RecordSort>,
+    #[arg(long, help = "Reverse the order of the records")]
+    reverse: bool,
 }
 
-fn refresh(dynu_client: DynuClient, domain: &str) -> Result<(), SelfError> {
-    let ipv4 = ip(IP::V4);
-    let ipv6 = ip(IP::V6);
-    eprintln!(
-        "detected ipv4='{}', ipv6='{}'",
-        or_empty(&ipv4),
-        or_empty(&ipv6)
-    );
-    let resolved = public_ip_of(domain)?;
-    eprintln!(
-        "domain={}, resolved ipv4={}, resolved ipv6={}",
-        domain,
-        or_empty(&resolved.v4),
-        or_empty(&resolved.v6)
-    );
-    if resolved.v4 == ipv4 && resolved.v6 == ipv6 {
-        eprintln!("ips resolved(v4={}, v6={}) are identical to the current ones(v4={}, v6={}), not updating domain={}",
-                  or_empty(&resolved.v4), or_empty(&resolved.v6), or_empty(&ipv4), or_empty(&ipv6), domain);
-        return Ok(());
+impl RecordOrder {
+    // The sort is stable, so records with the same key keep the order of dynu.
+    fn apply(&self, records: &mut [RecordDTO]) {
+        match self.sort {
+            Some(RecordSort::Node) => records.sort_by(|a, b| a.node_name().cmp(b.node_name())),
+            Some(RecordSort::Type) => records.sort_by(|a, b| a.record_type().cmp(b.record_type())),
+            Some(RecordSort::Ttl) => records.sort_by_key(|r| r.ttl()),
+            Some(RecordSort::Updated) => {
+                records.sort_by(|a, b| a.updated_on().cmp(&b.updated_on()))
+            }
+            None => {}
+        }
+        if self.reverse {
+            records.reverse();
+        }
     }
-    eprintln!("ips resolved(v4={}, v6={}) are different from the registered ones(v4={}, v6={}), updating the record for domain={}",
-              or_empty(&resolved.v4), or_empty(&resolved.v6), or_empty(&ipv4), or_empty(&ipv6), domain);
-    let mut domain_dto = find_domain_id(&dynu_client, domain)?;
-    eprintln!("{:?}", &domain_dto);
-    domain_dto.ipv4 = ipv4.is_some();
-    domain_dto.ipv6 = ipv6.is_some();
-    domain_dto.ipv4_address = ipv4;
-    domain_dto.ipv6_address = ipv6;
-    dynu_client.update_domain(&domain_dto)?;
-    let result = dynu_client.get_domain(domain_dto.id.unwrap())?;
-    eprintln!("updated domain={:?}", &result);
-    Ok(())
 }
 
-fn txt_update(
-    dynu_client: DynuClient,
-    domain: &str,
-    name: &str,
-    value: &str,
-    ttl: u64,
-) -> Result<(), SelfError> {
-    let domain = find_domain_id(&dynu_client, domain)?;
-    let domain_id = domain.id.unwrap();
-    let records = dynu_client.get_records(domain_id)?;
-    let maybe_existing_record = records.dns_records.iter().find(|r| match r {
-        RecordDTO::TxtRecord { node_name, .. } => node_name == name,
-        _ => false,
-    });
-    if maybe_existing_record.is_none() {
-        eprintln!("{} record does not exist, defining a new one now", name);
-        let txt_record = RecordDTO::txt_record(name, value, ttl, None);
-        let id = dynu_client.create_record(domain_id, &txt_record)?;
-        eprintln!("created record with id={}", id);
-    } else {
-        let record_id = maybe_existing_record.unwrap().id().unwrap();
-        eprintln!(
-            "{} record already exists with id={}, updating it",
-            name, record_id
-        );
-        let txt_record = RecordDTO::txt_record(name, value, ttl, Some(record_id));
-        dynu_client.update_record(domain_id, &txt_record)?;
-        eprintln!("{} record updated", record_id)
-    }
-    Ok(())
+#[derive(Subcommand, Debug)]
+enum EnsureState {
+    #[command(about = "Ensure the host has exactly this address as A record")]
+    A {
+        #[arg(help = "Full hostname of the record")]
+        host: String,
+        address: Ipv4Addr,
+    },
+    #[command(about = "Ensure the host has a TXT record holding this value")]
+    Txt {
+        #[arg(help = "Full hostname of the record")]
+        host: String,
+        value: String,
+    },
+    #[command(about = "Ensure the host has no record of the type, or none holding the value")]
+    Absent {
+        #[arg(value_enum)]
+        record_type: RecordKind,
+        #[arg(help = "Full hostname of the record")]
+        host: String,
+        value: Option<String>,
+    },
 }
 
-fn txt_delete(dynu_client: DynuClient, domain_name: &str, name: &str) -> Result<(), SelfError> {
-    let domain = find_domain_id(&dynu_client, domain_name)?;
-    let domain_id = domain.id.unwrap();
-    let records = dynu_client.get_records(domain_id)?;
-    let maybe_existing_record = records.dns_records.iter().find(|r| match r {
-        RecordDTO::TxtRecord { node_name, .. } => node_name == name,
-        _ => false,
-    });
-    if maybe_existing_record.is_none() {
-        return Err(MsgError(format!(
-            "{} in domain {} does not exist",
-            name, domain_name
-        )));
-    }
-    let existing_record = maybe_existing_record.unwrap();
-    dynu_client.delete_record(domain_id, existing_record.id().unwrap())?;
-    eprintln!("{} in domain {} deleted", name, domain_name);
-    Ok(dynu_client.delete_record(domain_id, existing_record.id().unwrap())?)
+#[derive(Subcommand, Debug)]
+enum DomainAction {
+    #[command(about = "List the address updates dynu received for a domain")]
+    History {
+        #[arg(help = "Domain to list the updates of")]
+        domain: String,
+    },
+    #[command(about = "Add a domain to the dynu account and print its id")]
+    Add {
+        #[arg(help = "Domain to add, e.g. a new host under one of dynu's domains")]
+        name: String,
+        #[arg(long, help = "IPv4 address of the domain")]
+        ipv4: Option<Ipv4Addr>,
+        #[arg(long, help = "IPv6 address of the domain")]
+        ipv6: Option<Ipv6Addr>,
+        #[arg(long, default_value = "120", help = "TTL of the domain in seconds")]
+        ttl: u64,
+        #[arg(
+            long,
+            help = "Group of the domain, to update it with the other domains of the group"
+        )]
+        group: Option<String>,
+    },
+    #[command(about = "Remove a domain and all its records from the dynu account")]
+    Remove {
+        #[arg(help = "Domain to remove")]
+        name: String,
+        #[arg(
+            long,
+            help = "Show the domain and the records that would be removed without removing them"
+        )]
+        dry_run: bool,
+    },
 }
 
-fn main() -> Result<(), SelfError> {
-    let arguments = MainArguments::parse();
-    let api_key = get_api_key(&arguments)?;
-    let dynu_client = DynuClient::new(&api_key)?;
-    match arguments.cmd {
-        Commands::Refresh { domain } => refresh(dynu_client, &domain),
-        Commands::UpdateTxtRecord {
-            ttl,
-            name,
-            value,
-            domain,
-        } => txt_update(dynu_client, &domain, &name, &value, ttl),
-        Commands::DeleteTxtRecord { domain, name } => txt_delete(dynu_client, &domain, &name),
+#[derive(Subcommand, Debug)]
+enum GroupAction {
+    #[command(about = "List the groups of the dynu account with their domains")]
+    List,
+    #[command(
+        about = "Set the addresses of every domain of a group, the public ip of the system when none is given"
+    )]
+    Update {
+        #[arg(help = "Group to update")]
+        name: String,
+        #[arg(long, help = "IPv4 address of the domains of the group")]
+        ipv4: Option<Ipv4Addr>,
+        #[arg(long, help = "IPv6 address of the domains of the group")]
+        ipv6: Option<Ipv6Addr>,
+        #[arg(
+            long,
+            help = "Show the changes that would be sent to dynu without updating the domains"
+        )]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum BackupAction {
+    #[command(about = "Write a snapshot of the settings and the records of dynu domains")]
+    Take {
+        #[arg(help = "Domains to back up, every domain of the dynu account when missing")]
+        domains: Vec<String>,
+        #[arg(
+            long,
+            help = "File to write the snapshot to, the standard output when missing"
+        )]
+        output: Option<String>,
+    },
+    #[command(about = "Show what changed between two snapshots")]
+    Diff {
+        #[arg(help = "Older snapshot")]
+        old: String,
+        #[arg(help = "Newer snapshot")]
+        new: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum DomainsAction {
+    #[command(
+        about = "List the domains of the dynu account with their id, addresses, TTL and state"
+    )]
+    List {
+        #[arg(
+            long,
+            help = "Show the domains cached by the last online listing instead of querying dynu"
+        )]
+        cached: bool,
+    },
+    #[command(about = "Show every field dynu stores for a domain of the account")]
+    Get {
+        #[arg(help = "Domain to show")]
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum RecordsAction {
+    #[command(about = "Delete the duplicate records of a dynu domain, keeping the oldest one")]
+    Dedupe {
+        #[arg(help = "Domain to deduplicate the records of")]
+        domain: String,
+        #[arg(
+            long,
+            help = "Show the records that would be deleted without deleting them"
+        )]
+        dry_run: bool,
+    },
+    #[command(
+        name = "set-ttl",
+        about = "Rewrite the TTL of the records of a dynu domain, e.g. lowering them before a planned address change"
+    )]
+    SetTtl {
+        #[arg(help = "Domain to rewrite the TTL of the records of")]
+        domain: String,
+        #[arg(long = "type", help = "Only rewrite the records of this type")]
+        record_type: Option<String>,
+        #[arg(
+            long,
+            conflicts_with_all = ["min", "max"],
+            help = "Set the TTL of every matching record"
+        )]
+        ttl: Option<u64>,
+        #[arg(long, help = "Raise the TTLs lower than this")]
+        min: Option<u64>,
+        #[arg(long, help = "Lower the TTLs higher than this")]
+        max: Option<u64>,
+        #[arg(
+            long,
+            help = "Show the records that would be rewritten without rewriting them"
+        )]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum RecordAction {
+    #[command(about = "List the records of a dynu domain, of every type")]
+    List {
+        #[arg(help = "Domain to list the records of")]
+        domain: String,
+        #[arg(long = "type", help = "Only list the records of this type")]
+        record_type: Option<String>,
+    },
+    #[command(about = "Show a record of a dynu domain")]
+    Get {
+        #[arg(help = "Domain of the record")]
+        domain: String,
+        #[arg(help = "Id of the record, as listed by record list")]
+        id: u64,
+    },
+    #[command(about = "Create a record in a dynu domain, printing its id")]
+    Create {
+        #[arg(help = "Domain to create the record in")]
+        domain: String,
+        #[arg(
+            long = "type",
+            help = "Type of the record, e.g. AAAA, CNAME, MX or SRV"
+        )]
+        record_type: String,
+        #[arg(
+            long,
+            default_value = "",
+            help = "Node of the record, the domain itself when missing"
+        )]
+        node: String,
+        #[arg(long, default_value_t = 300, help = "TTL of the record")]
+        ttl: u64,
+        #[command(flatten)]
+        fields: RecordFields,
+    },
+    #[command(about = "Change a record of a dynu domain, keeping the fields not given")]
+    Update {
+        #[arg(help = "Domain of the record")]
+        domain: String,
+        #[arg(help = "Id of the record, as listed by record list")]
+        id: u64,
+        #[arg(long, help = "New node of the record")]
+        node: Option<String>,
+        #[arg(long, help = "New TTL of the record")]
+        ttl: Option<u64>,
+        #[command(flatten)]
+        fields: RecordFields,
+    },
+    #[command(about = "Delete a record of a dynu domain")]
+    Delete {
+        #[arg(help = "Domain of the record")]
+        domain: String,
+        #[arg(help = "Id of the record, as listed by record list")]
+        id: u64,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AcmeAction {
+    #[command(
+        about = "Delete the _acme-challenge TXT records left behind by failed issuances in every domain"
+    )]
+    Cleanup {
+        #[arg(
+            long,
+            default_value = "24h",
+            help = "Delete the challenge records last updated longer ago than this"
+        )]
+        older_than: humantime::Duration,
+        #[arg(
+            long,
+            help = "Show the records that would be deleted without deleting them"
+        )]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    #[command(about = "Rewrite the configuration file in the current format version")]
+    Migrate,
+}
+
+#[derive(Subcommand, Debug)]
+enum ServeMode {
+    #[command(about = "Serve the cert-manager DNS-01 webhook solver API")]
+    #[command(name = "cert-manager")]
+    CertManager {
+        #[arg(long, help = "Address to listen on", default_value = "0.0.0.0:8443")]
+        listen: String,
+        #[arg(
+            long,
+            env = "GROUP_NAME",
+            help = "API group name the solver is registered with in the issuers"
+        )]
+        group_name: String,
+        #[arg(
+            long,
+            help = "Solver name referenced by the issuers",
+            default_value = "dynu"
+        )]
+        solver_name: String,
+        #[command(flatten)]
+        server: ServerArgs,
+    },
+
+    #[command(about = "Translate RFC 2136 DNS UPDATE messages into dynu record operations")]
+    Rfc2136 {
+        #[arg(
+            long,
+            help = "Address to listen on over UDP and TCP",
+            default_value = "127.0.0.1:5353"
+        )]
+        listen: String,
+        #[arg(
+            long,
+            help = "TSIG key required to sign updates as [algorithm:]name:secret, used with priority over the DYNU_TSIG_KEY and DYNU_TSIG_KEY_FILE environment variables"
+        )]
+        tsig_key: Option<String>,
+    },
+
+    #[command(
+        about = "Refresh domains periodically, serving a dashboard of their addresses and of the recent events"
+    )]
+    Dashboard {
+        #[arg(help = "Domains to refresh", required_unless_present = "all")]
+        domains: Vec<String>,
+        #[arg(
+            long,
+            conflicts_with = "domains",
+            help = "Refresh every domain of the dynu account"
+        )]
+        all: bool,
+        #[arg(long, help = "Address to listen on", default_value = "127.0.0.1:8080")]
+        listen: String,
+        #[arg(long, help = "Time between refreshes", default_value = "5m")]
+        interval: humantime::Duration,
+        #[arg(
+            long,
+            help = "Also refresh as soon as the network configuration of the host changes, only supported on macOS"
+        )]
+        watch_network: bool,
+        #[arg(
+            long,
+            help = "Minimum time between two refreshes of a domain requested through /trigger",
+            default_value = "1m"
+        )]
+        trigger_interval: humantime::Duration,
+        #[command(flatten)]
+        server: ServerArgs,
+    },
+}
+
+#[derive(Args, Debug)]
+struct ServerArgs {
+    #[arg(
+        long,
+        help = "PEM certificate to serve TLS with",
+        requires = "tls_key",
+        conflicts_with = "tls_self_signed"
+    )]
+    tls_cert: Option<String>,
+    #[arg(
+        long,
+        help = "PEM private key to serve TLS with",
+        requires = "tls_cert"
+    )]
+    tls_key: Option<String>,
+    #[arg(
+        long,
+        help = "Serve TLS with a self-signed certificate generated at startup"
+    )]
+    tls_self_signed: bool,
+    #[arg(
+        long,
+        help = "Token required as bearer token, used with priority over the DYNU_SERVER_TOKEN and DYNU_SERVER_TOKEN_FILE environment variables"
+    )]
+    auth_token: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "auth_token",
+        help = "user:password required with HTTP basic authentication, used with priority over the DYNU_SERVER_BASIC_AUTH and DYNU_SERVER_BASIC_AUTH_FILE environment variables"
+    )]
+    basic_auth: Option<String>,
+}
+
+fn tsig_key(argument: Option<String>) -> Result<Option<TsigKey>, SelfError> {
+    let spec = match argument {
+        Some(value) => Some(value),
+        None => config::secret_env(TSIG_KEY_NAME)?,
+    };
+    spec.map(|s| TsigKey::parse(&s)).transpose()
+}
+
+// Arguments win over the environment, where a token wins over basic
+// authentication credentials.
+fn server_auth(args: &ServerArgs) -> Result<Option<Auth>, SelfError> {
+    if let Some(token) = &args.auth_token {
+        return Ok(Some(Auth::Token(token.clone())));
+    }
+    if let Some(credentials) = &args.basic_auth {
+        return Auth::basic(credentials).map(Some);
+    }
+    if let Some(token) = config::secret_env(SERVER_TOKEN_NAME)? {
+        return Ok(Some(Auth::Token(token)));
+    }
+    config::secret_env(SERVER_BASIC_AUTH_NAME)?
+        .map(|credentials| Auth::basic(&credentials))
+        .transpose()
+}
+
+fn server_options(listen: String, args: ServerArgs) -> Result<ServerOptions, SelfError> {
+    let auth = server_auth(&args)?;
+    let tls = match (args.tls_cert, args.tls_key) {
+        (Some(certificate), Some(private_key)) => Some(Tls::Files(TlsFiles {
+            certificate,
+            private_key,
+        })),
+        _ if args.tls_self_signed => Some(Tls::SelfSigned),
+        _ => None,
+    };
+    Ok(ServerOptions { listen, tls, auth })
+}
+
+fn get_api_key(args: &MainArguments, config: &Config) -> Result<String, SelfError> {
+    if let Some(value) = &args.api_key {
+        return Ok(value.clone());
+    }
+    if let Some(value) = config::secret_env(API_KEY_NAME)? {
+        return Ok(value);
+    }
+    if let Some(value) = &config.api_key {
+        return Ok(value.clone());
+    }
+    if let Some(path) = &config.api_key_file {
+        return config::read_secret_file(path)
+            .map_err(|e| SelfError::MsgError(format!("cannot read api_key_file {}: {}", path, e)));
+    }
+    Err(SelfError::MsgError(format!(
+        "provide 'api-key' argument, define environment variable {} or {}_FILE, or set api_key in the configuration file",
+        API_KEY_NAME, API_KEY_NAME
+    )))
+}
+
+fn get_notifier(
+    args: &MainArguments,
+    config: &Config,
+    state: &StateDir,
+    recorder: Option<&Recorder>,
+) -> Result<Notifier, SelfError> {
+    let url = match &args.webhook_url {
+        Some(value) => Some(value.clone()),
+        None => config::secret_env(WEBHOOK_URL_NAME)?.or(config.webhook.url.clone()),
+    };
+    let template = args
+        .webhook_template
+        .as_deref()
+        .or(config.webhook.template.as_deref())
+        .map(notify::load_template)
+        .transpose()?;
+    let content_type = args
+        .webhook_content_type
+        .clone()
+        .or(config.webhook.content_type.clone())
+        .unwrap_or(DEFAULT_CONTENT_TYPE.to_string());
+    let webhooks = url
+        .map(|url| Webhook {
+            url,
+            template,
+            content_type,
+        })
+        .into_iter()
+        .collect();
+    let mut sinks = plugin_sinks();
+    let mqtt_url = match &args.mqtt_url {
+        Some(value) => Some(value.clone()),
+        None => config::secret_env(MQTT_URL_NAME)?.or(config.mqtt.url.clone()),
+    };
+    if let Some(url) = mqtt_url {
+        let discovery_prefix = args
+            .mqtt_discovery_prefix
+            .as_deref()
+            .or(config.mqtt.discovery_prefix.as_deref())
+            .unwrap_or(mqtt::DEFAULT_DISCOVERY_PREFIX);
+        sinks.push(Box::new(Mqtt::parse(&url, discovery_prefix)?));
+    }
+    if let Some(recorder) = recorder {
+        sinks.push(Box::new(recorder.clone()));
+    }
+    sinks.push(Box::new(OwnWrites::new(state)));
+    Notifier::new(webhooks, sinks)
+}
+
+#[cfg(feature = "wasm")]
+fn plugin_sinks() -> Vec<Box<dyn Sink>> {
+    wasm::notifiers()
+}
+
+#[cfg(not(feature = "wasm"))]
+fn plugin_sinks() -> Vec<Box<dyn Sink>> {
+    Vec::new()
+}
+
+fn or_empty(option: &Option<String>) -> String {
+    option
+        .as_ref()
+        .map(|s| s.to_string())
+        .unwrap_or("".to_string())
+}
+
+fn find_domain_id(dynu_client: &DynuClient, domain: &str) -> Result<DomainDTO, SelfError> {
+    let normalised = normalise_fqdn(domain);
+    let ascii = to_ascii(&normalised)?;
+    let body: DomainsDTO = dynu_client.get_domains()?;
+    let suggestion = did_you_mean(domain, body.domains.iter().map(|d| d.name.as_str()));
+    body.domains
+        .into_iter()
+        .find(|d| same_name(&d.name, &ascii) || same_name(&d.unicode_name, &normalised))
+        .ok_or_else(|| {
+            SelfError::MsgError(format!(
+                "domain={} cannot be found in dynu{}",
+                domain, suggestion
+            ))
+        })
+}
+
+// The TXT value given with --value, --value-file or --value-stdin. Values
+// read from files or the standard input lose the trailing newline editors and
+// echo add, like secrets read from files.
+fn txt_value(
+    value: Option<String>,
+    value_file: Option<&str>,
+    value_stdin: bool,
+) -> Result<String, SelfError> {
+    if let Some(path) = value_file {
+        return config::read_secret_file(path)
+            .map_err(|e| MsgError(format!("cannot read value from {}: {}", path, e)));
+    }
+    if value_stdin {
+        let value = io::read_to_string(io::stdin())?;
+        return Ok(value.trim_end_matches(['\r', '\n']).to_string());
+    }
+    value.ok_or_else(|| MsgError("no value given".to_string()))
+}
+
+// Returns whether the records changed, or would change with check.
+fn ensure(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    host: &str,
+    desired: &Desired,
+    ttl: Option<u64>,
+    check: bool,
+    cache: Option<&ZoneCache>,
+) -> Result<bool, SelfError> {
+    if let Some(ttl) = ttl {
+        check_ttl(dynu_client, ttl)?;
+    }
+    let (domain, node) = acme::find_zone(dynu_client, host, None)?;
+    check_node(&node)?;
+    if node.is_empty() && matches!(desired, Desired::Address(_)) {
+        return Err(MsgError(format!(
+            "the address of domain={} itself is not a record, set it with refresh",
+            domain.name
+        )));
+    }
+    let domain_id = domain.require_id()?;
+    let records = match cache
+        .map(|c| c.current_records(&domain.name, domain.updated_on.as_deref()))
+        .transpose()?
+        .flatten()
+    {
+        Some(records) => records,
+        None => {
+            let records = dynu_client.get_records(domain_id)?.dns_records;
+            if let Some(cache) = cache {
+                warn_on_cache_failure(cache.store_records(
+                    &domain.name,
+                    domain.updated_on.clone(),
+                    records.clone(),
+                ));
+            }
+            records
+        }
+    };
+    let steps = ensure::plan(&records, &node, desired, ttl);
+    for step in &steps {
+        eprintln!(
+            "domain={} {}{}",
+            domain.name,
+            step.describe(),
+            if check { " (check)" } else { "" }
+        );
+    }
+    if !check && !steps.is_empty() {
+        if let Some(cache) = cache {
+            warn_on_cache_failure(cache.forget_records(&domain.name));
+        }
+        ensure::apply(dynu_client, domain_id, &steps)
+            .map_err(|e| constraints::explain(e, ttl, None))?;
+        for step in &steps {
+            let (record_type, old, new) = match step {
+                ensure::Step::Create(new) => (new.record_type(), None, Some(new.value())),
+                ensure::Step::Update(old, new) => {
+                    (new.record_type(), Some(old.value()), Some(new.value()))
+                }
+                ensure::Step::Delete(old) => (old.record_type(), Some(old.value()), None),
+            };
+            notifier.notify(
+                &Event::new("ensure", Status::Updated, &domain.name)
+                    .node(&node)
+                    .record_type(record_type)
+                    .values(old, new),
+            );
+        }
+    }
+    Ok(!steps.is_empty())
+}
+
+fn domain_or_default(domain: Option<String>, config: &Config) -> Result<String, SelfError> {
+    domain.or(config.defaults.domain.clone()).ok_or_else(|| {
+        MsgError("no domain given and no default domain in the configuration".to_string())
+    })
+}
+
+fn host_or_default(
+    domain: Option<String>,
+    name: Option<String>,
+    config: &Config,
+) -> Result<(String, Option<String>), SelfError> {
+    config.defaults.host(domain, name).ok_or_else(|| {
+        MsgError("no domain given and no default domain in the configuration".to_string())
+    })
+}
+
+// Splits a full hostname into the dynu domain hosting it and its node name,
+// unless the node name is provided explicitly.
+fn split_host(
+    dynu_client: &DynuClient,
+    domain: &str,
+    name: Option<&str>,
+) -> Result<(String, String), SelfError> {
+    if let Some(name) = name {
+        check_node(name)?;
+        return Ok((domain.to_string(), name.to_string()));
+    }
+    let (zone, node) = acme::find_zone(dynu_client, domain, None)?;
+    check_node(&node)?;
+    eprintln!(
+        "hostname={} resolved to node='{}' in domain={}",
+        domain, node, zone.name
+    );
+    Ok((zone.name, node))
+}
+
+fn unique_node(
+    (domain, name): (String, String),
+    unique_suffix: bool,
+) -> Result<(String, String), SelfError> {
+    if !unique_suffix {
+        return Ok((domain, name));
+    }
+    let name = names::with_suffix(&name, &names::local_hostname()?)?;
+    eprintln!("using node='{}' unique to this host", name);
+    Ok((domain, name))
+}
+
+fn is_unavailable(error: &SelfError) -> bool {
+    matches!(error, SelfError::ClientError(e) if e.is_unavailable())
+}
+
+fn is_rate_limited(error: &SelfError) -> bool {
+    matches!(error, SelfError::ClientError(e) if e.is_rate_limited())
+}
+
+// Loads the offline queue, dropping the changes superseded by the one about to
+// be executed and retrying the others. The queue is best effort: when the state
+// directory is not usable, operations run as if it didn't exist. A read-only
+// client leaves the queue untouched, its writes failing without reaching Dynu.
+fn prepare_queue<'a>(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    state: &'a StateDir,
+    superseded_by: &PendingChange,
+) -> Option<Queue<'a>> {
+    let prepared = Queue::prepare(state, dynu_client.is_read_only(), superseded_by, |change| {
+        let result = match change {
+            PendingChange::DomainAddresses { domain, ipv4, ipv6 } => update_domain_addresses(
+                dynu_client,
+                domain,
+                ipv4.clone(),
+                ipv6.clone(),
+                &[],
+                false,
+                &UpdateOptions::default(),
+            )
+            .map(|_| ()),
+            PendingChange::TxtValue {
+                domain,
+                node,
+                value,
+                ttl,
+            } => txt_update(dynu_client, notifier, domain, node, value, *ttl),
+        };
+        match result {
+            Ok(()) => Ok(ReplayOutcome::Applied),
+            Err(e) if is_unavailable(&e) => Ok(ReplayOutcome::Unavailable),
+            Err(e) => Err(e),
+        }
+    });
+    match prepared {
+        Ok(queue) => queue,
+        Err(e) => {
+            eprintln!("offline queue disabled: {}", e);
+            None
+        }
+    }
+}
+
+fn enqueue_if_unavailable<T>(
+    queue: Option<Queue>,
+    change: PendingChange,
+    result: Result<T, SelfError>,
+) -> Result<T, SelfError> {
+    if let (Some(mut queue), Err(e)) = (queue, &result) {
+        if is_unavailable(e) {
+            match queue.push(change) {
+                Ok(()) => {
+                    eprintln!("dynu is unavailable, change queued to be retried on the next run")
+                }
+                Err(queue_error) => eprintln!("cannot queue change: {}", queue_error),
+            }
+        }
+    }
+    result
+}
+
+#[derive(Default, Clone)]
+struct UpdateOptions {
+    verbose: bool,
+    dry_run: bool,
+    ttl: Option<u64>,
+    group: Option<String>,
+    reachability: Option<Reachability>,
+    max_age: Option<Duration>,
+    ip_source: String,
+    drift: Option<DriftAction>,
+}
+
+// Fields which dynu sets by itself on every update.
+const SERVER_MANAGED_FIELDS: [&str; 1] = ["updatedOn"];
+
+// Only the address related fields, and the TTL and group when requested, are
+// changed: everything else is sent back as fetched and checked after the write
+// for changes dynu may have applied on its own. The addresses of the families
+// in keep are left as they are. Unless forced, nothing is sent when the domain
+// is already up to date. Returns whether the domain was sent to dynu.
+fn update_domain_addresses(
+    dynu_client: &DynuClient,
+    domain: &str,
+    ipv4: Option<String>,
+    ipv6: Option<String>,
+    keep: &[Family],
+    force: bool,
+    options: &UpdateOptions,
+) -> Result<bool, SelfError> {
+    let current = find_domain_id(dynu_client, domain)?;
+    eprintln!("{:?}", &current);
+    let mut domain_dto = current.clone();
+    if !keep.contains(&Family::Ipv4) {
+        domain_dto.ipv4 = ipv4.is_some();
+        domain_dto.ipv4_address = ipv4;
+    }
+    if !keep.contains(&Family::Ipv6) {
+        domain_dto.ipv6 = ipv6.is_some();
+        domain_dto.ipv6_address = ipv6;
+    }
+    if let Some(ttl) = options.ttl {
+        domain_dto.ttl = ttl;
+    }
+    if let Some(group) = &options.group {
+        domain_dto.group = group.clone();
+    }
+    let changes = field_diff(&current, &domain_dto);
+    if changes.is_empty() && !force {
+        eprintln!("domain={} is already up to date, not updating it", domain);
+        return Ok(false);
+    }
+    if options.verbose || options.dry_run {
+        for (field, old, new) in &changes {
+            eprintln!("domain={} {}: {} -> {}", domain, field, old, new);
+        }
+    }
+    if options.dry_run {
+        eprintln!("dry run, not updating domain={}", domain);
+        return Ok(false);
+    }
+    dynu_client.update_domain(&domain_dto)?;
+    let result = dynu_client.get_domain(domain_dto.require_id()?)?;
+    eprintln!("updated domain={:?}", &result);
+    if let Some(updated) = result {
+        for (field, expected, actual) in field_diff(&domain_dto, &updated) {
+            if !SERVER_MANAGED_FIELDS.contains(&field.as_str()) {
+                eprintln!(
+                    "warning: dynu changed field {} of domain={} on its own, sent {} but stored {}",
+                    field, domain, expected, actual
+                );
+            }
+        }
+    }
+    Ok(true)
+}
+
+fn ip_source_timeout(
+    arguments: &MainArguments,
+    config: &Config,
+) -> Result<Option<Duration>, SelfError> {
+    if let Some(timeout) = arguments.ip_source_timeout {
+        return Ok(Some(timeout.into()));
+    }
+    config
+        .ip_source_timeout
+        .as_ref()
+        .map(|timeout| {
+            humantime::parse_duration(timeout)
+                .map_err(|e| MsgError(format!("invalid ip_source_timeout {}: {}", timeout, e)))
+        })
+        .transpose()
+}
+
+fn detect_addresses(source: &str) -> Result<Addresses, SelfError> {
+    let detected = ipsource::detect(source)?;
+    eprintln!(
+        "detected with source={} ipv4='{}', ipv6='{}'",
+        source,
+        or_empty(&detected.v4),
+        or_empty(&detected.v6)
+    );
+    Ok(detected)
+}
+
+const FEATURES: [(&str, bool); 3] = [
+    ("native-tls", cfg!(feature = "native-tls")),
+    ("rustls", cfg!(feature = "rustls")),
+    ("wasm", cfg!(feature = "wasm")),
+];
+
+// reqwest prefers native-tls when both backends are enabled, the servers
+// always use rustls.
+const CLIENT_TLS: &str = if cfg!(feature = "native-tls") {
+    "native-tls"
+} else {
+    "rustls"
+};
+
+fn version(verbose: bool) -> Result<(), SelfError> {
+    let mut lines = vec![format!("dynuupdater {}", env!("CARGO_PKG_VERSION"))];
+    if verbose {
+        let features: Vec<&str> = FEATURES
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| *name)
+            .collect();
+        lines.push(format!("commit: {}", env!("DYNUUPDATER_GIT_COMMIT")));
+        lines.push(format!("build date: {}", env!("DYNUUPDATER_BUILD_DATE")));
+        lines.push(format!("target: {}", env!("DYNUUPDATER_TARGET")));
+        lines.push(format!("compiler: {}", env!("DYNUUPDATER_RUSTC")));
+        lines.push(format!("features: {}", features.join(", ")));
+        lines.push(format!(
+            "tls backend: {} (client), rustls (server)",
+            CLIENT_TLS
+        ));
+    }
+    print_text(&format!("{}\n", lines.join("\n")));
+    Ok(())
+}
+
+fn ip_sources() -> Result<(), SelfError> {
+    let mut table = Table::new(vec!["NAME", "PATH"]);
+    table.push(vec![ipsource::BUILTIN.to_string(), "(builtin)".to_string()]);
+    #[cfg(feature = "wasm")]
+    for (name, path) in wasm::ip_sources() {
+        table.push(vec![name, path.display().to_string()]);
+    }
+    for (name, path) in ipsource::discover() {
+        table.push(vec![name, path.display().to_string()]);
+    }
+    print_text(&format!("{}\n", table.render()));
+    Ok(())
+}
+
+// Whether the last update of domain, as remembered in the state directory or
+// reported by dynu, is older than max_age. Dynu is only asked when the state
+// directory doesn't know of a recent enough update.
+fn is_stale(
+    dynu_client: &DynuClient,
+    state: &StateDir,
+    domain: &str,
+    max_age: Duration,
+) -> Result<bool, SelfError> {
+    let now = SystemTime::now();
+    let remembered = LastUpdates::new(state).get(domain).unwrap_or_else(|e| {
+        eprintln!("cannot read the last updates: {}", e);
+        None
+    });
+    if !staleness::is_older(remembered, now, max_age) {
+        return Ok(false);
+    }
+    let current = find_domain_id(dynu_client, domain)?;
+    Ok(staleness::is_older(
+        staleness::updated_at(&current),
+        now,
+        max_age,
+    ))
+}
+
+fn refresh(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    state: &StateDir,
+    domain: &str,
+    detected: &Addresses,
+    options: &UpdateOptions,
+) -> Result<ChangeSet, SelfError> {
+    let resolved = public_ip_of(&to_ascii(domain)?)?;
+    let mut change_set = ChangeSet::new(domain, resolved, detected);
+    let change = PendingChange::DomainAddresses {
+        domain: domain.to_string(),
+        ipv4: detected.v4.clone(),
+        ipv6: detected.v6.clone(),
+    };
+    // a dry run must neither replay nor queue changes
+    let queue = if options.dry_run {
+        None
+    } else {
+        prepare_queue(dynu_client, notifier, state, &change)
+    };
+    eprintln!("{}", change_set);
+    let overrides = options.ttl.is_some() || options.group.is_some();
+    if change_set.is_unchanged() && !overrides {
+        let stale = match options.max_age {
+            Some(max_age) => is_stale(dynu_client, state, domain, max_age)?.then_some(max_age),
+            None => None,
+        };
+        let Some(max_age) = stale else {
+            eprintln!("not updating domain={}", domain);
+            return Ok(change_set);
+        };
+        eprintln!(
+            "domain={} not updated for more than {}, updating it anyway",
+            domain,
+            humantime::format_duration(max_age)
+        );
+        change_set.forced = true;
+    }
+    // a family whose address cannot be published is left as it is while the
+    // other one is still updated, the run only fails when neither can be
+    if let Some(reachability) = &options.reachability {
+        let mut failure = None;
+        let mut failed = Vec::new();
+        for change in &change_set.changes {
+            if let (Action::Add | Action::Update, Some(address)) = (change.action, &change.new) {
+                if let Err(e) = reachability.verify(address) {
+                    eprintln!(
+                        "not updating {} of domain={}: {}",
+                        change.family.record_type(),
+                        domain,
+                        e
+                    );
+                    failed.push((change.family, format!("{}", e)));
+                    failure.get_or_insert(e);
+                }
+            }
+        }
+        for (family, error) in failed {
+            change_set.fail(family, error);
+        }
+        if let Some(e) = failure {
+            if !change_set.has_applicable() {
+                return Err(e);
+            }
+        }
+    }
+    let keep = change_set.failed_families();
+    // queued changes carry whole domains, the kept families get the address
+    // they currently resolve to
+    let address = |family: Family, detected: &Option<String>| match change_set
+        .changes
+        .iter()
+        .find(|c| c.family == family && c.error.is_some())
+    {
+        Some(kept) => kept.old.clone(),
+        None => detected.clone(),
+    };
+    let change = PendingChange::DomainAddresses {
+        domain: domain.to_string(),
+        ipv4: address(Family::Ipv4, &detected.v4),
+        ipv6: address(Family::Ipv6, &detected.v6),
+    };
+    let result = update_domain_addresses(
+        dynu_client,
+        domain,
+        detected.v4.clone(),
+        detected.v6.clone(),
+        &keep,
+        change_set.forced,
+        options,
+    );
+    if options.dry_run {
+        return result.map(|_| change_set);
+    }
+    if enqueue_if_unavailable(queue, change, result)? {
+        warn_on_state_failure(LastUpdates::new(state).record(domain, SystemTime::now()));
+    }
+    change_set.applied = true;
+    for event in change_set.events() {
+        notifier.notify(&event);
+    }
+    Ok(change_set)
+}
+
+// Sends the domain back as it is, which dynu still counts as an update.
+fn touch(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    state: &StateDir,
+    domain: &str,
+) -> Result<(), SelfError> {
+    update_domain_addresses(
+        dynu_client,
+        domain,
+        None,
+        None,
+        &[Family::Ipv4, Family::Ipv6],
+        true,
+        &UpdateOptions::default(),
+    )?;
+    warn_on_state_failure(LastUpdates::new(state).record(domain, SystemTime::now()));
+    notifier.notify(&Event::new("touch", Status::Updated, domain));
+    Ok(())
+}
+
+// Domains refreshed together, with the settings declared for them in the
+// configuration: the given names, every domain of the dynu account with all,
+// or else the declared domains.
+struct Managed {
+    all: bool,
+    names: Vec<String>,
+    declared: BTreeMap<String, DomainConfig>,
+}
+
+impl Managed {
+    fn new(names: Vec<String>, all: bool, config: &Config) -> Managed {
+        Managed {
+            all,
+            names,
+            declared: config.domains.clone(),
+        }
+    }
+
+    fn settings(&self, domain: &str) -> DomainConfig {
+        let domain = normalise_fqdn(domain);
+        self.declared
+            .iter()
+            .find(|(name, _)| normalise_fqdn(name) == domain)
+            .map(|(_, settings)| settings.clone())
+            .unwrap_or_default()
+    }
+
+    fn resolve(
+        &self,
+        dynu_client: &DynuClient,
+        only: Option<&[String]>,
+    ) -> Result<Vec<(String, DomainConfig)>, SelfError> {
+        let names = if let Some(only) = only {
+            only.to_vec()
+        } else if self.all {
+            dynu_client
+                .get_domains()?
+                .domains
+                .into_iter()
+                .map(|d| d.name)
+                .collect()
+        } else if !self.names.is_empty() {
+            self.names.clone()
+        } else {
+            self.declared.keys().cloned().collect()
+        };
+        Ok(names
+            .into_iter()
+            .map(|name| {
+                let settings = self.settings(&name);
+                (name, settings)
+            })
+            .collect())
+    }
+}
+
+// Refreshes a domain with the TTL declared for it, and brings its declared TXT
+// records to their values.
+fn refresh_managed(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    state: &StateDir,
+    domain: &str,
+    settings: &DomainConfig,
+    detected: &Addresses,
+    options: &UpdateOptions,
+) -> Result<ChangeSet, SelfError> {
+    let options = UpdateOptions {
+        ttl: settings.ttl.or(options.ttl),
+        ..options.clone()
+    };
+    if let Some(ttl) = settings.ttl {
+        check_ttl(dynu_client, ttl)?;
+    }
+    if let Some(duration) = lease_duration(domain, settings)? {
+        if !acquire_lease(dynu_client, notifier, domain, duration, options.dry_run)? {
+            // the holder changes the domain in the meantime, so the snapshot
+            // would be outdated once this host takes the lease over
+            if let Err(e) = Snapshots::new(state).forget(domain) {
+                eprintln!("cannot forget the snapshot of domain={}: {}", domain, e);
+            }
+            let resolved = public_ip_of(&to_ascii(domain)?)?;
+            return Ok(ChangeSet::new(domain, resolved, detected));
+        }
+    }
+    if let Some(action) = options.drift {
+        if let Err(e) = report_drift(dynu_client, notifier, state, domain, action) {
+            eprintln!("cannot check domain={} for changes: {}", domain, e);
+        }
+    }
+    let change_set = refresh(dynu_client, notifier, state, domain, detected, &options)?;
+    for (node, value) in &settings.txt {
+        let host = if node.is_empty() || node == "@" {
+            domain.to_string()
+        } else {
+            format!("{}.{}", node, domain)
+        };
+        ensure(
+            dynu_client,
+            notifier,
+            &host,
+            &Desired::Text(value.clone()),
+            None,
+            options.dry_run,
+            None,
+        )?;
+    }
+    if settings.metadata && change_set.applied {
+        if let Err(e) = publish_metadata(dynu_client, notifier, domain, &options.ip_source) {
+            eprintln!("cannot publish the metadata of domain={}: {}", domain, e);
+        }
+    }
+    if options.drift.is_some() && !options.dry_run {
+        let snapshot =
+            take_backup(dynu_client, &[domain.to_string()], false).and_then(|mut backup| {
+                match backup.zones.pop() {
+                    Some(zone) => Snapshots::new(state).store(zone),
+                    None => Ok(()),
+                }
+            });
+        if let Err(e) = snapshot {
+            eprintln!("cannot snapshot domain={}: {}", domain, e);
+        }
+    }
+    Ok(change_set)
+}
+
+// Compares the domain and its records with the snapshot the last refresh left
+// them in. The first refresh has nothing to compare with.
+fn report_drift(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    state: &StateDir,
+    domain: &str,
+    action: DriftAction,
+) -> Result<(), SelfError> {
+    let Some(zone) = take_backup(dynu_client, &[domain.to_string()], false)?
+        .zones
+        .pop()
+    else {
+        return Ok(());
+    };
+    for difference in Snapshots::new(state).drift(&zone)?.unwrap_or_default() {
+        eprintln!("{}", drift::describe(&difference));
+        if action == DriftAction::Notify {
+            notifier.notify(&drift::event(&difference));
+        }
+    }
+    Ok(())
+}
+
+fn current_lease(dynu_client: &DynuClient, domain: &str) -> Result<Option<Lease>, SelfError> {
+    let domain = find_domain_id(dynu_client, domain)?;
+    let records = dynu_client.get_records(domain.require_id()?)?.dns_records;
+    Ok(records
+        .iter()
+        .find(|r| r.record_type() == "TXT" && same_name(r.node_name(), lease::NODE))
+        .and_then(|r| Lease::parse(&r.value())))
+}
+
+fn lease_duration(domain: &str, settings: &DomainConfig) -> Result<Option<Duration>, SelfError> {
+    settings
+        .lease
+        .as_ref()
+        .map(|lease| {
+            humantime::parse_duration(lease).map_err(|e| {
+                MsgError(format!(
+                    "invalid lease {} of domain={}: {}",
+                    lease, domain, e
+                ))
+            })
+        })
+        .transpose()
+}
+
+// The holder renews the lease once per refresh, so a lease not outliving the
+// time between two refreshes lapses in between and the hosts keep taking it
+// over from each other.
+fn check_leases(config: &Config, interval: Duration) -> Result<(), SelfError> {
+    for (domain, settings) in &config.domains {
+        match lease_duration(domain, settings)? {
+            Some(duration) if duration <= interval => {
+                return Err(MsgError(format!(
+                    "lease {} of domain={} must be longer than the interval {} between refreshes",
+                    humantime::format_duration(duration),
+                    domain,
+                    humantime::format_duration(interval)
+                )))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// How long a host taking a lease waits before reading it back, so that the
+// lease written by another host taking it at the same time lands in between.
+const LEASE_SETTLE: Duration = Duration::from_secs(5);
+
+// Takes or renews the lease of the domain unless another host holds it,
+// returning whether this host may refresh the domain. Two hosts taking an
+// expired lease at once both write it: the lease is read back once the writes
+// have settled, the last write winning. A lease this host holds and renews
+// can't be taken concurrently, so it's read back at once. Renewals are not
+// notified, a lease taken over from another host is.
+fn acquire_lease(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    domain: &str,
+    duration: Duration,
+    dry_run: bool,
+) -> Result<bool, SelfError> {
+    let host = names::local_hostname()?;
+    let now = SystemTime::now();
+    let current = current_lease(dynu_client, domain)?;
+    if let Some(current) = current.as_ref().filter(|l| l.is_held_by_other(&host, now)) {
+        eprintln!(
+            "domain={} is leased to host={} until {}, not refreshing it",
+            domain,
+            current.holder,
+            humantime::format_rfc3339_seconds(current.expires)
+        );
+        return Ok(false);
+    }
+    if dry_run {
+        return Ok(true);
+    }
+    let lease = Lease::new(&host, now, duration);
+    txt_update(
+        dynu_client,
+        &Notifier::new(vec![], vec![])?,
+        domain,
+        lease::NODE,
+        &lease.value(),
+        ensure::DEFAULT_TTL,
+    )?;
+    if !current.as_ref().is_some_and(|l| l.is_held_by(&host, now)) {
+        thread::sleep(LEASE_SETTLE);
+    }
+    match current_lease(dynu_client, domain)? {
+        Some(acquired) if acquired.holder == host => {
+            let previous = current.map(|l| l.holder).filter(|holder| *holder != host);
+            if let Some(previous) = previous {
+                notifier.notify(
+                    &Event::new("lease", Status::Updated, domain)
+                        .node(lease::NODE)
+                        .values(Some(previous), Some(host)),
+                );
+            }
+            Ok(true)
+        }
+        acquired => {
+            eprintln!(
+                "domain={} was leased concurrently to host={}, not refreshing it",
+                domain,
+                acquired.map(|l| l.holder).unwrap_or_default()
+            );
+            Ok(false)
+        }
+    }
+}
+
+// Node whose TXT record tells when, from which host and with which IP source
+// a domain was last updated.
+const METADATA_NODE: &str = "_dynuupdater";
+
+fn publish_metadata(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    domain: &str,
+    ip_source: &str,
+) -> Result<(), SelfError> {
+    let value = format!(
+        "updated={} host={} source={}",
+        humantime::format_rfc3339_seconds(SystemTime::now()),
+        names::local_hostname()?,
+        ip_source
+    );
+    txt_update(
+        dynu_client,
+        notifier,
+        domain,
+        METADATA_NODE,
+        &value,
+        ensure::DEFAULT_TTL,
+    )
+}
+
+fn refresh_all(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    state: &StateDir,
+    managed: &Managed,
+    options: &UpdateOptions,
+    listing: &Listing,
+    ip_source: &str,
+) -> Result<Vec<String>, SelfError> {
+    let detected = detect_addresses(ip_source)?;
+    let domains = managed.resolve(dynu_client, None)?;
+    let progress = Progress::new(domains.len(), "refresh");
+    let mut results = Vec::new();
+    for (domain, settings) in &domains {
+        let result = progress.item(domain, || {
+            refresh_managed(
+                dynu_client,
+                notifier,
+                state,
+                domain,
+                settings,
+                &detected,
+                options,
+            )
+        });
+        let result = notifier.on_failure(result, "refresh", domain, None);
+        if let Err(e) = &result {
+            eprintln!("cannot refresh domain={}: {}", domain, e);
+        }
+        results.push(DomainResult::new(domain, result));
+    }
+    progress.finish();
+    listing.print(&results, || results_table(&results))?;
+    let with_outcome = |outcome: Outcome| -> Vec<String> {
+        results
+            .iter()
+            .filter(|r| r.outcome == outcome)
+            .map(|r| r.domain.clone())
+            .collect()
+    };
+    let failed = with_outcome(Outcome::Failed);
+    if !failed.is_empty() {
+        return Err(SelfError::MsgError(format!(
+            "refresh failed for {} of {} domains: {}",
+            failed.len(),
+            domains.len(),
+            failed.join(", ")
+        )));
+    }
+    Ok(with_outcome(Outcome::Partial))
+}
+
+// A refresh updating only some families of a domain exits with 3, or fails the
+// ansible report which still records the families that were updated.
+fn exit_if_partial(partial: Vec<String>, recorder: Option<&Recorder>) -> Result<(), SelfError> {
+    if partial.is_empty() {
+        return Ok(());
+    }
+    let message = format!(
+        "some address families were not updated for {}",
+        partial.join(", ")
+    );
+    if recorder.is_some() {
+        return Err(SelfError::MsgError(message));
+    }
+    eprintln!("{}", message);
+    apicalls::flush();
+    std::process::exit(3);
+}
+
+fn set_domain_ttl(dynu_client: &DynuClient, domain: &str, ttl: u64) -> Result<bool, SelfError> {
+    update_domain_addresses(
+        dynu_client,
+        domain,
+        None,
+        None,
+        &[Family::Ipv4, Family::Ipv6],
+        false,
+        &UpdateOptions {
+            ttl: Some(ttl),
+            ..Default::default()
+        },
+    )
+}
+
+// Switches the addresses of a domain so that resolvers pick up the new ones as
+// soon as they are published: the TTL is lowered and the old TTL waited out,
+// then the addresses are switched and checked on the authoritative servers
+// before the TTL is restored. When the switch fails the TTL is left lowered,
+// so that fixing it propagates quickly as well.
+#[allow(clippy::too_many_arguments)]
+fn migrate_ip(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    domain: &str,
+    ipv4: Option<String>,
+    ipv6: Option<String>,
+    low_ttl: u64,
+    verify_timeout: Duration,
+    resolvers: &[Resolver],
+    listing: &Listing,
+) -> Result<(), SelfError> {
+    let current = find_domain_id(dynu_client, domain)?;
+    let original_ttl = current.ttl;
+    let lowered = original_ttl > low_ttl;
+    if lowered {
+        eprintln!(
+            "domain={} lowering the TTL from {} to {}",
+            domain, original_ttl, low_ttl
+        );
+        set_domain_ttl(dynu_client, domain, low_ttl)?;
+        let expiry = Duration::from_secs(original_ttl);
+        eprintln!(
+            "domain={} waiting {} for the old TTL to expire",
+            domain,
+            humantime::format_duration(expiry)
+        );
+        thread::sleep(expiry);
+    }
+    let switched = [
+        (Family::Ipv4, TYPE_A, current.ipv4_address, ipv4),
+        (Family::Ipv6, TYPE_AAAA, current.ipv6_address, ipv6),
+    ];
+    let keep: Vec<Family> = switched
+        .iter()
+        .filter(|(_, _, _, new)| new.is_none())
+        .map(|(family, ..)| *family)
+        .collect();
+    update_domain_addresses(
+        dynu_client,
+        domain,
+        switched[0].3.clone(),
+        switched[1].3.clone(),
+        &keep,
+        false,
+        &UpdateOptions::default(),
+    )?;
+    let zone = to_ascii(&current.name)?;
+    for (family, qtype, old, new) in switched {
+        let Some(new) = new else {
+            continue;
+        };
+        notifier.notify(
+            &Event::new("migrate-ip", Status::Updated, domain)
+                .record_type(family.record_type())
+                .values(old, Some(new.clone())),
+        );
+        let verified =
+            propagation::wait_until_switched(resolvers, &zone, &zone, qtype, &new, verify_timeout)
+                .and_then(|verification| print_verification(&verification, listing));
+        if let Err(e) = verified {
+            if lowered {
+                eprintln!(
+                    "domain={} leaving the TTL at {}, restore it with refresh --ttl {} once fixed",
+                    domain, low_ttl, original_ttl
+                );
+            }
+            return Err(e);
+        }
+    }
+    if lowered {
+        eprintln!("domain={} restoring the TTL to {}", domain, original_ttl);
+        set_domain_ttl(dynu_client, domain, original_ttl)?;
+    }
+    Ok(())
+}
+
+// Resolvers given on the command line, the ones of the configuration
+// otherwise, the authoritative servers by default.
+fn verification_resolvers(
+    resolvers: Vec<Resolver>,
+    config: &Config,
+) -> Result<Vec<Resolver>, SelfError> {
+    if !resolvers.is_empty() {
+        return Ok(resolvers);
+    }
+    match &config.defaults.resolvers {
+        Some(resolvers) if !resolvers.is_empty() => resolvers
+            .iter()
+            .map(|resolver| resolver.parse().map_err(MsgError))
+            .collect(),
+        _ => Ok(vec![Resolver::Authoritative]),
+    }
+}
+
+// Prints what every server served, then fails if some hadn't settled.
+fn print_verification(verification: &Verification, listing: &Listing) -> Result<(), SelfError> {
+    listing.print(&verification.results, || verification.table())?;
+    verification.result()
+}
+
+fn failover_switch(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    options: &FailoverOptions,
+    target: Target,
+) -> Result<(), SelfError> {
+    let current = find_domain_id(dynu_client, &options.domain)?;
+    let address = options.address(target).to_string();
+    if current.ipv4_address.as_deref() == Some(&address) {
+        eprintln!(
+            "domain={} already points to {:?} address {}",
+            options.domain, target, address
+        );
+        return Ok(());
+    }
+    eprintln!(
+        "switching domain={} to {:?} address {}",
+        options.domain, target, address
+    );
+    // only the A record is switched, the AAAA record is kept as it is
+    update_domain_addresses(
+        dynu_client,
+        &options.domain,
+        Some(address.clone()),
+        current.ipv6_address.clone(),
+        &[],
+        false,
+        &UpdateOptions::default(),
+    )?;
+    notifier.notify(
+        &Event::new("failover", Status::Updated, &options.domain)
+            .record_type("A")
+            .values(current.ipv4_address, Some(address)),
+    );
+    Ok(())
+}
+
+// The monitor starts from the address the domain currently points to, so that
+// restarting it while on the backup doesn't switch back before the primary has
+// recovered.
+fn failover(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    options: &FailoverOptions,
+) -> Result<(), SelfError> {
+    let current = find_domain_id(dynu_client, &options.domain)?;
+    let initial = if current.ipv4_address.as_deref() == Some(&options.backup) {
+        Target::Backup
+    } else {
+        Target::Primary
+    };
+    failover::run(options, initial, |target| {
+        notifier.on_failure(
+            failover_switch(dynu_client, notifier, options, target),
+            "failover",
+            &options.domain,
+            None,
+        )
+    })
+}
+
+// One round of the dashboard: the public ip is detected once for all the
+// domains, like refresh --all.
+fn dashboard_round(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    state: &StateDir,
+    managed: &Managed,
+    options: &UpdateOptions,
+    ip_source: &str,
+    only: Option<&[String]>,
+) -> Result<dashboard::Round, SelfError> {
+    let detected = detect_addresses(ip_source)?;
+    Ok(managed
+        .resolve(dynu_client, only)?
+        .into_iter()
+        .map(|(domain, settings)| {
+            let result = notifier.on_failure(
+                refresh_managed(
+                    dynu_client,
+                    notifier,
+                    state,
+                    &domain,
+                    &settings,
+                    &detected,
+                    options,
+                ),
+                "refresh",
+                &domain,
+                None,
+            );
+            (domain, result)
+        })
+        .collect())
+}
+
+fn txt_update(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    domain: &str,
+    name: &str,
+    value: &str,
+    ttl: u64,
+) -> Result<(), SelfError> {
+    let domain = find_domain_id(dynu_client, domain)?;
+    let domain_id = domain.require_id()?;
+    let records = dynu_client.get_records(domain_id)?.dns_records;
+    match txt_write(dynu_client, notifier, &domain, &records, name, value, ttl) {
+        // a concurrent run created or changed the record after it was listed,
+        // the write is retried once against the current records
+        Err(SelfError::ClientError(e)) if e.is_conflict() => {
+            eprintln!(
+                "{} record changed concurrently ({}), retrying with the current records",
+                name, e
+            );
+            let records = dynu_client.get_records(domain_id)?.dns_records;
+            txt_write(dynu_client, notifier, &domain, &records, name, value, ttl)
+        }
+        result => result,
+    }
+}
+
+fn txt_write(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    domain: &DomainDTO,
+    records: &[RecordDTO],
+    name: &str,
+    value: &str,
+    ttl: u64,
+) -> Result<(), SelfError> {
+    let domain_id = domain.require_id()?;
+    let maybe_existing_record = records.iter().find(|r| match r {
+        RecordDTO::TxtRecord { node_name, .. } => same_name(node_name, name),
+        _ => false,
+    });
+    let event = Event::new("txt-update", Status::Updated, &domain.name)
+        .node(name)
+        .record_type("TXT");
+    if let Some(existing_record) = maybe_existing_record {
+        let record_id = existing_record.require_id()?;
+        let old_value = existing_record.value();
+        if same_content("TXT", &old_value, value) && existing_record.ttl() == ttl {
+            eprintln!(
+                "{} record with id={} already holds the requested value, not updating it",
+                name, record_id
+            );
+            return Ok(());
+        }
+        eprintln!(
+            "{} record already exists with id={}, updating it",
+            name, record_id
+        );
+        let txt_record = RecordDTO::txt_record(name, value, ttl, Some(record_id));
+        dynu_client.update_record(domain_id, &txt_record)?;
+        eprintln!("{} record updated", record_id);
+        notifier.notify(&event.values(Some(old_value), Some(value.to_string())));
+    } else {
+        eprintln!("{} record does not exist, defining a new one now", name);
+        let txt_record = RecordDTO::txt_record(name, value, ttl, None);
+        let id = dynu_client.create_record(domain_id, &txt_record)?;
+        eprintln!("created record with id={}", id);
+        notifier.notify(&event.values(None, Some(value.to_string())));
+    }
+    Ok(())
+}
+
+// Without value only the first TXT record of the node is deleted, with value
+// every record of the node holding it is, leaving the others in place.
+fn txt_delete(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    domain_name: &str,
+    name: &str,
+    value: Option<&str>,
+) -> Result<Vec<String>, SelfError> {
+    let domain = find_domain_id(dynu_client, domain_name)?;
+    let domain_id = domain.require_id()?;
+    let records = dynu_client.get_records(domain_id)?;
+    let node_records: Vec<&RecordDTO> = records
+        .dns_records
+        .iter()
+        .filter(|r| match r {
+            RecordDTO::TxtRecord { node_name, .. } => same_name(node_name, name),
+            _ => false,
+        })
+        .collect();
+    if node_records.is_empty() {
+        let nodes = records.dns_records.iter().filter_map(|r| match r {
+            RecordDTO::TxtRecord { node_name, .. } => Some(node_name.as_str()),
+            _ => None,
+        });
+        return Err(MsgError(format!(
+            "{} in domain {} does not exist{}",
+            name,
+            domain_name,
+            did_you_mean(name, nodes)
+        )));
+    }
+    let to_delete: Vec<&RecordDTO> = match value {
+        Some(value) => node_records
+            .into_iter()
+            .filter(|r| same_content("TXT", &r.value(), value))
+            .collect(),
+        None => node_records.into_iter().take(1).collect(),
+    };
+    if to_delete.is_empty() {
+        return Err(MsgError(format!(
+            "{} in domain {} has no TXT record with value={}",
+            name,
+            domain_name,
+            value.unwrap_or_default()
+        )));
+    }
+    let mut deleted = Vec::new();
+    for existing_record in to_delete {
+        dynu_client.delete_record(domain_id, existing_record.require_id()?)?;
+        eprintln!(
+            "{} in domain {} with id={} deleted",
+            name,
+            domain_name,
+            existing_record.require_id()?
+        );
+        notifier.notify(
+            &Event::new("txt-delete", Status::Updated, domain_name)
+                .node(name)
+                .record_type("TXT")
+                .values(Some(existing_record.value()), None),
+        );
+        deleted.push(existing_record.value());
+    }
+    Ok(deleted)
+}
+
+fn address_records(
+    dynu_client: &DynuClient,
+    domain: &DomainDTO,
+    family: Family,
+    name: &str,
+) -> Result<Vec<GenericRecordDTO>, SelfError> {
+    Ok(dynu_client
+        .get_generic_records(domain.require_id()?)?
+        .dns_records
+        .into_iter()
+        .filter(|r| r.record_type == family.record_type() && same_name(&r.node_name, name))
+        .collect())
+}
+
+fn address_of(record: &GenericRecordDTO, family: Family) -> String {
+    record
+        .other
+        .get(family.address_field())
+        .and_then(|address| address.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+// As txt-update, the first A or AAAA record of the node is updated, or created
+// when the node has none.
+fn address_update(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    domain: &str,
+    name: &str,
+    family: Family,
+    address: &str,
+    ttl: u64,
+) -> Result<(), SelfError> {
+    let record_type = family.record_type();
+    let domain = find_domain_id(dynu_client, domain)?;
+    let domain_id = domain.require_id()?;
+    let records = address_records(dynu_client, &domain, family, name)?;
+    let event = Event::new(
+        &format!("{}-update", record_type.to_lowercase()),
+        Status::Updated,
+        &domain.name,
+    )
+    .node(name)
+    .record_type(record_type);
+    if let Some(existing_record) = records.first() {
+        let record_id = existing_record.require_id()?;
+        let old_address = address_of(existing_record, family);
+        if same_content(record_type, &old_address, address) && existing_record.ttl == ttl {
+            eprintln!(
+                "{} {} record with id={} already holds the requested address, not updating it",
+                name, record_type, record_id
+            );
+            return Ok(());
+        }
+        eprintln!(
+            "{} {} record already exists with id={}, updating it",
+            name, record_type, record_id
+        );
+        let mut record = existing_record.clone();
+        record.ttl = ttl;
+        record
+            .other
+            .insert(family.address_field().to_string(), address.into());
+        dynu_client.update_generic_record(domain_id, &record)?;
+        eprintln!("{} record updated", record_id);
+        notifier.notify(&event.values(Some(old_address), Some(address.to_string())));
+    } else {
+        eprintln!(
+            "{} {} record does not exist, defining a new one now",
+            name, record_type
+        );
+        let mut record = GenericRecordDTO {
+            node_name: name.to_string(),
+            record_type: record_type.to_string(),
+            ttl,
+            state: true,
+            ..GenericRecordDTO::default()
+        };
+        record
+            .other
+            .insert(family.address_field().to_string(), address.into());
+        let id = dynu_client.create_generic_record(domain_id, &record)?;
+        eprintln!("created record with id={}", id);
+        notifier.notify(&event.values(None, Some(address.to_string())));
+    }
+    Ok(())
+}
+
+// Unlike txt-delete, every A or AAAA record of the node is deleted without
+// address, a node having one record per address it resolves to.
+fn address_delete(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    domain_name: &str,
+    name: &str,
+    family: Family,
+    address: Option<&str>,
+) -> Result<(), SelfError> {
+    let record_type = family.record_type();
+    let domain = find_domain_id(dynu_client, domain_name)?;
+    let records = address_records(dynu_client, &domain, family, name)?;
+    if records.is_empty() {
+        return Err(MsgError(format!(
+            "{} in domain {} has no {} record",
+            name, domain_name, record_type
+        )));
+    }
+    let to_delete: Vec<&GenericRecordDTO> = match address {
+        Some(address) => records
+            .iter()
+            .filter(|r| same_content(record_type, &address_of(r, family), address))
+            .collect(),
+        None => records.iter().collect(),
+    };
+    if to_delete.is_empty() {
+        return Err(MsgError(format!(
+            "{} in domain {} has no {} record with address={}",
+            name,
+            domain_name,
+            record_type,
+            address.unwrap_or_default()
+        )));
+    }
+    for existing_record in to_delete {
+        let record_id = existing_record.require_id()?;
+        dynu_client.delete_record(domain.require_id()?, record_id)?;
+        eprintln!(
+            "{} {} in domain {} with id={} deleted",
+            name, record_type, domain_name, record_id
+        );
+        notifier.notify(
+            &Event::new(
+                &format!("{}-delete", record_type.to_lowercase()),
+                Status::Updated,
+                domain_name,
+            )
+            .node(name)
+            .record_type(record_type)
+            .values(Some(address_of(existing_record, family)), None),
+        );
+    }
+    Ok(())
+}
+
+// Builds a record of a type holding a single target, e.g. CNAME, PTR or the
+// policy of an SPF record, from the node, the target, the TTL and the id.
+type TargetRecord = fn(&str, &str, u64, Option<u64>) -> RecordDTO;
+
+// A node has at most one CNAME, PTR or SPF record, which is updated in place
+// or created.
+fn target_update(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    domain: &str,
+    name: &str,
+    new_record: TargetRecord,
+    target: &str,
+    ttl: u64,
+) -> Result<(), SelfError> {
+    let template = new_record(name, target, ttl, None);
+    let record_type = template.record_type();
+    let domain = find_domain_id(dynu_client, domain)?;
+    let domain_id = domain.require_id()?;
+    let records = dynu_client.get_records(domain_id)?.dns_records;
+    if record_type == "CNAME" {
+        if let Some(reason) = lint::cname_refused(name, &records) {
+            return Err(MsgError(format!(
+                "cannot set a CNAME on {} in domain {}: {}",
+                if name.is_empty() { "the apex" } else { name },
+                domain.name,
+                reason
+            )));
+        }
+    }
+    let existing = records
+        .iter()
+        .find(|r| r.record_type() == record_type && same_name(r.node_name(), name));
+    let event = Event::new(
+        &format!("{}-update", record_type.to_lowercase()),
+        Status::Updated,
+        &domain.name,
+    )
+    .node(name)
+    .record_type(record_type);
+    if let Some(existing_record) = existing {
+        let record_id = existing_record.require_id()?;
+        let old_target = existing_record.value();
+        if same_content(record_type, &old_target, target) && existing_record.ttl() == ttl {
+            eprintln!(
+                "{} {} record with id={} already holds the requested target, not updating it",
+                name, record_type, record_id
+            );
+            return Ok(());
+        }
+        eprintln!(
+            "{} {} record already exists with id={}, updating it",
+            name, record_type, record_id
+        );
+        let record = new_record(name, target, ttl, Some(record_id));
+        dynu_client.update_record(domain_id, &record)?;
+        eprintln!("{} record updated", record_id);
+        notifier.notify(&event.values(Some(old_target), Some(target.to_string())));
+    } else {
+        eprintln!(
+            "{} {} record does not exist, defining a new one now",
+            name, record_type
+        );
+        let record = new_record(name, target, ttl, None);
+        let id = dynu_client.create_record(domain_id, &record)?;
+        eprintln!("created record with id={}", id);
+        notifier.notify(&event.values(None, Some(target.to_string())));
+    }
+    Ok(())
+}
+
+fn target_delete(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    domain_name: &str,
+    name: &str,
+    record_type: &str,
+) -> Result<(), SelfError> {
+    let domain = find_domain_id(dynu_client, domain_name)?;
+    let domain_id = domain.require_id()?;
+    let records = dynu_client.get_records(domain_id)?.dns_records;
+    let targets = records.iter().filter(|r| r.record_type() == record_type);
+    let Some(existing_record) = targets.clone().find(|r| same_name(r.node_name(), name)) else {
+        return Err(MsgError(format!(
+            "{} in domain {} has no {} record{}",
+            name,
+            domain_name,
+            record_type,
+            did_you_mean(name, targets.map(|r| r.node_name()))
+        )));
+    };
+    let record_id = existing_record.require_id()?;
+    dynu_client.delete_record(domain_id, record_id)?;
+    eprintln!(
+        "{} {} in domain {} with id={} deleted",
+        name, record_type, domain_name, record_id
+    );
+    notifier.notify(
+        &Event::new(
+            &format!("{}-delete", record_type.to_lowercase()),
+            Status::Updated,
+            domain_name,
+        )
+        .node(name)
+        .record_type(record_type)
+        .values(Some(existing_record.value()), None),
+    );
+    Ok(())
+}
+
+fn ns_records<'a>(records: &'a [RecordDTO], name: &str) -> Vec<&'a RecordDTO> {
+    records
+        .iter()
+        .filter(|r| r.record_type() == "NS" && same_name(r.node_name(), name))
+        .collect()
+}
+
+// The NS records of the node become exactly the name servers given: the
+// missing ones are created before the others are deleted, so that the
+// delegation never goes through an empty set. The NS records of the domain
+// itself are dynu's.
+fn ns_update(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    domain: &str,
+    name: &str,
+    nameservers: &[String],
+    ttl: u64,
+) -> Result<(), SelfError> {
+    let domain = find_domain_id(dynu_client, domain)?;
+    if name.is_empty() {
+        return Err(MsgError(format!(
+            "the NS records of domain={} itself are managed by dynu, give the node to delegate",
+            domain.name
+        )));
+    }
+    let domain_id = domain.require_id()?;
+    let records = dynu_client.get_records(domain_id)?.dns_records;
+    let existing = ns_records(&records, name);
+    let event = Event::new("ns-update", Status::Updated, &domain.name)
+        .node(name)
+        .record_type("NS");
+    for nameserver in nameservers {
+        let nameserver = nameserver.trim_end_matches('.');
+        match existing
+            .iter()
+            .find(|r| same_content("NS", &r.value(), nameserver))
+        {
+            Some(record) if record.ttl() == ttl => eprintln!(
+                "{} NS record with id={} already delegates to {}, not updating it",
+                name,
+                record.require_id()?,
+                nameserver
+            ),
+            Some(record) => {
+                let record_id = record.require_id()?;
+                let updated = RecordDTO::ns_record(name, nameserver, ttl, Some(record_id));
+                dynu_client.update_record(domain_id, &updated)?;
+                eprintln!("{} record updated", record_id);
+            }
+            None => {
+                let record = RecordDTO::ns_record(name, nameserver, ttl, None);
+                let id = dynu_client.create_record(domain_id, &record)?;
+                eprintln!(
+                    "created {} NS record with id={} for {}",
+                    name, id, nameserver
+                );
+                notifier.notify(&event.clone().values(None, Some(nameserver.to_string())));
+            }
+        }
+    }
+    for record in existing {
+        if !nameservers
+            .iter()
+            .any(|n| same_content("NS", &record.value(), n))
+        {
+            let record_id = record.require_id()?;
+            dynu_client.delete_record(domain_id, record_id)?;
+            eprintln!(
+                "{} NS record with id={} for {} deleted",
+                name,
+                record_id,
+                record.value()
+            );
+            notifier.notify(&event.clone().values(Some(record.value()), None));
+        }
+    }
+    Ok(())
+}
+
+// Every NS record of the node is deleted, or only the ones of the name
+// servers given.
+fn ns_delete(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    domain_name: &str,
+    name: &str,
+    nameservers: &[String],
+) -> Result<(), SelfError> {
+    let domain = find_domain_id(dynu_client, domain_name)?;
+    if name.is_empty() {
+        return Err(MsgError(format!(
+            "the NS records of domain={} itself are managed by dynu, give the delegated node",
+            domain.name
+        )));
+    }
+    let domain_id = domain.require_id()?;
+    let records = dynu_client.get_records(domain_id)?.dns_records;
+    let to_delete: Vec<&RecordDTO> = ns_records(&records, name)
+        .into_iter()
+        .filter(|r| {
+            nameservers.is_empty()
+                || nameservers
+                    .iter()
+                    .any(|n| same_content("NS", &r.value(), n))
+        })
+        .collect();
+    if to_delete.is_empty() {
+        return Err(MsgError(format!(
+            "{} in domain {} has no NS record{}",
+            name,
+            domain_name,
+            if nameservers.is_empty() {
+                String::new()
+            } else {
+                format!(" for {}", nameservers.join(", "))
+            }
+        )));
+    }
+    for record in to_delete {
+        let record_id = record.require_id()?;
+        dynu_client.delete_record(domain_id, record_id)?;
+        eprintln!(
+            "{} NS in domain {} with id={} deleted",
+            name, domain_name, record_id
+        );
+        notifier.notify(
+            &Event::new("ns-delete", Status::Updated, domain_name)
+                .node(name)
+                .record_type("NS")
+                .values(Some(record.value()), None),
+        );
+    }
+    Ok(())
+}
+
+// A node has a CAA record per authority it allows: the record with the tag and
+// value given is updated in place when its flags or TTL differ, or created.
+#[allow(clippy::too_many_arguments)]
+fn caa_update(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    domain: &str,
+    name: &str,
+    flags: u8,
+    tag: &str,
+    value: &str,
+    ttl: u64,
+) -> Result<(), SelfError> {
+    let domain = find_domain_id(dynu_client, domain)?;
+    let domain_id = domain.require_id()?;
+    let records = dynu_client.get_records(domain_id)?.dns_records;
+    let existing = records.iter().find(|r| match r {
+        RecordDTO::CaaRecord {
+            node_name,
+            tag: record_tag,
+            value: record_value,
+            ..
+        } => {
+            same_name(node_name, name)
+                && record_tag.eq_ignore_ascii_case(tag)
+                && record_value == value
+        }
+        _ => false,
+    });
+    let record = RecordDTO::caa_record(name, flags, tag, value, ttl, None);
+    let event = Event::new("caa-update", Status::Updated, &domain.name)
+        .node(name)
+        .record_type("CAA");
+    if let Some(existing_record) = existing {
+        let record_id = existing_record.require_id()?;
+        if existing_record.value() == record.value() && existing_record.ttl() == ttl {
+            eprintln!(
+                "{} CAA record with id={} already holds the requested value, not updating it",
+                name, record_id
+            );
+            return Ok(());
+        }
+        eprintln!(
+            "{} CAA record already exists with id={}, updating it",
+            name, record_id
+        );
+        let updated = RecordDTO::caa_record(name, flags, tag, value, ttl, Some(record_id));
+        dynu_client.update_record(domain_id, &updated)?;
+        eprintln!("{} record updated", record_id);
+        notifier.notify(&event.values(Some(existing_record.value()), Some(record.value())));
+    } else {
+        eprintln!("{} CAA record does not exist, defining a new one now", name);
+        let id = dynu_client.create_record(domain_id, &record)?;
+        eprintln!("created record with id={}", id);
+        notifier.notify(&event.values(None, Some(record.value())));
+    }
+    Ok(())
+}
+
+// Every CAA record of the node is deleted, or only the ones with the tag and
+// value given.
+fn caa_delete(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    domain_name: &str,
+    name: &str,
+    tag: Option<&str>,
+    value: Option<&str>,
+) -> Result<(), SelfError> {
+    let domain = find_domain_id(dynu_client, domain_name)?;
+    let domain_id = domain.require_id()?;
+    let records = dynu_client.get_records(domain_id)?.dns_records;
+    let to_delete: Vec<&RecordDTO> = records
+        .iter()
+        .filter(|r| match r {
+            RecordDTO::CaaRecord {
+                node_name,
+                tag: record_tag,
+                value: record_value,
+                ..
+            } => {
+                same_name(node_name, name)
+                    && tag.iter().all(|tag| record_tag.eq_ignore_ascii_case(tag))
+                    && value.iter().all(|value| record_value == value)
+            }
+            _ => false,
+        })
+        .collect();
+    if to_delete.is_empty() {
+        return Err(MsgError(format!(
+            "{} in domain {} has no CAA record{}{}",
+            name,
+            domain_name,
+            tag.map(|tag| format!(" with tag={}", tag))
+                .unwrap_or_default(),
+            value
+                .map(|value| format!(" with value={}", value))
+                .unwrap_or_default()
+        )));
+    }
+    for existing_record in to_delete {
+        let record_id = existing_record.require_id()?;
+        dynu_client.delete_record(domain_id, record_id)?;
+        eprintln!(
+            "{} CAA in domain {} with id={} deleted",
+            name, domain_name, record_id
+        );
+        notifier.notify(
+            &Event::new("caa-delete", Status::Updated, domain_name)
+                .node(name)
+                .record_type("CAA")
+                .values(Some(existing_record.value()), None),
+        );
+    }
+    Ok(())
+}
+
+fn domains_table(domains: &[DomainDTO]) -> Table {
+    let mut table = Table::new(vec![
+        "ID", "NAME", "IPV4", "IPV6", "WILDCARD", "TTL", "STATE",
+    ]);
+    for domain in domains {
+        // dynu serves *.<domain> through flags of the domain rather than records
+        let wildcard = match (domain.ipv4_wildcard_alias, domain.ipv6_wildcard_alias) {
+            (true, true) => "ipv4,ipv6",
+            (true, false) => "ipv4",
+            (false, true) => "ipv6",
+            (false, false) => "-",
+        };
+        table.push(vec![
+            domain.id.map(|id| id.to_string()).unwrap_or_default(),
+            domain.name.clone(),
+            or_dash(&domain.ipv4_address),
+            or_dash(&domain.ipv6_address),
+            wildcard.to_string(),
+            domain.ttl.to_string(),
+            domain.state.clone(),
+        ]);
+    }
+    table
+}
+
+fn account(
+    dynu_client: &DynuClient,
+    require_free_domains: Option<u64>,
+    require_free_records: Option<u64>,
+    listing: &Listing,
+) -> Result<(), SelfError> {
+    let details = dynu_client.get_account()?;
+    let domains = dynu_client.domains_iter()?.len() as u64;
+    let mut records = 0;
+    for record in dynu_client.account_records_iter()? {
+        record?;
+        records += 1;
+    }
+    let usage = account::Usage::new(&details, domains, records);
+    listing.print(&usage, || usage.table())?;
+    usage.require(require_free_domains, require_free_records)
+}
+
+fn history_table(updates: &[IpUpdateDTO]) -> Table {
+    let mut table = Table::new(vec!["DATE", "HOSTNAME", "IPV4", "IPV6"]);
+    for update in updates {
+        table.push(vec![
+            or_dash(&update.date),
+            or_dash(&update.hostname),
+            or_dash(&update.ipv4_address),
+            or_dash(&update.ipv6_address),
+        ]);
+    }
+    table
+}
+
+// Adds the domain with the addresses given, enabling the address families
+// which have one, and prints the id dynu gave it.
+fn domain_add(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    name: &str,
+    ipv4: Option<Ipv4Addr>,
+    ipv6: Option<Ipv6Addr>,
+    ttl: u64,
+    group: Option<String>,
+) -> Result<(), SelfError> {
+    check_ttl(dynu_client, ttl)?;
+    let name = to_ascii(&normalise_fqdn(name))?;
+    let domain = DomainDTO {
+        name: name.clone(),
+        group: group.unwrap_or_default(),
+        ipv4_address: ipv4.map(|a| a.to_string()),
+        ipv6_address: ipv6.map(|a| a.to_string()),
+        ttl,
+        ipv4: ipv4.is_some(),
+        ipv6: ipv6.is_some(),
+        ..Default::default()
+    };
+    let id = dynu_client
+        .create_domain(&domain)
+        .map_err(|e| constraints::explain(e.into(), Some(ttl), None))?;
+    eprintln!(
+        "domain={} added with id={} ipv4={} ipv6={} ttl={}",
+        name,
+        id,
+        or_dash(&domain.ipv4_address),
+        or_dash(&domain.ipv6_address),
+        ttl
+    );
+    notifier.notify(
+        &Event::new("domain-add", Status::Updated, &name)
+            .values(None, domain.ipv4_address.or(domain.ipv6_address)),
+    );
+    print_text(&format!("{}\n", id));
+    Ok(())
+}
+
+fn lint_domain(
+    dynu_client: &DynuClient,
+    name: &str,
+    min_ttl: u64,
+    max_ttl: u64,
+    no_resolve: bool,
+    listing: &Listing,
+) -> Result<(), SelfError> {
+    let domain = find_domain_id(dynu_client, name)?;
+    let records = dynu_client.get_records(domain.require_id()?)?.dns_records;
+    let resolve = |host: &str| propagation::name_exists(&to_ascii(host)?);
+    let findings = lint::lint(
+        &domain,
+        &records,
+        min_ttl,
+        max_ttl,
+        (!no_resolve).then_some(&resolve as &lint::Resolve),
+    );
+    listing.print(&findings, || {
+        let mut table = Table::new(vec!["SEVERITY", "CHECK", "NODE", "MESSAGE"]);
+        for finding in &findings {
+            table.push(vec![
+                finding.severity.name().to_string(),
+                finding.check.to_string(),
+                match finding.node.as_str() {
+                    "" => "@".to_string(),
+                    node => node.to_string(),
+                },
+                finding.message.clone(),
+            ]);
+        }
+        table
+    })?;
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == lint::Severity::Error)
+        .count();
+    eprintln!(
+        "domain={} has {} errors and {} warnings",
+        domain.name,
+        errors,
+        findings.len() - errors
+    );
+    if errors > 0 {
+        return Err(MsgError(format!(
+            "domain={} has {} zone errors",
+            domain.name, errors
+        )));
+    }
+    Ok(())
+}
+
+fn group_list(dynu_client: &DynuClient, listing: &Listing) -> Result<(), SelfError> {
+    let groups = dynu_client.get_groups()?;
+    listing.print(&groups, || {
+        let mut table = Table::new(vec!["GROUP", "DOMAIN", "IPV4", "IPV6"]);
+        for (group, domains) in &groups {
+            for domain in domains {
+                table.push(vec![
+                    group.clone(),
+                    domain.name.clone(),
+                    or_dash(&domain.ipv4_address),
+                    or_dash(&domain.ipv6_address),
+                ]);
+            }
+        }
+        table
+    })
+}
+
+// The addresses are only sent to the domains of the group which don't hold
+// them yet, the family without an address being left as it is on each. The
+// detected addresses only go to the families enabled on each domain, so that
+// e.g. an IPv6 only host doesn't get the IPv4 address of the network.
+fn group_update(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    name: &str,
+    addresses: &Addresses,
+    detected: bool,
+    dry_run: bool,
+) -> Result<(), SelfError> {
+    let mut groups = dynu_client.get_groups()?;
+    let Some(domains) = groups.remove(name) else {
+        return Err(MsgError(format!(
+            "no group named {} in the dynu account{}",
+            name,
+            did_you_mean(name, groups.keys().map(|g| g.as_str()))
+        )));
+    };
+    let (current, changed): (Vec<DomainDTO>, Vec<DomainDTO>) = domains
+        .iter()
+        .filter_map(|domain| {
+            let updated = domain.with_addresses(
+                addresses.v4.as_deref().filter(|_| !detected || domain.ipv4),
+                addresses.v6.as_deref().filter(|_| !detected || domain.ipv6),
+            );
+            (&updated != domain).then(|| (domain.clone(), updated))
+        })
+        .unzip();
+    for (old, new) in current.iter().zip(&changed) {
+        for (field, before, after) in field_diff(old, new) {
+            eprintln!(
+                "group={} domain={} {}: {} -> {}",
+                name, new.name, field, before, after
+            );
+        }
+    }
+    if dry_run {
+        eprintln!(
+            "dry run, not updating {} of the {} domains of group={}",
+            changed.len(),
+            domains.len(),
+            name
+        );
+        return Ok(());
+    }
+    let mut failure = None;
+    let mut failed = 0;
+    for ((old, new), result) in current
+        .iter()
+        .zip(&changed)
+        .zip(dynu_client.update_domains(&changed))
+    {
+        match result {
+            Ok(()) => notifier.notify(
+                &Event::new("group-update", Status::Updated, &new.name).values(
+                    old.ipv4_address.clone().or(old.ipv6_address.clone()),
+                    new.ipv4_address.clone().or(new.ipv6_address.clone()),
+                ),
+            ),
+            Err(e) => {
+                eprintln!("cannot update domain={} of group={}: {}", new.name, name, e);
+                failed += 1;
+                failure.get_or_insert(e);
+            }
+        }
+    }
+    eprintln!(
+        "group={} updated {} of its {} domains, {} already up to date",
+        name,
+        changed.len() - failed,
+        domains.len(),
+        domains.len() - changed.len()
+    );
+    match failure {
+        Some(e) => Err(e.into()),
+        None => Ok(()),
+    }
+}
+
+// Dynu deletes the records of the domain with it, so they are listed first
+// for the log to keep a trace of them.
+fn domain_remove(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    name: &str,
+    dry_run: bool,
+) -> Result<(), SelfError> {
+    let domain = find_domain_id(dynu_client, name)?;
+    let id = domain.require_id()?;
+    let records = dynu_client.get_records(id)?.dns_records;
+    for record in &records {
+        eprintln!(
+            "domain={} {} {} id={} node={} value={}",
+            domain.name,
+            if dry_run { "would remove" } else { "removing" },
+            record.record_type(),
+            record.id().unwrap_or_default(),
+            record.node_name(),
+            record.value()
+        );
+    }
+    if !dry_run {
+        dynu_client.delete_domain(id)?;
+        notifier.notify(
+            &Event::new("domain-remove", Status::Updated, &domain.name)
+                .values(domain.ipv4_address.clone(), None),
+        );
+    }
+    eprintln!(
+        "domain={} id={} {} with {} records{}",
+        domain.name,
+        id,
+        if dry_run {
+            "would be removed"
+        } else {
+            "removed"
+        },
+        records.len(),
+        if dry_run { " (dry run)" } else { "" }
+    );
+    Ok(())
+}
+
+// Updates of the hostname as recorded by dynu, to compare with what refresh
+// sent. Updates without a hostname can't be attributed and are shown as well.
+fn domain_history(
+    dynu_client: &DynuClient,
+    domain: &str,
+    listing: &Listing,
+) -> Result<(), SelfError> {
+    let domain = to_ascii(domain)?;
+    let updates: Vec<IpUpdateDTO> = dynu_client
+        .get_ip_update_history()?
+        .ip_update_histories
+        .into_iter()
+        .filter(|u| match &u.hostname {
+            Some(hostname) => same_name(hostname, &domain),
+            None => true,
+        })
+        .collect();
+    if updates.is_empty() {
+        eprintln!("dynu has no recorded updates of domain={}", domain);
+    }
+    listing.print(&updates, || history_table(&updates))
+}
+
+fn records_table(records: &[RecordDTO]) -> Table {
+    let mut table = Table::new(vec!["ID", "NODE", "TYPE", "TTL", "VALUE"]);
+    for record in records {
+        let node = match record.node_name() {
+            "" => "@",
+            node => node,
+        };
+        table.push(vec![
+            record.id().map(|id| id.to_string()).unwrap_or_default(),
+            node.to_string(),
+            record.record_type().to_string(),
+            record.ttl().to_string(),
+            record.value(),
+        ]);
+    }
+    table
+}
+
+fn warn_on_cache_failure(result: Result<(), SelfError>) {
+    if let Err(e) = result {
+        eprintln!("cannot update the zone cache: {}", e);
+    }
+}
+
+// The TTL constraints of the plan of the account, only looked up when some of
+// the TTLs are accepted on the paid plans only.
+fn ttl_constraints(dynu_client: &DynuClient, ttls: &[u64]) -> Constraints {
+    if !ttls
+        .iter()
+        .any(|ttl| (constraints::MIN_TTL..constraints::FREE_MIN_TTL).contains(ttl))
+    {
+        return Constraints::for_level(None);
+    }
+    match dynu_client.get_account() {
+        Ok(account) => Constraints::for_level(account.membership_level.as_deref()),
+        Err(e) => {
+            eprintln!(
+                "cannot look up the plan of the account to check TTLs: {}",
+                e
+            );
+            Constraints::for_level(None)
+        }
+    }
+}
+
+fn check_ttl(dynu_client: &DynuClient, ttl: u64) -> Result<(), SelfError> {
+    ttl_constraints(dynu_client, &[ttl]).check_ttl(ttl)
+}
+
+fn warn_on_state_failure(result: Result<(), SelfError>) {
+    if let Err(e) = result {
+        eprintln!("cannot record the last update: {}", e);
+    }
+}
+
+fn print_domains(domains: &[DomainDTO], listing: &Listing) -> Result<(), SelfError> {
+    listing.print(&domains, || domains_table(domains))
+}
+
+fn print_records(records: &[RecordDTO], listing: &Listing) -> Result<(), SelfError> {
+    listing.print(&records, || records_table(records))
+}
+
+fn domains(
+    dynu_client: &DynuClient,
+    cache: &ZoneCache,
+    listing: &Listing,
+) -> Result<(), SelfError> {
+    let domains = dynu_client.get_domains()?.domains;
+    let printed = print_domains(&domains, listing);
+    warn_on_cache_failure(cache.store_domains(domains));
+    printed
+}
+
+fn records(
+    dynu_client: &DynuClient,
+    cache: &ZoneCache,
+    domain: &str,
+    order: &RecordOrder,
+    listing: &Listing,
+) -> Result<(), SelfError> {
+    let domain_dto = find_domain_id(dynu_client, domain)?;
+    let mut records = dynu_client
+        .get_records(domain_dto.require_id()?)?
+        .dns_records;
+    order.apply(&mut records);
+    let printed = print_records(&records, listing);
+    warn_on_cache_failure(cache.store_records(domain, domain_dto.updated_on, records));
+    printed
+}
+
+// Every field dynu stores for the domain, including the ones DomainDTO doesn't
+// model, one per row.
+fn domain_table(domain: &DomainDTO) -> Table {
+    let mut table = Table::new(vec!["FIELD", "VALUE"]);
+    if let serde_json::Value::Object(fields) = serde_json::to_value(domain).unwrap() {
+        for (field, value) in fields {
+            let value = match value {
+                serde_json::Value::String(value) => value,
+                serde_json::Value::Null => "-".to_string(),
+                value => value.to_string(),
+            };
+            table.push(vec![field, value]);
+        }
+    }
+    table
+}
+
+fn domain_get(dynu_client: &DynuClient, name: &str, listing: &Listing) -> Result<(), SelfError> {
+    let listed = find_domain_id(dynu_client, name)?;
+    let domain = dynu_client
+        .get_domain(listed.require_id()?)?
+        .unwrap_or(listed);
+    listing.print(&domain, || domain_table(&domain))
+}
+
+fn cached_domains(cache: &ZoneCache, listing: &Listing) -> Result<(), SelfError> {
+    let cached = cache.domains()?;
+    eprintln!("showing domains cached at {}", cached.fetched_at());
+    print_domains(&cached.data, listing)
+}
+
+fn cached_records(
+    cache: &ZoneCache,
+    domain: &str,
+    order: &RecordOrder,
+    listing: &Listing,
+) -> Result<(), SelfError> {
+    let mut cached = cache.records(domain)?;
+    order.apply(&mut cached.data);
+    eprintln!(
+        "showing records of domain={} cached at {}",
+        domain,
+        cached.fetched_at()
+    );
+    print_records(&cached.data, listing)
+}
+
+fn dedupe(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    domain: &str,
+    dry_run: bool,
+) -> Result<(), SelfError> {
+    let domain_dto = find_domain_id(dynu_client, domain)?;
+    let domain_id = domain_dto.require_id()?;
+    let records = dynu_client.get_records(domain_id)?.dns_records;
+    let duplicates = content::duplicates(&records);
+    let progress = Progress::new(duplicates.len(), "dedupe");
+    for record in &duplicates {
+        let label = format!("{} node={}", record.record_type(), record.node_name());
+        progress.item(&label, || {
+            eprintln!(
+                "domain={} deleting duplicate {} id={} node={} value={}",
+                domain,
+                record.record_type(),
+                record.id().unwrap_or_default(),
+                record.node_name(),
+                record.value()
+            );
+            if !dry_run {
+                dynu_client.delete_record(domain_id, record.require_id()?)?;
+                notifier.notify(
+                    &Event::new("dedupe", Status::Updated, &domain_dto.name)
+                        .node(record.node_name())
+                        .record_type(record.record_type())
+                        .values(Some(record.value()), None),
+                );
+            }
+            Ok(())
+        })?;
+    }
+    progress.finish();
+    eprintln!(
+        "domain={} {} {} duplicate records{}",
+        domain,
+        if dry_run { "would delete" } else { "deleted" },
+        duplicates.len(),
+        if dry_run { " (dry run)" } else { "" }
+    );
+    Ok(())
+}
+
+fn set_ttl(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    domain: &str,
+    record_type: Option<&str>,
+    bounds: &ttl::Bounds,
+    dry_run: bool,
+) -> Result<(), SelfError> {
+    if let Some(record_type) = record_type {
+        constraints::check_record_type(record_type)?;
+    }
+    let limits: Vec<u64> = bounds.min.into_iter().chain(bounds.max).collect();
+    let constraints = ttl_constraints(dynu_client, &limits);
+    for ttl in limits {
+        constraints.check_ttl(ttl)?;
+    }
+    let domain_dto = find_domain_id(dynu_client, domain)?;
+    let domain_id = domain_dto.require_id()?;
+    let records = dynu_client.get_records(domain_id)?.dns_records;
+    let steps = ttl::plan(&records, record_type, bounds);
+    if dry_run {
+        for step in &steps {
+            eprintln!("domain={} would {}", domain, step.describe());
+        }
+        eprintln!(
+            "domain={} would rewrite {} records (dry run)",
+            domain,
+            steps.len()
+        );
+        return Ok(());
+    }
+    // Updates rejected by Dynu's rate limiting are retried by the progress
+    // after a pause. An update failing anyway stops the rewrite, the records
+    // not rewritten yet being reported so that it can be resumed.
+    let progress = Progress::new(steps.len(), "set-ttl");
+    for (index, step) in steps.iter().enumerate() {
+        let Step::Update(old, new) = step else {
+            continue;
+        };
+        let updated = progress.item(&step.describe(), || {
+            eprintln!("domain={} {}", domain, step.describe());
+            dynu_client
+                .update_record(domain_id, new)
+                .map_err(|e| constraints::explain(e.into(), Some(new.ttl()), None))
+        });
+        if let Err(e) = updated {
+            progress.finish();
+            eprintln!(
+                "domain={} rewrote {} records, {} left unchanged:",
+                domain,
+                index,
+                steps.len() - index
+            );
+            for step in &steps[index..] {
+                eprintln!("  {}", step.describe());
+            }
+            return Err(e);
+        }
+        notifier.notify(
+            &Event::new("set-ttl", Status::Updated, &domain_dto.name)
+                .node(new.node_name())
+                .record_type(new.record_type())
+                .values(Some(old.ttl().to_string()), Some(new.ttl().to_string())),
+        );
+    }
+    progress.finish();
+    eprintln!("domain={} rewrote {} records", domain, steps.len());
+    Ok(())
+}
+
+fn generic_records_table(records: &[GenericRecordDTO]) -> Table {
+    let mut table = Table::new(vec!["ID", "NODE", "TYPE", "TTL", "VALUE"]);
+    for record in records {
+        let node = match record.node_name.as_str() {
+            "" => "@",
+            node => node,
+        };
+        table.push(vec![
+            record.id.map(|id| id.to_string()).unwrap_or_default(),
+            node.to_string(),
+            record.record_type.clone(),
+            record.ttl.to_string(),
+            record.value(),
+        ]);
+    }
+    table
+}
+
+fn record_list(
+    dynu_client: &DynuClient,
+    domain: &str,
+    record_type: Option<&str>,
+    listing: &Listing,
+) -> Result<(), SelfError> {
+    let domain_dto = find_domain_id(dynu_client, domain)?;
+    let records: Vec<GenericRecordDTO> = dynu_client
+        .get_generic_records(domain_dto.require_id()?)?
+        .dns_records
+        .into_iter()
+        .filter(|r| match record_type {
+            Some(record_type) => r.record_type.eq_ignore_ascii_case(record_type),
+            None => true,
+        })
+        .collect();
+    listing.print(&records, || generic_records_table(&records))
+}
+
+fn find_generic_record(
+    dynu_client: &DynuClient,
+    domain_dto: &DomainDTO,
+    id: u64,
+) -> Result<GenericRecordDTO, SelfError> {
+    dynu_client
+        .get_generic_record(domain_dto.require_id()?, id)?
+        .ok_or_else(|| {
+            MsgError(format!(
+                "record id={} cannot be found in domain={}",
+                id, domain_dto.name
+            ))
+        })
+}
+
+fn record_get(
+    dynu_client: &DynuClient,
+    domain: &str,
+    id: u64,
+    listing: &Listing,
+) -> Result<(), SelfError> {
+    let domain_dto = find_domain_id(dynu_client, domain)?;
+    let record = [find_generic_record(dynu_client, &domain_dto, id)?];
+    listing.print(&record[0], || generic_records_table(&record))
+}
+
+fn record_create(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    domain: &str,
+    record: &GenericRecordDTO,
+) -> Result<(), SelfError> {
+    check_ttl(dynu_client, record.ttl)?;
+    let domain_dto = find_domain_id(dynu_client, domain)?;
+    let id = dynu_client
+        .create_generic_record(domain_dto.require_id()?, record)
+        .map_err(|e| constraints::explain(e.into(), Some(record.ttl), None))?;
+    eprintln!(
+        "domain={} created {} id={} node={} {}",
+        domain,
+        record.record_type,
+        id,
+        record.node_name,
+        record.value()
+    );
+    notifier.notify(
+        &Event::new("record-create", Status::Updated, &domain_dto.name)
+            .node(&record.node_name)
+            .record_type(&record.record_type)
+            .values(None, Some(record.value())),
+    );
+    print_text(&format!("{}\n", id));
+    Ok(())
+}
+
+fn record_update(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    domain: &str,
+    id: u64,
+    update: impl FnOnce(&GenericRecordDTO) -> Result<GenericRecordDTO, SelfError>,
+) -> Result<(), SelfError> {
+    let domain_dto = find_domain_id(dynu_client, domain)?;
+    let record = find_generic_record(dynu_client, &domain_dto, id)?;
+    let updated = update(&record)?;
+    if updated.ttl != record.ttl {
+        check_ttl(dynu_client, updated.ttl)?;
+    }
+    dynu_client
+        .update_generic_record(domain_dto.require_id()?, &updated)
+        .map_err(|e| constraints::explain(e.into(), Some(updated.ttl), None))?;
+    eprintln!(
+        "domain={} updated {} id={} node={} {}",
+        domain,
+        updated.record_type,
+        id,
+        updated.node_name,
+        updated.value()
+    );
+    notifier.notify(
+        &Event::new("record-update", Status::Updated, &domain_dto.name)
+            .node(&updated.node_name)
+            .record_type(&updated.record_type)
+            .values(Some(record.value()), Some(updated.value())),
+    );
+    Ok(())
+}
+
+fn record_delete(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    domain: &str,
+    id: u64,
+) -> Result<(), SelfError> {
+    let domain_dto = find_domain_id(dynu_client, domain)?;
+    let record = find_generic_record(dynu_client, &domain_dto, id)?;
+    dynu_client.delete_record(domain_dto.require_id()?, id)?;
+    eprintln!(
+        "domain={} deleted {} id={} node={} {}",
+        domain,
+        record.record_type,
+        id,
+        record.node_name,
+        record.value()
+    );
+    notifier.notify(
+        &Event::new("record-delete", Status::Updated, &domain_dto.name)
+            .node(&record.node_name)
+            .record_type(&record.record_type)
+            .values(Some(record.value()), None),
+    );
+    Ok(())
+}
+
+fn export(
+    dynu_client: &DynuClient,
+    domain: &str,
+    format: ExportFormat,
+    provider: TerraformProvider,
+) -> Result<(), SelfError> {
+    let domain_dto = find_domain_id(dynu_client, domain)?;
+    let domain_id = domain_dto.require_id()?;
+    let records = dynu_client.get_records(domain_id)?.dns_records;
+    match format {
+        ExportFormat::Terraform => print_text(&export::terraform(
+            provider,
+            &domain_dto.name,
+            domain_id,
+            &records,
+        )),
+        ExportFormat::Dnscontrol => print_text(&export::dnscontrol(&domain_dto.name, &records)),
+        ExportFormat::DnscontrolJson => {
+            print_text(&export::dnscontrol_json(&domain_dto.name, &records))
+        }
+    }
+    Ok(())
+}
+
+fn take_backup(
+    dynu_client: &DynuClient,
+    domains: &[String],
+    show_progress: bool,
+) -> Result<Backup, SelfError> {
+    let domains: Vec<DomainDTO> = if domains.is_empty() {
+        dynu_client.get_domains()?.domains
+    } else {
+        domains
+            .iter()
+            .map(|d| find_domain_id(dynu_client, d))
+            .collect::<Result<_, _>>()?
+    };
+    let progress = if show_progress {
+        Progress::new(domains.len(), "backup")
+    } else {
+        Progress::hidden()
+    };
+    let mut zones = Vec::new();
+    for domain in domains {
+        let records = progress.item(&domain.name, || {
+            Ok(dynu_client.get_records(domain.require_id()?)?.dns_records)
+        })?;
+        zones.push(backup::ZoneBackup { domain, records });
+    }
+    progress.finish();
+    Ok(Backup::new(zones))
+}
+
+fn backup_take(
+    dynu_client: &DynuClient,
+    domains: &[String],
+    output: Option<&str>,
+) -> Result<(), SelfError> {
+    let snapshot = take_backup(dynu_client, domains, true)?;
+    match output {
+        Some(path) => {
+            let content = serde_json::to_string_pretty(&snapshot).unwrap();
+            std::fs::write(path, content + "\n")
+                .map_err(|e| MsgError(format!("cannot write {}: {}", path, e)))?;
+            eprintln!("backed up {} domains to {}", snapshot.zones.len(), path);
+        }
+        None => print_json(&snapshot),
+    }
+    Ok(())
+}
+
+// Backs up the managed domains into the state directory when the last backup
+// is older than every.
+fn scheduled_backup(
+    dynu_client: &DynuClient,
+    rotation: &backup::Rotation,
+    managed: &Managed,
+    every: Duration,
+) -> Result<(), SelfError> {
+    let now = SystemTime::now();
+    if !staleness::is_older(rotation.last()?, now, every) {
+        return Ok(());
+    }
+    let domains: Vec<String> = if managed.all {
+        vec![]
+    } else {
+        managed
+            .resolve(dynu_client, None)?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect()
+    };
+    let path = rotation.store(&take_backup(dynu_client, &domains, false)?, now)?;
+    eprintln!("backed up the domains to {}", path.display());
+    Ok(())
+}
+
+fn backup_diff(old: &str, new: &str, listing: &Listing) -> Result<(), SelfError> {
+    let (old, new) = (Backup::read(old)?, Backup::read(new)?);
+    eprintln!(
+        "comparing backups taken at {} and {}",
+        old.taken_at, new.taken_at
+    );
+    let differences = backup::diff(&old, &new);
+    listing.print(&differences, || backup::diff_table(&differences))
+}
+
+fn import(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    domain: &str,
+    from: &str,
+    format: Option<ImportFormat>,
+    dry_run: bool,
+) -> Result<(), SelfError> {
+    let content = std::fs::read_to_string(from)
+        .map_err(|e| MsgError(format!("cannot read {}: {}", from, e)))?;
+    let format = format.unwrap_or_else(|| import::detect(from, &content));
+    let foreign = import::parse(format, &content)?;
+    let domain_dto = find_domain_id(dynu_client, domain)?;
+    let domain_id = domain_dto.require_id()?;
+    let (records, skipped) = import::to_dynu(&foreign, &domain_dto.name);
+    for reason in &skipped {
+        eprintln!("skipping {}", reason);
+    }
+    let ttls: Vec<u64> = records.iter().map(|r| r.ttl()).collect();
+    let constraints = ttl_constraints(dynu_client, &ttls);
+    let existing = dynu_client.get_records(domain_id)?.dns_records;
+    let mut missing = Vec::new();
+    for record in &records {
+        check_node(record.node_name())?;
+        constraints.check_ttl(record.ttl())?;
+        let exists = existing.iter().any(|e| {
+            e.record_type() == record.record_type()
+                && same_name(e.node_name(), record.node_name())
+                && same_content(record.record_type(), &e.value(), &record.value())
+        });
+        if !exists {
+            missing.push(record.clone());
+        }
+    }
+    let present = records.len() - missing.len();
+    for record in &missing {
+        eprintln!(
+            "domain={} creating {} node={} value={} ttl={}",
+            domain,
+            record.record_type(),
+            record.node_name(),
+            record.value(),
+            record.ttl()
+        );
+    }
+    let results = if dry_run {
+        vec![]
+    } else {
+        let progress = Progress::new(missing.len(), "import");
+        let results = dynu_client.create_records(domain_id, &missing, || progress.inc());
+        progress.finish();
+        results
+    };
+    let (mut failure, mut failed) = (None, 0);
+    for (record, result) in missing.iter().zip(results) {
+        match result {
+            Ok(_) => notifier.notify(
+                &Event::new("import", Status::Updated, &domain_dto.name)
+                    .node(record.node_name())
+                    .record_type(record.record_type())
+                    .values(None, Some(record.value())),
+            ),
+            Err(e) => {
+                let e =
+                    constraints::explain(e.into(), Some(record.ttl()), Some(record.record_type()));
+                eprintln!(
+                    "domain={} cannot create {} node={} value={}: {}",
+                    domain,
+                    record.record_type(),
+                    record.node_name(),
+                    record.value(),
+                    e
+                );
+                failed += 1;
+                failure.get_or_insert(e);
+            }
+        }
+    }
+    eprintln!(
+        "domain={} {} {} records, {} already present, {} skipped{}",
+        domain,
+        if dry_run { "would create" } else { "created" },
+        missing.len() - failed,
+        present,
+        skipped.len(),
+        if dry_run { " (dry run)" } else { "" }
+    );
+    match failure {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+// Resolves the domain and node of the address and CNAME commands before
+// running them, notifying their failures as for txt-update.
+fn node_command(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    config: &Config,
+    node: RecordNode,
+    kind: &str,
+    command: impl FnOnce(&str, &str) -> Result<(), SelfError>,
+) -> Result<(), SelfError> {
+    let domain = domain_or_default(node.domain, config)?;
+    notifier.on_failure(
+        split_host(dynu_client, &domain, node.name.as_deref())
+            .and_then(|(domain, name)| command(&domain, &name)),
+        kind,
+        &domain,
+        node.name.as_deref(),
+    )
+}
+
+// The PTR commands take an address, or directly its name in the reverse zone,
+// split as the other record commands into the zone dynu hosts and the node.
+fn reverse_node(address: &str) -> RecordNode {
+    let name = match address.parse() {
+        Ok(address) => names::reverse_name(address),
+        Err(_) => address.to_string(),
+    };
+    RecordNode {
+        name: None,
+        domain: Some(name),
+    }
+}
+
+fn txt_change(domain: &str, name: &str, value: &str, ttl: u64) -> PendingChange {
+    PendingChange::TxtValue {
+        domain: domain.to_string(),
+        node: name.to_string(),
+        value: value.to_string(),
+        ttl,
+    }
+}
+
+fn queued_txt_update(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    state: &StateDir,
+    domain: &str,
+    name: &str,
+    value: &str,
+    ttl: u64,
+) -> Result<(), SelfError> {
+    let change = txt_change(domain, name, value, ttl);
+    let queue = prepare_queue(dynu_client, notifier, state, &change);
+    let result = txt_update(dynu_client, notifier, domain, name, value, ttl);
+    enqueue_if_unavailable(queue, change, result)
+}
+
+fn queued_txt_delete(
+    dynu_client: &DynuClient,
+    notifier: &Notifier,
+    state: &StateDir,
+    domain: &str,
+    name: &str,
+    value: Option<&str>,
+) -> Result<Vec<String>, SelfError> {
+    prepare_queue(
+        dynu_client,
+        notifier,
+        state,
+        &txt_change(domain, name, "", 0),
+    );
+    txt_delete(dynu_client, notifier, domain, name, value)
+}
+
+// A wedged connection can block a request longer than any timeout of the
+// client, so the run is aborted from a separate thread instead of being
+// cancelled. The changes already sent to dynu are kept, and reported as those
+// of a failed run with --ansible.
+fn abort_after(max_runtime: humantime::Duration, recorder: Option<Recorder>) {
+    thread::spawn(move || {
+        thread::sleep(max_runtime.into());
+        let error = MsgError(format!("run aborted after --max-runtime {}", max_runtime));
+        apicalls::flush();
+        if let Some(recorder) = recorder {
+            let report = Report::new(&recorder, output::captured(), Err(format!("{}", error)));
+            println!("{}", serde_json::to_string(&report).unwrap());
+            std::process::exit(1);
+        }
+        eprintln!("Error: {:?}", error);
+        std::process::exit(4);
+    });
+}
+
+// With --ansible nothing but the report is printed on stdout and the exit code
+// only tells failures apart, as ansible's command module expects.
+fn main() -> Result<(), SelfError> {
+    crash::install_hook();
+    let command = MainArguments::command();
+    let matches = command
+        .clone()
+        .get_matches_from(expand_alias(env::args_os().collect(), &command));
+    let arguments = MainArguments::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let command = allow::command_path(&matches);
+    let recorder = arguments.ansible.then(Recorder::default);
+    if let Some(max_runtime) = arguments.max_runtime {
+        abort_after(max_runtime, recorder.clone());
+    }
+    let Some(recorder) = recorder else {
+        let result = run(arguments, &command, None);
+        apicalls::flush();
+        return result;
+    };
+    output::capture();
+    let result = run(arguments, &command, Some(&recorder)).map_err(|e| format!("{}", e));
+    apicalls::flush();
+    let report = Report::new(&recorder, output::captured(), result);
+    println!("{}", serde_json::to_string(&report).unwrap());
+    std::process::exit(if report.failed { 1 } else { 0 });
+}
+
+// The configuration file is only read before the command line is parsed when
+// the command isn't one of dynuupdater's, to look it up in the aliases. When
+// it can't be read the command line is left as is, for clap to report the
+// unknown command.
+fn expand_alias(args: Vec<OsString>, command: &clap::Command) -> Vec<OsString> {
+    let words: Vec<String> = args
+        .iter()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    if alias::alias_name(&words, command).is_none() {
+        return args;
+    }
+    let option = |name: &str, var: &str| alias::option_value(&words, name).or(env::var(var).ok());
+    let config = config::load(
+        option("config", "DYNU_CONFIG").as_deref(),
+        option("profile", "DYNU_PROFILE").as_deref(),
+        option("env", "DYNU_ENV").as_deref(),
+    );
+    match config {
+        Ok(config) => alias::expand(words, command, &config.aliases)
+            .into_iter()
+            .map(OsString::from)
+            .collect(),
+        Err(e) => {
+            eprintln!("cannot read the aliases of the configuration: {}", e);
+            args
+        }
+    }
+}
+
+fn run(
+    arguments: MainArguments,
+    command: &str,
+    recorder: Option<&Recorder>,
+) -> Result<(), SelfError> {
+    let load_config = || {
+        config::load(
+            arguments.config.as_deref(),
+            arguments.profile.as_deref(),
+            arguments.env.as_deref(),
+        )
+    };
+    let check_allowed = |config: &config::Config| {
+        allow::check(config.allow.as_deref(), &MainArguments::command(), command)
+    };
+    // These commands run without a configuration: timeout, ip-sources and
+    // version only print information and are always allowed, while config
+    // migrate and init rewrite the configuration file, which is only done
+    // when the file being replaced allows them.
+    match arguments.cmd {
+        Commands::Timeout => return lego::timeout(),
+        Commands::IpSources => return ip_sources(),
+        Commands::Version { verbose } => return version(verbose),
+        Commands::Config {
+            action: ConfigAction::Migrate,
+        } => {
+            check_allowed(&load_config()?)?;
+            return config::migrate_file(arguments.config.as_deref());
+        }
+        Commands::Init {
+            secret_backend,
+            force,
+        } => {
+            let path = config::config_path(arguments.config.as_deref());
+            if path.as_ref().is_some_and(|path| path.exists()) {
+                check_allowed(&load_config()?)?;
+            }
+            return init::run(path, secret_backend, force);
+        }
+        _ => {}
+    }
+    let config = load_config()?;
+    check_allowed(&config)?;
+    if let Some(user_agent) = &config.user_agent {
+        netutils::set_user_agent(user_agent);
+    }
+    let ip_source = arguments
+        .ip_source
+        .clone()
+        .or(config.ip_source.clone())
+        .unwrap_or(ipsource::BUILTIN.to_string());
+    if let Some(timeout) = ip_source_timeout(&arguments, &config)? {
+        ipsource::set_timeout(timeout);
+    }
+    let state = StateDir::new(
+        arguments
+            .state_dir
+            .as_deref()
+            .or(config.state_dir.as_deref()),
+    );
+    let cache = ZoneCache::new(&state);
+    let listing = Listing {
+        format: arguments.output,
+        fields: arguments.fields.clone(),
+    };
+    match &arguments.cmd {
+        Commands::Domains { cached: true, .. }
+        | Commands::Domains {
+            action: Some(DomainsAction::List { cached: true }),
+            ..
+        } => return cached_domains(&cache, &listing),
+        Commands::Backup {
+            action: BackupAction::Diff { old, new },
+        } => return backup_diff(old, new, &listing),
+        Commands::Records {
+            domain: Some(domain),
+            cached: true,
+            order,
+            action: None,
+        } => return cached_records(&cache, domain, order, &listing),
+        _ => {}
+    }
+    let api_key = get_api_key(&arguments, &config)?;
+    let dynu_client = DynuClient::new(&api_key, netutils::user_agent())?
+        .with_limits(config.client.limits()?)
+        .with_read_only(arguments.read_only || config.read_only.unwrap_or(false));
+    apicalls::install(
+        command,
+        arguments.api_calls,
+        arguments
+            .api_calls_file
+            .as_ref()
+            .or(config.api_calls_file.as_ref())
+            .map(PathBuf::from),
+        dynu_client.api_calls(),
+    );
+    let notifier = get_notifier(&arguments, &config, &state, recorder)?;
+    match arguments.cmd {
+        Commands::Refresh {
+            domain,
+            all,
+            dry_run,
+            ttl,
+            group,
+            require_port,
+            probe,
+            max_age,
+            drift,
+        } => {
+            if let Some(ttl) = ttl {
+                check_ttl(&dynu_client, ttl)?;
+            }
+            let reachability = match require_port {
+                Some(port) => Some(Reachability {
+                    port,
+                    probe: Probe::parse(&probe)?,
+                }),
+                None => None,
+            };
+            let options = UpdateOptions {
+                verbose: arguments.verbose,
+                dry_run,
+                ttl,
+                group,
+                reachability,
+                max_age: max_age.map(Into::into),
+                ip_source: ip_source.clone(),
+                drift,
+            };
+            let managed = Managed::new(vec![], all, &config);
+            if all || (domain.is_none() && !config.domains.is_empty()) {
+                let partial = refresh_all(
+                    &dynu_client,
+                    &notifier,
+                    &state,
+                    &managed,
+                    &options,
+                    &listing,
+                    &ip_source,
+                )?;
+                return exit_if_partial(partial, recorder);
+            }
+            let domain = domain_or_default(domain, &config)?;
+            let change_set = notifier.on_failure(
+                detect_addresses(&ip_source).and_then(|detected| {
+                    refresh_managed(
+                        &dynu_client,
+                        &notifier,
+                        &state,
+                        &domain,
+                        &managed.settings(&domain),
+                        &detected,
+                        &options,
+                    )
+                }),
+                "refresh",
+                &domain,
+                None,
+            )?;
+            if arguments.output == OutputFormat::Json {
+                print_json(&change_set);
+            }
+            let partial = match change_set.failure() {
+                Some(_) => vec![domain],
+                None => vec![],
+            };
+            exit_if_partial(partial, recorder)
+        }
+        Commands::Touch { domain } => {
+            let domain = domain_or_default(domain, &config)?;
+            notifier.on_failure(
+                touch(&dynu_client, &notifier, &state, &domain),
+                "touch",
+                &domain,
+                None,
+            )
+        }
+        Commands::Daemon {
+            domains,
+            all,
+            interval,
+            watch_network,
+            max_age,
+            drift,
+            backup_every,
+            backup_keep,
+        } => {
+            let domains = if all || !domains.is_empty() || !config.domains.is_empty() {
+                domains
+            } else {
+                vec![domain_or_default(None, &config)?]
+            };
+            let managed = Managed::new(domains, all, &config);
+            let options = UpdateOptions {
+                verbose: arguments.verbose,
+                max_age: max_age.map(Into::into),
+                ip_source: ip_source.clone(),
+                drift,
+                ..Default::default()
+            };
+            check_leases(&config, interval.into())?;
+            let rotation = backup::Rotation::new(&state, backup_keep);
+            daemon::run(interval.into(), watch_network, || {
+                if let Some(every) = backup_every {
+                    if let Err(e) =
+                        scheduled_backup(&dynu_client, &rotation, &managed, every.into())
+                    {
+                        eprintln!("cannot back up the domains: {}", e);
+                    }
+                }
+                dashboard_round(
+                    &dynu_client,
+                    &notifier,
+                    &state,
+                    &managed,
+                    &options,
+                    &ip_source,
+                    None,
+                )
+            })
+        }
+        Commands::UpdateTxtRecord {
+            ttl,
+            name,
+            value,
+            value_file,
+            value_stdin,
+            domain,
+            unique_suffix,
+        } => {
+            let value = txt_value(value, value_file.as_deref(), value_stdin)?;
+            let (domain, name) = host_or_default(domain, name, &config)?;
+            let ttl = ttl
+                .or(config.defaults.ttl)
+                .unwrap_or(acme::DEFAULT_CHALLENGE_TTL);
+            notifier.on_failure(
+                check_ttl(&dynu_client, ttl)
+                    .and_then(|_| split_host(&dynu_client, &domain, name.as_deref()))
+                    .and_then(|host| unique_node(host, unique_suffix))
+                    .and_then(|(domain, name)| {
+                        queued_txt_update(
+                            &dynu_client,
+                            &notifier,
+                            &state,
+                            &domain,
+                            &name,
+                            &value,
+                            ttl,
+                        )
+                    })
+                    .map_err(|e| constraints::explain(e, Some(ttl), Some("TXT"))),
+                "txt-update",
+                &domain,
+                name.as_deref(),
+            )
+        }
+        Commands::DeleteTxtRecord {
+            domain,
+            name,
+            value,
+            unique_suffix,
+            verify,
+            verify_timeout,
+            resolvers,
+        } => {
+            let (domain, name) = host_or_default(domain, name, &config)?;
+            notifier.on_failure(
+                split_host(&dynu_client, &domain, name.as_deref())
+                    .and_then(|host| unique_node(host, unique_suffix))
+                    .and_then(|(domain, name)| {
+                        let deleted = queued_txt_delete(
+                            &dynu_client,
+                            &notifier,
+                            &state,
+                            &domain,
+                            &name,
+                            value.as_deref(),
+                        )?;
+                        if !verify {
+                            return Ok(());
+                        }
+                        let resolvers = verification_resolvers(resolvers, &config)?;
+                        let zone = to_ascii(&normalise_fqdn(&domain))?;
+                        let fqdn = if name.is_empty() {
+                            zone.clone()
+                        } else {
+                            format!("{}.{}", to_ascii(&name)?, zone)
+                        };
+                        let verification = propagation::wait_until_gone(
+                            &resolvers,
+                            &zone,
+                            &fqdn,
+                            &deleted,
+                            verify_timeout.into(),
+                        )?;
+                        print_verification(&verification, &listing)
+                    }),
+                "txt-delete",
+                &domain,
+                name.as_deref(),
+            )
+        }
+        Commands::TxtApplyTemplate {
+            domain,
+            template,
+            value,
+            name,
+            ttl,
+            dry_run,
+        } => {
+            let template = templates::find(&config.txt_templates, &template)?;
+            let node = name.as_deref().or(template.node()).unwrap_or("");
+            let host = if node.is_empty() {
+                domain.clone()
+            } else {
+                format!("{}.{}", node, domain)
+            };
+            let ttl = ttl.or(template.ttl()).or(config.defaults.ttl);
+            let rendered = template.render(&value, &domain, node)?;
+            print_text(&format!("{}\n", rendered));
+            // Added next to the other TXT values of the node as by ensure txt,
+            // so that applying the template again changes nothing.
+            notifier.on_failure(
+                ensure(
+                    &dynu_client,
+                    &notifier,
+                    &host,
+                    &Desired::Text(rendered),
+                    ttl,
+                    dry_run,
+                    None,
+                ),
+                "txt-apply-template",
+                &domain,
+                Some(node),
+            )?;
+            Ok(())
+        }
+        Commands::AUpdate { address, ttl, node } => node_command(
+            &dynu_client,
+            &notifier,
+            &config,
+            node,
+            "a-update",
+            |domain, name| {
+                let ttl = ttl.unwrap_or(ensure::DEFAULT_TTL);
+                check_ttl(&dynu_client, ttl)?;
+                let address = address.to_string();
+                address_update(
+                    &dynu_client,
+                    &notifier,
+                    domain,
+                    name,
+                    Family::Ipv4,
+                    &address,
+                    ttl,
+                )
+                .map_err(|e| constraints::explain(e, Some(ttl), Some("A")))
+            },
+        ),
+        Commands::ADelete { address, node } => node_command(
+            &dynu_client,
+            &notifier,
+            &config,
+            node,
+            "a-delete",
+            |domain, name| {
+                let address = address.map(|a| a.to_string());
+                address_delete(
+                    &dynu_client,
+                    &notifier,
+                    domain,
+                    name,
+                    Family::Ipv4,
+                    address.as_deref(),
+                )
+            },
+        ),
+        Commands::AaaaUpdate { address, ttl, node } => node_command(
+            &dynu_client,
+            &notifier,
+            &config,
+            node,
+            "aaaa-update",
+            |domain, name| {
+                let ttl = ttl.unwrap_or(ensure::DEFAULT_TTL);
+                check_ttl(&dynu_client, ttl)?;
+                let address = address.to_string();
+                address_update(
+                    &dynu_client,
+                    &notifier,
+                    domain,
+                    name,
+                    Family::Ipv6,
+                    &address,
+                    ttl,
+                )
+                .map_err(|e| constraints::explain(e, Some(ttl), Some("AAAA")))
+            },
+        ),
+        Commands::AaaaDelete { address, node } => node_command(
+            &dynu_client,
+            &notifier,
+            &config,
+            node,
+            "aaaa-delete",
+            |domain, name| {
+                let address = address.map(|a| a.to_string());
+                address_delete(
+                    &dynu_client,
+                    &notifier,
+                    domain,
+                    name,
+                    Family::Ipv6,
+                    address.as_deref(),
+                )
+            },
+        ),
+        Commands::CnameUpdate { target, ttl, node } => node_command(
+            &dynu_client,
+            &notifier,
+            &config,
+            node,
+            "cname-update",
+            |domain, name| {
+                let ttl = ttl.unwrap_or(ensure::DEFAULT_TTL);
+                check_ttl(&dynu_client, ttl)?;
+                target_update(
+                    &dynu_client,
+                    &notifier,
+                    domain,
+                    name,
+                    RecordDTO::cname_record,
+                    &target,
+                    ttl,
+                )
+                .map_err(|e| constraints::explain(e, Some(ttl), Some("CNAME")))
+            },
+        ),
+        Commands::PtrUpdate {
+            target,
+            ttl,
+            address,
+        } => node_command(
+            &dynu_client,
+            &notifier,
+            &config,
+            reverse_node(&address),
+            "ptr-update",
+            |domain, name| {
+                let ttl = ttl.unwrap_or(ensure::DEFAULT_TTL);
+                check_ttl(&dynu_client, ttl)?;
+                target_update(
+                    &dynu_client,
+                    &notifier,
+                    domain,
+                    name,
+                    RecordDTO::ptr_record,
+                    &target,
+                    ttl,
+                )
+                .map_err(|e| constraints::explain(e, Some(ttl), Some("PTR")))
+            },
+        ),
+        Commands::PtrDelete { address } => node_command(
+            &dynu_client,
+            &notifier,
+            &config,
+            reverse_node(&address),
+            "ptr-delete",
+            |domain, name| target_delete(&dynu_client, &notifier, domain, name, "PTR"),
+        ),
+        Commands::NsUpdate {
+            nameservers,
+            ttl,
+            node,
+        } => node_command(
+            &dynu_client,
+            &notifier,
+            &config,
+            node,
+            "ns-update",
+            |domain, name| {
+                let ttl = ttl.unwrap_or(ensure::DEFAULT_TTL);
+                check_ttl(&dynu_client, ttl)?;
+                ns_update(&dynu_client, &notifier, domain, name, &nameservers, ttl)
+                    .map_err(|e| constraints::explain(e, Some(ttl), Some("NS")))
+            },
+        ),
+        Commands::NsDelete { nameservers, node } => node_command(
+            &dynu_client,
+            &notifier,
+            &config,
+            node,
+            "ns-delete",
+            |domain, name| ns_delete(&dynu_client, &notifier, domain, name, &nameservers),
+        ),
+        Commands::CaaUpdate {
+            tag,
+            value,
+            flags,
+            ttl,
+            node,
+        } => node_command(
+            &dynu_client,
+            &notifier,
+            &config,
+            node,
+            "caa-update",
+            |domain, name| {
+                let ttl = ttl.unwrap_or(ensure::DEFAULT_TTL);
+                check_ttl(&dynu_client, ttl)?;
+                caa_update(
+                    &dynu_client,
+                    &notifier,
+                    domain,
+                    name,
+                    flags,
+                    &tag.to_lowercase(),
+                    &value,
+                    ttl,
+                )
+                .map_err(|e| constraints::explain(e, Some(ttl), Some("CAA")))
+            },
+        ),
+        Commands::CaaDelete { tag, value, node } => node_command(
+            &dynu_client,
+            &notifier,
+            &config,
+            node,
+            "caa-delete",
+            |domain, name| {
+                caa_delete(
+                    &dynu_client,
+                    &notifier,
+                    domain,
+                    name,
+                    tag.as_deref(),
+                    value.as_deref(),
+                )
+            },
+        ),
+        Commands::SpfUpdate { policy, ttl, node } => node_command(
+            &dynu_client,
+            &notifier,
+            &config,
+            node,
+            "spf-update",
+            |domain, name| {
+                let ttl = ttl.unwrap_or(ensure::DEFAULT_TTL);
+                check_ttl(&dynu_client, ttl)?;
+                if !policy.starts_with("v=spf1") {
+                    return Err(MsgError(format!(
+                        "SPF policy {} must start with v=spf1",
+                        policy
+                    )));
+                }
+                target_update(
+                    &dynu_client,
+                    &notifier,
+                    domain,
+                    name,
+                    RecordDTO::spf_record,
+                    &policy,
+                    ttl,
+                )
+                .map_err(|e| constraints::explain(e, Some(ttl), Some("SPF")))
+            },
+        ),
+        Commands::SpfDelete { node } => node_command(
+            &dynu_client,
+            &notifier,
+            &config,
+            node,
+            "spf-delete",
+            |domain, name| target_delete(&dynu_client, &notifier, domain, name, "SPF"),
+        ),
+        Commands::CnameDelete { node } => node_command(
+            &dynu_client,
+            &notifier,
+            &config,
+            node,
+            "cname-delete",
+            |domain, name| target_delete(&dynu_client, &notifier, domain, name, "CNAME"),
+        ),
+        Commands::PingApi { count, interval } => {
+            let options = ping::PingOptions {
+                count,
+                interval: interval.into(),
+            };
+            let summary = ping::run(&options, || dynu_client.get_domains().map(|_| ()));
+            listing.print(&summary, || summary.table())?;
+            if summary.requests > 0 && summary.errors == summary.requests {
+                return Err(MsgError("every request to dynu failed".to_string()));
+            }
+            Ok(())
+        }
+        Commands::Ensure {
+            check,
+            ttl,
+            reuse_records,
+            state,
+        } => {
+            let (host, desired) = match state {
+                EnsureState::A { host, address } => (host, Desired::Address(address.to_string())),
+                EnsureState::Txt { host, value } => (host, Desired::Text(value)),
+                EnsureState::Absent {
+                    record_type,
+                    host,
+                    value,
+                } => (host, Desired::Absent(record_type, value)),
+            };
+            let changed = notifier.on_failure(
+                ensure(
+                    &dynu_client,
+                    &notifier,
+                    &host,
+                    &desired,
+                    ttl,
+                    check,
+                    reuse_records.then_some(&cache),
+                ),
+                "ensure",
+                &host,
+                None,
+            )?;
+            if let Some(recorder) = recorder {
+                if changed {
+                    recorder.mark_changed();
+                }
+                return Ok(());
+            }
+            println!("{}", if changed { "changed" } else { "unchanged" });
+            if changed {
+                apicalls::flush();
+                std::process::exit(2);
+            }
+            Ok(())
+        }
+        Commands::Account {
+            require_free_domains,
+            require_free_records,
+        } => account(
+            &dynu_client,
+            require_free_domains,
+            require_free_records,
+            &listing,
+        ),
+        Commands::Domains {
+            action: Some(DomainsAction::Get { name }),
+            ..
+        } => domain_get(&dynu_client, &name, &listing),
+        Commands::Domains { .. } => domains(&dynu_client, &cache, &listing),
+        Commands::Domain {
+            action: DomainAction::History { domain },
+        } => domain_history(&dynu_client, &domain, &listing),
+        Commands::Domain {
+            action:
+                DomainAction::Add {
+                    name,
+                    ipv4,
+                    ipv6,
+                    ttl,
+                    group,
+                },
+        } => domain_add(&dynu_client, &notifier, &name, ipv4, ipv6, ttl, group),
+        Commands::Domain {
+            action: DomainAction::Remove { name, dry_run },
+        } => domain_remove(&dynu_client, &notifier, &name, dry_run),
+        Commands::Lint {
+            domain,
+            min_ttl,
+            max_ttl,
+            no_resolve,
+        } => {
+            let domain = domain_or_default(domain, &config)?;
+            lint_domain(
+                &dynu_client,
+                &domain,
+                min_ttl,
+                max_ttl,
+                no_resolve,
+                &listing,
+            )
+        }
+        Commands::Group {
+            action: GroupAction::List,
+        } => group_list(&dynu_client, &listing),
+        Commands::Group {
+            action:
+                GroupAction::Update {
+                    name,
+                    ipv4,
+                    ipv6,
+                    dry_run,
+                },
+        } => {
+            let detected = ipv4.is_none() && ipv6.is_none();
+            let addresses = if detected {
+                detect_addresses(&ip_source)?
+            } else {
+                Addresses {
+                    v4: ipv4.map(|a| a.to_string()),
+                    v6: ipv6.map(|a| a.to_string()),
+                }
+            };
+            notifier.on_failure(
+                group_update(
+                    &dynu_client,
+                    &notifier,
+                    &name,
+                    &addresses,
+                    detected,
+                    dry_run,
+                ),
+                "group-update",
+                &name,
+                None,
+            )
+        }
+        Commands::Records {
+            action: Some(RecordsAction::Dedupe { domain, dry_run }),
+            ..
+        } => dedupe(&dynu_client, &notifier, &domain, dry_run),
+        Commands::Records {
+            action:
+                Some(RecordsAction::SetTtl {
+                    domain,
+                    record_type,
+                    ttl,
+                    min,
+                    max,
+                    dry_run,
+                }),
+            ..
+        } => set_ttl(
+            &dynu_client,
+            &notifier,
+            &domain,
+            record_type.as_deref(),
+            &ttl::Bounds::new(ttl, min, max)?,
+            dry_run,
+        ),
+        Commands::Records {
+            domain: Some(domain),
+            order,
+            ..
+        } => records(&dynu_client, &cache, &domain, &order, &listing),
+        Commands::Records { .. } => Err(MsgError("records requires a domain".to_string())),
+        Commands::Record {
+            action:
+                RecordAction::List {
+                    domain,
+                    record_type,
+                },
+        } => record_list(&dynu_client, &domain, record_type.as_deref(), &listing),
+        Commands::Record {
+            action: RecordAction::Get { domain, id },
+        } => record_get(&dynu_client, &domain, id, &listing),
+        Commands::Record {
+            action:
+                RecordAction::Create {
+                    domain,
+                    record_type,
+                    node,
+                    ttl,
+                    fields,
+                },
+        } => {
+            let record = payload::new_record(&record_type, &node, ttl, &fields)?;
+            record_create(&dynu_client, &notifier, &domain, &record)
+        }
+        Commands::Record {
+            action:
+                RecordAction::Update {
+                    domain,
+                    id,
+                    node,
+                    ttl,
+                    fields,
+                },
+        } => record_update(&dynu_client, &notifier, &domain, id, |record| {
+            payload::updated_record(record, node.as_deref(), ttl, &fields)
+        }),
+        Commands::Record {
+            action: RecordAction::Delete { domain, id },
+        } => record_delete(&dynu_client, &notifier, &domain, id),
+        Commands::Export {
+            domain,
+            format,
+            provider,
+        } => export(&dynu_client, &domain, format, provider),
+        Commands::Import {
+            domain,
+            from,
+            format,
+            dry_run,
+        } => import(&dynu_client, &notifier, &domain, &from, format, dry_run),
+        Commands::Backup {
+            action: BackupAction::Take { domains, output },
+        } => backup_take(&dynu_client, &domains, output.as_deref()),
+        Commands::Backup {
+            action: BackupAction::Diff { .. },
+        } => {
+            unreachable!("backup diff only reads local files and is run before the client is built")
+        }
+        Commands::Present { args } => lego::run(dynu_client, lego::Action::Present, &args),
+        Commands::Cleanup { args } => lego::run(dynu_client, lego::Action::Cleanup, &args),
+        Commands::Timeout => lego::timeout(),
+        Commands::IpSources => ip_sources(),
+        Commands::Version { verbose } => version(verbose),
+        Commands::Config { .. } => config::migrate_file(arguments.config.as_deref()),
+        Commands::Init {
+            secret_backend,
+            force,
+        } => init::run(
+            config::config_path(arguments.config.as_deref()),
+            secret_backend,
+            force,
+        ),
+        Commands::Acme {
+            action:
+                AcmeAction::Cleanup {
+                    older_than,
+                    dry_run,
+                },
+        } => acme::cleanup_stale(&dynu_client, &notifier, older_than.into(), dry_run),
+        Commands::MigrateIp {
+            domain,
+            ipv4,
+            ipv6,
+            low_ttl,
+            verify_timeout,
+            resolvers,
+        } => notifier.on_failure(
+            check_ttl(&dynu_client, low_ttl)
+                .and_then(|_| verification_resolvers(resolvers, &config))
+                .and_then(|resolvers| {
+                    migrate_ip(
+                        &dynu_client,
+                        &notifier,
+                        &domain,
+                        ipv4.map(|a| a.to_string()),
+                        ipv6.map(|a| a.to_string()),
+                        low_ttl,
+                        verify_timeout.into(),
+                        &resolvers,
+                        &listing,
+                    )
+                }),
+            "migrate-ip",
+            &domain,
+            None,
+        ),
+        Commands::Failover {
+            domain,
+            primary,
+            backup,
+            check,
+            interval,
+            failures,
+            recoveries,
+        } => failover(
+            &dynu_client,
+            &notifier,
+            &FailoverOptions {
+                domain,
+                primary: primary.to_string(),
+                backup: backup.to_string(),
+                check: Check::parse(&check)?,
+                interval: interval.into(),
+                failures,
+                recoveries,
+            },
+        ),
+        Commands::Serve {
+            mode:
+                ServeMode::CertManager {
+                    listen,
+                    group_name,
+                    solver_name,
+                    server,
+                },
+        } => certmanager::serve(
+            dynu_client,
+            CertManagerOptions {
+                server: server_options(listen, server)?,
+                group_name,
+                solver_name,
+            },
+        ),
+        Commands::Serve {
+            mode:
+                ServeMode::Rfc2136 {
+                    listen,
+                    tsig_key: key,
+                },
+        } => rfc2136::serve(
+            dynu_client,
+            Rfc2136Options {
+                listen,
+                key: tsig_key(key)?,
+            },
+        ),
+        Commands::Serve {
+            mode:
+                ServeMode::Dashboard {
+                    domains,
+                    all,
+                    listen,
+                    interval,
+                    watch_network,
+                    trigger_interval,
+                    server,
+                },
+        } => {
+            let server = server_options(listen, server)?;
+            if server.auth.is_none() {
+                return Err(MsgError(format!(
+                    "the dashboard requires authentication, provide a token with --auth-token or {} or credentials with --basic-auth or {}",
+                    SERVER_TOKEN_NAME, SERVER_BASIC_AUTH_NAME
+                )));
+            }
+            check_leases(&config, interval.into())?;
+            let managed = Managed::new(domains, all, &config);
+            let options = UpdateOptions {
+                verbose: arguments.verbose,
+                ip_source: ip_source.clone(),
+                ..Default::default()
+            };
+            dashboard::serve(
+                DashboardOptions {
+                    server,
+                    interval: interval.into(),
+                    watch_network,
+                    trigger_interval: trigger_interval.into(),
+                },
+                |only| {
+                    dashboard_round(
+                        &dynu_client,
+                        &notifier,
+                        &state,
+                        &managed,
+                        &options,
+                        &ip_source,
+                        only,
+                    )
+                },
+            )
+        }
     }
 }
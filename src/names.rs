@@ -0,0 +1,220 @@
+use std::fs;
+use std::net::IpAddr;
+
+use crate::SelfError;
+
+const MAX_LABEL_LENGTH: usize = 63;
+
+pub fn normalise_fqdn(fqdn: &str) -> String {
+    fqdn.trim_end_matches('.').to_lowercase()
+}
+
+// Names coming from users and from Dynu differ in case and trailing dots
+// without denoting different hosts.
+pub fn same_name(a: &str, b: &str) -> bool {
+    normalise_fqdn(a) == normalise_fqdn(b)
+}
+
+pub fn node_of(fqdn: &str, zone: &str) -> Option<String> {
+    if fqdn == zone {
+        return Some("".to_string());
+    }
+    fqdn.strip_suffix(zone)
+        .and_then(|prefix| prefix.strip_suffix('.'))
+        .map(|node| node.to_string())
+}
+
+// A wildcard is only meaningful as the whole leftmost label of a node name, as
+// in "*" or "*.lab", anywhere else it would be stored as a literal asterisk.
+pub fn check_node(node: &str) -> Result<(), SelfError> {
+    let misplaced = node
+        .split('.')
+        .enumerate()
+        .any(|(i, label)| label.contains('*') && (i > 0 || label != "*"));
+    if misplaced {
+        return Err(SelfError::MsgError(format!(
+            "invalid node name {}, '*' is only allowed as the whole leftmost label",
+            node
+        )));
+    }
+    Ok(())
+}
+
+// Dynu's API and DNS resolution only deal with the ASCII (punycode) form of
+// internationalised domain names.
+pub fn to_ascii(name: &str) -> Result<String, SelfError> {
+    idna::domain_to_ascii(name)
+        .map_err(|e| SelfError::MsgError(format!("invalid domain name {}: {:?}", name, e)))
+}
+
+// Name of the PTR record of an address in the reverse zones, e.g.
+// 1.2.0.192.in-addr.arpa, IPv6 addresses being spelled out nibble by nibble.
+pub fn reverse_name(address: IpAddr) -> String {
+    match address {
+        IpAddr::V4(address) => {
+            let octets: Vec<String> = address
+                .octets()
+                .iter()
+                .rev()
+                .map(|o| o.to_string())
+                .collect();
+            format!("{}.in-addr.arpa", octets.join("."))
+        }
+        IpAddr::V6(address) => {
+            let nibbles: Vec<String> = address
+                .octets()
+                .iter()
+                .rev()
+                .flat_map(|o| [o & 0xf, o >> 4])
+                .map(|n| format!("{:x}", n))
+                .collect();
+            format!("{}.ip6.arpa", nibbles.join("."))
+        }
+    }
+}
+
+// Label derived from the hostname of the system, the first label of it reduced
+// to the characters allowed in DNS labels.
+pub fn host_suffix(hostname: &str) -> Option<String> {
+    let label: String = hostname
+        .trim()
+        .split('.')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let label = label.trim_matches('-');
+    (!label.is_empty()).then(|| label.to_string())
+}
+
+pub fn local_hostname() -> Result<String, SelfError> {
+    ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .iter()
+        .find_map(|path| fs::read_to_string(path).ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .and_then(|hostname| host_suffix(&hostname))
+        .ok_or_else(|| SelfError::MsgError("cannot determine the hostname".to_string()))
+}
+
+// Appends the suffix to the leftmost label of the node, so that the record
+// stays in the same subdomain. The suffix is shortened when the label would
+// exceed the DNS limit.
+pub fn with_suffix(node: &str, suffix: &str) -> Result<String, SelfError> {
+    let (label, rest) = match node.split_once('.') {
+        Some((label, rest)) => (label, Some(rest)),
+        None => (node, None),
+    };
+    if label.is_empty() || label == "*" {
+        return Err(SelfError::MsgError(format!(
+            "cannot add a suffix to node name '{}'",
+            node
+        )));
+    }
+    let room = MAX_LABEL_LENGTH.saturating_sub(label.len() + 1);
+    let suffix = suffix[..suffix.len().min(room)].trim_end_matches('-');
+    if suffix.is_empty() {
+        return Err(SelfError::MsgError(format!(
+            "node name '{}' is too long for a suffix",
+            node
+        )));
+    }
+    Ok(match rest {
+        Some(rest) => format!("{}-{}.{}", label, suffix, rest),
+        None => format!("{}-{}", label, suffix),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_of_should_strip_zone() {
+        assert_eq!(
+            node_of("_acme-challenge.example.dynu.net", "example.dynu.net"),
+            Some("_acme-challenge".to_string())
+        );
+        assert_eq!(
+            node_of("example.dynu.net", "example.dynu.net"),
+            Some("".to_string())
+        );
+    }
+
+    #[test]
+    fn node_of_should_reject_partial_labels() {
+        assert_eq!(node_of("myexample.dynu.net", "example.dynu.net"), None);
+        assert_eq!(node_of("other.net", "example.dynu.net"), None);
+    }
+
+    #[test]
+    fn normalise_fqdn_should_strip_trailing_dot() {
+        assert_eq!(normalise_fqdn("Example.Dynu.Net."), "example.dynu.net");
+    }
+
+    #[test]
+    fn same_name_should_ignore_case_and_trailing_dot() {
+        assert!(same_name("Example.DYNU.net.", "example.dynu.net"));
+        assert!(same_name("_ACME-Challenge", "_acme-challenge"));
+        assert!(!same_name("www.example.dynu.net", "example.dynu.net"));
+    }
+
+    #[test]
+    fn check_node_should_accept_leftmost_wildcard_only() {
+        assert!(check_node("*").is_ok());
+        assert!(check_node("*.lab").is_ok());
+        assert!(check_node("www").is_ok());
+        assert!(check_node("w*").is_err());
+        assert!(check_node("lab.*").is_err());
+    }
+
+    #[test]
+    fn host_suffix_should_keep_a_valid_label() {
+        assert_eq!(host_suffix("Web_01.lan\n"), Some("web-01".to_string()));
+        assert_eq!(host_suffix("-"), None);
+    }
+
+    #[test]
+    fn with_suffix_should_extend_the_leftmost_label() {
+        assert_eq!(
+            with_suffix("_acme-challenge", "web-01").unwrap(),
+            "_acme-challenge-web-01"
+        );
+        assert_eq!(
+            with_suffix("_acme-challenge.vpn", "web-01").unwrap(),
+            "_acme-challenge-web-01.vpn"
+        );
+        assert_eq!(with_suffix(&"a".repeat(60), "web-01").unwrap().len(), 63);
+        assert!(with_suffix("", "web-01").is_err());
+        assert!(with_suffix("*.lab", "web-01").is_err());
+    }
+
+    #[test]
+    fn reverse_name_should_reverse_octets_and_nibbles() {
+        assert_eq!(
+            reverse_name("192.0.2.1".parse().unwrap()),
+            "1.2.0.192.in-addr.arpa"
+        );
+        assert_eq!(
+            reverse_name("2001:db8::1".parse().unwrap()),
+            format!("1.{}8.b.d.0.1.0.0.2.ip6.arpa", "0.".repeat(23))
+        );
+    }
+
+    #[test]
+    fn to_ascii_should_encode_unicode_labels() {
+        assert_eq!(
+            to_ascii("bücher.dynu.net").unwrap(),
+            "xn--bcher-kva.dynu.net"
+        );
+        assert_eq!(
+            to_ascii("_acme-challenge.example.dynu.net").unwrap(),
+            "_acme-challenge.example.dynu.net"
+        );
+        assert_eq!(
+            to_ascii("*.bücher.dynu.net").unwrap(),
+            "*.xn--bcher-kva.dynu.net"
+        );
+    }
+}
@@ -1,31 +1,142 @@
 use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{mpsc, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
 
-const IP4_API: &str = "https://api.ipify.org";
-const IP6_API: &str = "https://api6.ipify.org";
+use crate::dynu;
+
+// Dual-stack ipify endpoint, answering with the address of the family the
+// request came through.
+const IP_API: &str = "https://api64.ipify.org";
+/// Address family of a public address.
 pub enum IP {
+    /// IPv4 address.
     V4,
+    /// IPv6 address.
     V6,
 }
 
+static USER_AGENT: OnceLock<String> = OnceLock::new();
+
+/// Sets the User-Agent of the HTTP requests made besides the calls to dynu, it
+/// can only be set once, before the first request.
+pub fn set_user_agent(user_agent: &str) {
+    let _ = USER_AGENT.set(user_agent.to_string());
+}
+
+/// User-Agent of the HTTP requests, dynuupdater's unless set otherwise.
+pub fn user_agent() -> &'static str {
+    USER_AGENT
+        .get()
+        .map(|u| u.as_str())
+        .unwrap_or(dynu::USER_AGENT)
+}
+
+/// Builder of HTTP clients sending the User-Agent.
+pub fn http_client() -> reqwest::blocking::ClientBuilder {
+    reqwest::blocking::Client::builder().user_agent(user_agent())
+}
+
+// Delay before the next connection attempt is started, recommended by RFC 8305.
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Public address of the host in the family, as ipify sees it, None when it
+/// has none or ipify can't be reached.
 pub fn ip(ip: IP) -> Option<String> {
-    let address = match ip {
-        IP::V4 => IP4_API,
-        IP::V6 => IP6_API,
+    let accept: fn(&str) -> bool = match ip {
+        IP::V4 => |answer| answer.parse::<std::net::Ipv4Addr>().is_ok(),
+        IP::V6 => |answer| answer.parse::<std::net::Ipv6Addr>().is_ok(),
     };
-    let ipv4 = reqwest::blocking::get(address).and_then(|r| r.text());
-    match ipv4 {
-        Ok(t) => Some(t),
-        _ => None,
+    get_text(IP_API, accept)
+}
+
+fn fetch(url: &str, pinned: Option<(&str, SocketAddr)>) -> Option<String> {
+    let builder = match pinned {
+        Some((host, address)) => http_client().resolve(host, address),
+        None => http_client(),
+    };
+    builder
+        .build()
+        .and_then(|client| client.get(url).send())
+        .and_then(|r| r.text())
+        .ok()
+        .map(|text| text.trim().to_string())
+}
+
+// GETs url, and when its host has both IPv4 and IPv6 addresses races the two
+// families as in RFC 8305, so that a broken IPv6 path doesn't stall the
+// request: IPv6 is tried first and IPv4 as well if no answer accepted came
+// within the attempt delay. An answer of the wrong family, from an endpoint
+// serving both, is not accepted, so that e.g. the IPv4 address is still
+// detected within the attempt delay when the IPv6 path is broken.
+fn get_text(url: &str, accept: fn(&str) -> bool) -> Option<String> {
+    let parsed = reqwest::Url::parse(url).ok()?;
+    let (Some(host), Some(port)) = (parsed.host_str(), parsed.port_or_known_default()) else {
+        return fetch(url, None).filter(|answer| accept(answer));
+    };
+    let resolved = dns_lookup::lookup_host(host).unwrap_or_default();
+    let v6 = resolved.iter().find(|a| a.is_ipv6());
+    let v4 = resolved.iter().find(|a| a.is_ipv4());
+    let (Some(v6), Some(v4)) = (v6, v4) else {
+        return fetch(url, None).filter(|answer| accept(answer));
+    };
+    let attempts = [*v6, *v4].map(|address: IpAddr| {
+        let (url, host) = (url.to_string(), host.to_string());
+        move || fetch(&url, Some((&host, SocketAddr::new(address, port))))
+    });
+    race(attempts.into(), CONNECTION_ATTEMPT_DELAY, accept)
+}
+
+// Starts the attempts one after the other, the next one as soon as the ones
+// started have failed or after delay, and returns the first accepted answer.
+// Slower attempts are left to finish in the background.
+fn race<F>(attempts: Vec<F>, delay: Duration, accept: fn(&str) -> bool) -> Option<String>
+where
+    F: FnOnce() -> Option<String> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    let mut attempts = attempts.into_iter();
+    let mut running = 0;
+    loop {
+        if let Some(attempt) = attempts.next() {
+            let sender = sender.clone();
+            thread::spawn(move || sender.send(attempt()));
+            running += 1;
+        }
+        if running == 0 {
+            return None;
+        }
+        let next_attempt = Instant::now() + delay;
+        while running > 0 {
+            let answer = if attempts.len() > 0 {
+                match receiver.recv_timeout(next_attempt.saturating_duration_since(Instant::now()))
+                {
+                    Ok(answer) => answer,
+                    Err(_) => break,
+                }
+            } else {
+                receiver.recv().ok()?
+            };
+            running -= 1;
+            if let Some(answer) = answer.filter(|answer| accept(answer)) {
+                return Some(answer);
+            }
+        }
     }
 }
 
+/// Addresses of a host, one per family.
 pub struct Addresses {
+    /// IPv4 address.
     pub v4: Option<String>,
+    /// IPv6 address.
     pub v6: Option<String>,
 }
 
+/// Addresses the domain currently resolves to, the first one of each family.
 pub fn public_ip_of(domain: &str) -> Result<Addresses, io::Error> {
-    let resolved = dns_lookup::lookup_host(domain).unwrap_or_else(|_| vec!());
+    let resolved = dns_lookup::lookup_host(domain).unwrap_or_else(|_| vec![]);
     if resolved.is_empty() {
         Ok(Addresses { v4: None, v6: None })
     } else {
@@ -48,3 +159,46 @@ pub fn public_ip_of(domain: &str) -> Result<Addresses, io::Error> {
         Ok(Addresses { v4, v6 })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attempt(
+        answer: &'static str,
+        after: Duration,
+    ) -> Box<dyn FnOnce() -> Option<String> + Send> {
+        Box::new(move || {
+            thread::sleep(after);
+            Some(answer.to_string())
+        })
+    }
+
+    fn is_ipv4(answer: &str) -> bool {
+        answer.parse::<std::net::Ipv4Addr>().is_ok()
+    }
+
+    #[test]
+    fn race_should_not_wait_for_a_stalled_first_attempt() {
+        let started = Instant::now();
+        let attempts = vec![
+            attempt("2001:db8::1", Duration::from_secs(5)),
+            attempt("192.0.2.1", Duration::ZERO),
+        ];
+        let answer = race(attempts, Duration::from_millis(50), |_| true);
+        assert_eq!(answer.as_deref(), Some("192.0.2.1"));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn race_should_skip_answers_not_accepted() {
+        let attempts = vec![
+            attempt("2001:db8::1", Duration::ZERO),
+            attempt("192.0.2.1", Duration::from_millis(20)),
+        ];
+        let answer = race(attempts, Duration::from_secs(5), is_ipv4);
+        assert_eq!(answer.as_deref(), Some("192.0.2.1"));
+        let attempts = vec![attempt("2001:db8::1", Duration::ZERO)];
+        assert_eq!(race(attempts, Duration::from_secs(5), is_ipv4), None);
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::mpsc::Sender;
+
+use crate::dashboard::Trigger;
+use crate::SelfError;
+
+// Requests a refresh through trigger whenever the network configuration of the
+// host changes, e.g. when a laptop joins another network, so that the new
+// address is published without waiting for the next interval. The changes
+// are only observed on macOS, through the SystemConfiguration dynamic store.
+#[cfg(target_os = "macos")]
+pub fn watch(trigger: Sender<Trigger>) -> Result<(), SelfError> {
+    use core_foundation::array::CFArray;
+    use core_foundation::runloop::{kCFRunLoopCommonModes, CFRunLoop};
+    use core_foundation::string::CFString;
+    use std::sync::mpsc;
+    use std::thread;
+    use system_configuration::dynamic_store::{
+        SCDynamicStore, SCDynamicStoreBuilder, SCDynamicStoreCallBackContext,
+    };
+
+    fn changed(_: SCDynamicStore, keys: CFArray<CFString>, trigger: &mut Sender<Trigger>) {
+        let keys: Vec<String> = keys.iter().map(|key| (*key).to_string()).collect();
+        eprintln!("network changed ({}), refreshing", keys.join(", "));
+        let _ = trigger.send(None);
+    }
+
+    // the store is bound to the run loop of the thread creating it
+    let (registered, registration) = mpsc::channel();
+    thread::spawn(move || {
+        let store = SCDynamicStoreBuilder::new("dynuupdater")
+            .callback_context(SCDynamicStoreCallBackContext {
+                callout: changed,
+                info: trigger,
+            })
+            .build();
+        let keys = CFArray::from_CFTypes(&[
+            CFString::from("State:/Network/Global/IPv4"),
+            CFString::from("State:/Network/Global/IPv6"),
+        ]);
+        let patterns =
+            CFArray::from_CFTypes(&[CFString::from("State:/Network/Interface/[^/]+/IPv[46]")]);
+        if !store.set_notification_keys(&keys, &patterns) {
+            let _ = registered.send(false);
+            return;
+        }
+        let source = store.create_run_loop_source();
+        CFRunLoop::get_current().add_source(&source, unsafe { kCFRunLoopCommonModes });
+        let _ = registered.send(true);
+        CFRunLoop::run_current();
+    });
+    match registration.recv() {
+        Ok(true) => Ok(()),
+        _ => Err(SelfError::MsgError(
+            "cannot watch the network configuration".to_string(),
+        )),
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn watch(_: Sender<Trigger>) -> Result<(), SelfError> {
+    Err(SelfError::MsgError(
+        "watching network changes is only supported on macOS".to_string(),
+    ))
+}
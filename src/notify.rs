@@ -0,0 +1,196 @@
+use minijinja::Environment;
+use serde::Serialize;
+use std::fs;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::netutils;
+use crate::SelfError;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Updated,
+    Failed,
+}
+
+// Event describing the outcome of an operation on a domain, exposed as is to
+// the webhook templates.
+#[derive(Serialize, Debug, Clone)]
+pub struct Event {
+    pub kind: String,
+    pub status: Status,
+    pub domain: String,
+    pub node: Option<String>,
+    pub record_type: Option<String>,
+    pub old: Option<String>,
+    pub new: Option<String>,
+    pub error: Option<String>,
+    pub timestamp: u64,
+}
+
+impl Event {
+    pub fn new(kind: &str, status: Status, domain: &str) -> Event {
+        Event {
+            kind: kind.to_string(),
+            status,
+            domain: domain.to_string(),
+            node: None,
+            record_type: None,
+            old: None,
+            new: None,
+            error: None,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        }
+    }
+
+    pub fn node(mut self, node: &str) -> Event {
+        self.node = Some(node.to_string());
+        self
+    }
+
+    pub fn record_type(mut self, record_type: &str) -> Event {
+        self.record_type = Some(record_type.to_string());
+        self
+    }
+
+    pub fn values(mut self, old: Option<String>, new: Option<String>) -> Event {
+        self.old = old;
+        self.new = new;
+        self
+    }
+}
+
+pub struct Webhook {
+    pub url: String,
+    pub template: Option<String>,
+    pub content_type: String,
+}
+
+// Destination of notifications other than webhooks, such as plugins.
+pub trait Sink {
+    fn name(&self) -> String;
+    fn send(&self, event: &Event) -> Result<(), SelfError>;
+}
+
+pub struct Notifier {
+    client: reqwest::blocking::Client,
+    webhooks: Vec<Webhook>,
+    sinks: Vec<Box<dyn Sink>>,
+}
+
+pub fn load_template(path: &str) -> Result<String, SelfError> {
+    fs::read_to_string(path)
+        .map_err(|e| SelfError::MsgError(format!("cannot read template {}: {}", path, e)))
+}
+
+pub fn render(template: &str, event: &Event) -> Result<String, SelfError> {
+    let mut environment = Environment::new();
+    environment
+        .add_template("webhook", template)
+        .and_then(|_| environment.get_template("webhook"))
+        .and_then(|t| t.render(event))
+        .map_err(|e| SelfError::MsgError(format!("cannot render webhook template: {}", e)))
+}
+
+impl Notifier {
+    pub fn new(webhooks: Vec<Webhook>, sinks: Vec<Box<dyn Sink>>) -> Result<Notifier, SelfError> {
+        let client = netutils::http_client()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .map_err(|e| SelfError::MsgError(format!("{}", e)))?;
+        Ok(Notifier {
+            client,
+            webhooks,
+            sinks,
+        })
+    }
+
+    fn send(&self, webhook: &Webhook, event: &Event) -> Result<(), SelfError> {
+        let body = match &webhook.template {
+            Some(template) => render(template, event)?,
+            None => serde_json::to_string(event).unwrap(),
+        };
+        let response = self
+            .client
+            .post(&webhook.url)
+            .header(reqwest::header::CONTENT_TYPE, &webhook.content_type)
+            .body(body)
+            .send()
+            .map_err(|e| SelfError::MsgError(format!("{}", e)))?;
+        if !response.status().is_success() {
+            return Err(SelfError::MsgError(format!(
+                "webhook answered with status_code={}",
+                response.status().as_str()
+            )));
+        }
+        Ok(())
+    }
+
+    // Delivery failures are only logged, notifications never fail the operation
+    // they describe.
+    pub fn notify(&self, event: &Event) {
+        for webhook in &self.webhooks {
+            if let Err(e) = self.send(webhook, event) {
+                eprintln!("cannot notify {} event to webhook: {}", event.kind, e);
+            }
+        }
+        for sink in &self.sinks {
+            if let Err(e) = sink.send(event) {
+                eprintln!(
+                    "cannot notify {} event to {}: {}",
+                    event.kind,
+                    sink.name(),
+                    e
+                );
+            }
+        }
+    }
+
+    pub fn on_failure<T>(
+        &self,
+        result: Result<T, SelfError>,
+        kind: &str,
+        domain: &str,
+        node: Option<&str>,
+    ) -> Result<T, SelfError> {
+        if let Err(e) = &result {
+            let mut event = Event::new(kind, Status::Failed, domain);
+            event.node = node.map(|n| n.to_string());
+            event.error = Some(format!("{}", e));
+            self.notify(&event);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_should_expose_event_fields() {
+        let event = Event::new("refresh", Status::Updated, "example.dynu.net")
+            .record_type("A")
+            .values(Some("192.0.2.1".to_string()), Some("192.0.2.2".to_string()));
+        let rendered = render(
+            "{{ domain }} {{ record_type }} {{ old }} -> {{ new }} ({{ status }})",
+            &event,
+        )
+        .unwrap();
+        assert_eq!(
+            rendered,
+            "example.dynu.net A 192.0.2.1 -> 192.0.2.2 (updated)"
+        );
+    }
+
+    #[test]
+    fn render_should_fail_on_invalid_template() {
+        let event = Event::new("refresh", Status::Failed, "example.dynu.net");
+        assert!(render("{{ domain ", &event).is_err());
+    }
+}
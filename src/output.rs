@@ -0,0 +1,308 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::sync::Mutex;
+
+use crate::suggest::did_you_mean;
+use crate::SelfError;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Csv,
+    Tsv,
+}
+
+// While capturing, as for the ansible report, the values printed on stdout are
+// kept instead, listings as JSON.
+static CAPTURED: Mutex<Option<Vec<Value>>> = Mutex::new(None);
+
+pub fn capture() {
+    *CAPTURED.lock().unwrap() = Some(Vec::new());
+}
+
+pub fn captured() -> Vec<Value> {
+    CAPTURED.lock().unwrap().take().unwrap_or_default()
+}
+
+fn capturing() -> bool {
+    CAPTURED.lock().unwrap().is_some()
+}
+
+fn print_or_capture(value: Value, print: impl FnOnce()) {
+    match CAPTURED.lock().unwrap().as_mut() {
+        Some(captured) => captured.push(value),
+        None => print(),
+    }
+}
+
+pub fn print_json<T: Serialize>(value: &T) {
+    print_or_capture(serde_json::to_value(value).unwrap(), || {
+        println!("{}", serde_json::to_string_pretty(value).unwrap())
+    });
+}
+
+pub fn print_text(text: &str) {
+    print_or_capture(Value::String(text.to_string()), || print!("{}", text));
+}
+
+// How listings are printed: the format and the columns to keep, all of them
+// when fields is empty.
+pub struct Listing {
+    pub format: OutputFormat,
+    pub fields: Vec<String>,
+}
+
+impl Listing {
+    // Listings are printed as tables, except for JSON where the listed value is
+    // printed as is. When fields are selected the JSON output is the projection
+    // of the table instead, with the selected columns as keys.
+    pub fn print<T: Serialize>(
+        &self,
+        value: &T,
+        table: impl FnOnce() -> Table,
+    ) -> Result<(), SelfError> {
+        let format = if capturing() {
+            OutputFormat::Json
+        } else {
+            self.format
+        };
+        if self.fields.is_empty() && format == OutputFormat::Json {
+            print_json(value);
+            return Ok(());
+        }
+        let table = table().select(&self.fields)?;
+        match format {
+            OutputFormat::Text => println!("{}", table.render()),
+            OutputFormat::Json => print_json(&table.to_json()),
+            OutputFormat::Csv => print!("{}", table.render_delimited(',')),
+            OutputFormat::Tsv => print!("{}", table.render_delimited('\t')),
+        }
+        Ok(())
+    }
+}
+
+// CSV cells are quoted when needed as in RFC 4180, TSV has no quoting so tabs
+// and line breaks in cells are replaced by spaces.
+fn delimited_cell(cell: &str, delimiter: char) -> String {
+    if delimiter != ',' {
+        return cell.replace(['\t', '\r', '\n'], " ");
+    }
+    if cell.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+pub struct Table {
+    pub headers: Vec<&'static str>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: Vec<&'static str>) -> Table {
+        Table {
+            headers,
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    pub fn render(&self) -> String {
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.len()).collect();
+        for row in &self.rows {
+            for (i, cell) in row.iter().enumerate() {
+                widths[i] = widths[i].max(cell.chars().count());
+            }
+        }
+        let headers: Vec<String> = self.headers.iter().map(|h| h.to_string()).collect();
+        std::iter::once(&headers)
+            .chain(self.rows.iter())
+            .map(|row| {
+                row.iter()
+                    .enumerate()
+                    .map(|(i, cell)| format!("{:width$}", cell, width = widths[i]))
+                    .collect::<Vec<String>>()
+                    .join("  ")
+                    .trim_end()
+                    .to_string()
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    // Columns are named by their lowercase headers, in the order requested.
+    pub fn select(self, fields: &[String]) -> Result<Table, SelfError> {
+        if fields.is_empty() {
+            return Ok(self);
+        }
+        let names: Vec<String> = self.headers.iter().map(|h| h.to_lowercase()).collect();
+        let mut indexes = Vec::new();
+        for field in fields {
+            let field = field.trim().to_lowercase();
+            let index = names.iter().position(|n| *n == field).ok_or_else(|| {
+                SelfError::MsgError(format!(
+                    "unknown field {}{}, available fields are {}",
+                    field,
+                    did_you_mean(&field, names.iter().map(|n| n.as_str())),
+                    names.join(",")
+                ))
+            })?;
+            indexes.push(index);
+        }
+        Ok(Table {
+            headers: indexes.iter().map(|&i| self.headers[i]).collect(),
+            rows: self
+                .rows
+                .iter()
+                .map(|row| indexes.iter().map(|&i| row[i].clone()).collect())
+                .collect(),
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        Value::Array(
+            self.rows
+                .iter()
+                .map(|row| {
+                    let object: Map<String, Value> = self
+                        .headers
+                        .iter()
+                        .zip(row)
+                        .map(|(header, cell)| (header.to_lowercase(), Value::from(cell.as_str())))
+                        .collect();
+                    Value::Object(object)
+                })
+                .collect(),
+        )
+    }
+
+    pub fn render_delimited(&self, delimiter: char) -> String {
+        let headers: Vec<String> = self.headers.iter().map(|h| h.to_string()).collect();
+        std::iter::once(&headers)
+            .chain(self.rows.iter())
+            .map(|row| {
+                let cells: Vec<String> = row
+                    .iter()
+                    .map(|cell| delimited_cell(cell, delimiter))
+                    .collect();
+                format!("{}\n", cells.join(&delimiter.to_string()))
+            })
+            .collect()
+    }
+}
+
+// Top level fields whose serialized value differs between old and new, with
+// the old and new values.
+pub fn field_diff<T: Serialize>(old: &T, new: &T) -> Vec<(String, Value, Value)> {
+    let old = serde_json::to_value(old).unwrap_or(Value::Null);
+    let new = serde_json::to_value(new).unwrap_or(Value::Null);
+    let (Value::Object(old), Value::Object(new)) = (old, new) else {
+        return Vec::new();
+    };
+    let mut fields: Vec<&String> = old.keys().chain(new.keys()).collect();
+    fields.sort();
+    fields.dedup();
+    fields
+        .into_iter()
+        .filter_map(|field| {
+            let before = old.get(field).cloned().unwrap_or(Value::Null);
+            let after = new.get(field).cloned().unwrap_or(Value::Null);
+            (before != after).then(|| (field.clone(), before, after))
+        })
+        .collect()
+}
+
+pub fn or_dash(value: &Option<String>) -> String {
+    value.clone().unwrap_or_else(|| "-".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_should_align_columns() {
+        let mut table = Table::new(vec!["ID", "NAME"]);
+        table.push(vec!["1".to_string(), "example.dynu.net".to_string()]);
+        table.push(vec!["1234".to_string(), "a".to_string()]);
+        assert_eq!(
+            table.render(),
+            "ID    NAME\n1     example.dynu.net\n1234  a"
+        );
+    }
+
+    #[test]
+    fn render_delimited_should_escape_cells() {
+        let mut table = Table::new(vec!["NODE", "VALUE"]);
+        table.push(vec!["@".to_string(), "\"v=spf1, -all\"".to_string()]);
+        table.push(vec!["_acme".to_string(), "a\tb".to_string()]);
+        assert_eq!(
+            table.render_delimited(','),
+            "NODE,VALUE\n@,\"\"\"v=spf1, -all\"\"\"\n_acme,a\tb\n"
+        );
+        assert_eq!(
+            table.render_delimited('\t'),
+            "NODE\tVALUE\n@\t\"v=spf1, -all\"\n_acme\ta b\n"
+        );
+    }
+
+    #[test]
+    fn select_should_project_columns_in_requested_order() {
+        let mut table = Table::new(vec!["ID", "NODE", "TYPE", "VALUE"]);
+        table.push(vec![
+            "1".to_string(),
+            "@".to_string(),
+            "TXT".to_string(),
+            "v=spf1".to_string(),
+        ]);
+        let fields = vec!["value".to_string(), "ID".to_string()];
+        let selected = table.select(&fields).unwrap();
+        assert_eq!(selected.render(), "VALUE   ID\nv=spf1  1");
+        assert_eq!(
+            selected.to_json(),
+            serde_json::json!([{ "value": "v=spf1", "id": "1" }])
+        );
+    }
+
+    #[test]
+    fn select_should_reject_unknown_fields() {
+        let table = Table::new(vec!["ID", "NODE"]);
+        match table.select(&["nodes".to_string()]) {
+            Err(error) => assert!(format!("{}", error).contains("did you mean node?")),
+            Ok(_) => panic!("expected unknown field error"),
+        }
+    }
+
+    #[test]
+    fn field_diff_should_report_changed_fields_only() {
+        #[derive(Serialize)]
+        struct Domain {
+            ttl: u64,
+            ipv4: Option<String>,
+            group: String,
+        }
+        let old = Domain {
+            ttl: 90,
+            ipv4: None,
+            group: "home".to_string(),
+        };
+        let new = Domain {
+            ttl: 90,
+            ipv4: Some("192.0.2.1".to_string()),
+            group: "".to_string(),
+        };
+        assert_eq!(
+            field_diff(&old, &new),
+            vec![
+                ("group".to_string(), Value::from("home"), Value::from("")),
+                ("ipv4".to_string(), Value::Null, Value::from("192.0.2.1")),
+            ]
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use clap::Args;
+use serde_json::{Map, Value};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::dynu::GenericRecordDTO;
+use crate::SelfError;
+
+// Fields dynu requires for the common record types, with the flag setting
+// each of them. The other types are left for dynu to validate.
+const REQUIRED_FIELDS: [(&str, &[(&str, &str)]); 8] = [
+    ("A", &[("ipv4Address", "--ipv4")]),
+    ("AAAA", &[("ipv6Address", "--ipv6")]),
+    ("CNAME", &[("host", "--host")]),
+    ("MX", &[("host", "--host"), ("priority", "--priority")]),
+    ("NS", &[("host", "--host")]),
+    ("TXT", &[("textData", "--text")]),
+    (
+        "SRV",
+        &[
+            ("host", "--host"),
+            ("priority", "--priority"),
+            ("weight", "--weight"),
+            ("port", "--port"),
+        ],
+    ),
+    (
+        "CAA",
+        &[
+            ("flags", "--flags"),
+            ("tag", "--tag"),
+            ("value", "--caa-value"),
+        ],
+    ),
+];
+
+// Fields of the record commands specific to some record types, sent to dynu
+// under the names it gives them. The JSON payload is applied last, so it can
+// set any field, including the ones without a flag.
+#[derive(Args, Debug, Default)]
+pub struct RecordFields {
+    #[arg(long, help = "Address of A records")]
+    ipv4: Option<Ipv4Addr>,
+    #[arg(long, help = "Address of AAAA records")]
+    ipv6: Option<Ipv6Addr>,
+    #[arg(long, help = "Target of CNAME, MX, NS and SRV records")]
+    host: Option<String>,
+    #[arg(long, help = "Text of TXT records")]
+    text: Option<String>,
+    #[arg(long, help = "Priority of MX and SRV records")]
+    priority: Option<u16>,
+    #[arg(long, help = "Weight of SRV records")]
+    weight: Option<u16>,
+    #[arg(long, help = "Port of SRV records")]
+    port: Option<u16>,
+    #[arg(long, help = "Flags of CAA records")]
+    flags: Option<u8>,
+    #[arg(long, help = "Tag of CAA records, e.g. issue")]
+    tag: Option<String>,
+    #[arg(long, help = "Value of CAA records, e.g. letsencrypt.org")]
+    caa_value: Option<String>,
+    #[arg(
+        long,
+        help = "JSON object of record fields as dynu names them, e.g. '{\"host\": \"mail.example.com\", \"priority\": 10}'"
+    )]
+    json: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "json",
+        help = "Read the JSON object of record fields from this file"
+    )]
+    json_file: Option<String>,
+}
+
+impl RecordFields {
+    fn payload(&self) -> Result<Map<String, Value>, SelfError> {
+        let mut fields = Map::new();
+        let mut set = |key: &str, value: Option<Value>| {
+            if let Some(value) = value {
+                fields.insert(key.to_string(), value);
+            }
+        };
+        set("ipv4Address", self.ipv4.map(|a| a.to_string().into()));
+        set("ipv6Address", self.ipv6.map(|a| a.to_string().into()));
+        set("host", self.host.clone().map(Value::from));
+        set("textData", self.text.clone().map(Value::from));
+        set("priority", self.priority.map(Value::from));
+        set("weight", self.weight.map(Value::from));
+        set("port", self.port.map(Value::from));
+        set("flags", self.flags.map(Value::from));
+        set("tag", self.tag.clone().map(Value::from));
+        set("value", self.caa_value.clone().map(Value::from));
+        let json = match (&self.json, &self.json_file) {
+            (Some(json), _) => Some(json.clone()),
+            (None, Some(path)) => Some(std::fs::read_to_string(path).map_err(|e| {
+                SelfError::MsgError(format!("cannot read record fields from {}: {}", path, e))
+            })?),
+            (None, None) => None,
+        };
+        if let Some(json) = json {
+            match serde_json::from_str(&json) {
+                Ok(Value::Object(object)) => fields.extend(object),
+                Ok(_) => {
+                    return Err(SelfError::MsgError(
+                        "the record fields must be a JSON object".to_string(),
+                    ))
+                }
+                Err(e) => {
+                    return Err(SelfError::MsgError(format!(
+                        "invalid JSON record fields: {}",
+                        e
+                    )))
+                }
+            }
+        }
+        Ok(fields)
+    }
+}
+
+fn to_record(object: Map<String, Value>) -> Result<GenericRecordDTO, SelfError> {
+    let record: GenericRecordDTO = serde_json::from_value(Value::Object(object))
+        .map_err(|e| SelfError::MsgError(format!("invalid record: {}", e)))?;
+    if record.record_type == "SOA" {
+        return Err(SelfError::MsgError(
+            "SOA records are managed by dynu and can't be written".to_string(),
+        ));
+    }
+    let required = REQUIRED_FIELDS
+        .iter()
+        .find(|(record_type, _)| *record_type == record.record_type)
+        .map_or(&[][..], |(_, fields)| *fields);
+    let missing: Vec<&str> = required
+        .iter()
+        .filter(|(field, _)| !record.other.contains_key(*field))
+        .map(|(_, flag)| *flag)
+        .collect();
+    if !missing.is_empty() {
+        return Err(SelfError::MsgError(format!(
+            "{} records require {}",
+            record.record_type,
+            missing.join(", ")
+        )));
+    }
+    Ok(record)
+}
+
+// A record to create, of any type dynu accepts.
+pub fn new_record(
+    record_type: &str,
+    node: &str,
+    ttl: u64,
+    fields: &RecordFields,
+) -> Result<GenericRecordDTO, SelfError> {
+    let mut object = Map::new();
+    object.insert("nodeName".to_string(), node.into());
+    object.insert("recordType".to_string(), record_type.to_uppercase().into());
+    object.insert("ttl".to_string(), ttl.into());
+    object.insert("state".to_string(), true.into());
+    object.extend(fields.payload()?);
+    to_record(object)
+}
+
+// The record with the node, TTL and fields given replacing its own, the
+// fields not given being sent back as dynu answered them.
+pub fn updated_record(
+    record: &GenericRecordDTO,
+    node: Option<&str>,
+    ttl: Option<u64>,
+    fields: &RecordFields,
+) -> Result<GenericRecordDTO, SelfError> {
+    let Value::Object(mut object) = serde_json::to_value(record).unwrap() else {
+        unreachable!("records are serialised as objects")
+    };
+    if let Some(node) = node {
+        object.insert("nodeName".to_string(), node.into());
+    }
+    if let Some(ttl) = ttl {
+        object.insert("ttl".to_string(), ttl.into());
+    }
+    object.extend(fields.payload()?);
+    let updated = to_record(object)?;
+    if updated.record_type != record.record_type || updated.id != record.id {
+        return Err(SelfError::MsgError(format!(
+            "the type and id of record id={} cannot be changed, delete it and create a new one instead",
+            record.id.unwrap_or_default()
+        )));
+    }
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn new_record_should_take_the_flags_and_the_json_payload() {
+        let fields = RecordFields {
+            host: Some("mail.example.com".to_string()),
+            priority: Some(10),
+            json: Some(r#"{"priority": 20}"#.to_string()),
+            ..RecordFields::default()
+        };
+        let record = new_record("mx", "", 300, &fields).unwrap();
+        assert_eq!(record.record_type, "MX");
+        assert_eq!(record.other["host"], json!("mail.example.com"));
+        assert_eq!(record.other["priority"], json!(20));
+        assert_eq!(record.value(), "host=mail.example.com priority=20");
+
+        let missing = new_record("SRV", "_sip._tcp", 300, &fields).unwrap_err();
+        assert_eq!(
+            missing.to_string(),
+            "MsgError(SRV records require --weight, --port)"
+        );
+        assert!(new_record("SOA", "", 300, &RecordFields::default()).is_err());
+    }
+
+    #[test]
+    fn updated_record_should_keep_the_fields_not_given() {
+        let record: GenericRecordDTO = serde_json::from_value(json!({
+            "id": 7,
+            "domainId": 1,
+            "nodeName": "www",
+            "recordType": "AAAA",
+            "ttl": 300,
+            "state": true,
+            "ipv6Address": "2001:db8::1",
+        }))
+        .unwrap();
+        let updated = updated_record(&record, None, Some(120), &RecordFields::default()).unwrap();
+        assert_eq!(updated.ttl, 120);
+        assert_eq!(updated.node_name, "www");
+        assert_eq!(updated.other["ipv6Address"], json!("2001:db8::1"));
+
+        let retyped = RecordFields {
+            json: Some(r#"{"recordType": "A", "ipv4Address": "192.0.2.1"}"#.to_string()),
+            ..RecordFields::default()
+        };
+        assert!(updated_record(&record, None, None, &retyped).is_err());
+    }
+}
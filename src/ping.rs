@@ -0,0 +1,125 @@
+use serde::Serialize;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::output::Table;
+
+pub struct PingOptions {
+    pub count: u32,
+    pub interval: Duration,
+}
+
+// Latencies in milliseconds of the successful requests, the failed ones only
+// counting towards the error rate.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Summary {
+    pub requests: u32,
+    pub errors: u32,
+    pub error_rate: f64,
+    pub min_ms: Option<f64>,
+    pub avg_ms: Option<f64>,
+    pub p95_ms: Option<f64>,
+    pub max_ms: Option<f64>,
+}
+
+fn millis(duration: Duration) -> f64 {
+    (duration.as_secs_f64() * 100_000.0).round() / 100.0
+}
+
+// Nearest-rank percentile of the sorted samples.
+fn percentile(sorted: &[Duration], percent: usize) -> Option<Duration> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (percent * sorted.len()).div_ceil(100).max(1);
+    sorted.get(rank - 1).copied()
+}
+
+pub fn summarise(samples: &[Duration], errors: u32) -> Summary {
+    let mut sorted = samples.to_vec();
+    sorted.sort();
+    let requests = samples.len() as u32 + errors;
+    let total: Duration = sorted.iter().sum();
+    Summary {
+        requests,
+        errors,
+        error_rate: if requests == 0 {
+            0.0
+        } else {
+            errors as f64 / requests as f64
+        },
+        min_ms: sorted.first().map(|d| millis(*d)),
+        avg_ms: (!sorted.is_empty()).then(|| millis(total / sorted.len() as u32)),
+        p95_ms: percentile(&sorted, 95).map(millis),
+        max_ms: sorted.last().map(|d| millis(*d)),
+    }
+}
+
+// Times count calls to request, pausing interval between them. Each outcome is
+// logged as it comes so that a slow run shows progress.
+pub fn run<E, F>(options: &PingOptions, mut request: F) -> Summary
+where
+    E: std::fmt::Display,
+    F: FnMut() -> Result<(), E>,
+{
+    let mut samples = Vec::new();
+    let mut errors = 0;
+    for i in 0..options.count {
+        if i > 0 {
+            thread::sleep(options.interval);
+        }
+        let started = Instant::now();
+        match request() {
+            Ok(()) => {
+                let elapsed = started.elapsed();
+                eprintln!("request {}: {} ms", i + 1, millis(elapsed));
+                samples.push(elapsed);
+            }
+            Err(e) => {
+                eprintln!("request {}: failed: {}", i + 1, e);
+                errors += 1;
+            }
+        }
+    }
+    summarise(&samples, errors)
+}
+
+impl Summary {
+    pub fn table(&self) -> Table {
+        let ms = |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{:.2}", v));
+        let mut table = Table::new(vec!["REQUESTS", "ERRORS", "MIN", "AVG", "P95", "MAX"]);
+        table.push(vec![
+            self.requests.to_string(),
+            format!("{} ({:.0}%)", self.errors, self.error_rate * 100.0),
+            ms(self.min_ms),
+            ms(self.avg_ms),
+            ms(self.p95_ms),
+            ms(self.max_ms),
+        ]);
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarise_should_compute_percentiles_and_error_rate() {
+        let samples: Vec<Duration> = (1..=20)
+            .rev()
+            .map(|ms| Duration::from_millis(ms * 10))
+            .collect();
+        let summary = summarise(&samples, 5);
+        assert_eq!(summary.requests, 25);
+        assert_eq!(summary.error_rate, 0.2);
+        assert_eq!(summary.min_ms, Some(10.0));
+        assert_eq!(summary.avg_ms, Some(105.0));
+        assert_eq!(summary.p95_ms, Some(190.0));
+        assert_eq!(summary.max_ms, Some(200.0));
+
+        let failed = summarise(&[], 3);
+        assert_eq!(failed.error_rate, 1.0);
+        assert_eq!(failed.p95_ms, None);
+    }
+}
@@ -0,0 +1,88 @@
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::io::{self, IsTerminal};
+use std::thread;
+use std::time::Duration;
+
+use crate::{is_rate_limited, SelfError};
+
+const TEMPLATE: &str = "{prefix} [{bar:30}] {pos}/{len} (eta {eta}) {msg}";
+const RATE_LIMIT_PAUSE: Duration = Duration::from_secs(30);
+const RATE_LIMIT_ATTEMPTS: u32 = 3;
+
+// Progress of an operation over several items, drawn on stderr only when it is
+// attached to a terminal so that logs collected by cron or systemd stay clean.
+pub struct Progress {
+    bar: ProgressBar,
+}
+
+impl Progress {
+    pub fn new(len: usize, prefix: &str) -> Progress {
+        if !io::stderr().is_terminal() {
+            return Progress::hidden();
+        }
+        Progress::with_bar(
+            ProgressBar::with_draw_target(Some(len as u64), ProgressDrawTarget::stderr()),
+            prefix,
+        )
+    }
+
+    // For work which is also part of larger operations, e.g. the backup of a
+    // single domain during refresh, where a bar would only flicker.
+    pub fn hidden() -> Progress {
+        Progress::with_bar(ProgressBar::hidden(), "")
+    }
+
+    fn with_bar(bar: ProgressBar, prefix: &str) -> Progress {
+        bar.set_style(
+            ProgressStyle::with_template(TEMPLATE)
+                .unwrap()
+                .progress_chars("=> "),
+        );
+        bar.set_prefix(prefix.to_string());
+        Progress { bar }
+    }
+
+    // Runs the work for one item with the bar cleared, so that the logs it
+    // writes on stderr don't get mixed with the bar. Work rejected by Dynu's
+    // rate limiting is retried after a pause instead of failing the item.
+    pub fn item<T, F>(&self, name: &str, mut work: F) -> Result<T, SelfError>
+    where
+        F: FnMut() -> Result<T, SelfError>,
+    {
+        let mut attempt = 1;
+        loop {
+            self.bar.set_message(name.to_string());
+            match self.bar.suspend(&mut work) {
+                Err(e) if is_rate_limited(&e) && attempt < RATE_LIMIT_ATTEMPTS => {
+                    attempt += 1;
+                    self.pause(name)
+                }
+                result => {
+                    self.bar.inc(1);
+                    return result;
+                }
+            }
+        }
+    }
+
+    // Counts an item whose work was done elsewhere, e.g. by the concurrent
+    // requests of the client.
+    pub fn inc(&self) {
+        self.bar.inc(1);
+    }
+
+    fn pause(&self, name: &str) {
+        let message = format!(
+            "rate limited by dynu on {}, pausing for {}",
+            name,
+            humantime::format_duration(RATE_LIMIT_PAUSE)
+        );
+        self.bar.suspend(|| eprintln!("{}", message));
+        self.bar.set_message(message);
+        thread::sleep(RATE_LIMIT_PAUSE);
+    }
+
+    pub fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+}
@@ -0,0 +1,651 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use dynuupdater::netutils::http_client;
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::content::same_content;
+use crate::dns::{
+    self, Message, RCODE_NOERROR, RCODE_NXDOMAIN, TYPE_A, TYPE_AAAA, TYPE_NS, TYPE_TXT,
+};
+use crate::names::same_name;
+use crate::output::{or_dash, Table};
+use crate::SelfError;
+
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(40);
+
+fn propagation_error(message: String) -> SelfError {
+    SelfError::MsgError(format!("propagation: {}", message))
+}
+
+// Where a change is checked: the authoritative servers of the zone, which
+// serve it as soon as dynu published it, or resolvers answering from their
+// cache, which show when the clients using them see it. A resolver is the one
+// of the system, one given by address (port 53 unless given) or a DNS over
+// HTTPS endpoint.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolver {
+    Authoritative,
+    System,
+    Address(SocketAddr),
+    Doh(String),
+}
+
+impl FromStr for Resolver {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Resolver, String> {
+        match s {
+            "authoritative" => Ok(Resolver::Authoritative),
+            "system" => Ok(Resolver::System),
+            url if url.starts_with("https://") => Ok(Resolver::Doh(url.to_string())),
+            address => address
+                .parse()
+                .or_else(|_| address.parse().map(|ip| SocketAddr::new(ip, 53)))
+                .map(Resolver::Address)
+                .map_err(|_| {
+                    format!(
+                        "invalid resolver {}, expected authoritative, system, an address or an https:// URL",
+                        address
+                    )
+                }),
+        }
+    }
+}
+
+impl fmt::Display for Resolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Resolver::Authoritative => write!(f, "authoritative"),
+            Resolver::System => write!(f, "system"),
+            Resolver::Address(address) if address.port() == 53 => write!(f, "{}", address.ip()),
+            Resolver::Address(address) => write!(f, "{}", address),
+            Resolver::Doh(url) => write!(f, "{}", url),
+        }
+    }
+}
+
+// First nameserver of resolv.conf, the resolver of the system.
+fn parse_resolv_conf(content: &str) -> Option<IpAddr> {
+    content.lines().find_map(|line| {
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("nameserver"), Some(address)) => address.parse().ok(),
+            _ => None,
+        }
+    })
+}
+
+fn system_resolver() -> Result<IpAddr, SelfError> {
+    fs::read_to_string(RESOLV_CONF)
+        .ok()
+        .and_then(|content| parse_resolv_conf(&content))
+        .ok_or_else(|| propagation_error(format!("no nameserver found in {}", RESOLV_CONF)))
+}
+
+fn nanos() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0)
+}
+
+fn exchange(
+    server: SocketAddr,
+    name: &str,
+    qtype: u16,
+    recursion: bool,
+) -> Result<(Message, Vec<u8>), SelfError> {
+    let id = nanos() as u16;
+    let local = if server.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.set_read_timeout(Some(QUERY_TIMEOUT))?;
+    socket.send_to(
+        &Message::query(id, name, qtype, recursion).to_bytes(),
+        server,
+    )?;
+    let mut buffer = [0u8; 4096];
+    loop {
+        let (len, from) = socket.recv_from(&mut buffer)?;
+        let bytes = buffer[..len].to_vec();
+        match Message::parse(&bytes) {
+            Ok(parsed) if from == server && parsed.message.id == id => {
+                return Ok((parsed.message, bytes))
+            }
+            _ => continue,
+        }
+    }
+}
+
+// Queries are prefixed by their length over TCP, as in RFC 1035.
+fn tcp_exchange(
+    server: SocketAddr,
+    name: &str,
+    qtype: u16,
+    recursion: bool,
+) -> Result<Message, SelfError> {
+    let mut stream = TcpStream::connect_timeout(&server, QUERY_TIMEOUT)?;
+    stream.set_read_timeout(Some(QUERY_TIMEOUT))?;
+    let query = Message::query(nanos() as u16, name, qtype, recursion).to_bytes();
+    stream.write_all(&(query.len() as u16).to_be_bytes())?;
+    stream.write_all(&query)?;
+    let mut len = [0u8; 2];
+    stream.read_exact(&mut len)?;
+    let mut bytes = vec![0u8; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut bytes)?;
+    Message::parse(&bytes)
+        .map(|parsed| parsed.message)
+        .map_err(|e| propagation_error(format!("{} answered {}", server, e)))
+}
+
+// Whether name exists for the system resolver. Only NXDOMAIN tells it doesn't,
+// a lookup failing, e.g. without network, is an error rather than a no.
+pub fn name_exists(name: &str) -> Result<bool, SelfError> {
+    let resolver = SocketAddr::new(system_resolver()?, 53);
+    let (message, _) = exchange(resolver, name, TYPE_A, true)?;
+    match message.rcode() {
+        RCODE_NXDOMAIN => Ok(false),
+        RCODE_NOERROR => Ok(true),
+        rcode => Err(propagation_error(format!(
+            "{} answered rcode {} for {}",
+            resolver, rcode, name
+        ))),
+    }
+}
+
+// Addresses of the nameservers of the zone, of the same family as the system
+// resolver so that they can be reached.
+pub fn authoritative_servers(zone: &str) -> Result<Vec<SocketAddr>, SelfError> {
+    let resolver = system_resolver()?;
+    let (_, bytes) = exchange(SocketAddr::new(resolver, 53), zone, TYPE_NS, true)?;
+    let names = dns::ns_names(&bytes).map_err(|e| propagation_error(format!("{}", e)))?;
+    let servers: Vec<SocketAddr> = names
+        .iter()
+        .filter_map(|name| (name.as_str(), 53).to_socket_addrs().ok())
+        .flatten()
+        .filter(|address| address.is_ipv4() == resolver.is_ipv4())
+        .collect();
+    if servers.is_empty() {
+        return Err(propagation_error(format!(
+            "no reachable nameserver found for {}",
+            zone
+        )));
+    }
+    Ok(servers)
+}
+
+fn rdata_value(qtype: u16, rdata: &[u8]) -> Option<String> {
+    match qtype {
+        TYPE_A => dns::parse_a(rdata).ok().map(|a| a.to_string()),
+        TYPE_AAAA => dns::parse_aaaa(rdata).ok().map(|a| a.to_string()),
+        _ => dns::parse_txt(rdata).ok(),
+    }
+}
+
+// A DNS over HTTPS query as in RFC 8484, with id 0 so that it can be cached.
+fn doh_exchange(url: &str, name: &str, qtype: u16) -> Result<Message, SelfError> {
+    let query = URL_SAFE_NO_PAD.encode(Message::query(0, name, qtype, true).to_bytes());
+    let separator = if url.contains('?') { '&' } else { '?' };
+    let bytes = http_client()
+        .timeout(QUERY_TIMEOUT)
+        .build()
+        .and_then(|client| {
+            client
+                .get(format!("{}{}dns={}", url, separator, query))
+                .header(reqwest::header::ACCEPT, "application/dns-message")
+                .send()
+        })
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.bytes())
+        .map_err(|e| propagation_error(format!("{}", e)))?;
+    Message::parse(&bytes)
+        .map(|parsed| parsed.message)
+        .map_err(|e| propagation_error(format!("{} answered {}", url, e)))
+}
+
+// Ways of querying a server, each bypassing more of the caches between
+// dynuupdater and the server, tried in turn while a change doesn't show. The
+// name in random case, as in the 0x20 encoding, misses the caches keyed on the
+// exact name, such as the ones of the proxies some networks intercept DNS
+// with, and TCP misses the proxies only intercepting UDP.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Strategy {
+    Plain,
+    RandomCase,
+    Tcp,
+}
+
+impl Strategy {
+    fn for_attempt(attempt: u32) -> Strategy {
+        match attempt {
+            0 => Strategy::Plain,
+            1 => Strategy::RandomCase,
+            _ => Strategy::Tcp,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Strategy::Plain => "udp",
+            Strategy::RandomCase => "udp random case",
+            Strategy::Tcp => "tcp random case",
+        }
+    }
+}
+
+// The name with the case of its letters drawn at random, answers matching it
+// whatever their case.
+fn random_case(name: &str, seed: u32) -> String {
+    let mut state = seed | 1;
+    name.chars()
+        .map(|c| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            if state & 1 == 1 {
+                c.to_ascii_uppercase()
+            } else {
+                c.to_ascii_lowercase()
+            }
+        })
+        .collect()
+}
+
+// A server queried on behalf of a resolver, the authoritative resolver
+// standing for every authoritative server of the zone.
+enum Server {
+    Udp(SocketAddr, bool),
+    Doh(String),
+}
+
+impl fmt::Display for Server {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Server::Udp(address, _) => write!(f, "{}", address),
+            Server::Doh(url) => write!(f, "{}", url),
+        }
+    }
+}
+
+fn servers(resolver: &Resolver, zone: &str) -> Result<Vec<Server>, SelfError> {
+    Ok(match resolver {
+        Resolver::Authoritative => authoritative_servers(zone)?
+            .into_iter()
+            .map(|address| Server::Udp(address, false))
+            .collect(),
+        Resolver::System => vec![Server::Udp(SocketAddr::new(system_resolver()?, 53), true)],
+        Resolver::Address(address) => vec![Server::Udp(*address, true)],
+        Resolver::Doh(url) => vec![Server::Doh(url.clone())],
+    })
+}
+
+// DNS over HTTPS always goes over TCP, so its strategies stop at the random
+// case, which also misses the HTTP caches keyed on the query.
+fn served_values(
+    server: &Server,
+    name: &str,
+    qtype: u16,
+    strategy: Strategy,
+) -> Result<Vec<String>, SelfError> {
+    let queried = match strategy {
+        Strategy::Plain => name.to_string(),
+        _ => random_case(name, nanos()),
+    };
+    let message = match server {
+        Server::Udp(address, recursion) if strategy == Strategy::Tcp => {
+            tcp_exchange(*address, &queried, qtype, *recursion)?
+        }
+        Server::Udp(address, recursion) => {
+            let (message, _) = exchange(*address, &queried, qtype, *recursion)?;
+            if message.truncated() {
+                tcp_exchange(*address, &queried, qtype, *recursion)?
+            } else {
+                message
+            }
+        }
+        Server::Doh(url) => doh_exchange(url, &queried, qtype)?,
+    };
+    match message.rcode() {
+        RCODE_NXDOMAIN => Ok(vec![]),
+        RCODE_NOERROR => Ok(message
+            .answers
+            .iter()
+            .filter(|r| r.rtype == qtype && same_name(&r.name, name))
+            .filter_map(|r| rdata_value(qtype, &r.rdata))
+            .collect()),
+        rcode => Err(propagation_error(format!(
+            "{} answered rcode {}",
+            server, rcode
+        ))),
+    }
+}
+
+fn still_served(served: &[String], values: &[String]) -> bool {
+    served
+        .iter()
+        .any(|s| values.iter().any(|v| same_content("TXT", s, v)))
+}
+
+// Whether the addresses served are exactly the expected one.
+fn only_serves(served: &[String], qtype: u16, address: &str) -> bool {
+    let record_type = if qtype == TYPE_AAAA { "AAAA" } else { "A" };
+    !served.is_empty() && served.iter().all(|s| same_content(record_type, s, address))
+}
+
+// What a server served last for the name checked, and how long the change
+// took to show there and through which strategy.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ServerResult {
+    pub resolver: String,
+    pub server: String,
+    pub settled: bool,
+    pub after: Option<String>,
+    pub via: Option<String>,
+    pub served: Vec<String>,
+    pub error: Option<String>,
+}
+
+// Results of a verification on every server of the resolvers checked.
+pub struct Verification {
+    name: String,
+    expectation: String,
+    timeout: Duration,
+    pub results: Vec<ServerResult>,
+}
+
+impl Verification {
+    pub fn table(&self) -> Table {
+        let mut table = Table::new(vec![
+            "RESOLVER", "SERVER", "SETTLED", "AFTER", "VIA", "SERVED", "ERROR",
+        ]);
+        for result in &self.results {
+            table.push(vec![
+                result.resolver.clone(),
+                result.server.clone(),
+                result.settled.to_string(),
+                or_dash(&result.after),
+                or_dash(&result.via),
+                match result.served.is_empty() {
+                    true => "-".to_string(),
+                    false => result.served.join(","),
+                },
+                or_dash(&result.error),
+            ]);
+        }
+        table
+    }
+
+    // Fails when some server hadn't settled by the timeout.
+    pub fn result(&self) -> Result<(), SelfError> {
+        let pending: Vec<&str> = self
+            .results
+            .iter()
+            .filter(|r| !r.settled)
+            .map(|r| match r.server.as_str() {
+                "-" => r.resolver.as_str(),
+                server => server,
+            })
+            .collect();
+        if pending.is_empty() {
+            return Ok(());
+        }
+        Err(propagation_error(format!(
+            "{} not {} on {} after {}",
+            self.name,
+            self.expectation,
+            pending.join(", "),
+            humantime::format_duration(self.timeout)
+        )))
+    }
+}
+
+// Polls the servers of every resolver until the values of qtype they serve
+// for name are settled, or until timeout, the polls being further and further
+// apart. A server not answering counts as not settled, and a server not
+// settled is queried with the next strategy; a resolver whose servers cannot
+// be found counts as not settled without stopping the checks of the others.
+// expectation describes what is waited for in the results.
+fn wait_until(
+    resolvers: &[Resolver],
+    zone: &str,
+    name: &str,
+    qtype: u16,
+    timeout: Duration,
+    expectation: &str,
+    settled: impl Fn(&[String]) -> bool,
+) -> Result<Verification, SelfError> {
+    let start = Instant::now();
+    let deadline = start + timeout;
+    let mut servers_checked = Vec::new();
+    for resolver in resolvers {
+        let result = |server: String, error: Option<String>| ServerResult {
+            resolver: resolver.to_string(),
+            server,
+            settled: false,
+            after: None,
+            via: None,
+            served: vec![],
+            error,
+        };
+        match servers(resolver, zone) {
+            Ok(servers) => {
+                for server in servers {
+                    let result = result(server.to_string(), None);
+                    servers_checked.push((Some(server), 0, result));
+                }
+            }
+            Err(e) => {
+                eprintln!(
+                    "cannot find the servers of {} for {}: {}",
+                    resolver, zone, e
+                );
+                servers_checked.push((None, 0, result("-".to_string(), Some(format!("{}", e)))));
+            }
+        }
+    }
+    let mut interval = POLL_INTERVAL;
+    loop {
+        for (server, attempt, result) in servers_checked.iter_mut().filter(|(.., r)| !r.settled) {
+            let Some(server) = server else {
+                continue;
+            };
+            let strategy = Strategy::for_attempt(*attempt);
+            *attempt += 1;
+            match served_values(server, name, qtype, strategy) {
+                Ok(served) => {
+                    result.settled = settled(&served);
+                    result.served = served;
+                    result.error = None;
+                    if result.settled {
+                        let elapsed = Duration::from_secs(start.elapsed().as_secs());
+                        result.after = Some(humantime::format_duration(elapsed).to_string());
+                        result.via = Some(strategy.name().to_string());
+                        eprintln!("{} {} on {}", name, expectation, server);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("cannot query {} for {}: {}", server, name, e);
+                    result.error = Some(format!("{}", e));
+                }
+            }
+        }
+        let now = Instant::now();
+        let checked = servers_checked
+            .iter()
+            .all(|(server, _, r)| r.settled || server.is_none());
+        if checked || now >= deadline {
+            return Ok(Verification {
+                name: name.to_string(),
+                expectation: expectation.to_string(),
+                timeout,
+                results: servers_checked.into_iter().map(|(.., r)| r).collect(),
+            });
+        }
+        thread::sleep(interval.min(deadline - now));
+        interval = (interval * 2).min(MAX_POLL_INTERVAL);
+    }
+}
+
+// Waits until no server of the resolvers serves any of the TXT values for
+// name any longer.
+pub fn wait_until_gone(
+    resolvers: &[Resolver],
+    zone: &str,
+    name: &str,
+    values: &[String],
+    timeout: Duration,
+) -> Result<Verification, SelfError> {
+    wait_until(
+        resolvers,
+        zone,
+        name,
+        TYPE_TXT,
+        timeout,
+        "no longer served",
+        |served| !still_served(served, values),
+    )
+}
+
+// Waits until every server of the resolvers serves only address for name,
+// with qtype TYPE_A or TYPE_AAAA.
+pub fn wait_until_switched(
+    resolvers: &[Resolver],
+    zone: &str,
+    name: &str,
+    qtype: u16,
+    address: &str,
+    timeout: Duration,
+) -> Result<Verification, SelfError> {
+    let expectation = format!("switched to {}", address);
+    wait_until(
+        resolvers,
+        zone,
+        name,
+        qtype,
+        timeout,
+        &expectation,
+        |served| only_serves(served, qtype, address),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_resolv_conf_should_take_the_first_nameserver() {
+        let content = "# generated\nsearch lan\nnameserver 192.0.2.53\nnameserver 2001:db8::53\n";
+        assert_eq!(
+            parse_resolv_conf(content),
+            Some("192.0.2.53".parse().unwrap())
+        );
+        assert_eq!(parse_resolv_conf("search lan\n"), None);
+    }
+
+    #[test]
+    fn still_served_should_compare_normalised_values() {
+        let values = vec!["token".to_string()];
+        assert!(still_served(
+            &["other".to_string(), "token".to_string()],
+            &values
+        ));
+        assert!(!still_served(&["other".to_string()], &values));
+        assert!(!still_served(&[], &values));
+    }
+
+    #[test]
+    fn only_serves_should_require_the_single_address() {
+        let address = "2001:db8::1";
+        assert!(only_serves(
+            &["2001:DB8:0::1".to_string()],
+            TYPE_AAAA,
+            address
+        ));
+        assert!(!only_serves(
+            &["2001:db8::1".to_string(), "2001:db8::2".to_string()],
+            TYPE_AAAA,
+            address
+        ));
+        assert!(!only_serves(&[], TYPE_A, "192.0.2.1"));
+    }
+
+    #[test]
+    fn resolvers_should_be_parsed_from_their_names() {
+        let parse = |s: &str| s.parse::<Resolver>();
+        assert_eq!(parse("authoritative"), Ok(Resolver::Authoritative));
+        assert_eq!(parse("system"), Ok(Resolver::System));
+        assert_eq!(
+            parse("1.1.1.1"),
+            Ok(Resolver::Address("1.1.1.1:53".parse().unwrap()))
+        );
+        assert_eq!(
+            parse("[2001:db8::53]:5353").unwrap().to_string(),
+            "[2001:db8::53]:5353"
+        );
+        assert_eq!(parse("9.9.9.9").unwrap().to_string(), "9.9.9.9");
+        assert_eq!(
+            parse("https://dns.google/dns-query"),
+            Ok(Resolver::Doh("https://dns.google/dns-query".to_string()))
+        );
+        assert!(parse("http://dns.google/dns-query").is_err());
+    }
+
+    #[test]
+    fn strategies_should_bypass_more_caches_at_each_attempt() {
+        assert_eq!(Strategy::for_attempt(0), Strategy::Plain);
+        assert_eq!(Strategy::for_attempt(1), Strategy::RandomCase);
+        assert_eq!(Strategy::for_attempt(5), Strategy::Tcp);
+        let name = "_acme-challenge.example.dynu.net";
+        let randomised = random_case(name, 0x5eed);
+        assert_ne!(randomised, name);
+        assert!(same_name(&randomised, name));
+        assert_eq!(random_case(name, 0x5eed), randomised);
+    }
+
+    #[test]
+    fn verification_should_fail_on_the_servers_not_settled() {
+        let result = |server: &str, settled: bool| ServerResult {
+            resolver: "authoritative".to_string(),
+            server: server.to_string(),
+            settled,
+            after: settled.then(|| "10s".to_string()),
+            via: settled.then(|| "udp".to_string()),
+            served: vec!["token".to_string()],
+            error: None,
+        };
+        let mut verification = Verification {
+            name: "_acme-challenge.example.dynu.net".to_string(),
+            expectation: "no longer served".to_string(),
+            timeout: Duration::from_secs(120),
+            results: vec![result("192.0.2.1:53", true), result("192.0.2.2:53", false)],
+        };
+        assert_eq!(
+            verification.result().unwrap_err().to_string(),
+            "MsgError(propagation: _acme-challenge.example.dynu.net not no longer served on 192.0.2.2:53 after 2m)"
+        );
+        assert_eq!(verification.table().rows[0][3], "10s");
+        verification.results[1].settled = true;
+        assert!(verification.result().is_ok());
+        verification.results.push(ServerResult {
+            resolver: "system".to_string(),
+            error: Some("no nameserver found in /etc/resolv.conf".to_string()),
+            ..result("-", false)
+        });
+        assert_eq!(
+            verification.result().unwrap_err().to_string(),
+            "MsgError(propagation: _acme-challenge.example.dynu.net not no longer served on system after 2m)"
+        );
+    }
+}
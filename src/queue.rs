@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+
+use crate::state::StateDir;
+use crate::SelfError;
+
+const QUEUE_FILE: &str = "queue.json";
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum PendingChange {
+    DomainAddresses {
+        domain: String,
+        ipv4: Option<String>,
+        ipv6: Option<String>,
+    },
+    TxtValue {
+        domain: String,
+        node: String,
+        value: String,
+        ttl: u64,
+    },
+}
+
+impl PendingChange {
+    // Two changes with the same target overwrite the same data in Dynu, so only
+    // the most recent one needs to be retried.
+    pub fn same_target(&self, other: &PendingChange) -> bool {
+        match (self, other) {
+            (
+                PendingChange::DomainAddresses { domain: a, .. },
+                PendingChange::DomainAddresses { domain: b, .. },
+            ) => a == b,
+            (
+                PendingChange::TxtValue {
+                    domain: a,
+                    node: node_a,
+                    ..
+                },
+                PendingChange::TxtValue {
+                    domain: b,
+                    node: node_b,
+                    ..
+                },
+            ) => a == b && node_a == node_b,
+            _ => false,
+        }
+    }
+}
+
+pub enum ReplayOutcome {
+    Applied,
+    // the provider is still unreachable, the change must be retried later
+    Unavailable,
+}
+
+// Changes which couldn't be applied because Dynu was unreachable, persisted in
+// the state directory until a later run manages to apply them.
+pub struct Queue<'a> {
+    state: &'a StateDir,
+    changes: Vec<PendingChange>,
+}
+
+impl<'a> Queue<'a> {
+    pub fn load(state: &'a StateDir) -> Result<Queue<'a>, SelfError> {
+        let changes = state.read_json(QUEUE_FILE)?.unwrap_or_default();
+        Ok(Queue { state, changes })
+    }
+
+    fn save(&self) -> Result<(), SelfError> {
+        self.state.write_json(QUEUE_FILE, &self.changes)
+    }
+
+    pub fn push(&mut self, change: PendingChange) -> Result<(), SelfError> {
+        self.changes.retain(|c| !c.same_target(&change));
+        self.changes.push(change);
+        self.save()
+    }
+
+    pub fn discard(&mut self, superseded_by: &PendingChange) -> Result<(), SelfError> {
+        let before = self.changes.len();
+        self.changes.retain(|c| !c.same_target(superseded_by));
+        if self.changes.len() != before {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    // Loads the queue, dropping the changes superseded by the one about to be
+    // executed and replaying the others. A read-only run can neither apply nor
+    // supersede the queued changes, so it leaves the queue alone for the next
+    // run able to write.
+    pub fn prepare<F>(
+        state: &'a StateDir,
+        read_only: bool,
+        superseded_by: &PendingChange,
+        apply: F,
+    ) -> Result<Option<Queue<'a>>, SelfError>
+    where
+        F: FnMut(&PendingChange) -> Result<ReplayOutcome, SelfError>,
+    {
+        if read_only {
+            return Ok(None);
+        }
+        let mut queue = Queue::load(state)?;
+        queue.discard(superseded_by)?;
+        queue.replay(apply)?;
+        Ok(Some(queue))
+    }
+
+    // Applies the queued changes in order, stopping at the first one finding the
+    // provider still unavailable. Changes failing for any other reason are
+    // dropped, since retrying them would fail forever.
+    pub fn replay<F>(&mut self, mut apply: F) -> Result<(), SelfError>
+    where
+        F: FnMut(&PendingChange) -> Result<ReplayOutcome, SelfError>,
+    {
+        if self.changes.is_empty() {
+            return Ok(());
+        }
+        eprintln!("replaying {} queued changes", self.changes.len());
+        let mut remaining = Vec::new();
+        let mut unavailable = false;
+        for change in self.changes.drain(..) {
+            if unavailable {
+                remaining.push(change);
+                continue;
+            }
+            match apply(&change) {
+                Ok(ReplayOutcome::Applied) => eprintln!("applied queued change {:?}", change),
+                Ok(ReplayOutcome::Unavailable) => {
+                    unavailable = true;
+                    remaining.push(change);
+                }
+                Err(e) => eprintln!("dropping queued change {:?}: {}", change, e),
+            }
+        }
+        self.changes = remaining;
+        self.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn txt(node: &str, value: &str) -> PendingChange {
+        PendingChange::TxtValue {
+            domain: "example.dynu.net".to_string(),
+            node: node.to_string(),
+            value: value.to_string(),
+            ttl: 120,
+        }
+    }
+
+    fn state(name: &str) -> StateDir {
+        let path = std::env::temp_dir().join(format!("dynu_queue_test_{}", name));
+        let _ = std::fs::remove_dir_all(&path);
+        StateDir::new(Some(path.to_str().unwrap()))
+    }
+
+    #[test]
+    fn push_should_keep_only_latest_change_per_target() {
+        let state = state("push");
+        let mut queue = Queue::load(&state).unwrap();
+        queue.push(txt("a", "1")).unwrap();
+        queue.push(txt("b", "1")).unwrap();
+        queue.push(txt("a", "2")).unwrap();
+        let reloaded = Queue::load(&state).unwrap();
+        assert_eq!(reloaded.changes, vec![txt("b", "1"), txt("a", "2")]);
+    }
+
+    #[test]
+    fn replay_should_stop_when_still_unavailable() {
+        let state = state("replay");
+        let mut queue = Queue::load(&state).unwrap();
+        queue.push(txt("a", "1")).unwrap();
+        queue.push(txt("b", "1")).unwrap();
+        queue.push(txt("c", "1")).unwrap();
+        let mut calls = 0;
+        queue
+            .replay(|change| {
+                calls += 1;
+                match change {
+                    PendingChange::TxtValue { node, .. } if node == "a" => {
+                        Err(SelfError::MsgError("gone".to_string()))
+                    }
+                    _ => Ok(ReplayOutcome::Unavailable),
+                }
+            })
+            .unwrap();
+        assert_eq!(calls, 2);
+        let reloaded = Queue::load(&state).unwrap();
+        assert_eq!(reloaded.changes, vec![txt("b", "1"), txt("c", "1")]);
+    }
+
+    #[test]
+    fn prepare_should_leave_the_queue_alone_when_read_only() {
+        let state = state("read_only");
+        let mut queue = Queue::load(&state).unwrap();
+        queue.push(txt("a", "1")).unwrap();
+        queue.push(txt("b", "1")).unwrap();
+        let path = state.file(QUEUE_FILE).unwrap();
+        let before = std::fs::read_to_string(&path).unwrap();
+        let prepared = Queue::prepare(&state, true, &txt("a", "2"), |_| {
+            panic!("a read-only run must not replay the queue")
+        })
+        .unwrap();
+        assert!(prepared.is_none());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), before);
+    }
+}
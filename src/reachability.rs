@@ -0,0 +1,102 @@
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::failover::Check;
+use crate::netutils;
+use crate::SelfError;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// How the port is probed before publishing an address:
+//   direct       connects to the address itself, which needs the router to
+//                support hairpin NAT when the host is behind it
+//   loopback     connects to the port on 127.0.0.1, only checking that the
+//                service is listening on the host
+//   <url>        an external checker, GET with {address} and {port} replaced,
+//                answering with a success status when the port is reachable
+#[derive(Debug, Clone, PartialEq)]
+pub enum Probe {
+    Direct,
+    Loopback,
+    Checker(String),
+}
+
+impl Probe {
+    pub fn parse(spec: &str) -> Result<Probe, SelfError> {
+        match spec {
+            "direct" => Ok(Probe::Direct),
+            "loopback" => Ok(Probe::Loopback),
+            _ if spec.starts_with("http://") || spec.starts_with("https://") => {
+                Ok(Probe::Checker(spec.to_string()))
+            }
+            _ => Err(SelfError::MsgError(format!(
+                "invalid reachability probe {}, expected direct, loopback or an http(s) url",
+                spec
+            ))),
+        }
+    }
+
+    fn check(&self, address: &str, port: u16) -> Result<Check, SelfError> {
+        let ip: IpAddr = address
+            .parse()
+            .map_err(|_| SelfError::MsgError(format!("invalid address {}", address)))?;
+        Ok(match self {
+            Probe::Direct => Check::Tcp(match ip {
+                IpAddr::V4(v4) => format!("{}:{}", v4, port),
+                IpAddr::V6(v6) => format!("[{}]:{}", v6, port),
+            }),
+            Probe::Loopback => Check::Tcp(format!("127.0.0.1:{}", port)),
+            Probe::Checker(url) => Check::Http(
+                url.replace("{address}", &ip.to_string())
+                    .replace("{port}", &port.to_string()),
+            ),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Reachability {
+    pub port: u16,
+    pub probe: Probe,
+}
+
+impl Reachability {
+    pub fn verify(&self, address: &str) -> Result<(), SelfError> {
+        let http = netutils::http_client()
+            .timeout(PROBE_TIMEOUT)
+            .build()
+            .map_err(|e| SelfError::MsgError(format!("{}", e)))?;
+        let check = self.probe.check(address, self.port)?;
+        check.run(&http).map_err(|e| {
+            SelfError::MsgError(format!(
+                "port {} is not reachable on {}, not publishing it: {}",
+                self.port, address, e
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn probe_should_build_the_check_of_each_kind() {
+        let direct = Probe::parse("direct").unwrap();
+        assert_eq!(
+            direct.check("2001:db8::1", 443).unwrap(),
+            Check::Tcp("[2001:db8::1]:443".to_string())
+        );
+        let loopback = Probe::parse("loopback").unwrap();
+        assert_eq!(
+            loopback.check("192.0.2.1", 443).unwrap(),
+            Check::Tcp("127.0.0.1:443".to_string())
+        );
+        let checker = Probe::parse("https://checker.example/{address}/{port}").unwrap();
+        assert_eq!(
+            checker.check("192.0.2.1", 8443).unwrap(),
+            Check::Http("https://checker.example/192.0.2.1/8443".to_string())
+        );
+        assert!(Probe::parse("udp").is_err());
+    }
+}
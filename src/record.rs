@@ -0,0 +1,580 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::dynu::RecordDTO;
+
+/// Type of a [`Record`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RecordType {
+    /// IPv4 address.
+    A,
+    /// IPv6 address.
+    Aaaa,
+    /// Text.
+    Txt,
+    /// Sender policy, held as text.
+    Spf,
+    /// Start of authority, managed by dynu.
+    Soa,
+    /// Alias of another name.
+    Cname,
+    /// Delegation to a name server.
+    Ns,
+    /// Name an address of a reverse zone points back to.
+    Ptr,
+    /// Certificate authorities allowed to issue certificates.
+    Caa,
+    /// The types RecordDTO doesn't model, named by the record data.
+    Unknown,
+}
+
+impl RecordType {
+    /// Name of the type as dynu gives it, e.g. AAAA.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecordType::A => "A",
+            RecordType::Aaaa => "AAAA",
+            RecordType::Txt => "TXT",
+            RecordType::Spf => "SPF",
+            RecordType::Soa => "SOA",
+            RecordType::Cname => "CNAME",
+            RecordType::Ns => "NS",
+            RecordType::Ptr => "PTR",
+            RecordType::Caa => "CAA",
+            RecordType::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+impl fmt::Display for RecordType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for RecordType {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_uppercase().as_str() {
+            "A" => Ok(RecordType::A),
+            "AAAA" => Ok(RecordType::Aaaa),
+            "TXT" => Ok(RecordType::Txt),
+            "SPF" => Ok(RecordType::Spf),
+            "SOA" => Ok(RecordType::Soa),
+            "CNAME" => Ok(RecordType::Cname),
+            "NS" => Ok(RecordType::Ns),
+            "PTR" => Ok(RecordType::Ptr),
+            "CAA" => Ok(RecordType::Caa),
+            other => Err(format!("unsupported record type {}", other)),
+        }
+    }
+}
+
+/// Data of a [`Record`], specific to its type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordData {
+    /// IPv4 address of the node.
+    A {
+        /// The address, e.g. 192.0.2.1.
+        address: Option<String>,
+    },
+    /// IPv6 address of the node.
+    Aaaa {
+        /// The address, e.g. 2001:db8::1.
+        address: Option<String>,
+    },
+    /// Text of the node.
+    Txt {
+        /// The text, e.g. a verification token.
+        text: String,
+    },
+    /// Sender policy of the node.
+    Spf {
+        /// The policy, e.g. v=spf1 mx -all.
+        text: String,
+    },
+    /// Start of authority of the domain.
+    Soa {
+        /// Primary name server of the domain.
+        master_name: String,
+        /// Mailbox of the person responsible for the domain.
+        responsible_name: String,
+        /// Seconds between the refreshes of the secondary name servers.
+        refresh: u64,
+        /// Seconds before a failed refresh is retried.
+        retry: u64,
+        /// Seconds after which secondary name servers stop answering.
+        expire: u64,
+        /// Seconds negative answers are cached.
+        negative_ttl: u64,
+    },
+    /// Alias of another name.
+    Cname {
+        /// The canonical name the node is an alias of.
+        host: String,
+    },
+    /// Delegation of the node.
+    Ns {
+        /// A name server the node is delegated to.
+        host: String,
+    },
+    /// Reverse pointer of the node.
+    Ptr {
+        /// The name an address of a reverse zone points back to.
+        host: String,
+    },
+    /// Certificate authority allowed for the node.
+    Caa {
+        /// 128 marks the tag as critical, to be understood by the CAs.
+        flags: u8,
+        /// issue, issuewild or iodef.
+        tag: String,
+        /// The authority, e.g. letsencrypt.org.
+        value: String,
+    },
+    /// A record of a type RecordDTO doesn't model.
+    Unknown {
+        /// The type dynu gives the record, e.g. MX.
+        record_type: String,
+        /// The fields of the record under the names dynu gives them.
+        fields: serde_json::Map<String, serde_json::Value>,
+    },
+}
+
+/// A flattened view of RecordDTO for library consumers, easier to build and to
+/// match on than the serde tagged enum. The fields managed by dynu (domain,
+/// hostname, content, update time) are left out, so converting back gives a
+/// record ready to be created or updated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// Id dynu gives the record, missing from records not created yet.
+    pub id: Option<u64>,
+    /// Node of the record, empty for the domain itself.
+    pub node: String,
+    /// TTL of the record, in seconds.
+    pub ttl: u64,
+    /// Whether the record is served.
+    pub state: bool,
+    /// Data of the record.
+    pub data: RecordData,
+}
+
+impl Record {
+    /// Type of the record, following from its data.
+    pub fn record_type(&self) -> RecordType {
+        match self.data {
+            RecordData::A { .. } => RecordType::A,
+            RecordData::Aaaa { .. } => RecordType::Aaaa,
+            RecordData::Txt { .. } => RecordType::Txt,
+            RecordData::Spf { .. } => RecordType::Spf,
+            RecordData::Soa { .. } => RecordType::Soa,
+            RecordData::Cname { .. } => RecordType::Cname,
+            RecordData::Ns { .. } => RecordType::Ns,
+            RecordData::Ptr { .. } => RecordType::Ptr,
+            RecordData::Caa { .. } => RecordType::Caa,
+            RecordData::Unknown { .. } => RecordType::Unknown,
+        }
+    }
+}
+
+impl From<RecordDTO> for Record {
+    fn from(record: RecordDTO) -> Self {
+        match record {
+            RecordDTO::ARecord {
+                id,
+                node_name,
+                ttl,
+                state,
+                ipv4_address,
+                ..
+            } => Record {
+                id,
+                node: node_name,
+                ttl,
+                state,
+                data: RecordData::A {
+                    address: ipv4_address,
+                },
+            },
+            RecordDTO::AaaaRecord {
+                id,
+                node_name,
+                ttl,
+                state,
+                ipv6_address,
+                ..
+            } => Record {
+                id,
+                node: node_name,
+                ttl,
+                state,
+                data: RecordData::Aaaa {
+                    address: ipv6_address,
+                },
+            },
+            RecordDTO::TxtRecord {
+                id,
+                node_name,
+                ttl,
+                state,
+                text_data,
+                ..
+            } => Record {
+                id,
+                node: node_name,
+                ttl,
+                state,
+                data: RecordData::Txt { text: text_data },
+            },
+            RecordDTO::SpfRecord {
+                id,
+                node_name,
+                ttl,
+                state,
+                text_data,
+                ..
+            } => Record {
+                id,
+                node: node_name,
+                ttl,
+                state,
+                data: RecordData::Spf { text: text_data },
+            },
+            RecordDTO::SoaRecord {
+                id,
+                node_name,
+                ttl,
+                state,
+                master_name,
+                responsible_name,
+                refresh,
+                retry,
+                expire,
+                negative_ttl,
+                ..
+            } => Record {
+                id,
+                node: node_name,
+                ttl,
+                state,
+                data: RecordData::Soa {
+                    master_name,
+                    responsible_name,
+                    refresh,
+                    retry,
+                    expire,
+                    negative_ttl,
+                },
+            },
+            RecordDTO::CnameRecord {
+                id,
+                node_name,
+                ttl,
+                state,
+                host,
+                ..
+            } => Record {
+                id,
+                node: node_name,
+                ttl,
+                state,
+                data: RecordData::Cname { host },
+            },
+            RecordDTO::NsRecord {
+                id,
+                node_name,
+                ttl,
+                state,
+                host,
+                ..
+            } => Record {
+                id,
+                node: node_name,
+                ttl,
+                state,
+                data: RecordData::Ns { host },
+            },
+            RecordDTO::PtrRecord {
+                id,
+                node_name,
+                ttl,
+                state,
+                host,
+                ..
+            } => Record {
+                id,
+                node: node_name,
+                ttl,
+                state,
+                data: RecordData::Ptr { host },
+            },
+            RecordDTO::CaaRecord {
+                id,
+                node_name,
+                ttl,
+                state,
+                flags,
+                tag,
+                value,
+                ..
+            } => Record {
+                id,
+                node: node_name,
+                ttl,
+                state,
+                data: RecordData::Caa { flags, tag, value },
+            },
+            RecordDTO::Unknown {
+                id,
+                node_name,
+                record_type,
+                ttl,
+                other,
+                ..
+            } => Record {
+                id,
+                node: node_name,
+                ttl,
+                state: other
+                    .get("state")
+                    .and_then(|state| state.as_bool())
+                    .unwrap_or(true),
+                data: RecordData::Unknown {
+                    record_type,
+                    fields: other,
+                },
+            },
+        }
+    }
+}
+
+impl From<Record> for RecordDTO {
+    fn from(record: Record) -> Self {
+        let Record {
+            id,
+            node: node_name,
+            ttl,
+            state,
+            data,
+        } = record;
+        match data {
+            RecordData::A { address } => RecordDTO::ARecord {
+                id,
+                domain_id: None,
+                domain_name: None,
+                node_name,
+                hostname: None,
+                ttl,
+                state,
+                content: None,
+                updated_on: None,
+                group: "".to_string(),
+                ipv4_address: address,
+                other: serde_json::Map::new(),
+            },
+            RecordData::Aaaa { address } => RecordDTO::AaaaRecord {
+                id,
+                domain_id: None,
+                domain_name: None,
+                node_name,
+                hostname: None,
+                ttl,
+                state,
+                content: None,
+                updated_on: None,
+                group: "".to_string(),
+                ipv6_address: address,
+                other: serde_json::Map::new(),
+            },
+            RecordData::Txt { text } => RecordDTO::TxtRecord {
+                id,
+                domain_id: None,
+                domain_name: None,
+                node_name,
+                hostname: None,
+                ttl,
+                state,
+                content: None,
+                updated_on: None,
+                text_data: text,
+                other: serde_json::Map::new(),
+            },
+            RecordData::Spf { text } => RecordDTO::SpfRecord {
+                id,
+                domain_id: None,
+                domain_name: None,
+                node_name,
+                hostname: None,
+                ttl,
+                state,
+                content: None,
+                updated_on: None,
+                text_data: text,
+                other: serde_json::Map::new(),
+            },
+            RecordData::Soa {
+                master_name,
+                responsible_name,
+                refresh,
+                retry,
+                expire,
+                negative_ttl,
+            } => RecordDTO::SoaRecord {
+                id,
+                domain_id: None,
+                domain_name: None,
+                node_name,
+                hostname: None,
+                ttl,
+                state,
+                content: None,
+                updated_on: None,
+                master_name,
+                responsible_name,
+                refresh,
+                retry,
+                expire,
+                negative_ttl,
+                other: serde_json::Map::new(),
+            },
+            RecordData::Cname { host } => RecordDTO::CnameRecord {
+                id,
+                domain_id: None,
+                domain_name: None,
+                node_name,
+                hostname: None,
+                ttl,
+                state,
+                content: None,
+                updated_on: None,
+                host,
+                other: serde_json::Map::new(),
+            },
+            RecordData::Ns { host } => RecordDTO::NsRecord {
+                id,
+                domain_id: None,
+                domain_name: None,
+                node_name,
+                hostname: None,
+                ttl,
+                state,
+                content: None,
+                updated_on: None,
+                host,
+                other: serde_json::Map::new(),
+            },
+            RecordData::Ptr { host } => RecordDTO::PtrRecord {
+                id,
+                domain_id: None,
+                domain_name: None,
+                node_name,
+                hostname: None,
+                ttl,
+                state,
+                content: None,
+                updated_on: None,
+                host,
+                other: serde_json::Map::new(),
+            },
+            RecordData::Caa { flags, tag, value } => RecordDTO::CaaRecord {
+                id,
+                domain_id: None,
+                domain_name: None,
+                node_name,
+                hostname: None,
+                ttl,
+                state,
+                content: None,
+                updated_on: None,
+                flags,
+                tag,
+                value,
+                other: serde_json::Map::new(),
+            },
+            RecordData::Unknown {
+                record_type,
+                mut fields,
+            } => {
+                fields.insert("state".to_string(), state.into());
+                RecordDTO::Unknown {
+                    id,
+                    node_name,
+                    record_type,
+                    ttl,
+                    updated_on: None,
+                    other: fields,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_should_convert_from_and_to_dtos() {
+        let dto = RecordDTO::txt_record("_acme-challenge", "token", 120, Some(3));
+        let record = Record::from(dto.clone());
+        assert_eq!(
+            record,
+            Record {
+                id: Some(3),
+                node: "_acme-challenge".to_string(),
+                ttl: 120,
+                state: true,
+                data: RecordData::Txt {
+                    text: "token".to_string()
+                },
+            }
+        );
+        assert_eq!(record.record_type(), RecordType::Txt);
+        assert_eq!(RecordDTO::from(record), dto);
+
+        let dto = RecordDTO::a_record("www", "192.0.2.1", 300, None);
+        assert_eq!(RecordDTO::from(Record::from(dto.clone())), dto);
+
+        let dto = RecordDTO::aaaa_record("www", "2001:db8::1", 300, Some(8));
+        assert_eq!(Record::from(dto.clone()).record_type(), RecordType::Aaaa);
+        assert_eq!(RecordDTO::from(Record::from(dto.clone())), dto);
+
+        let dto = RecordDTO::spf_record("", "v=spf1 mx -all", 3600, Some(9));
+        assert_eq!(Record::from(dto.clone()).record_type(), RecordType::Spf);
+        assert_eq!(RecordDTO::from(Record::from(dto.clone())), dto);
+
+        let dto = RecordDTO::cname_record("blog", "example.github.io", 300, Some(4));
+        assert_eq!(Record::from(dto.clone()).record_type(), RecordType::Cname);
+        assert_eq!(RecordDTO::from(Record::from(dto.clone())), dto);
+
+        let dto = RecordDTO::ns_record("lab", "ns1.example.net", 3600, Some(6));
+        assert_eq!(Record::from(dto.clone()).record_type(), RecordType::Ns);
+        assert_eq!(RecordDTO::from(Record::from(dto.clone())), dto);
+
+        let dto = RecordDTO::ptr_record("1", "nas.example.dynu.net", 300, Some(7));
+        assert_eq!(Record::from(dto.clone()).record_type(), RecordType::Ptr);
+        assert_eq!(RecordDTO::from(Record::from(dto.clone())), dto);
+
+        let dto = RecordDTO::caa_record("", 128, "issue", "letsencrypt.org", 300, Some(5));
+        assert_eq!(Record::from(dto.clone()).record_type(), RecordType::Caa);
+        assert_eq!(RecordDTO::from(Record::from(dto.clone())), dto);
+
+        let dto: RecordDTO = serde_json::from_value(serde_json::json!({
+            "id": 11, "nodeName": "", "recordType": "MX", "ttl": 300, "state": false,
+            "host": "mail.example.com", "priority": 10
+        }))
+        .unwrap();
+        let record = Record::from(dto.clone());
+        assert_eq!(record.record_type(), RecordType::Unknown);
+        assert!(!record.state);
+        assert_eq!(RecordDTO::from(record), dto);
+    }
+
+    #[test]
+    fn record_type_should_parse_any_case() {
+        assert_eq!("txt".parse::<RecordType>(), Ok(RecordType::Txt));
+        assert_eq!(RecordType::Soa.to_string(), "SOA");
+        assert!("MX".parse::<RecordType>().is_err());
+    }
+}
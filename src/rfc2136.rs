@@ -0,0 +1,914 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use sha2::{Sha256, Sha384, Sha512};
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, TcpListener, TcpStream, UdpSocket};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::dns::{self, Message, ParsedMessage, ResourceRecord};
+use crate::dynu::{DomainDTO, DynuClient, RecordDTO};
+use crate::names::{node_of, normalise_fqdn};
+use crate::SelfError;
+
+const TSIG_FUDGE: u16 = 300;
+const TSIG_BADSIG: u16 = 16;
+const TSIG_BADKEY: u16 = 17;
+const TSIG_BADTIME: u16 = 18;
+// TCP clients idle for longer are disconnected, RFC 7766 suggesting a timeout
+// in the order of seconds.
+const TCP_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TsigAlgorithm {
+    HmacSha256,
+    HmacSha384,
+    HmacSha512,
+}
+
+impl TsigAlgorithm {
+    fn from_name(name: &str) -> Option<TsigAlgorithm> {
+        match normalise_fqdn(name).as_str() {
+            "hmac-sha256" => Some(TsigAlgorithm::HmacSha256),
+            "hmac-sha384" => Some(TsigAlgorithm::HmacSha384),
+            "hmac-sha512" => Some(TsigAlgorithm::HmacSha512),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            TsigAlgorithm::HmacSha256 => "hmac-sha256",
+            TsigAlgorithm::HmacSha384 => "hmac-sha384",
+            TsigAlgorithm::HmacSha512 => "hmac-sha512",
+        }
+    }
+}
+
+pub struct TsigKey {
+    name: String,
+    algorithm: TsigAlgorithm,
+    secret: Vec<u8>,
+}
+
+impl TsigKey {
+    // Parses keys in the same [algorithm:]name:secret format accepted by
+    // `nsupdate -y`, with hmac-sha256 as default algorithm.
+    pub fn parse(spec: &str) -> Result<TsigKey, SelfError> {
+        let parts: Vec<&str> = spec.trim().split(':').collect();
+        let (algorithm, name, secret) = match parts.as_slice() {
+            [name, secret] => (TsigAlgorithm::HmacSha256, *name, *secret),
+            [algorithm, name, secret] => (
+                TsigAlgorithm::from_name(algorithm).ok_or_else(|| {
+                    SelfError::MsgError(format!("unsupported TSIG algorithm {}", algorithm))
+                })?,
+                *name,
+                *secret,
+            ),
+            _ => {
+                return Err(SelfError::MsgError(
+                    "TSIG key must be formatted as [algorithm:]name:secret".to_string(),
+                ))
+            }
+        };
+        let secret = STANDARD
+            .decode(secret)
+            .map_err(|e| SelfError::MsgError(format!("TSIG secret is not base64: {}", e)))?;
+        Ok(TsigKey {
+            name: normalise_fqdn(name),
+            algorithm,
+            secret,
+        })
+    }
+
+    fn sign(&self, data: &[u8]) -> Vec<u8> {
+        match self.algorithm {
+            TsigAlgorithm::HmacSha256 => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).unwrap();
+                mac.update(data);
+                mac.finalize().into_bytes().to_vec()
+            }
+            TsigAlgorithm::HmacSha384 => {
+                let mut mac = Hmac::<Sha384>::new_from_slice(&self.secret).unwrap();
+                mac.update(data);
+                mac.finalize().into_bytes().to_vec()
+            }
+            TsigAlgorithm::HmacSha512 => {
+                let mut mac = Hmac::<Sha512>::new_from_slice(&self.secret).unwrap();
+                mac.update(data);
+                mac.finalize().into_bytes().to_vec()
+            }
+        }
+    }
+
+    fn verify(&self, data: &[u8], signature: &[u8]) -> bool {
+        match self.algorithm {
+            TsigAlgorithm::HmacSha256 => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).unwrap();
+                mac.update(data);
+                mac.verify_slice(signature).is_ok()
+            }
+            TsigAlgorithm::HmacSha384 => {
+                let mut mac = Hmac::<Sha384>::new_from_slice(&self.secret).unwrap();
+                mac.update(data);
+                mac.verify_slice(signature).is_ok()
+            }
+            TsigAlgorithm::HmacSha512 => {
+                let mut mac = Hmac::<Sha512>::new_from_slice(&self.secret).unwrap();
+                mac.update(data);
+                mac.verify_slice(signature).is_ok()
+            }
+        }
+    }
+}
+
+pub struct Rfc2136Options {
+    pub listen: String,
+    pub key: Option<TsigKey>,
+}
+
+#[derive(Debug)]
+struct Tsig {
+    algorithm: String,
+    time_signed: u64,
+    fudge: u16,
+    mac: Vec<u8>,
+    original_id: u16,
+    error: u16,
+    other: Vec<u8>,
+}
+
+impl Tsig {
+    fn parse(rdata: &[u8]) -> Option<Tsig> {
+        let (algorithm, mut position) = dns::read_name(rdata, 0).ok()?;
+        let u16_at = |p: usize| Some(u16::from_be_bytes([*rdata.get(p)?, *rdata.get(p + 1)?]));
+        let time = rdata.get(position..position + 6)?;
+        let time_signed = time.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64);
+        position += 6;
+        let fudge = u16_at(position)?;
+        let mac_size = u16_at(position + 2)? as usize;
+        position += 4;
+        let mac = rdata.get(position..position + mac_size)?.to_vec();
+        position += mac_size;
+        let original_id = u16_at(position)?;
+        let error = u16_at(position + 2)?;
+        let other_len = u16_at(position + 4)? as usize;
+        position += 6;
+        let other = rdata.get(position..position + other_len)?.to_vec();
+        Some(Tsig {
+            algorithm,
+            time_signed,
+            fudge,
+            mac,
+            original_id,
+            error,
+            other,
+        })
+    }
+
+    fn time_bytes(&self) -> [u8; 6] {
+        let bytes = self.time_signed.to_be_bytes();
+        [bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]]
+    }
+
+    fn to_record(&self, key_name: &str) -> ResourceRecord {
+        let mut rdata = Vec::new();
+        dns::write_name(&mut rdata, &self.algorithm);
+        rdata.extend_from_slice(&self.time_bytes());
+        rdata.extend_from_slice(&self.fudge.to_be_bytes());
+        rdata.extend_from_slice(&(self.mac.len() as u16).to_be_bytes());
+        rdata.extend_from_slice(&self.mac);
+        rdata.extend_from_slice(&self.original_id.to_be_bytes());
+        rdata.extend_from_slice(&self.error.to_be_bytes());
+        rdata.extend_from_slice(&(self.other.len() as u16).to_be_bytes());
+        rdata.extend_from_slice(&self.other);
+        ResourceRecord {
+            name: key_name.to_string(),
+            rtype: dns::TYPE_TSIG,
+            class: dns::CLASS_ANY,
+            ttl: 0,
+            rdata,
+        }
+    }
+
+    // TSIG variables covered by the MAC after the message itself (RFC 8945 4.3.3)
+    fn variables(&self, key_name: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        dns::write_name(&mut data, &key_name.to_lowercase());
+        data.extend_from_slice(&dns::CLASS_ANY.to_be_bytes());
+        data.extend_from_slice(&0u32.to_be_bytes());
+        dns::write_name(&mut data, &self.algorithm.to_lowercase());
+        data.extend_from_slice(&self.time_bytes());
+        data.extend_from_slice(&self.fudge.to_be_bytes());
+        data.extend_from_slice(&self.error.to_be_bytes());
+        data.extend_from_slice(&(self.other.len() as u16).to_be_bytes());
+        data.extend_from_slice(&self.other);
+        data
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Record of the zone in wire format, so that the comparisons required by the
+// prerequisites and deletions are carried out as a name server would.
+struct ZoneRecord {
+    id: Option<u64>,
+    node: String,
+    rtype: u16,
+    rdata: Vec<u8>,
+}
+
+fn zone_records(records: &[RecordDTO]) -> Vec<ZoneRecord> {
+    records
+        .iter()
+        .map(|r| match r {
+            RecordDTO::ARecord {
+                id,
+                node_name,
+                ipv4_address,
+                ..
+            } => ZoneRecord {
+                id: *id,
+                node: node_name.to_lowercase(),
+                rtype: dns::TYPE_A,
+                rdata: ipv4_address
+                    .as_ref()
+                    .and_then(|a| a.parse::<Ipv4Addr>().ok())
+                    .map(|a| dns::a_rdata(&a))
+                    .unwrap_or_default(),
+            },
+            RecordDTO::AaaaRecord {
+                id,
+                node_name,
+                ipv6_address,
+                ..
+            } => ZoneRecord {
+                id: *id,
+                node: node_name.to_lowercase(),
+                rtype: dns::TYPE_AAAA,
+                rdata: ipv6_address
+                    .as_ref()
+                    .and_then(|a| a.parse::<Ipv6Addr>().ok())
+                    .map(|a| dns::aaaa_rdata(&a))
+                    .unwrap_or_default(),
+            },
+            RecordDTO::TxtRecord {
+                id,
+                node_name,
+                text_data,
+                ..
+            } => ZoneRecord {
+                id: *id,
+                node: node_name.to_lowercase(),
+                rtype: dns::TYPE_TXT,
+                rdata: dns::txt_rdata(text_data),
+            },
+            RecordDTO::SpfRecord {
+                id,
+                node_name,
+                text_data,
+                ..
+            } => ZoneRecord {
+                id: *id,
+                node: node_name.to_lowercase(),
+                rtype: dns::TYPE_SPF,
+                rdata: dns::txt_rdata(text_data),
+            },
+            RecordDTO::CnameRecord {
+                id,
+                node_name,
+                host,
+                ..
+            } => ZoneRecord {
+                id: *id,
+                node: node_name.to_lowercase(),
+                rtype: dns::TYPE_CNAME,
+                rdata: {
+                    let mut rdata = Vec::new();
+                    dns::write_name(&mut rdata, &host.to_lowercase());
+                    rdata
+                },
+            },
+            RecordDTO::NsRecord {
+                id,
+                node_name,
+                host,
+                ..
+            } => ZoneRecord {
+                id: *id,
+                node: node_name.to_lowercase(),
+                rtype: dns::TYPE_NS,
+                rdata: {
+                    let mut rdata = Vec::new();
+                    dns::write_name(&mut rdata, &host.to_lowercase());
+                    rdata
+                },
+            },
+            RecordDTO::PtrRecord {
+                id,
+                node_name,
+                host,
+                ..
+            } => ZoneRecord {
+                id: *id,
+                node: node_name.to_lowercase(),
+                rtype: dns::TYPE_PTR,
+                rdata: {
+                    let mut rdata = Vec::new();
+                    dns::write_name(&mut rdata, &host.to_lowercase());
+                    rdata
+                },
+            },
+            RecordDTO::CaaRecord {
+                id,
+                node_name,
+                flags,
+                tag,
+                value,
+                ..
+            } => ZoneRecord {
+                id: *id,
+                node: node_name.to_lowercase(),
+                rtype: dns::TYPE_CAA,
+                rdata: dns::caa_rdata(*flags, tag, value),
+            },
+            RecordDTO::SoaRecord { id, node_name, .. } => ZoneRecord {
+                id: *id,
+                node: node_name.to_lowercase(),
+                rtype: dns::TYPE_SOA,
+                rdata: vec![],
+            },
+            // only counts for the node being in use, its type being unknown
+            RecordDTO::Unknown { id, node_name, .. } => ZoneRecord {
+                id: *id,
+                node: node_name.to_lowercase(),
+                rtype: 0,
+                rdata: vec![],
+            },
+        })
+        .collect()
+}
+
+fn is_mutable_type(rtype: u16) -> bool {
+    rtype == dns::TYPE_A || rtype == dns::TYPE_AAAA || rtype == dns::TYPE_TXT
+}
+
+struct Bridge {
+    dynu_client: DynuClient,
+    key: Option<TsigKey>,
+    updates: Mutex<()>,
+}
+
+impl Bridge {
+    fn handle(&self, packet: &[u8]) -> Option<Vec<u8>> {
+        let parsed = match Message::parse(packet) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                eprintln!("rfc2136 discarding malformed message: {}", e);
+                if packet.len() < 2 {
+                    return None;
+                }
+                let id = u16::from_be_bytes([packet[0], packet[1]]);
+                let response = Message {
+                    id,
+                    ..Default::default()
+                }
+                .response(dns::RCODE_FORMERR);
+                return Some(response.to_bytes());
+            }
+        };
+        let request = &parsed.message;
+        let tsig = match self.verify(packet, &parsed) {
+            Ok(tsig) => tsig,
+            Err((tsig, error)) => return Some(self.unsigned_error(request, tsig, error)),
+        };
+        let rcode = if request.opcode() != dns::OPCODE_UPDATE {
+            dns::RCODE_NOTIMP
+        } else {
+            let _guard = self.updates.lock().unwrap();
+            match self.update(request) {
+                Ok(()) => dns::RCODE_NOERROR,
+                Err(rcode) => rcode,
+            }
+        };
+        let response = request.response(rcode);
+        Some(match tsig {
+            Some(tsig) => self.sign(response, &tsig),
+            None => response.to_bytes(),
+        })
+    }
+
+    fn verify(
+        &self,
+        packet: &[u8],
+        parsed: &ParsedMessage,
+    ) -> Result<Option<Tsig>, (Option<Tsig>, u16)> {
+        let request = &parsed.message;
+        let tsig_record = request
+            .additionals
+            .last()
+            .filter(|r| r.rtype == dns::TYPE_TSIG);
+        let (record, offset) = match (tsig_record, &self.key) {
+            (None, None) => return Ok(None),
+            (None, Some(_)) => {
+                eprintln!("rfc2136 rejecting unsigned message");
+                return Err((None, TSIG_BADKEY));
+            }
+            (Some(record), _) => (record, parsed.last_additional_offset.unwrap()),
+        };
+        let tsig = Tsig::parse(&record.rdata).ok_or((None, TSIG_BADSIG))?;
+        let key = match &self.key {
+            Some(key)
+                if normalise_fqdn(&record.name) == key.name
+                    && TsigAlgorithm::from_name(&tsig.algorithm) == Some(key.algorithm) =>
+            {
+                key
+            }
+            _ => {
+                eprintln!(
+                    "rfc2136 rejecting message signed with unknown key {}",
+                    record.name
+                );
+                return Err((Some(tsig), TSIG_BADKEY));
+            }
+        };
+        let mut data = packet[..offset].to_vec();
+        data[0..2].copy_from_slice(&tsig.original_id.to_be_bytes());
+        let additionals = u16::from_be_bytes([data[10], data[11]]) - 1;
+        data[10..12].copy_from_slice(&additionals.to_be_bytes());
+        data.extend_from_slice(&tsig.variables(&record.name));
+        if !key.verify(&data, &tsig.mac) {
+            eprintln!("rfc2136 rejecting message with invalid signature");
+            return Err((Some(tsig), TSIG_BADSIG));
+        }
+        if now().abs_diff(tsig.time_signed) > tsig.fudge as u64 {
+            eprintln!("rfc2136 rejecting message signed outside of the time window");
+            return Err((Some(tsig), TSIG_BADTIME));
+        }
+        Ok(Some(tsig))
+    }
+
+    fn unsigned_error(&self, request: &Message, tsig: Option<Tsig>, error: u16) -> Vec<u8> {
+        let mut response = request.response(dns::RCODE_NOTAUTH);
+        if let (Some(tsig), Some(record)) = (tsig, request.additionals.last()) {
+            let error_tsig = Tsig {
+                mac: vec![],
+                error,
+                other: vec![],
+                ..tsig
+            };
+            response
+                .additionals
+                .push(error_tsig.to_record(&record.name));
+        }
+        response.to_bytes()
+    }
+
+    fn sign(&self, mut response: Message, request_tsig: &Tsig) -> Vec<u8> {
+        let key = self.key.as_ref().unwrap();
+        let mut tsig = Tsig {
+            algorithm: key.algorithm.name().to_string(),
+            time_signed: now(),
+            fudge: TSIG_FUDGE,
+            mac: vec![],
+            original_id: request_tsig.original_id,
+            error: 0,
+            other: vec![],
+        };
+        let mut data = Vec::new();
+        data.extend_from_slice(&(request_tsig.mac.len() as u16).to_be_bytes());
+        data.extend_from_slice(&request_tsig.mac);
+        data.extend_from_slice(&response.to_bytes());
+        data.extend_from_slice(&tsig.variables(&key.name));
+        tsig.mac = key.sign(&data);
+        response.additionals.push(tsig.to_record(&key.name));
+        response.to_bytes()
+    }
+
+    fn find_domain(&self, zone: &str) -> Result<DomainDTO, u8> {
+        let domains = self.dynu_client.get_domains().map_err(|e| {
+            eprintln!("rfc2136 cannot list domains: {}", e);
+            dns::RCODE_SERVFAIL
+        })?;
+        domains
+            .domains
+            .into_iter()
+            .find(|d| normalise_fqdn(&d.name) == zone)
+            .ok_or_else(|| {
+                eprintln!("rfc2136 zone {} is not a domain in dynu", zone);
+                dns::RCODE_NOTAUTH
+            })
+    }
+
+    fn update(&self, request: &Message) -> Result<(), u8> {
+        let zone = match request.questions.as_slice() {
+            [question] if question.qtype == dns::TYPE_SOA => normalise_fqdn(&question.name),
+            _ => return Err(dns::RCODE_FORMERR),
+        };
+        let domain = self.find_domain(&zone)?;
+        let domain_id = domain.require_id().map_err(|e| {
+            eprintln!("rfc2136 cannot update {}: {}", zone, e);
+            dns::RCODE_SERVFAIL
+        })?;
+        let records = self.dynu_client.get_records(domain_id).map_err(|e| {
+            eprintln!("rfc2136 cannot list records of {}: {}", zone, e);
+            dns::RCODE_SERVFAIL
+        })?;
+        let mut existing = zone_records(&records.dns_records);
+        let node = |name: &str| node_of(&normalise_fqdn(name), &zone).ok_or(dns::RCODE_NOTZONE);
+
+        check_prerequisites(&request.answers, &existing, &node)?;
+        for update in &request.authorities {
+            node(&update.name)?;
+            let supported = match update.class {
+                dns::CLASS_ANY => update.rtype == dns::TYPE_ANY || is_mutable_type(update.rtype),
+                _ => is_mutable_type(update.rtype),
+            };
+            if !supported {
+                eprintln!("rfc2136 unsupported update of type {}", update.rtype);
+                return Err(dns::RCODE_NOTIMP);
+            }
+            if update.class != dns::CLASS_IN
+                && update.class != dns::CLASS_ANY
+                && update.class != dns::CLASS_NONE
+            {
+                return Err(dns::RCODE_FORMERR);
+            }
+        }
+        for update in &request.authorities {
+            let node = node(&update.name)?;
+            self.apply(domain_id, &node, update, &mut existing)
+                .map_err(|e| {
+                    eprintln!("rfc2136 update of {} failed: {}", update.name, e);
+                    dns::RCODE_SERVFAIL
+                })?;
+        }
+        Ok(())
+    }
+
+    fn apply(
+        &self,
+        domain_id: u64,
+        node: &str,
+        update: &ResourceRecord,
+        existing: &mut Vec<ZoneRecord>,
+    ) -> Result<(), SelfError> {
+        if update.class == dns::CLASS_IN {
+            if existing
+                .iter()
+                .any(|r| r.node == node && r.rtype == update.rtype && r.rdata == update.rdata)
+            {
+                return Ok(());
+            }
+            let record = match update.rtype {
+                dns::TYPE_A => RecordDTO::a_record(
+                    node,
+                    &dns::parse_a(&update.rdata)
+                        .map_err(|e| SelfError::MsgError(format!("{}", e)))?
+                        .to_string(),
+                    update.ttl as u64,
+                    None,
+                ),
+                dns::TYPE_AAAA => RecordDTO::aaaa_record(
+                    node,
+                    &dns::parse_aaaa(&update.rdata)
+                        .map_err(|e| SelfError::MsgError(format!("{}", e)))?
+                        .to_string(),
+                    update.ttl as u64,
+                    None,
+                ),
+                _ => RecordDTO::txt_record(
+                    node,
+                    &dns::parse_txt(&update.rdata)
+                        .map_err(|e| SelfError::MsgError(format!("{}", e)))?,
+                    update.ttl as u64,
+                    None,
+                ),
+            };
+            let id = self.dynu_client.create_record(domain_id, &record)?;
+            eprintln!(
+                "rfc2136 created record {} type {} with id={}",
+                node, update.rtype, id
+            );
+            existing.push(ZoneRecord {
+                id: Some(id),
+                node: node.to_string(),
+                rtype: update.rtype,
+                rdata: update.rdata.clone(),
+            });
+            return Ok(());
+        }
+        let (deleted, kept): (Vec<ZoneRecord>, Vec<ZoneRecord>) =
+            existing.drain(..).partition(|r| {
+                r.node == node
+                    && is_mutable_type(r.rtype)
+                    && (update.rtype == dns::TYPE_ANY || r.rtype == update.rtype)
+                    && (update.class == dns::CLASS_ANY || r.rdata == update.rdata)
+            });
+        *existing = kept;
+        for record in deleted {
+            if let Some(id) = record.id {
+                self.dynu_client.delete_record(domain_id, id)?;
+                eprintln!(
+                    "rfc2136 deleted record {} type {} with id={}",
+                    node, record.rtype, id
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_prerequisites(
+    prerequisites: &[ResourceRecord],
+    existing: &[ZoneRecord],
+    node: &dyn Fn(&str) -> Result<String, u8>,
+) -> Result<(), u8> {
+    let mut value_dependent: Vec<(String, u16, Vec<Vec<u8>>)> = Vec::new();
+    for prerequisite in prerequisites {
+        if prerequisite.ttl != 0 {
+            return Err(dns::RCODE_FORMERR);
+        }
+        let node = node(&prerequisite.name)?;
+        let at_node = existing.iter().filter(|r| r.node == node);
+        let mut of_type = existing
+            .iter()
+            .filter(|r| r.node == node && r.rtype == prerequisite.rtype);
+        match (prerequisite.class, prerequisite.rtype) {
+            (dns::CLASS_ANY, dns::TYPE_ANY) => {
+                if at_node.count() == 0 {
+                    return Err(dns::RCODE_NXDOMAIN);
+                }
+            }
+            (dns::CLASS_ANY, _) => {
+                if of_type.next().is_none() {
+                    return Err(dns::RCODE_NXRRSET);
+                }
+            }
+            (dns::CLASS_NONE, dns::TYPE_ANY) => {
+                if at_node.count() > 0 {
+                    return Err(dns::RCODE_YXDOMAIN);
+                }
+            }
+            (dns::CLASS_NONE, _) => {
+                if of_type.next().is_some() {
+                    return Err(dns::RCODE_YXRRSET);
+                }
+            }
+            (dns::CLASS_IN, rtype) => {
+                match value_dependent
+                    .iter_mut()
+                    .find(|(n, t, _)| n == &node && *t == rtype)
+                {
+                    Some((_, _, values)) => values.push(prerequisite.rdata.clone()),
+                    None => value_dependent.push((node, rtype, vec![prerequisite.rdata.clone()])),
+                }
+            }
+            _ => return Err(dns::RCODE_FORMERR),
+        }
+    }
+    for (node, rtype, mut expected) in value_dependent {
+        let mut actual: Vec<Vec<u8>> = existing
+            .iter()
+            .filter(|r| r.node == node && r.rtype == rtype)
+            .map(|r| r.rdata.clone())
+            .collect();
+        expected.sort();
+        expected.dedup();
+        actual.sort();
+        actual.dedup();
+        if expected != actual {
+            return Err(dns::RCODE_NXRRSET);
+        }
+    }
+    Ok(())
+}
+
+fn serve_udp(bridge: &Bridge, socket: UdpSocket) {
+    let mut buffer = vec![0u8; 65535];
+    loop {
+        let (len, peer) = match socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(e) => {
+                eprintln!("rfc2136 cannot receive datagram: {}", e);
+                continue;
+            }
+        };
+        if let Some(response) = bridge.handle(&buffer[..len]) {
+            if let Err(e) = socket.send_to(&response, peer) {
+                eprintln!("rfc2136 cannot answer {}: {}", peer, e);
+            }
+        }
+    }
+}
+
+fn serve_tcp_connection(bridge: &Bridge, mut stream: TcpStream) -> std::io::Result<()> {
+    loop {
+        let mut len = [0u8; 2];
+        if stream.read_exact(&mut len).is_err() {
+            return Ok(());
+        }
+        let mut packet = vec![0u8; u16::from_be_bytes(len) as usize];
+        stream.read_exact(&mut packet)?;
+        if let Some(response) = bridge.handle(&packet) {
+            stream.write_all(&(response.len() as u16).to_be_bytes())?;
+            stream.write_all(&response)?;
+        }
+    }
+}
+
+// Each connection is served by its own thread, so that a slow client doesn't
+// hold up the others; the updates themselves are still applied one at a time.
+fn serve_tcp(bridge: &Bridge, listener: TcpListener) {
+    thread::scope(|scope| {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    scope.spawn(move || {
+                        let served = stream
+                            .set_read_timeout(Some(TCP_IDLE_TIMEOUT))
+                            .and_then(|_| serve_tcp_connection(bridge, stream));
+                        if let Err(e) = served {
+                            eprintln!("rfc2136 tcp connection failed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => eprintln!("rfc2136 cannot accept connection: {}", e),
+            }
+        }
+    });
+}
+
+// Accepts DNS UPDATE messages over UDP and TCP and translates them into record
+// operations on the Dynu domain matching the zone of the update.
+pub fn serve(dynu_client: DynuClient, options: Rfc2136Options) -> Result<(), SelfError> {
+    let udp = UdpSocket::bind(&options.listen)?;
+    let tcp = TcpListener::bind(&options.listen)?;
+    match &options.key {
+        Some(key) => eprintln!(
+            "serving rfc2136 updates on {} requiring TSIG key {} ({})",
+            options.listen,
+            key.name,
+            key.algorithm.name()
+        ),
+        None => eprintln!(
+            "serving rfc2136 updates on {} WITHOUT authentication",
+            options.listen
+        ),
+    }
+    let bridge = Bridge {
+        dynu_client,
+        key: options.key,
+        updates: Mutex::new(()),
+    };
+    thread::scope(|scope| {
+        scope.spawn(|| serve_tcp(&bridge, tcp));
+        serve_udp(&bridge, udp);
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> TsigKey {
+        TsigKey::parse("hmac-sha256:update-key.:c2VjcmV0").unwrap()
+    }
+
+    fn signed_update(key: &TsigKey, time_signed: u64) -> Vec<u8> {
+        let mut message = Message {
+            id: 1234,
+            flags: (dns::OPCODE_UPDATE as u16) << 11,
+            ..Default::default()
+        };
+        message.questions.push(dns::Question {
+            name: "example.dynu.net".to_string(),
+            qtype: dns::TYPE_SOA,
+            class: dns::CLASS_IN,
+        });
+        let mut tsig = Tsig {
+            algorithm: key.algorithm.name().to_string(),
+            time_signed,
+            fudge: TSIG_FUDGE,
+            mac: vec![],
+            original_id: 1234,
+            error: 0,
+            other: vec![],
+        };
+        let mut data = message.to_bytes();
+        data.extend_from_slice(&tsig.variables(&key.name));
+        tsig.mac = key.sign(&data);
+        message.additionals.push(tsig.to_record(&key.name));
+        message.to_bytes()
+    }
+
+    fn bridge() -> Bridge {
+        Bridge {
+            dynu_client: DynuClient::new("unused", crate::dynu::USER_AGENT).unwrap(),
+            key: Some(key()),
+            updates: Mutex::new(()),
+        }
+    }
+
+    #[test]
+    fn tsig_key_should_parse_nsupdate_format() {
+        let key = TsigKey::parse("update-key:c2VjcmV0").unwrap();
+        assert_eq!(key.name, "update-key");
+        assert_eq!(key.algorithm, TsigAlgorithm::HmacSha256);
+        assert_eq!(key.secret, b"secret");
+        assert!(TsigKey::parse("hmac-md5:update-key:c2VjcmV0").is_err());
+    }
+
+    #[test]
+    fn verify_should_accept_valid_signature() {
+        let packet = signed_update(&key(), now());
+        let parsed = Message::parse(&packet).unwrap();
+        assert!(bridge().verify(&packet, &parsed).unwrap().is_some());
+    }
+
+    #[test]
+    fn verify_should_reject_tampered_message() {
+        let mut packet = signed_update(&key(), now());
+        packet[2] |= 0x01;
+        let parsed = Message::parse(&packet).unwrap();
+        let error = bridge().verify(&packet, &parsed).err().unwrap();
+        assert_eq!(error.1, TSIG_BADSIG);
+    }
+
+    #[test]
+    fn verify_should_reject_stale_signature() {
+        let packet = signed_update(&key(), now() - 3600);
+        let parsed = Message::parse(&packet).unwrap();
+        let error = bridge().verify(&packet, &parsed).err().unwrap();
+        assert_eq!(error.1, TSIG_BADTIME);
+    }
+
+    #[test]
+    fn prerequisites_should_follow_rfc2136_semantics() {
+        let existing = vec![ZoneRecord {
+            id: Some(1),
+            node: "www".to_string(),
+            rtype: dns::TYPE_A,
+            rdata: vec![192, 0, 2, 1],
+        }];
+        let node = |name: &str| Ok(name.to_string());
+        let prerequisite = |class, rtype, rdata: Vec<u8>| ResourceRecord {
+            name: "www".to_string(),
+            rtype,
+            class,
+            ttl: 0,
+            rdata,
+        };
+        let check = |p: ResourceRecord| check_prerequisites(&[p], &existing, &node);
+        assert_eq!(
+            check(prerequisite(dns::CLASS_ANY, dns::TYPE_A, vec![])),
+            Ok(())
+        );
+        assert_eq!(
+            check(prerequisite(dns::CLASS_ANY, dns::TYPE_TXT, vec![])),
+            Err(dns::RCODE_NXRRSET)
+        );
+        assert_eq!(
+            check(prerequisite(dns::CLASS_NONE, dns::TYPE_ANY, vec![])),
+            Err(dns::RCODE_YXDOMAIN)
+        );
+        assert_eq!(
+            check(prerequisite(dns::CLASS_IN, dns::TYPE_A, vec![192, 0, 2, 1])),
+            Ok(())
+        );
+        assert_eq!(
+            check(prerequisite(dns::CLASS_IN, dns::TYPE_A, vec![192, 0, 2, 2])),
+            Err(dns::RCODE_NXRRSET)
+        );
+    }
+
+    #[test]
+    fn serve_tcp_should_not_wait_for_idle_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let bridge: &'static Bridge = Box::leak(Box::new(bridge()));
+        thread::spawn(move || serve_tcp(bridge, listener));
+        let _idle = TcpStream::connect(address).unwrap();
+        let mut client = TcpStream::connect(address).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let query = Message::query(7, "example.dynu.net", dns::TYPE_A, false).to_bytes();
+        client
+            .write_all(&(query.len() as u16).to_be_bytes())
+            .unwrap();
+        client.write_all(&query).unwrap();
+        let mut len = [0u8; 2];
+        client.read_exact(&mut len).unwrap();
+        let mut response = vec![0u8; u16::from_be_bytes(len) as usize];
+        client.read_exact(&mut response).unwrap();
+        assert_eq!(Message::parse(&response).unwrap().message.id, 7);
+    }
+}
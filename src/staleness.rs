@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::dynu::DomainDTO;
+use crate::names::normalise_fqdn;
+use crate::state::StateDir;
+use crate::SelfError;
+
+const LAST_UPDATES_FILE: &str = "last-updates.json";
+
+// Time of the last update sent to dynu for each domain, so that refresh
+// --max-age can push the address again before dynu expires a hostname it
+// considers inactive.
+pub struct LastUpdates<'a> {
+    state: &'a StateDir,
+}
+
+impl<'a> LastUpdates<'a> {
+    pub fn new(state: &'a StateDir) -> LastUpdates<'a> {
+        LastUpdates { state }
+    }
+
+    fn updates(&self) -> Result<BTreeMap<String, u64>, SelfError> {
+        Ok(self.state.read_json(LAST_UPDATES_FILE)?.unwrap_or_default())
+    }
+
+    pub fn get(&self, domain: &str) -> Result<Option<SystemTime>, SelfError> {
+        Ok(self
+            .updates()?
+            .get(&normalise_fqdn(domain))
+            .map(|seconds| UNIX_EPOCH + Duration::from_secs(*seconds)))
+    }
+
+    pub fn record(&self, domain: &str, at: SystemTime) -> Result<(), SelfError> {
+        let mut updates = self.updates()?;
+        let seconds = at
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        updates.insert(normalise_fqdn(domain), seconds);
+        self.state.write_json(LAST_UPDATES_FILE, &updates)
+    }
+}
+
+// Dynu's updatedOn timestamps have no offset and are in UTC.
+pub fn updated_at(domain: &DomainDTO) -> Option<SystemTime> {
+    domain
+        .updated_on
+        .as_deref()
+        .and_then(|u| humantime::parse_rfc3339_weak(u).ok())
+}
+
+// An unknown update time counts as too old.
+pub fn is_older(updated: Option<SystemTime>, now: SystemTime, max_age: Duration) -> bool {
+    match updated {
+        Some(updated) => now.duration_since(updated).unwrap_or_default() >= max_age,
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_updates_should_be_kept_per_domain() {
+        let dir = std::env::temp_dir().join("dynu_staleness_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let state = StateDir::new(dir.to_str());
+        let updates = LastUpdates::new(&state);
+        let at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(updates.get("example.dynu.net").unwrap(), None);
+        updates.record("Example.dynu.net.", at).unwrap();
+        assert_eq!(updates.get("example.dynu.net").unwrap(), Some(at));
+        assert_eq!(updates.get("other.dynu.net").unwrap(), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn is_older_should_compare_with_max_age() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let day = Duration::from_secs(86400);
+        assert!(!is_older(Some(now - day), now, 7 * day));
+        assert!(is_older(Some(now - 8 * day), now, 7 * day));
+        assert!(is_older(None, now, 7 * day));
+        let domain = DomainDTO {
+            updated_on: Some("2023-11-14T22:13:20".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(updated_at(&domain), Some(now));
+    }
+}
@@ -0,0 +1,65 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::SelfError;
+
+// Directory holding what dynuupdater needs to remember between runs. Defaults to
+// $XDG_STATE_HOME/dynuupdater, falling back to ~/.local/state/dynuupdater.
+#[derive(Clone)]
+pub struct StateDir {
+    path: Option<PathBuf>,
+}
+
+impl StateDir {
+    pub fn new(path: Option<&str>) -> StateDir {
+        StateDir {
+            path: path.map(PathBuf::from).or_else(default_path),
+        }
+    }
+
+    fn dir(&self) -> Result<&Path, SelfError> {
+        self.path.as_deref().ok_or_else(|| {
+            SelfError::MsgError(
+                "cannot determine the state directory, provide 'state-dir' argument or define environment variable DYNU_STATE_DIR".to_string(),
+            )
+        })
+    }
+
+    pub fn file(&self, name: &str) -> Result<PathBuf, SelfError> {
+        Ok(self.dir()?.join(name))
+    }
+
+    pub fn read_json<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>, SelfError> {
+        let path = self.file(name)?;
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|e| SelfError::MsgError(format!("cannot parse {}: {}", path.display(), e)))
+    }
+
+    // Writes through a temporary file renamed over the target, so that a crash
+    // halfway never leaves a truncated state file behind.
+    pub fn write_json<T: Serialize>(&self, name: &str, value: &T) -> Result<(), SelfError> {
+        fs::create_dir_all(self.dir()?)?;
+        let path = self.file(name)?;
+        let temporary = self.file(&format!(".{}.tmp", name))?;
+        fs::write(&temporary, serde_json::to_vec_pretty(value).unwrap())?;
+        fs::rename(&temporary, &path)?;
+        Ok(())
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    env::var_os("XDG_STATE_HOME")
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/state")))
+        .map(|base| base.join("dynuupdater"))
+}
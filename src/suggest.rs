@@ -0,0 +1,66 @@
+// Closest candidates to a name which couldn't be found, to point out typos in
+// error messages.
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+pub fn closest<'a, I>(name: &str, candidates: I) -> Vec<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let threshold = (name.chars().count() / 3).max(2);
+    let mut scored: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .map(|c| (levenshtein(name, c), c))
+        .filter(|(distance, _)| *distance <= threshold)
+        .collect();
+    scored.sort();
+    scored.dedup();
+    scored.into_iter().take(3).map(|(_, c)| c).collect()
+}
+
+// Suffix for "not found" error messages, empty when nothing is close enough.
+pub fn did_you_mean<'a, I>(name: &str, candidates: I) -> String
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let closest = closest(name, candidates);
+    if closest.is_empty() {
+        String::new()
+    } else {
+        format!(", did you mean {}?", closest.join(" or "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levenshtein_should_count_edits() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("same", "same"), 0);
+    }
+
+    #[test]
+    fn did_you_mean_should_suggest_close_candidates_only() {
+        let candidates = ["example.dynu.net", "other.dynu.net", "exemple.dynu.net"];
+        assert_eq!(
+            did_you_mean("exmple.dynu.net", candidates),
+            ", did you mean example.dynu.net or exemple.dynu.net?"
+        );
+        assert_eq!(did_you_mean("unrelated.org", candidates), "");
+    }
+}
@@ -0,0 +1,106 @@
+use minijinja::{context, Environment, UndefinedBehavior};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::suggest::did_you_mean;
+use crate::SelfError;
+
+// A TXT value the [txt_templates] table of the configuration names, e.g.
+// google-site-verification = "google-site-verification={{ value }}", given
+// as a string or as a table also holding the node and TTL of the record when
+// the verification expects them, e.g. _dmarc for DMARC policies.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum TxtTemplate {
+    Value(String),
+    Record {
+        value: String,
+        node: Option<String>,
+        ttl: Option<u64>,
+    },
+}
+
+impl TxtTemplate {
+    fn template(&self) -> &str {
+        match self {
+            TxtTemplate::Value(value) | TxtTemplate::Record { value, .. } => value,
+        }
+    }
+
+    pub fn node(&self) -> Option<&str> {
+        match self {
+            TxtTemplate::Value(_) => None,
+            TxtTemplate::Record { node, .. } => node.as_deref(),
+        }
+    }
+
+    pub fn ttl(&self) -> Option<u64> {
+        match self {
+            TxtTemplate::Value(_) => None,
+            TxtTemplate::Record { ttl, .. } => *ttl,
+        }
+    }
+
+    // The TXT value, the template seeing the value given on the command line,
+    // the domain and the node. Undefined variables are errors rather than
+    // empty strings, so that a typo doesn't end up in the record.
+    pub fn render(&self, value: &str, domain: &str, node: &str) -> Result<String, SelfError> {
+        let mut environment = Environment::new();
+        environment.set_undefined_behavior(UndefinedBehavior::Strict);
+        environment
+            .render_str(
+                self.template(),
+                context! { value => value, domain => domain, node => node },
+            )
+            .map_err(|e| SelfError::MsgError(format!("cannot render TXT template: {}", e)))
+    }
+}
+
+pub fn find<'a>(
+    templates: &'a BTreeMap<String, TxtTemplate>,
+    name: &str,
+) -> Result<&'a TxtTemplate, SelfError> {
+    templates.get(name).ok_or_else(|| {
+        SelfError::MsgError(format!(
+            "no TXT template named {} in the configuration{}",
+            name,
+            did_you_mean(name, templates.keys().map(|k| k.as_str()))
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_should_expose_the_value_domain_and_node() {
+        let templates: BTreeMap<String, TxtTemplate> = toml::from_str(
+            r#"
+            google-site-verification = "google-site-verification={{ value }}"
+            dmarc = { value = "v=DMARC1; p={{ value }}; rua=mailto:dmarc@{{ domain }}", node = "_dmarc" }
+            "#,
+        )
+        .unwrap();
+        let google = find(&templates, "google-site-verification").unwrap();
+        assert_eq!(
+            google.render("abc", "example.dynu.net", "").unwrap(),
+            "google-site-verification=abc"
+        );
+        let dmarc = find(&templates, "dmarc").unwrap();
+        assert_eq!(dmarc.node(), Some("_dmarc"));
+        assert_eq!(
+            dmarc
+                .render("reject", "example.dynu.net", "_dmarc")
+                .unwrap(),
+            "v=DMARC1; p=reject; rua=mailto:dmarc@example.dynu.net"
+        );
+        assert!(TxtTemplate::Value("{{ valeu }}".to_string())
+            .render("abc", "example.dynu.net", "")
+            .is_err());
+        assert!(find(&templates, "dmrac")
+            .unwrap_err()
+            .to_string()
+            .contains("did you mean dmarc?"));
+    }
+}
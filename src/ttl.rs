@@ -0,0 +1,97 @@
+use crate::dynu::RecordDTO;
+use crate::ensure::Step;
+use crate::SelfError;
+
+// Bounds records set-ttl brings the TTLs of records within, a fixed TTL
+// being both the lower and the upper bound.
+#[derive(Debug, PartialEq)]
+pub struct Bounds {
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+}
+
+impl Bounds {
+    pub fn new(ttl: Option<u64>, min: Option<u64>, max: Option<u64>) -> Result<Bounds, SelfError> {
+        let bounds = match ttl {
+            Some(ttl) => Bounds {
+                min: Some(ttl),
+                max: Some(ttl),
+            },
+            None => Bounds { min, max },
+        };
+        match (bounds.min, bounds.max) {
+            (None, None) => Err(SelfError::MsgError(
+                "one of --ttl, --min or --max is required".to_string(),
+            )),
+            (Some(min), Some(max)) if min > max => Err(SelfError::MsgError(format!(
+                "--min {} is greater than --max {}",
+                min, max
+            ))),
+            _ => Ok(bounds),
+        }
+    }
+
+    fn apply(&self, ttl: u64) -> u64 {
+        let ttl = self.min.map_or(ttl, |min| ttl.max(min));
+        self.max.map_or(ttl, |max| ttl.min(max))
+    }
+}
+
+// Updates bringing the TTL of the records of record_type, or of every type,
+// within the bounds. SOA records are managed by dynu and left alone.
+pub fn plan(records: &[RecordDTO], record_type: Option<&str>, bounds: &Bounds) -> Vec<Step> {
+    records
+        .iter()
+        .filter(|r| r.record_type() != "SOA")
+        .filter(|r| match record_type {
+            Some(record_type) => r.record_type().eq_ignore_ascii_case(record_type),
+            None => true,
+        })
+        .filter(|r| bounds.apply(r.ttl()) != r.ttl())
+        .map(|r| Step::Update(Box::new(r.clone()), r.with_ttl(bounds.apply(r.ttl()))))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds_should_require_a_consistent_range() {
+        assert!(Bounds::new(None, None, None).is_err());
+        assert!(Bounds::new(None, Some(600), Some(300)).is_err());
+        assert_eq!(
+            Bounds::new(Some(60), Some(600), None).unwrap(),
+            Bounds {
+                min: Some(60),
+                max: Some(60)
+            }
+        );
+    }
+
+    #[test]
+    fn plan_should_only_update_records_out_of_bounds() {
+        let records = vec![
+            RecordDTO::a_record("", "192.0.2.1", 3600, Some(1)),
+            RecordDTO::a_record("www", "192.0.2.1", 120, Some(2)),
+            RecordDTO::txt_record("", "v=spf1 -all", 3600, Some(3)),
+        ];
+        let bounds = Bounds::new(None, None, Some(300)).unwrap();
+        assert_eq!(
+            plan(&records, Some("a"), &bounds),
+            vec![Step::Update(
+                Box::new(records[0].clone()),
+                RecordDTO::a_record("", "192.0.2.1", 300, Some(1))
+            )]
+        );
+        assert_eq!(plan(&records, None, &bounds).len(), 2);
+        let bounds = Bounds::new(None, Some(300), None).unwrap();
+        assert_eq!(
+            plan(&records, None, &bounds),
+            vec![Step::Update(
+                Box::new(records[1].clone()),
+                RecordDTO::a_record("www", "192.0.2.1", 300, Some(2))
+            )]
+        );
+    }
+}
@@ -0,0 +1,409 @@
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use wasmtime::{
+    Caller, Config, Engine, Extern, Instance, Linker, Module, Store, StoreLimits,
+    StoreLimitsBuilder,
+};
+
+use crate::config;
+use crate::ipsource;
+use crate::netutils::{self, Addresses};
+use crate::notify::{Event, Sink};
+use crate::SelfError;
+
+// Plugins are core WASM modules in <config dir>/plugins, sandboxed from the
+// host except for the functions of the "dynu" import module:
+//   log(ptr, len)                   writes a message on stderr
+//   http_get(ptr, len) -> i64       GETs the url, returns the body (or -1)
+// Strings are passed as ptr/len in the plugin memory, strings returned to the
+// plugin are allocated through its alloc export and packed as ptr << 32 | len.
+// Plugins export memory, alloc(len) -> ptr and either
+//   detect() -> i64                 returns {"v4": ..., "v6": ...} as ip source
+//   notify(ptr, len) -> i32         receives the JSON event, 0 on success
+const FUEL: u64 = 5_000_000_000;
+const MAX_MEMORY: usize = 64 << 20;
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Host {
+    plugin: String,
+    http: reqwest::blocking::Client,
+    limits: StoreLimits,
+}
+
+pub struct Plugin {
+    name: String,
+    engine: Engine,
+    module: Module,
+    fuel: u64,
+}
+
+fn wasm_error(plugin: &str, e: impl Display) -> SelfError {
+    SelfError::MsgError(format!("wasm plugin {}: {:#}", plugin, e))
+}
+
+pub fn plugins_dir() -> Option<PathBuf> {
+    config::config_dir().map(|d| d.join("plugins"))
+}
+
+fn pack(ptr: i32, len: usize) -> i64 {
+    ((ptr as i64) << 32) | len as i64
+}
+
+fn unpack(packed: i64) -> (usize, usize) {
+    ((packed >> 32) as u32 as usize, packed as u32 as usize)
+}
+
+fn read_string(caller: &mut Caller<'_, Host>, ptr: i32, len: i32) -> Option<String> {
+    let memory = caller.get_export("memory").and_then(Extern::into_memory)?;
+    let bytes = memory
+        .data(&caller)
+        .get(ptr as usize..(ptr as usize).checked_add(len as usize)?)?;
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+fn write_bytes(caller: &mut Caller<'_, Host>, bytes: &[u8]) -> Option<i64> {
+    let memory = caller.get_export("memory").and_then(Extern::into_memory)?;
+    let alloc = caller
+        .get_export("alloc")
+        .and_then(Extern::into_func)?
+        .typed::<i32, i32>(&caller)
+        .ok()?;
+    let ptr = alloc.call(&mut *caller, bytes.len() as i32).ok()?;
+    memory.write(&mut *caller, ptr as usize, bytes).ok()?;
+    Some(pack(ptr, bytes.len()))
+}
+
+fn http_get(caller: &mut Caller<'_, Host>, ptr: i32, len: i32) -> i64 {
+    let Some(url) = read_string(caller, ptr, len) else {
+        return -1;
+    };
+    let body = caller
+        .data()
+        .http
+        .get(&url)
+        .send()
+        .and_then(|r| r.error_for_status())
+        .and_then(|r| r.bytes());
+    match body {
+        Ok(body) => write_bytes(caller, &body).unwrap_or(-1),
+        Err(e) => {
+            eprintln!(
+                "wasm plugin {}: GET {} failed: {}",
+                caller.data().plugin,
+                url,
+                e
+            );
+            -1
+        }
+    }
+}
+
+impl Plugin {
+    fn load(path: &Path) -> Result<Plugin, SelfError> {
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let bytes = fs::read(path).map_err(|e| wasm_error(&name, e))?;
+        Plugin::compile(name, &bytes)
+    }
+
+    fn compile(name: String, bytes: &[u8]) -> Result<Plugin, SelfError> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(|e| wasm_error(&name, e))?;
+        let module = Module::new(&engine, bytes).map_err(|e| wasm_error(&name, e))?;
+        Ok(Plugin {
+            name,
+            engine,
+            module,
+            fuel: FUEL,
+        })
+    }
+
+    fn exports(&self, name: &str) -> bool {
+        self.module.get_export(name).is_some()
+    }
+
+    // Every call runs in a fresh instance with a bounded amount of fuel and
+    // memory, so that plugins can neither keep state, run forever nor exhaust
+    // the memory of the host.
+    fn instantiate(&self) -> Result<(Store<Host>, Instance), SelfError> {
+        let error = |e| wasm_error(&self.name, e);
+        let http = netutils::http_client()
+            .timeout(HTTP_TIMEOUT)
+            .build()
+            .map_err(|e| wasm_error(&self.name, e))?;
+        let mut store = Store::new(
+            &self.engine,
+            Host {
+                plugin: self.name.clone(),
+                http,
+                limits: StoreLimitsBuilder::new().memory_size(MAX_MEMORY).build(),
+            },
+        );
+        store.limiter(|host| &mut host.limits);
+        store.set_fuel(self.fuel).map_err(error)?;
+        let mut linker = Linker::new(&self.engine);
+        linker
+            .func_wrap(
+                "dynu",
+                "log",
+                |mut caller: Caller<'_, Host>, ptr: i32, len: i32| {
+                    if let Some(message) = read_string(&mut caller, ptr, len) {
+                        eprintln!("wasm plugin {}: {}", caller.data().plugin, message);
+                    }
+                },
+            )
+            .map_err(error)?;
+        linker
+            .func_wrap(
+                "dynu",
+                "http_get",
+                |mut caller: Caller<'_, Host>, ptr: i32, len: i32| http_get(&mut caller, ptr, len),
+            )
+            .map_err(error)?;
+        let instance = linker
+            .instantiate(&mut store, &self.module)
+            .map_err(error)?;
+        Ok((store, instance))
+    }
+
+    pub fn detect(&self) -> Result<Addresses, SelfError> {
+        let error = |e| wasm_error(&self.name, e);
+        let (mut store, instance) = self.instantiate()?;
+        let detect = instance
+            .get_typed_func::<(), i64>(&mut store, "detect")
+            .map_err(error)?;
+        let packed = detect.call(&mut store, ()).map_err(error)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| wasm_error(&self.name, "no memory exported"))?;
+        let (ptr, len) = unpack(packed);
+        let output = memory
+            .data(&store)
+            .get(ptr..ptr + len)
+            .ok_or_else(|| wasm_error(&self.name, "detect returned an out of bounds string"))?;
+        ipsource::parse_output(output, &format!("wasm plugin {}", self.name))
+    }
+}
+
+impl Sink for Plugin {
+    fn name(&self) -> String {
+        format!("wasm plugin {}", self.name)
+    }
+
+    fn send(&self, event: &Event) -> Result<(), SelfError> {
+        let error = |e| wasm_error(&self.name, e);
+        let (mut store, instance) = self.instantiate()?;
+        let body = serde_json::to_vec(event).unwrap();
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, "alloc")
+            .map_err(error)?;
+        let notify = instance
+            .get_typed_func::<(i32, i32), i32>(&mut store, "notify")
+            .map_err(error)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| wasm_error(&self.name, "no memory exported"))?;
+        let ptr = alloc.call(&mut store, body.len() as i32).map_err(error)?;
+        memory
+            .write(&mut store, ptr as usize, &body)
+            .map_err(|e| wasm_error(&self.name, e))?;
+        match notify
+            .call(&mut store, (ptr, body.len() as i32))
+            .map_err(error)?
+        {
+            0 => Ok(()),
+            code => Err(wasm_error(&self.name, format!("notify returned {}", code))),
+        }
+    }
+}
+
+// Plugins failing to load are reported and skipped, so that a broken plugin
+// doesn't prevent the others from working.
+pub fn load_all() -> Vec<Plugin> {
+    let Some(entries) = plugins_dir().and_then(|d| fs::read_dir(d).ok()) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "wasm"))
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .filter_map(|path| match Plugin::load(path) {
+            Ok(plugin) => Some(plugin),
+            Err(e) => {
+                eprintln!("skipping {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect()
+}
+
+pub fn ip_source(name: &str) -> Result<Option<Plugin>, SelfError> {
+    let Some(path) = plugins_dir().map(|d| d.join(format!("{}.wasm", name))) else {
+        return Ok(None);
+    };
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let plugin = Plugin::load(&path)?;
+    if !plugin.exports("detect") {
+        return Err(wasm_error(name, "doesn't export detect"));
+    }
+    Ok(Some(plugin))
+}
+
+pub fn ip_sources() -> Vec<(String, PathBuf)> {
+    let Some(directory) = plugins_dir() else {
+        return Vec::new();
+    };
+    load_all()
+        .into_iter()
+        .filter(|p| p.exports("detect"))
+        .map(|p| {
+            let path = directory.join(format!("{}.wasm", p.name));
+            (p.name, path)
+        })
+        .collect()
+}
+
+pub fn notifiers() -> Vec<Box<dyn Sink>> {
+    load_all()
+        .into_iter()
+        .filter(|p| p.exports("notify"))
+        .map(|p| Box::new(p) as Box<dyn Sink>)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DETECTED: &str = r#"{"v4": "192.0.2.1", "v6": null}"#;
+
+    fn leb(mut value: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes.push(byte);
+                return bytes;
+            }
+            bytes.push(byte | 0x80);
+        }
+    }
+
+    fn section(id: u8, content: &[u8]) -> Vec<u8> {
+        [vec![id], leb(content.len() as u32), content.to_vec()].concat()
+    }
+
+    // Binary of a module exporting memory of pages pages holding DETECTED at
+    // offset 0, alloc returning offset 1024 and detect running instructions.
+    fn module(pages: u32, instructions: &[u8]) -> Vec<u8> {
+        let detect = [&[0x00], instructions, &[0x0b]].concat();
+        let alloc = [0x00, 0x41, 0x80, 0x08, 0x0b];
+        let exports = [
+            &[0x03, 0x06][..],
+            b"memory",
+            &[0x02, 0x00, 0x06],
+            b"detect",
+            &[0x00, 0x00, 0x05],
+            b"alloc",
+            &[0x00, 0x01],
+        ]
+        .concat();
+        [
+            b"\0asm".to_vec(),
+            vec![0x01, 0x00, 0x00, 0x00],
+            // () -> i64 and (i32) -> i32
+            section(
+                1,
+                &[0x02, 0x60, 0x00, 0x01, 0x7e, 0x60, 0x01, 0x7f, 0x01, 0x7f],
+            ),
+            section(3, &[0x02, 0x00, 0x01]),
+            section(5, &[[0x01, 0x00].as_slice(), &leb(pages)].concat()),
+            section(7, &exports),
+            section(
+                10,
+                &[
+                    vec![0x02],
+                    leb(detect.len() as u32),
+                    detect,
+                    leb(alloc.len() as u32),
+                    alloc.to_vec(),
+                ]
+                .concat(),
+            ),
+            section(
+                11,
+                &[
+                    vec![0x01, 0x00, 0x41, 0x00, 0x0b],
+                    leb(DETECTED.len() as u32),
+                    DETECTED.as_bytes().to_vec(),
+                ]
+                .concat(),
+            ),
+        ]
+        .concat()
+    }
+
+    fn plugin(name: &str, pages: u32, instructions: &[u8]) -> Plugin {
+        Plugin::compile(name.to_string(), &module(pages, instructions)).unwrap()
+    }
+
+    fn detect_error(plugin: &Plugin) -> String {
+        match plugin.detect() {
+            Err(SelfError::MsgError(msg)) => msg,
+            other => panic!("unexpected detection {:?}", other.map(|a| a.v4)),
+        }
+    }
+
+    #[test]
+    fn detect_should_read_the_addresses_returned() {
+        // i64.const len(DETECTED), the string being at offset 0
+        let plugin = plugin("valid", 1, &[0x42, DETECTED.len() as u8]);
+        let detected = plugin.detect().unwrap();
+        assert_eq!(detected.v4.as_deref(), Some("192.0.2.1"));
+        assert_eq!(detected.v6, None);
+    }
+
+    #[test]
+    fn detect_should_fail_when_the_plugin_traps() {
+        // unreachable
+        let plugin = plugin("trapping", 1, &[0x00]);
+        let error = detect_error(&plugin);
+        assert!(error.starts_with("wasm plugin trapping:"));
+        assert!(error.contains("`unreachable` instruction executed"));
+    }
+
+    #[test]
+    fn detect_should_stop_plugins_running_out_of_fuel() {
+        // loop br 0 end i64.const 0
+        let plugin = Plugin {
+            fuel: 1_000_000,
+            ..plugin("looping", 1, &[0x03, 0x40, 0x0c, 0x00, 0x0b, 0x42, 0x00])
+        };
+        let error = detect_error(&plugin);
+        assert!(error.starts_with("wasm plugin looping:"));
+        assert!(error.contains("all fuel consumed"));
+    }
+
+    #[test]
+    fn detect_should_refuse_memory_over_the_limit() {
+        let pages = (MAX_MEMORY / 65536) as u32 + 1;
+        let greedy = plugin("greedy", pages, &[0x42, DETECTED.len() as u8]);
+        assert!(detect_error(&greedy).contains("exceeds memory limits"));
+        assert!(plugin("fitting", pages - 1, &[0x42, DETECTED.len() as u8])
+            .detect()
+            .is_ok());
+    }
+}